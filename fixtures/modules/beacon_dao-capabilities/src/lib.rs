@@ -0,0 +1,82 @@
+/// A host resource that an actor must be granted access to by the permissions
+/// service before the runtime will let it through. Shared by the permissions
+/// service, which hands out capabilities, and the runtime, which checks them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+	/// Writing to stdout and stderr, and reading from stdin
+	Stdio,
+
+	/// Reading the wall and monotonic clocks
+	Clock,
+
+	/// Reading from the host's entropy source
+	Random,
+
+	/// Reading and writing files in the actor's preopened sandbox directory
+	Filesystem,
+}
+
+impl Capability {
+	/// Every capability the runtime provides.
+	pub const ALL: [Self; 4] = [Self::Stdio, Self::Clock, Self::Random, Self::Filesystem];
+
+	/// Gets the bit representing the capability in an actor's grant mask.
+	pub fn bit(self) -> u32 {
+		match self {
+			Self::Stdio => 1,
+			Self::Clock => 1 << 1,
+			Self::Random => 1 << 2,
+			Self::Filesystem => 1 << 3,
+		}
+	}
+
+	/// Gets the name of the permission granting the capability.
+	pub fn permission(self) -> &'static str {
+		match self {
+			Self::Stdio => "use the terminal",
+			Self::Clock => "read the clock",
+			Self::Random => "generate random numbers",
+			Self::Filesystem => "store files",
+		}
+	}
+
+	/// Gets what the capability lets an actor do, as shown to the user when
+	/// it asks for it.
+	pub fn description(self) -> &'static str {
+		match self {
+			Self::Stdio => "print messages to, and read input from your terminal.",
+			Self::Clock => "see what time it is.",
+			Self::Random => "read random numbers from your computer.",
+			Self::Filesystem => "read and write files in its own private folder.",
+		}
+	}
+
+	/// Gets the capability granted by the permission, if it is one.
+	pub fn from_permission(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|cap| cap.permission() == name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_bits() {
+		let mask = Capability::ALL.iter().fold(0, |mask, cap| {
+			assert_eq!(mask & cap.bit(), 0);
+
+			mask | cap.bit()
+		});
+		assert_eq!(mask, 0b1111);
+	}
+
+	#[test]
+	fn test_from_permission() {
+		for cap in Capability::ALL {
+			assert_eq!(Capability::from_permission(cap.permission()), Some(cap));
+		}
+
+		assert_eq!(Capability::from_permission("send transactions"), None);
+	}
+}
@@ -0,0 +1,51 @@
+#[path = "src/gen.rs"]
+#[allow(dead_code)]
+mod gen;
+
+use std::{env, fs, path::PathBuf};
+
+/// The contracts bindings are generated for: their names, the directories
+/// hardhat exports their ABIs to, and the modules of their bindings.
+const CONTRACTS: [(&str, &str, &str); 6] = [
+	("Idea", "Idea.sol", "idea"),
+	("MetaProp", "Idea.sol", "meta_prop"),
+	("Proposal", "Proposal.sol", "proposal"),
+	("User", "User.sol", "user"),
+	("RecoveryGroup", "RecoveryGroup.sol", "recovery_group"),
+	(
+		"IHasMetadata",
+		"interfaces/IHasMetadata.sol",
+		"has_metadata",
+	),
+];
+
+fn main() {
+	let abi_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap())
+		.join("../../../abi/contracts")
+		.canonicalize()
+		.expect(
+			"the ABIs of the contracts haven't been exported: run `cargo make build_contracts`",
+		);
+	let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+	for (name, dir, module) in CONTRACTS {
+		let path = abi_dir.join(dir).join(format!("{}.json", name));
+		println!("cargo:rerun-if-changed={}", path.display());
+
+		let abi = fs::read_to_string(&path).unwrap_or_else(|_| {
+			panic!(
+				"missing the ABI of {} at {}: run `cargo make build_contracts`",
+				name,
+				path.display()
+			)
+		});
+		let contract: ethabi::Contract =
+			serde_json::from_str(&abi).unwrap_or_else(|e| panic!("invalid ABI of {}: {}", name, e));
+
+		fs::write(
+			out_dir.join(format!("{}.rs", module)),
+			gen::generate(name, &path.display().to_string(), &contract),
+		)
+		.unwrap();
+	}
+}
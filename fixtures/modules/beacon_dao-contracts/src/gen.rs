@@ -0,0 +1,516 @@
+use ethabi::{param_type::Writer, Contract, Function, ParamType, StateMutability};
+use std::collections::HashSet;
+
+/// Names that can't be used as identifiers: Rust's keywords, including those
+/// reserved for later editions, and the names bindings already use.
+const RESERVED: [&str; 53] = [
+	"abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+	"do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+	"in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+	"return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+	"unsafe", "unsized", "use", "virtual", "where", "while", "yield", "callback", "new",
+];
+
+/// Converts a Solidity name (e.g., nAffirmative, _ipfsAddr, ERC20Token) to
+/// snake case.
+pub fn snake_case(name: &str) -> String {
+	let chars: Vec<char> = name.trim_matches('_').chars().collect();
+	let mut snake = String::new();
+
+	for (i, c) in chars.iter().enumerate() {
+		if c.is_ascii_uppercase() && i > 0 {
+			let prev = chars[i - 1];
+			let ends_acronym = prev.is_ascii_uppercase()
+				&& chars
+					.get(i + 1)
+					.is_some_and(|next| next.is_ascii_lowercase());
+
+			if prev.is_ascii_lowercase() || prev.is_ascii_digit() || ends_acronym {
+				snake.push('_');
+			}
+		}
+
+		snake.push(c.to_ascii_lowercase());
+	}
+
+	snake
+}
+
+/// Gets the identifier of the i-th parameter, which might not have a name.
+fn param_ident(name: &str, i: usize) -> String {
+	let snake = snake_case(name);
+
+	if snake.is_empty() {
+		format!("arg{}", i)
+	} else if RESERVED.contains(&snake.as_str()) {
+		format!("{}_", snake)
+	} else {
+		snake
+	}
+}
+
+/// Gets the identifiers of the parameters, keeping them unique.
+fn param_idents<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+	let mut idents: Vec<String> = Vec::new();
+
+	for (i, name) in names.enumerate() {
+		let ident = param_ident(name, i);
+
+		if idents.contains(&ident) {
+			idents.push(format!("{}{}", ident, i));
+		} else {
+			idents.push(ident);
+		}
+	}
+
+	idents
+}
+
+/// Gets the Rust type values of the ABI type are represented with.
+pub fn rust_type(kind: &ParamType) -> String {
+	match kind {
+		ParamType::Address => String::from("ethabi::Address"),
+		ParamType::Uint(8) => String::from("u8"),
+		ParamType::Uint(_) => String::from("ethabi::Uint"),
+		ParamType::Int(_) => String::from("ethabi::Int"),
+		ParamType::Bool => String::from("bool"),
+		ParamType::String => String::from("String"),
+		ParamType::Bytes => String::from("Vec<u8>"),
+		ParamType::FixedBytes(len) => format!("[u8; {}]", len),
+		ParamType::Array(kind) => format!("Vec<{}>", rust_type(kind)),
+		ParamType::FixedArray(kind, len) => format!("[{}; {}]", rust_type(kind), len),
+		ParamType::Tuple(kinds) => format!(
+			"({})",
+			kinds
+				.iter()
+				.map(|kind| format!("{},", rust_type(kind)))
+				.collect::<String>()
+		),
+	}
+}
+
+/// Gets an expression converting the Rust value expr to a token of the ABI
+/// type. depth keeps the names bound in nested conversions apart.
+pub fn to_token(expr: &str, kind: &ParamType, depth: usize) -> String {
+	let elems = |variant: &str, kind: &ParamType| {
+		let x = format!("x{}", depth);
+
+		format!(
+			"ethabi::Token::{}({}.into_iter().map(|{}| {}).collect())",
+			variant,
+			expr,
+			x,
+			to_token(&x, kind, depth + 1)
+		)
+	};
+
+	match kind {
+		ParamType::Address => format!("ethabi::Token::Address({})", expr),
+		ParamType::Uint(8) => format!("ethabi::Token::Uint({}.into())", expr),
+		ParamType::Uint(_) => format!("ethabi::Token::Uint({})", expr),
+		ParamType::Int(_) => format!("ethabi::Token::Int({})", expr),
+		ParamType::Bool => format!("ethabi::Token::Bool({})", expr),
+		ParamType::String => format!("ethabi::Token::String({})", expr),
+		ParamType::Bytes => format!("ethabi::Token::Bytes({})", expr),
+		ParamType::FixedBytes(_) => format!("ethabi::Token::FixedBytes({}.to_vec())", expr),
+		ParamType::Array(kind) => elems("Array", kind),
+		ParamType::FixedArray(kind, _) => elems("FixedArray", kind),
+		ParamType::Tuple(kinds) => {
+			let names: Vec<String> = (0..kinds.len())
+				.map(|i| format!("t{}_{}", depth, i))
+				.collect();
+
+			format!(
+				"{{ let ({}) = {}; ethabi::Token::Tuple(vec![{}]) }}",
+				names
+					.iter()
+					.map(|name| format!("{},", name))
+					.collect::<String>(),
+				expr,
+				names
+					.iter()
+					.zip(kinds.iter())
+					.map(|(name, kind)| to_token(name, kind, depth + 1))
+					.collect::<Vec<String>>()
+					.join(", ")
+			)
+		}
+	}
+}
+
+/// Gets an expression converting the token expr to an Option of the Rust
+/// value of the ABI type.
+pub fn from_token(expr: &str, kind: &ParamType) -> String {
+	match kind {
+		ParamType::Address => format!("{}.into_address()", expr),
+		ParamType::Uint(8) => format!("{}.into_uint().map(|n| n.low_u32() as u8)", expr),
+		ParamType::Uint(_) => format!("{}.into_uint()", expr),
+		ParamType::Int(_) => format!("{}.into_int()", expr),
+		ParamType::Bool => format!("{}.into_bool()", expr),
+		ParamType::String => format!("{}.into_string()", expr),
+		ParamType::Bytes => format!("{}.into_bytes()", expr),
+		ParamType::FixedBytes(len) => format!(
+			"{}.into_fixed_bytes().and_then(|b| <[u8; {}]>::try_from(b).ok())",
+			expr, len
+		),
+		ParamType::Array(kind) => elems_from_token(expr, "into_array", kind),
+		ParamType::FixedArray(kind, len) => format!(
+			"{}.and_then(|v| <[{}; {}]>::try_from(v).ok())",
+			elems_from_token(expr, "into_fixed_array", kind),
+			rust_type(kind),
+			len
+		),
+		ParamType::Tuple(kinds) => format!(
+			"{}.into_tuple().and_then(|ts| {{ let mut ts = ts.into_iter(); Some(({})) }})",
+			expr,
+			kinds
+				.iter()
+				.map(|kind| format!("{}?,", from_token("ts.next()?", kind)))
+				.collect::<String>()
+		),
+	}
+}
+
+fn elems_from_token(expr: &str, method: &str, kind: &ParamType) -> String {
+	format!(
+		"{}.{}().and_then(|ts| ts.into_iter().map(|t| {}).collect::<Option<Vec<_>>>())",
+		expr,
+		method,
+		from_token("t", kind)
+	)
+}
+
+/// Gets the Solidity signature of a function or event (e.g.,
+/// castVote(uint8,uint256)).
+fn signature<'a>(name: &str, kinds: impl Iterator<Item = &'a ParamType>) -> String {
+	format!(
+		"{}({})",
+		name,
+		kinds.map(Writer::write).collect::<Vec<String>>().join(",")
+	)
+}
+
+/// Gets the names of the methods bound to the contract's functions, ordered
+/// by the functions' names, with the index of each function among its
+/// overloads.
+/// The overload with the fewest parameters gets the plain name, and the
+/// others are named after the parameters they add (e.g.,
+/// cast_vote_with_voter), or numbered if they add none.
+pub fn method_names(contract: &Contract) -> Vec<(String, &Function, usize)> {
+	let mut used: HashSet<String> = HashSet::new();
+	let mut names = Vec::new();
+
+	for overloads in contract.functions.values() {
+		let plain = overloads
+			.iter()
+			.enumerate()
+			.min_by_key(|(_, f)| f.inputs.len())
+			.map(|(i, _)| i)
+			.unwrap_or_default();
+		let base = param_ident(&overloads[plain].name, 0);
+		let plain_params = param_idents(overloads[plain].inputs.iter().map(|p| p.name.as_str()));
+
+		for (i, f) in overloads.iter().enumerate() {
+			let mut name = if i == plain {
+				base.clone()
+			} else {
+				let added: Vec<String> = param_idents(f.inputs.iter().map(|p| p.name.as_str()))
+					.into_iter()
+					.filter(|param| !plain_params.contains(param))
+					.collect();
+
+				if added.is_empty() {
+					format!("{}_{}", base, i)
+				} else {
+					format!("{}_with_{}", base, added.join("_and_"))
+				}
+			};
+
+			if used.contains(&name) {
+				name = format!("{}_{}", name, i);
+			}
+
+			used.insert(name.clone());
+			names.push((name, f, i));
+		}
+	}
+
+	names
+}
+
+/// Generates the read method or transaction builder bound to the function.
+fn method(name: &str, f: &Function, overload: usize) -> String {
+	let params = param_idents(f.inputs.iter().map(|p| p.name.as_str()));
+	let args = params
+		.iter()
+		.zip(f.inputs.iter())
+		.map(|(param, input)| format!("{}: {}, ", param, rust_type(&input.kind)))
+		.collect::<String>();
+	let tokens = params
+		.iter()
+		.zip(f.inputs.iter())
+		.map(|(param, input)| to_token(param, &input.kind, 0))
+		.collect::<Vec<String>>()
+		.join(", ");
+	let sig = signature(&f.name, f.inputs.iter().map(|p| &p.kind));
+	let function = format!("function({:?}, {})", f.name, overload);
+
+	match f.state_mutability {
+		StateMutability::Pure | StateMutability::View => {
+			let (output, read) = match f.outputs.as_slice() {
+				[] => (String::from("()"), String::from("Some(())")),
+				[output] => (
+					rust_type(&output.kind),
+					from_token("out.next()?", &output.kind),
+				),
+				outputs => (
+					format!(
+						"({})",
+						outputs
+							.iter()
+							.map(|output| rust_type(&output.kind))
+							.collect::<Vec<String>>()
+							.join(", ")
+					),
+					format!(
+						"Some(({}))",
+						outputs
+							.iter()
+							.map(|output| format!("{}?", from_token("out.next()?", &output.kind)))
+							.collect::<Vec<String>>()
+							.join(", ")
+					),
+				),
+			};
+
+			format!(
+				"\t/// Calls `{sig}` at the latest block.\n\
+				\tpub fn {name}(&self, {args}callback: impl FnOnce(Result<{output}, crate::Error>) + 'static) {{\n\
+				\t\tcrate::call(&self.address, {function}, vec![{tokens}], |out| {{ #[allow(unused_mut, unused_variables)] let mut out = out.into_iter(); {read} }}, callback)\n\
+				\t}}\n"
+			)
+		}
+		StateMutability::NonPayable | StateMutability::Payable => {
+			let payable = if f.state_mutability == StateMutability::Payable {
+				"\t///\n\t/// The function is payable: set the value of the transaction to send ether with it.\n"
+			} else {
+				""
+			};
+
+			format!(
+				"\t/// Builds a transaction calling `{sig}`.\n\
+				{payable}\
+				\tpub fn {name}(&self, {args}) -> crate::Call {{\n\
+				\t\tcrate::Call::new(&self.address, {function}, &[{tokens}])\n\
+				\t}}\n"
+			)
+		}
+	}
+}
+
+/// Generates the enum of the contract's events, with their topics and a
+/// decoder of logs.
+fn events(contract: &Contract) -> String {
+	let mut variants = String::new();
+	let mut topics = String::new();
+	let mut arms = String::new();
+	let mut used: HashSet<String> = HashSet::new();
+
+	for event in contract.events().filter(|event| !event.anonymous) {
+		let mut variant = event.name.clone();
+		if used.contains(&variant) {
+			variant = format!("{}{}", variant, used.len());
+		}
+		used.insert(variant.clone());
+
+		let topic = snake_case(&variant).to_uppercase();
+		let sig = signature(&event.name, event.inputs.iter().map(|p| &p.kind));
+		let fields = param_idents(event.inputs.iter().map(|p| p.name.as_str()));
+
+		variants.push_str(&format!(
+			"\t/// `{}`\n\t{} {{ {} }},\n",
+			sig,
+			variant,
+			fields
+				.iter()
+				.zip(event.inputs.iter())
+				.map(|(field, input)| format!("{}: {}", field, rust_type(&input.kind)))
+				.collect::<Vec<String>>()
+				.join(", ")
+		));
+		topics.push_str(&format!(
+			"\t/// The topic logs of {} start with.\n\tpub const {}: &str = {:?};\n",
+			variant,
+			topic,
+			format!("{:#x}", event.signature())
+		));
+		arms.push_str(&format!(
+			"\t\t\tSelf::{} => Some(Self::{} {{ {} }}),\n",
+			topic,
+			variant,
+			fields
+				.iter()
+				.zip(event.inputs.iter())
+				.map(|(field, input)| format!(
+					"{}: {}?",
+					field,
+					from_token("params.next()?", &input.kind)
+				))
+				.collect::<Vec<String>>()
+				.join(", ")
+		));
+	}
+
+	if variants.is_empty() {
+		return String::new();
+	}
+
+	format!(
+		"/// An event emitted by the contract.\n\
+		#[derive(Debug, Clone, PartialEq, Eq)]\n\
+		pub enum Event {{\n{variants}}}\n\n\
+		impl Event {{\n{topics}\n\
+		\t/// Decodes the log as one of the contract's events.\n\
+		\tpub fn decode(log: &beacon_dao_web3::Log) -> Option<Self> {{\n\
+		\t\tlet (topic, mut params) = crate::parse_log(log, &ABI)?;\n\n\
+		\t\tmatch topic.as_str() {{\n{arms}\t\t\t_ => None,\n\t\t}}\n\
+		\t}}\n\
+		}}\n"
+	)
+}
+
+/// Generates the bindings of the contract, whose ABI is read from abi_path
+/// when the bindings are compiled.
+pub fn generate(name: &str, abi_path: &str, contract: &Contract) -> String {
+	let methods = method_names(contract)
+		.into_iter()
+		.map(|(method_name, f, overload)| method(&method_name, f, overload))
+		.collect::<Vec<String>>()
+		.join("\n");
+
+	format!(
+		"lazy_static::lazy_static! {{\n\
+		\tstatic ref ABI: ethabi::Contract = serde_json::from_str(include_str!({abi_path:?}))\n\
+		\t\t.expect(\"the exported ABI of {name} is valid\");\n\
+		}}\n\n\
+		/// Gets the ABI of {name}.\n\
+		pub fn abi() -> &'static ethabi::Contract {{\n\
+		\t&ABI\n\
+		}}\n\n\
+		#[allow(dead_code)]\n\
+		fn function(name: &str, overload: usize) -> &'static ethabi::Function {{\n\
+		\t&ABI.functions_by_name(name).expect(\"the function is in the ABI\")[overload]\n\
+		}}\n\n\
+		/// A {name} contract, at its address.\n\
+		#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]\n\
+		pub struct {name} {{\n\
+		\tpub address: String,\n\
+		}}\n\n\
+		#[allow(clippy::redundant_closure)]\n\
+		impl {name} {{\n\
+		\tpub fn new(address: impl Into<String>) -> Self {{\n\
+		\t\tSelf {{ address: address.into() }}\n\
+		\t}}\n\n\
+		{methods}\
+		}}\n\n\
+		{events}",
+		events = events(contract)
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ABI: &str = r#"[
+		{"type": "function", "name": "nAffirmative", "stateMutability": "view", "inputs": [], "outputs": [{"name": "", "type": "uint256"}]},
+		{"type": "function", "name": "castVote", "stateMutability": "nonpayable", "inputs": [{"name": "nature", "type": "uint8"}, {"name": "weight", "type": "uint256"}], "outputs": []},
+		{"type": "function", "name": "castVote", "stateMutability": "nonpayable", "inputs": [{"name": "voter", "type": "address"}, {"name": "nature", "type": "uint8"}, {"name": "weight", "type": "uint256"}], "outputs": []},
+		{"type": "function", "name": "commitment", "stateMutability": "view", "inputs": [{"name": "_prop", "type": "address"}, {"name": "type", "type": "address"}], "outputs": [{"name": "", "type": "tuple", "components": [{"name": "voter", "type": "address"}, {"name": "votes", "type": "uint256[]"}]}]},
+		{"type": "event", "name": "VoteCast", "anonymous": false, "inputs": [{"name": "voter", "type": "address", "indexed": true}, {"name": "nature", "type": "uint8", "indexed": false}]}
+	]"#;
+
+	#[test]
+	fn test_snake_case() {
+		assert_eq!(snake_case("nAffirmative"), "n_affirmative");
+		assert_eq!(snake_case("_ipfsAddr"), "ipfs_addr");
+		assert_eq!(snake_case("ERC20Token"), "erc20_token");
+		assert_eq!(snake_case("balanceOf"), "balance_of");
+		assert_eq!(snake_case("totalSupply"), "total_supply");
+		assert_eq!(param_ident("", 2), "arg2");
+		assert_eq!(param_ident("type", 0), "type_");
+		assert_eq!(param_ident("async", 0), "async_");
+		assert_eq!(param_ident("yield", 0), "yield_");
+	}
+
+	#[test]
+	fn test_rust_type() {
+		let tuple = ParamType::Tuple(vec![
+			ParamType::Address,
+			ParamType::Array(Box::new(ParamType::Uint(256))),
+		]);
+
+		assert_eq!(rust_type(&ParamType::Uint(8)), "u8");
+		assert_eq!(rust_type(&ParamType::FixedBytes(32)), "[u8; 32]");
+		assert_eq!(
+			rust_type(&ParamType::FixedArray(Box::new(ParamType::Address), 2)),
+			"[ethabi::Address; 2]"
+		);
+		assert_eq!(rust_type(&tuple), "(ethabi::Address,Vec<ethabi::Uint>,)");
+		assert_eq!(
+			to_token("c", &tuple, 0),
+			"{ let (t0_0,t0_1,) = c; ethabi::Token::Tuple(vec![ethabi::Token::Address(t0_0), \
+			 ethabi::Token::Array(t0_1.into_iter().map(|x1| ethabi::Token::Uint(x1)).collect())]) }"
+		);
+		assert_eq!(
+			from_token("t", &ParamType::Uint(8)),
+			"t.into_uint().map(|n| n.low_u32() as u8)"
+		);
+		assert_eq!(
+			from_token("t", &ParamType::FixedBytes(32)),
+			"t.into_fixed_bytes().and_then(|b| <[u8; 32]>::try_from(b).ok())"
+		);
+		assert_eq!(
+			from_token("t", &ParamType::FixedArray(Box::new(ParamType::Bool), 2)),
+			"t.into_fixed_array().and_then(|ts| ts.into_iter().map(|t| t.into_bool()).collect::<Option<Vec<_>>>())\
+			 .and_then(|v| <[bool; 2]>::try_from(v).ok())"
+		);
+	}
+
+	#[test]
+	fn test_method_names() {
+		let contract: Contract = serde_json::from_str(ABI).unwrap();
+		let names: Vec<(String, usize)> = method_names(&contract)
+			.into_iter()
+			.map(|(name, _, overload)| (name, overload))
+			.collect();
+
+		assert_eq!(
+			names,
+			vec![
+				(String::from("cast_vote"), 0),
+				(String::from("cast_vote_with_voter"), 1),
+				(String::from("commitment"), 0),
+				(String::from("n_affirmative"), 0),
+			]
+		);
+	}
+
+	#[test]
+	fn test_generate() {
+		let contract: Contract = serde_json::from_str(ABI).unwrap();
+		let code = generate("Proposal", "/abi/Proposal.json", &contract);
+
+		assert!(code.contains("include_str!(\"/abi/Proposal.json\")"));
+		assert!(code.contains(
+			"pub fn n_affirmative(&self, callback: impl FnOnce(Result<ethabi::Uint, crate::Error>) + 'static)"
+		));
+		assert!(code.contains(
+			"pub fn cast_vote_with_voter(&self, voter: ethabi::Address, nature: u8, weight: ethabi::Uint, ) -> crate::Call"
+		));
+		assert!(code
+			.contains("pub fn commitment(&self, prop: ethabi::Address, type_: ethabi::Address, "));
+		assert!(code.contains("VoteCast { voter: ethabi::Address, nature: u8 }"));
+		assert!(code.contains("pub const VOTE_CAST: &str = \"0x"));
+	}
+}
@@ -0,0 +1,221 @@
+use beacon_dao_web3::{
+	eth_call, BlockSelector, Error as Web3Error, Log, TransactionCall, WEB3_ADDR,
+};
+use ethabi::{ethereum_types::H256, Contract, Function, RawLog, Token};
+use serde::{Deserialize, Serialize};
+use vision_utils::types::Callback;
+
+/// Generation of the bindings from the ABIs hardhat exports. Used by the
+/// build script.
+#[cfg(test)]
+mod gen;
+
+/// Bindings to Idea.sol: an idea's ERC-20 token, which votes on the
+/// proposals that change the idea's metadata.
+pub mod idea {
+	include!(concat!(env!("OUT_DIR"), "/idea.rs"));
+}
+
+/// Bindings to MetaProp, declared in Idea.sol: a proposal to replace the
+/// metadata of its Idea with its payload.
+pub mod meta_prop {
+	include!(concat!(env!("OUT_DIR"), "/meta_prop.rs"));
+}
+
+/// Bindings to Proposal.sol.
+pub mod proposal {
+	include!(concat!(env!("OUT_DIR"), "/proposal.rs"));
+}
+
+/// Bindings to User.sol: a user's profile, which holds their encrypted key
+/// pair.
+pub mod user {
+	include!(concat!(env!("OUT_DIR"), "/user.rs"));
+}
+
+/// Bindings to RecoveryGroup.sol.
+pub mod recovery_group {
+	include!(concat!(env!("OUT_DIR"), "/recovery_group.rs"));
+}
+
+/// Bindings to IHasMetadata.sol, which every contract with IPFS metadata
+/// implements.
+pub mod has_metadata {
+	include!(concat!(env!("OUT_DIR"), "/has_metadata.rs"));
+}
+
+pub use has_metadata::IHasMetadata;
+pub use idea::Idea;
+pub use meta_prop::MetaProp;
+pub use proposal::Proposal;
+pub use recovery_group::RecoveryGroup;
+pub use user::User;
+
+/// Errors encountered while calling a contract.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	Web3(Web3Error),
+
+	/// The contract returned something other than what its ABI says
+	InvalidOutput,
+}
+
+/// A call to a function of a contract that changes its state, which is sent
+/// as a transaction by the wallet.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+	/// The address of the contract
+	pub to: String,
+
+	/// The calldata, as 0x-prefixed hex
+	pub data: String,
+}
+
+impl Call {
+	pub fn new(to: &str, function: &Function, args: &[Token]) -> Self {
+		Self {
+			to: to.to_owned(),
+			data: calldata(function, args),
+		}
+	}
+}
+
+/// Encodes a call of the function with the arguments. The bindings only pass
+/// arguments of the function's types, so this can't fail.
+fn calldata(function: &Function, args: &[Token]) -> String {
+	let mut data = function.short_signature().to_vec();
+	data.extend(ethabi::encode(args));
+
+	format!("0x{}", hex::encode(data))
+}
+
+/// Calls the view function of the contract at the address with eth_call,
+/// reading its outputs with read.
+pub(crate) fn call<T: 'static>(
+	to: &str,
+	function: &'static Function,
+	args: Vec<Token>,
+	read: fn(Vec<Token>) -> Option<T>,
+	callback: impl FnOnce(Result<T, Error>) + 'static,
+) {
+	eth_call(
+		WEB3_ADDR,
+		TransactionCall {
+			from: None,
+			to: to.to_owned(),
+			gas: None,
+			gasPrice: None,
+			value: None,
+			data: Some(calldata(function, &args)),
+		},
+		BlockSelector::Latest,
+		Callback::new(move |res: Result<String, Web3Error>| {
+			callback(res.map_err(Error::Web3).and_then(|out| {
+				hex::decode(out.trim_start_matches("0x"))
+					.ok()
+					.and_then(|bytes| function.decode_output(&bytes).ok())
+					.and_then(read)
+					.ok_or(Error::InvalidOutput)
+			}))
+		}),
+	);
+}
+
+/// Decodes the log as one of the events of the ABI, getting the topic of the
+/// event and its parameters, in the order they're declared.
+pub(crate) fn parse_log(log: &Log, abi: &Contract) -> Option<(String, std::vec::IntoIter<Token>)> {
+	let topics = log
+		.topics
+		.iter()
+		.map(|topic| {
+			let bytes = hex::decode(topic.trim_start_matches("0x")).ok()?;
+
+			(bytes.len() == 32).then(|| H256::from_slice(&bytes))
+		})
+		.collect::<Option<Vec<H256>>>()?;
+	let data = hex::decode(log.data.trim_start_matches("0x")).ok()?;
+
+	let topic = *topics.first()?;
+	let event = abi
+		.events()
+		.find(|event| !event.anonymous && event.signature() == topic)?;
+	let params: Vec<Token> = event
+		.parse_log(RawLog { topics, data })
+		.ok()?
+		.params
+		.into_iter()
+		.map(|param| param.value)
+		.collect();
+
+	Some((format!("{:#x}", topic), params.into_iter()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ABI: &str = r#"[
+		{"type": "function", "name": "castVote", "stateMutability": "nonpayable", "inputs": [{"name": "nature", "type": "uint8"}, {"name": "weight", "type": "uint256"}], "outputs": []},
+		{"type": "event", "name": "VoteCast", "anonymous": false, "inputs": [{"name": "voter", "type": "address", "indexed": true}, {"name": "nature", "type": "uint8", "indexed": false}, {"name": "weight", "type": "uint256", "indexed": false}]}
+	]"#;
+
+	#[test]
+	fn test_call() {
+		let abi: Contract = serde_json::from_str(ABI).unwrap();
+		let call = Call::new(
+			"0x02",
+			abi.function("castVote").unwrap(),
+			&[Token::Uint(1.into()), Token::Uint(5.into())],
+		);
+
+		assert_eq!(call.to, "0x02");
+		assert_eq!(
+			call.data,
+			format!(
+				"0x{}{:064x}{:064x}",
+				hex::encode(abi.function("castVote").unwrap().short_signature()),
+				1,
+				5
+			)
+		);
+	}
+
+	#[test]
+	fn test_parse_log() {
+		let abi: Contract = serde_json::from_str(ABI).unwrap();
+		let topic = format!("{:#x}", abi.event("VoteCast").unwrap().signature());
+		let mut log = Log {
+			address: String::from("0x02"),
+			topics: vec![
+				topic.clone(),
+				format!("0x{}{}", "00".repeat(12), "11".repeat(20)),
+			],
+			data: format!(
+				"0x{}",
+				hex::encode(ethabi::encode(&[
+					Token::Uint(1.into()),
+					Token::Uint(5.into())
+				]))
+			),
+			block_number: Some(1),
+			block_hash: None,
+			transaction_hash: None,
+			log_index: None,
+			removed: false,
+		};
+
+		let (parsed, params) = parse_log(&log, &abi).unwrap();
+		assert_eq!(parsed, topic);
+		assert_eq!(
+			params.collect::<Vec<Token>>(),
+			vec![
+				Token::Address([0x11; 20].into()),
+				Token::Uint(1.into()),
+				Token::Uint(5.into())
+			]
+		);
+
+		log.topics = vec![format!("0x{}", "00".repeat(32))];
+		assert!(parse_log(&log, &abi).is_none());
+	}
+}
@@ -0,0 +1,200 @@
+use ethabi::{Contract, Token};
+
+/// A call to a function of one of the contracts the OS knows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+	/// The signature of the function (e.g., castVote(uint8,uint256))
+	pub function: String,
+
+	/// The name and value of each argument, ready for display
+	pub args: Vec<(String, String)>,
+}
+
+/// Decodes the calldata against the functions of the contracts, by their
+/// selectors. Gets None if no function matches, or if the arguments don't
+/// decode as the function's.
+pub fn decode(contracts: &[Contract], data: &[u8]) -> Option<Call> {
+	if data.len() < 4 {
+		return None;
+	}
+
+	let (selector, args) = data.split_at(4);
+
+	contracts
+		.iter()
+		.flat_map(|contract| contract.functions())
+		.filter(|f| f.short_signature()[..] == *selector)
+		.find_map(|f| {
+			let tokens = f.decode_input(args).ok()?;
+
+			let kinds: Vec<String> = f
+				.inputs
+				.iter()
+				.map(|param| param.kind.to_string())
+				.collect();
+
+			Some(Call {
+				function: format!("{}({})", f.name, kinds.join(",")),
+				args: f
+					.inputs
+					.iter()
+					.zip(tokens.iter())
+					.enumerate()
+					.map(|(i, (param, token))| {
+						let name = if param.name.is_empty() {
+							format!("#{}", i)
+						} else {
+							param.name.clone()
+						};
+
+						(name, format_token(token))
+					})
+					.collect(),
+			})
+		})
+}
+
+/// Formats a decoded argument for display.
+pub fn format_token(token: &Token) -> String {
+	let list = |tokens: &[Token]| {
+		tokens
+			.iter()
+			.map(format_token)
+			.collect::<Vec<String>>()
+			.join(", ")
+	};
+
+	match token {
+		Token::Address(addr) => format!("0x{}", hex::encode(addr.as_bytes())),
+		Token::FixedBytes(bytes) | Token::Bytes(bytes) => format!("0x{}", hex::encode(bytes)),
+		Token::Uint(n) => n.to_string(),
+		Token::Int(n) if n.bit(255) => format!("-{}", (!*n).overflowing_add(1.into()).0),
+		Token::Int(n) => n.to_string(),
+		Token::Bool(b) => b.to_string(),
+		Token::String(s) => format!("{:?}", s),
+		Token::FixedArray(tokens) | Token::Array(tokens) => format!("[{}]", list(tokens)),
+		Token::Tuple(tokens) => format!("({})", list(tokens)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ethabi::ethereum_types::{Address, U256};
+
+	/// Part of the ABI of Proposal.sol and User.sol.
+	const ABI: &str = r#"[
+		{
+			"type": "function",
+			"name": "castVote",
+			"inputs": [
+				{ "name": "nature", "type": "uint8", "internalType": "enum VoteKind" },
+				{ "name": "weight", "type": "uint256", "internalType": "uint256" }
+			],
+			"outputs": [],
+			"stateMutability": "nonpayable"
+		},
+		{
+			"type": "function",
+			"name": "castVote",
+			"inputs": [
+				{ "name": "voter", "type": "address", "internalType": "address" },
+				{ "name": "nature", "type": "uint8", "internalType": "enum VoteKind" },
+				{ "name": "weight", "type": "uint256", "internalType": "uint256" }
+			],
+			"outputs": [],
+			"stateMutability": "nonpayable"
+		},
+		{
+			"type": "function",
+			"name": "setMetadata",
+			"inputs": [{ "name": "_ipfsAddr", "type": "string", "internalType": "string" }],
+			"outputs": [],
+			"stateMutability": "nonpayable"
+		}
+	]"#;
+
+	fn contracts() -> Vec<Contract> {
+		vec![serde_json::from_str(ABI).unwrap()]
+	}
+
+	fn calldata(contracts: &[Contract], name: &str, overload: usize, args: &[Token]) -> Vec<u8> {
+		contracts[0].functions_by_name(name).unwrap()[overload]
+			.encode_input(args)
+			.unwrap()
+	}
+
+	#[test]
+	fn test_decode() {
+		let contracts = contracts();
+
+		let data = calldata(
+			&contracts,
+			"castVote",
+			1,
+			&[
+				Token::Address(Address::repeat_byte(0x11)),
+				Token::Uint(1.into()),
+				Token::Uint(U256::exp10(18)),
+			],
+		);
+		assert_eq!(
+			decode(&contracts, &data),
+			Some(Call {
+				function: String::from("castVote(address,uint8,uint256)"),
+				args: vec![
+					(
+						String::from("voter"),
+						String::from("0x1111111111111111111111111111111111111111")
+					),
+					(String::from("nature"), String::from("1")),
+					(String::from("weight"), String::from("1000000000000000000")),
+				],
+			})
+		);
+
+		let data = calldata(
+			&contracts,
+			"setMetadata",
+			0,
+			&[Token::String(String::from("bafy\"quoted\""))],
+		);
+		assert_eq!(
+			decode(&contracts, &data).unwrap().args,
+			vec![(
+				String::from("_ipfsAddr"),
+				String::from("\"bafy\\\"quoted\\\"\"")
+			)]
+		);
+	}
+
+	#[test]
+	fn test_unknown() {
+		let contracts = contracts();
+
+		// Unknown selectors, and arguments that don't decode
+		assert_eq!(decode(&contracts, &[0xde, 0xad, 0xbe, 0xef]), None);
+		let mut data = calldata(
+			&contracts,
+			"castVote",
+			0,
+			&[Token::Uint(0.into()), Token::Uint(1.into())],
+		);
+		data.truncate(20);
+		assert_eq!(decode(&contracts, &data), None);
+		assert_eq!(decode(&contracts, &[]), None);
+	}
+
+	#[test]
+	fn test_format_token() {
+		assert_eq!(format_token(&Token::Int(U256::MAX)), "-1");
+		assert_eq!(format_token(&Token::Int(5.into())), "5");
+		assert_eq!(
+			format_token(&Token::Array(vec![
+				Token::Bool(true),
+				Token::Tuple(vec![Token::Bytes(vec![0xab]), Token::Uint(2.into())])
+			])),
+			"[true, (0xab, 2)]"
+		);
+	}
+}
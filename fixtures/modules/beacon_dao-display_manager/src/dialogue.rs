@@ -0,0 +1,268 @@
+use crate::{
+	template::{Html, Template},
+	wm::DIALOGUE_Z_INDEX,
+	DialogueError, DialogueKind, DialogueResponse,
+};
+
+/// Dialogues awaiting a response from the user, by slot. Each one has a
+/// random token that its script responds with, so that a response can't be
+/// forged by guessing the slot.
+#[derive(Debug)]
+pub struct Pending<C> {
+	slots: Vec<Option<(u32, DialogueKind, C)>>,
+}
+
+impl<C> Pending<C> {
+	pub const fn new() -> Self {
+		Self { slots: Vec::new() }
+	}
+
+	/// Records the dialogue, returning its slot.
+	pub fn open(&mut self, token: u32, kind: DialogueKind, callback: C) -> usize {
+		self.slots.push(Some((token, kind, callback)));
+
+		self.slots.len() - 1
+	}
+
+	/// Removes the dialogue in the slot, if its token matches, so that it is
+	/// only responded to once.
+	pub fn take(&mut self, slot: u32, token: u32) -> Option<(DialogueKind, C)> {
+		let entry = self.slots.get_mut(slot as usize)?;
+		if !matches!(entry, Some((expected, _, _)) if *expected == token) {
+			return None;
+		}
+
+		entry.take().map(|(_, kind, callback)| (kind, callback))
+	}
+}
+
+impl<C> Default for Pending<C> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Gets the buttons of a dialogue, from left to right, with the value each
+/// one responds with.
+pub fn buttons(kind: &DialogueKind) -> Vec<(u32, String)> {
+	match kind {
+		DialogueKind::Alert => Vec::new(),
+		DialogueKind::Affirm(label) => vec![(0, label.clone())],
+		DialogueKind::Choice(left, right) => vec![(0, left.clone()), (1, right.clone())],
+		DialogueKind::TextInput(_) | DialogueKind::Password(_) | DialogueKind::Select(_) => {
+			vec![(1, String::from("Cancel")), (0, String::from("Confirm"))]
+		}
+	}
+}
+
+/// Gets the markup of the field the user fills in for an input dialogue in
+/// the slot. The dialogue's script finds the field by the slot.
+pub fn field(kind: &DialogueKind, slot: usize) -> Html {
+	match kind {
+		DialogueKind::TextInput(placeholder) => {
+			Template::new(include_str!("./dialogue/input.html"))
+				.text("cbid", slot.to_string())
+				.text("type", "text")
+				.text("placeholder", placeholder)
+				.render()
+		}
+		DialogueKind::Password(placeholder) => Template::new(include_str!("./dialogue/input.html"))
+			.text("cbid", slot.to_string())
+			.text("type", "password")
+			.text("placeholder", placeholder)
+			.render(),
+		DialogueKind::Select(options) => Template::new(include_str!("./dialogue/select.html"))
+			.text("cbid", slot.to_string())
+			.html(
+				"options",
+				options
+					.iter()
+					.enumerate()
+					.map(|(i, label)| {
+						Template::new(include_str!("./dialogue/option.html"))
+							.text("value", i.to_string())
+							.text("label", label)
+							.render()
+					})
+					.collect(),
+			)
+			.render(),
+		DialogueKind::Alert | DialogueKind::Affirm(_) | DialogueKind::Choice(_, _) => {
+			Html::default()
+		}
+	}
+}
+
+/// Gets the markup of the dialogue in the slot, showing the details (e.g., of
+/// a transaction) above the field of its kind.
+pub fn markup(
+	title: &str,
+	description: &str,
+	kind: &DialogueKind,
+	details: Html,
+	slot: usize,
+) -> Html {
+	let buttons = buttons(kind)
+		.into_iter()
+		.map(|(value, label)| {
+			Template::new(include_str!("./dialogue/button.html"))
+				.text("value", value.to_string())
+				.text("label", label)
+				.render()
+		})
+		.collect();
+
+	Template::new(include_str!("./dialogue/dialogue.html"))
+		.text("title", title)
+		.text("desc", description)
+		.html("field", [details, field(kind, slot)].into_iter().collect())
+		.html("buttons", buttons)
+		.text("cbid", slot.to_string())
+		.text("zindex", DIALOGUE_Z_INDEX.to_string())
+		.render()
+}
+
+/// Gets the response to the dialogue when its button with the value is
+/// clicked. Input dialogues are only answered by their buttons when they're
+/// cancelled.
+pub fn button_response(kind: &DialogueKind, value: u32) -> Result<DialogueResponse, DialogueError> {
+	match (kind, value) {
+		(DialogueKind::Alert, _) => Ok(DialogueResponse::Dismissed),
+		(DialogueKind::Affirm(_), _) => Ok(DialogueResponse::Affirmed),
+		(DialogueKind::Choice(_, _), 0 | 1) => Ok(DialogueResponse::Chose(value)),
+		(DialogueKind::Choice(_, _), _) => Err(DialogueError::DisplayError),
+		_ => Err(DialogueError::Cancelled),
+	}
+}
+
+/// Gets the response to a select dialogue when the option at the index is
+/// confirmed.
+pub fn select_response(kind: &DialogueKind, index: u32) -> Result<DialogueResponse, DialogueError> {
+	match kind {
+		DialogueKind::Select(options) if (index as usize) < options.len() => {
+			Ok(DialogueResponse::Selected(index))
+		}
+		_ => Err(DialogueError::DisplayError),
+	}
+}
+
+/// Gets the response to an input dialogue when the text in its field, as
+/// JSON, is confirmed.
+pub fn input_response(kind: &DialogueKind, json: &str) -> Result<DialogueResponse, DialogueError> {
+	match (kind, serde_json::from_str::<String>(json)) {
+		(DialogueKind::TextInput(_) | DialogueKind::Password(_), Ok(text)) => {
+			Ok(DialogueResponse::Text(text))
+		}
+		_ => Err(DialogueError::DisplayError),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_field_ids() {
+		for kind in [
+			DialogueKind::TextInput(String::from("Name")),
+			DialogueKind::Password(String::from("Password")),
+			DialogueKind::Select(vec![String::from("a"), String::from("b")]),
+		] {
+			let html = markup("Title", "Description", &kind, Html::default(), 7);
+
+			// The script looks up the container and field by the slot
+			assert!(html.as_str().contains("id=\"dialogueContainer7\""));
+			assert!(html.as_str().contains("id=\"dialogueField7\""));
+			assert!(!html.as_str().contains("#cbid#"));
+		}
+
+		let html = markup(
+			"Title",
+			"Description",
+			&DialogueKind::Choice(String::from("No"), String::from("Yes")),
+			Html::default(),
+			3,
+		);
+		assert!(!html.as_str().contains("dialogueField"));
+	}
+
+	#[test]
+	fn test_pending() {
+		let mut pending = Pending::new();
+		let alert = pending.open(0xdead_beef, DialogueKind::Alert, "alert");
+		let choice = pending.open(
+			0x1234_5678,
+			DialogueKind::Choice(String::from("No"), String::from("Yes")),
+			"choice",
+		);
+		assert_eq!((alert, choice), (0, 1));
+
+		// Responses with the wrong token, or to a slot that was never
+		// opened, are ignored, and leave the dialogue open
+		assert!(pending.take(0, 0).is_none());
+		assert!(pending.take(0, 0x1234_5678).is_none());
+		assert!(pending.take(2, 0xdead_beef).is_none());
+
+		assert!(matches!(
+			pending.take(0, 0xdead_beef),
+			Some((DialogueKind::Alert, "alert"))
+		));
+		assert!(pending.take(0, 0xdead_beef).is_none());
+		assert_eq!(
+			pending.take(1, 0x1234_5678).map(|(_, cb)| cb),
+			Some("choice")
+		);
+	}
+
+	#[test]
+	fn test_responses() {
+		let password = DialogueKind::Password(String::from("Password"));
+		assert_eq!(
+			input_response(&password, "\"hunter2\""),
+			Ok(DialogueResponse::Text(String::from("hunter2")))
+		);
+		assert_eq!(
+			input_response(&password, "hunter2"),
+			Err(DialogueError::DisplayError)
+		);
+		assert_eq!(button_response(&password, 1), Err(DialogueError::Cancelled));
+
+		let select = DialogueKind::Select(vec![String::from("a"), String::from("b")]);
+		assert_eq!(
+			select_response(&select, 1),
+			Ok(DialogueResponse::Selected(1))
+		);
+		assert_eq!(
+			select_response(&select, 2),
+			Err(DialogueError::DisplayError)
+		);
+		assert_eq!(
+			input_response(&select, "\"a\""),
+			Err(DialogueError::DisplayError)
+		);
+
+		let choice = DialogueKind::Choice(String::from("No"), String::from("Yes"));
+		assert_eq!(button_response(&choice, 1), Ok(DialogueResponse::Chose(1)));
+		assert_eq!(
+			button_response(&choice, 2),
+			Err(DialogueError::DisplayError)
+		);
+	}
+
+	#[test]
+	fn test_field_escapes() {
+		let html = field(&DialogueKind::Select(vec![String::from("<b>a</b>")]), 0);
+
+		assert_eq!(
+			html.as_str(),
+			include_str!("./dialogue/select.html")
+				.replace("#cbid#", "0")
+				.replace(
+					"#options#",
+					&include_str!("./dialogue/option.html")
+						.replace("#value#", "0")
+						.replace("#label#", "&lt;b&gt;a&lt;/b&gt;")
+				)
+		);
+	}
+}
@@ -1,7 +1,31 @@
-const container = document.getElementById("dialogueContainer");
-container.style.opacity = "100%";
+const container = document.getElementById("dialogueContainer" + #cbid#);
+const field = document.getElementById("dialogueField" + #cbid#);
+const buttons = container.querySelectorAll(".dialogueButton");
 
-const buttons = [document.getElementById("leftButton"), document.getElementById("rightButton")];
+setTimeout(() => {
+	container.style.opacity = "100%";
+}, 0);
+
+const close = () => {
+	container.style.opacity = "0%";
+
+	setTimeout(() => {
+		container.remove();
+	}, 300);
+};
+
+// Buttons with a value of 0 submit the dialogue's field, if it has one
+const respond = (value) => {
+	close();
+
+	if (value === 0 && field && field.tagName === "SELECT") {
+		impulse(address(), "system_dialogue_select", Number(field.value), #cbid#, #token#);
+	} else if (value === 0 && field) {
+		impulse(address(), "system_dialogue_input", #cbid#, #token#, field.value);
+	} else {
+		impulse(address(), "system_dialogue_resp", value, #cbid#, #token#);
+	}
+};
 
 buttons.forEach((b) => {
 	b.addEventListener("mouseover", () => {
@@ -11,15 +35,18 @@ buttons.forEach((b) => {
 	b.addEventListener("mouseout", () => {
 		b.style.opacity = "100%";
 	});
-});
 
-const close = () => {
-	container.style.opacity = "0%";
+	b.addEventListener("click", (e) => {
+		e.stopPropagation();
+		respond(Number(b.dataset.value));
+	});
+});
 
-	setTimeout(() => {
-		container.remove();
-	}, 300);
-};
+// Alerts have no buttons, and are dismissed by clicking anywhere
+if (buttons.length === 0) {
+	container.addEventListener("click", () => respond(0));
+}
 
-buttons[0].addEventListener("click", () => {close(); impulse(address(), "system_dialogue_resp", 0, #cbid#)});
-buttons[1].addEventListener("click", () => {close(); impulse(address(), "system_dialogue_resp", 1, #cbid#)});
+if (field) {
+	field.focus();
+}
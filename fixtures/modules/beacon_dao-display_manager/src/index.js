@@ -47,7 +47,7 @@ login.addEventListener("click", next);
 
 // When the guest profile is activated, load its DAO modules
 guest.addEventListener("click", () => {
-	
+	impulse(address(), "login_as", "");
 });
 
 back.addEventListener("click", prev);
@@ -1,20 +1,27 @@
-use beacon_dao_dom::{create_element, eval_js};
+use beacon_dao_contracts::{idea, meta_prop, proposal, recovery_group, user, IHasMetadata};
+use beacon_dao_dom::{
+	close_window as dom_close_window, create as dom_create, create_element, eval_js, listen,
+	open_window as dom_open_window, read_event, remove as dom_remove, set_attribute, set_style,
+	set_text, Error as DomError,
+};
 use beacon_dao_fetch::{fetch_json, OptionsBuilder, Response};
 use beacon_dao_ipfs::{
-	change_rpc_endpoint as change_endpoint_ipfs, get, get_dag,
-	get_rpc_endpoint as get_endpoint_ipfs, Error as IpfsError, Format as IpfsFormat,
+	change_rpc_endpoint as change_endpoint_ipfs, get_dag, get_file, get_gateway_status,
+	get_rpc_endpoint as get_endpoint_ipfs, Error as IpfsError, Format as IpfsFormat, GatewayStatus,
 	Options as IpfsOptions,
 };
 use beacon_dao_logger_manager::info;
+use beacon_dao_permissions::reset_permissions;
+use beacon_dao_schema::{IdeaMetadata, IdeaPayload, Schema};
 use beacon_dao_web3::{
-	change_endpoint, eth_call, get_endpoint, BlockSelector, Error, TransactionCall,
-	DEFAULT_NETWORKS,
+	add_network, change_endpoint, ens, get_endpoint, list_networks, remove_network, resolve_name,
+	set_networks, Error, Network, Service, DEFAULT_NETWORKS, WALLET,
 };
-use ethabi::{Contract, Token};
+use ethabi::Contract;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use std::{
-	collections::HashMap,
+	ffi::CString,
 	sync::{
 		atomic::{AtomicUsize, Ordering},
 		Arc, Mutex, RwLock,
@@ -32,10 +39,50 @@ use vision_utils::{
 	},
 };
 
+/// Placement, stacking, and focus of app windows.
+pub mod wm;
+
+/// Rendering of the markup of system UI, with interpolated values escaped.
+pub mod template;
+
+/// The progress of loading the DAO modules in a user's profile.
+pub mod payload;
+
+/// Reading what transactions do from their calldata.
+pub mod calldata;
+
+/// The markup of system dialogues.
+pub mod dialogue;
+
+use dialogue::Pending;
+use payload::{LoadError, Progress, GUEST_MODULES};
+use template::{Html, Script, Template};
+pub use wm::{Error as WmError, Size, WindowEvent};
+use wm::{Events, WindowManager, DIALOGUE_Z_INDEX};
+
+/// The governance app, which is opened from the taskbar.
+pub static GOVERNANCE: Service = Service::new();
+
+/// Tells the display manager the address of the wallet. Sent by the runtime
+/// once it has spawned the wallet.
+#[cfg(feature = "module")]
+#[no_mangle]
+pub extern "C" fn handle_set_wallet(from: Address, addr: Address) {
+	WALLET.set(from, addr);
+}
+
+/// Tells the display manager the address of the governance app. Sent by the
+/// runtime once it has spawned the app.
+#[cfg(feature = "module")]
+#[no_mangle]
+pub extern "C" fn handle_set_governance(from: Address, addr: Address) {
+	GOVERNANCE.set(from, addr);
+}
+
 /// Kinds of dialogues supported by the display manager.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum DialogueKind {
-	/// A dialogue with no options
+	/// A dialogue with no options, dismissed by clicking anywhere
 	Alert,
 
 	/// A dialogue with only one option
@@ -43,6 +90,72 @@ pub enum DialogueKind {
 
 	/// A dialogue with two options
 	Choice(String, String),
+
+	/// A dialogue asking the user to enter text, with the given placeholder
+	TextInput(String),
+
+	/// A dialogue asking the user to enter a secret, with the given placeholder
+	Password(String),
+
+	/// A dialogue asking the user to pick one of the options
+	Select(Vec<String>),
+}
+
+/// The user's answer to a dialogue.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum DialogueResponse {
+	/// An alert was dismissed
+	Dismissed,
+
+	/// The option of an Affirm dialogue was chosen
+	Affirmed,
+
+	/// The left (0) or right (1) option of a Choice dialogue was chosen
+	Chose(u32),
+
+	/// The text entered in a TextInput or Password dialogue
+	Text(String),
+
+	/// The index of the option chosen in a Select dialogue
+	Selected(u32),
+}
+
+/// Reasons a dialogue may not produce a response.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DialogueError {
+	/// The actor may not display system dialogues
+	NoPermission,
+
+	/// The user cancelled an input dialogue
+	Cancelled,
+
+	/// The dialogue could not be displayed, or its response could not be read
+	DisplayError,
+}
+
+/// A transaction the wallet has been asked to sign, for the user to confirm.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PendingTransaction {
+	/// The actor asking for the transaction
+	pub actor: Address,
+
+	/// The name of the network the transaction is for
+	pub network: String,
+
+	pub from: String,
+
+	/// The account or contract called, or None to deploy a contract
+	pub to: Option<String>,
+
+	/// The value sent, and the most the transaction can cost in all, in units
+	/// of the network's currency (e.g., 0.25 ETH)
+	pub value: String,
+	pub max_cost: String,
+
+	pub gas: u64,
+
+	/// The calldata, as 0x-prefixed hex
+	pub data: String,
 }
 
 /// Spawns an actor from the given bytes
@@ -83,84 +196,100 @@ pub extern "C" fn handle_spawn_bytes(from: Address, bytes: Vec<u8>, callback: Ca
 	);
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct MetadataHaving {
-	payload: Vec<HashMap<String, String>>,
+/// Who is logged in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Session {
+	/// A temporary session, loading the modules bundled for guests. Nothing
+	/// done as a guest outlives the session.
+	Guest,
+
+	/// A session for the user at the Ethereum address
+	User(String),
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Payload {
-	module: Vec<HashMap<String, String>>,
+/// The current session, if anybody has logged in.
+static SESSION: RwLock<Option<Session>> = RwLock::new(None);
+
+/// Gets the address of the logged in user's profile, unless nobody, or a
+/// guest, is logged in.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get_user(from: Address, callback: Callback<Option<String>>) {
+	callback.call(current_user());
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Module(Vec<u8>);
+fn current_user() -> Option<String> {
+	SESSION.read().ok().and_then(|session| match &*session {
+		Some(Session::User(user)) => Some(user.clone()),
+		_ => None,
+	})
+}
 
-/// Loads the config profile at the specified Ethereum address.
+/// Loads the config profile at the specified Ethereum address or ENS name
+/// (e.g., alice.eth), or the guest profile if no address is given.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_login_as(from: Address, username: String, callback: Callback<u32>) {
 	// Login as the default 'guest' user
 	if username.is_empty() {
-		// TODO: Put some address here
+		login_as_guest(callback);
 
 		return;
 	}
 
-	// TODO: Resolve ENS name
-	// TODO: Resolve modules at that address
-	// Get the IPFS address of the metadata associated with the user account
-	// by calling the HasMetadata interface
-	let contract = if let Ok(contract) = serde_json::from_str::<Contract>(include_str!(
-		"../../../../abi/contracts/interfaces/IHasMetadata.sol/IHasMetadata.json"
-	)) {
-		contract
-	} else {
-		callback.call(EXIT_FAILURE);
+	if ens::is_address(&username) {
+		login_as_user(username, callback);
 
 		return;
-	};
+	}
 
-	let calldata = if let Ok(v) = contract.function("ipfsAddr").and_then(|f| {
-		f.encode_input(&[])
-			.map(|bytes| format!("0x{}", hex::encode(bytes)))
-	}) {
-		v
-	} else {
-		callback.call(EXIT_FAILURE);
+	// Find the address that the user's ENS name points to
+	resolve_name(
+		WEB3_ADDR,
+		username,
+		Callback::new(move |res: Result<String, Error>| match res {
+			Ok(addr) => login_as_user(addr, callback),
+			Err(_) => callback.call(EXIT_FAILURE),
+		}),
+	);
+}
 
-		return;
-	};
+/// Starts a session for the user, revoking every permission granted to
+/// whoever was logged in before. Calls the callback once the permissions have
+/// been reset, or fails the login if they couldn't be.
+fn start_session(
+	session: Session,
+	callback: Callback<u32>,
+	then: impl FnOnce(Callback<u32>) + 'static,
+) {
+	if let Ok(mut lock) = SESSION.write() {
+		*lock = Some(session);
+	}
 
-	eth_call(
-		WEB3_ADDR,
-		TransactionCall {
-			from: None,
-			to: username,
-			gas: None,
-			gasPrice: None,
-			value: None,
-			data: Some(calldata),
-		},
-		BlockSelector::Latest,
-		Callback::new(move |res: Result<String, Error>| {
-			// Convert 0x.. to raw bytes
-			let bytes = if let Some(v) = res.ok().and_then(|v| hex::decode(&v[2..]).ok()) {
-				v
-			} else {
+	reset_permissions(
+		PERM_ADDR,
+		Callback::new(move |stat| {
+			if stat != EXIT_SUCCESS {
 				callback.call(EXIT_FAILURE);
+
 				return;
-			};
+			}
 
-			// Use ABI to get CID
-			let cid = if let Some(cid) = contract
-				.function("ipfsAddr")
-				.ok()
-				.and_then(|f| f.decode_output(bytes.as_slice()).ok())
-				.and_then(|tokens| match tokens.get(0) {
-					Some(Token::String(s)) => Some(s.clone()),
-					_ => None,
-				}) {
+			then(callback);
+		}),
+	);
+}
+
+/// Loads the config profile at the Ethereum address.
+fn login_as_user(username: String, callback: Callback<u32>) {
+	start_session(Session::User(username.clone()), callback, move |callback| {
+		// Bring back the networks the user added in earlier sessions
+		restore_networks(&username);
+
+		// Get the IPFS address of the metadata associated with the user account
+		// by calling the HasMetadata interface
+		IHasMetadata::new(username).ipfs_addr(move |cid| {
+			let cid = if let Ok(cid) = cid {
 				cid
 			} else {
 				callback.call(EXIT_FAILURE);
@@ -170,7 +299,7 @@ pub extern "C" fn handle_login_as(from: Address, username: String, callback: Cal
 			// Get the content at the CID
 			get_dag(
 				IPFS_ADDR,
-				String::from(cid.clone()),
+				cid,
 				IpfsOptions {
 					format: Some(IpfsFormat::DagJson),
 				},
@@ -183,7 +312,7 @@ pub extern "C" fn handle_login_as(from: Address, username: String, callback: Cal
 						return;
 					};
 
-					let metadata: MetadataHaving = if let Ok(v) = serde_json::from_value(resp) {
+					let metadata = if let Ok(v) = IdeaMetadata::from_dag_json(&resp) {
 						v
 					} else {
 						callback.call(EXIT_FAILURE);
@@ -191,114 +320,275 @@ pub extern "C" fn handle_login_as(from: Address, username: String, callback: Cal
 						return;
 					};
 
-					let payload_cid = if let Some(cid) =
-						metadata.payload.get(0).and_then(|payload| payload.get("/"))
-					{
-						cid
-					} else {
-						callback.call(EXIT_FAILURE);
+					load_profile(metadata, callback);
+				}),
+			);
+		});
+	});
+}
 
-						return;
-					};
+/// Starts a guest session with a fresh set of permissions, loading the
+/// modules bundled for guests.
+fn login_as_guest(callback: Callback<u32>) {
+	start_session(Session::Guest, callback, |callback| {
+		// Networks added by whoever was logged in before aren't kept
+		set_networks(WEB3_ADDR, Vec::new(), Callback::new(|_| {}));
 
-					// Get the payload at the contained CID
-					// Register the user's callback for later
-					let slot = {
-						let mut slots = if let Ok(lock) = TASKS.write() {
-							lock
-						} else {
-							callback.call(2);
+		load_bundled(GUEST_MODULES, callback);
+	});
+}
 
-							return;
-						};
+/// A profile being loaded.
+struct Load {
+	progress: Progress,
 
-						let slot = slots.len();
-						slots.push(Some(callback));
+	/// The element displaying the progress, once it has been created
+	status: Option<u32>,
 
-						slot
-					};
+	/// The callback of whoever is waiting on the profile
+	callback: Callback<u32>,
+}
 
-					load_payload(
-						address(),
-						payload_cid.clone(),
-						slot as u32,
-						Callback::new(|_| {}),
-					);
-				}),
-			);
-		}),
-	)
+/// Profiles being loaded.
+static LOADS: RwLock<Vec<Option<Load>>> = RwLock::new(Vec::new());
+
+/// Starts loading a profile with the number of modules, showing how many have
+/// loaded on the login screen. Gets the ID of the load, or None if there is
+/// nothing to load, in which case the callback has already been called.
+fn begin_load(total: usize, callback: Callback<u32>) -> Option<usize> {
+	// Profiles without modules have nothing to load
+	if total == 0 {
+		callback.call(EXIT_SUCCESS);
+
+		return None;
+	}
+
+	let load_id = {
+		let mut loads = if let Ok(lock) = LOADS.write() {
+			lock
+		} else {
+			callback.call(EXIT_FAILURE);
+
+			return None;
+		};
+
+		loads.push(Some(Load {
+			progress: Progress::new(total),
+			status: None,
+			callback,
+		}));
+
+		loads.len() - 1
+	};
+
+	build(
+		"p",
+		None,
+		"position: absolute; bottom: 4em; left: 0; right: 0; text-align: center; color: white; z-index: 3",
+		move |status| {
+			let text = LOADS.write().ok().and_then(|mut loads| {
+				let load = loads.get_mut(load_id)?.as_mut()?;
+				load.status = Some(status);
+
+				Some(load.progress.status())
+			});
+
+			// Loading may have finished before the element was created
+			match text {
+				Some(text) => set_text(DOM_ADDR, status, text, Callback::new(|_| {})),
+				None => dom_remove(DOM_ADDR, status, Callback::new(|_| {})),
+			}
+		},
+	);
+
+	Some(load_id)
+}
+
+/// Spawns every module in the payload of the profile. Modules that fail to
+/// load are reported to the user once the rest have loaded.
+fn load_profile(metadata: IdeaMetadata, callback: Callback<u32>) {
+	let load_id = if let Some(load_id) = begin_load(metadata.payload.len(), callback) {
+		load_id
+	} else {
+		return;
+	};
+
+	for (i, link) in metadata.payload.into_iter().enumerate() {
+		load_payload(
+			address(),
+			load_id as u32,
+			i as u32,
+			link.cid().to_string(),
+			Callback::new(|_| {}),
+		);
+	}
+}
+
+/// Spawns every module bundled with the display manager in the list, by name.
+fn load_bundled(modules: &'static [(&'static str, &'static [u8])], callback: Callback<u32>) {
+	let load_id = if let Some(load_id) = begin_load(modules.len(), callback) {
+		load_id
+	} else {
+		return;
+	};
+
+	for (i, (name, bytes)) in modules.iter().enumerate() {
+		spawn_bytes(
+			address(),
+			bytes.to_vec(),
+			Callback::new(move |stat: u8| {
+				let res = if stat == EXIT_SUCCESS as u8 {
+					Ok(())
+				} else {
+					Err(LoadError::SpawnFailed)
+				};
+
+				record_payload(load_id, i, name.to_string(), res);
+			}),
+		);
+	}
+}
+
+/// Records the outcome of loading one of the modules of a profile, and
+/// finishes loading the profile if it was the last module.
+fn record_payload(load_id: usize, index: usize, cid: String, res: Result<(), LoadError>) {
+	let finished = if let Ok(mut loads) = LOADS.write() {
+		let load = if let Some(load) = loads.get_mut(load_id).and_then(|load| load.as_mut()) {
+			load
+		} else {
+			return;
+		};
+		load.progress.record(index, cid, res);
+
+		if load.progress.is_done() {
+			loads[load_id].take()
+		} else {
+			if let Some(status) = load.status {
+				set_text(
+					DOM_ADDR,
+					status,
+					load.progress.status(),
+					Callback::new(|_| {}),
+				);
+			}
+
+			None
+		}
+	} else {
+		return;
+	};
+
+	let Load {
+		progress,
+		status,
+		callback,
+	} = if let Some(load) = finished {
+		load
+	} else {
+		return;
+	};
+
+	if let Some(status) = status {
+		dom_remove(DOM_ADDR, status, Callback::new(|_| {}));
+	}
+
+	let errors = progress.errors();
+	if errors.is_empty() {
+		callback.call(EXIT_SUCCESS);
+
+		return;
+	}
+
+	for error in errors.iter() {
+		info(LOGGER_ADDR, error.clone(), Callback::new(|_| {}));
+	}
+
+	system_dialogue(
+		address(),
+		String::from("Some modules did not load"),
+		errors.join(" "),
+		DialogueKind::Alert,
+		Callback::new(|_| {}),
+	);
+
+	callback.call(EXIT_FAILURE);
 }
 
+/// Loads one of the modules of a profile being loaded: its loader is
+/// run in a sandboxed frame, and its module is spawned.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_load_payload(
 	from: Address,
+	load_id: u32,
+	index: u32,
 	payload_cid: String,
-	cb_id: u32,
 	callback: Callback<u32>,
 ) {
-	let callback = if let Some(task) = TASKS
-		.write()
-		.ok()
-		.and_then(|mut tasks| tasks.get_mut(cb_id as usize).and_then(|task| task.take()))
-	{
-		task
-	} else {
-		callback.call(1);
+	if from != address() {
+		callback.call(EXIT_FAILURE);
 
 		return;
-	};
+	}
 
+	callback.call(EXIT_SUCCESS);
+
+	let cid = payload_cid.clone();
+	spawn_payload(payload_cid, move |res| {
+		record_payload(load_id as usize, index as usize, cid, res)
+	});
+}
+
+/// Fetches the payload at the CID, runs its loader in a sandboxed frame, and
+/// spawns its module.
+fn spawn_payload(payload_cid: String, done: impl FnOnce(Result<(), LoadError>) + 'static) {
 	get_dag(
 		IPFS_ADDR,
-		String::from(payload_cid),
+		payload_cid,
 		IpfsOptions {
 			format: Some(IpfsFormat::DagJson),
 		},
 		Callback::new(move |resp: Result<Value, IpfsError>| {
-			let resp: Payload = if let Ok(resp) = resp.and_then(|resp| {
-				serde_json::from_value(resp).map_err(|_| IpfsError::SerializationError)
-			}) {
-				resp
+			let payload = if let Some(payload) = resp
+				.ok()
+				.and_then(|resp| IdeaPayload::from_dag_json(&resp).ok())
+			{
+				payload
 			} else {
-				callback.call(EXIT_FAILURE);
+				done(Err(LoadError::BadPayload));
 
 				return;
 			};
 
-			let payload_cid =
-				if let Some(cid) = resp.module.get(0).and_then(|payload| payload.get("/")) {
-					cid
-				} else {
-					callback.call(EXIT_FAILURE);
-
-					return;
-				};
-
-			// Load the binary module specified in the traversed data
-			get(
+			// The loader has to run before the module it loads
+			get_file(
 				IPFS_ADDR,
-				payload_cid.to_owned(),
-				IpfsOptions { format: None },
-				Callback::new(|resp: Result<Value, IpfsError>| {
-					let resp: Module = if let Ok(resp) = resp.and_then(|resp| {
-						serde_json::from_value(resp).map_err(|_| IpfsError::SerializationError)
-					}) {
-						resp
+				payload.loader.cid().to_string(),
+				Callback::new(move |resp: Result<Vec<u8>, IpfsError>| {
+					let src = if let Some(src) =
+						resp.ok().and_then(|bytes| String::from_utf8(bytes).ok())
+					{
+						src
 					} else {
-						callback.call(EXIT_FAILURE);
+						done(Err(LoadError::BadLoader));
 
 						return;
 					};
 
-					let Module(v) = resp;
+					// The loader isn't evaluated as the display manager, which
+					// could then answer dialogues
+					create_element(
+						DOM_ADDR,
+						String::from("div"),
+						payload::loader_frame(&src).into_string(),
+						Callback::new(move |stat: u8| {
+							if stat != 0 {
+								done(Err(LoadError::LoaderFailed));
+
+								return;
+							}
 
-					spawn_bytes(
-						address(),
-						v,
-						Callback::new(|_| {
-							callback.call(EXIT_SUCCESS);
+							spawn_module(payload.module.cid().to_string(), done);
 						}),
 					);
 				}),
@@ -307,13 +597,55 @@ pub extern "C" fn handle_load_payload(
 	);
 }
 
-/// System dialogue callbacks.
-pub static TASKS: RwLock<Vec<Option<Callback<u32>>>> = RwLock::new(Vec::new());
+/// Fetches the WASM module at the CID, and spawns an actor from it.
+fn spawn_module(module_cid: String, done: impl FnOnce(Result<(), LoadError>) + 'static) {
+	get_file(
+		IPFS_ADDR,
+		module_cid,
+		Callback::new(move |resp: Result<Vec<u8>, IpfsError>| {
+			let bytes = if let Ok(bytes) = resp {
+				bytes
+			} else {
+				done(Err(LoadError::BadModule));
+
+				return;
+			};
+
+			spawn_bytes(
+				address(),
+				bytes,
+				Callback::new(move |stat: u8| {
+					if stat == EXIT_SUCCESS as u8 {
+						done(Ok(()));
+					} else {
+						done(Err(LoadError::SpawnFailed));
+					}
+				}),
+			);
+		}),
+	);
+}
+
+/// Dialogues awaiting a response from the user.
+static DIALOGUES: RwLock<Pending<Callback<Result<DialogueResponse, DialogueError>>>> =
+	RwLock::new(Pending::new());
+
+/// Gets a random token for a dialogue to respond with.
+fn dialogue_token() -> Option<u32> {
+	extern "C" {
+		fn random_bytes(ptr: i32, len: i32) -> u8;
+	}
+
+	let mut token = [0u8; 4];
+	if unsafe { random_bytes(token.as_mut_ptr() as i32, token.len() as i32) } != 0 {
+		return None;
+	}
+
+	Some(u32::from_le_bytes(token))
+}
 
 /// Displays a dialogue on the screen with the given title, description, and nature.
-/// Calls the callback when the dialogue is dismissed, with a return value
-/// of 0 representing standard dismissal, and 1 representing the user choosing the
-/// right binary choice, and 2 representing an error.
+/// Calls the callback with the user's response once the dialogue is dismissed.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_system_dialogue(
@@ -321,141 +653,404 @@ pub extern "C" fn handle_system_dialogue(
 	title: String,
 	description: String,
 	kind: DialogueKind,
-	callback: Callback<u32>,
+	callback: Callback<Result<DialogueResponse, DialogueError>>,
 ) {
-	if from != PERM_ADDR && from != address() && from != PERM_AGENT_ADDR {
-		callback.call(2);
+	if from != PERM_ADDR && from != address() && from != PERM_AGENT_ADDR && !WALLET.is(from) {
+		callback.call(Err(DialogueError::NoPermission));
 
 		return;
 	}
 
-	// Register the user's callback for later
-	let slot = {
-		let mut slots = if let Ok(lock) = TASKS.write() {
-			lock
-		} else {
-			callback.call(2);
-
-			return;
-		};
-
-		let slot = slots.len();
-		slots.push(Some(callback));
+	open_dialogue(title, description, kind, Html::default(), callback);
+}
 
-		slot
-	};
+/// The kinds of the DAO's contracts, whose ABIs calldata sent to them is
+/// decoded against.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractKind {
+	Idea,
+	MetaProp,
+	Proposal,
+	User,
+	RecoveryGroup,
+}
 
-	match kind {
-		DialogueKind::Choice(yes, no) => create_element(
-			DOM_ADDR,
-			String::from("div"),
-			include_str!("./dialogue/dialogue.html")
-				.to_owned()
-				.replace("#yes#", &yes)
-				.replace("#no#", &no)
-				.replace("#title#", &title)
-				.replace("#desc#", &description),
-			Callback::new(move |_| {
-				eval_js(
-					DOM_ADDR,
-					include_str!("./dialogue/dialogue.js")
-						.to_owned()
-						.replace("#cbid#", &slot.to_string()),
-					Callback::new(|_| {}),
-				);
-			}),
-		),
-		// TODO:
-		_ => unimplemented!(),
+impl ContractKind {
+	fn abi(self) -> &'static Contract {
+		match self {
+			Self::Idea => idea::abi(),
+			Self::MetaProp => meta_prop::abi(),
+			Self::Proposal => proposal::abi(),
+			Self::User => user::abi(),
+			Self::RecoveryGroup => recovery_group::abi(),
+		}
 	}
 }
 
-/// Handle the user clicking on yes or no, and return that status code to the end user.
+/// The DAO's contracts the OS has been told about, by their addresses in
+/// lowercase.
+static KNOWN_CONTRACTS: RwLock<Vec<(String, ContractKind)>> = RwLock::new(Vec::new());
+
+/// Records that the contract at the address is one of the DAO's, so that
+/// transactions calling it are shown with what they do. Only the governance
+/// app, which finds the DAO's contracts, may do this.
 #[no_mangle]
 #[with_bindings]
-pub extern "C" fn handle_system_dialogue_resp(
+pub extern "C" fn handle_add_known_contract(
 	from: Address,
-	value: u32,
-	cb_id: u32,
-	cb: Callback<u32>,
+	address: String,
+	kind: ContractKind,
+	callback: Callback<Result<(), DialogueError>>,
 ) {
-	let task = if let Some(task) = TASKS
-		.write()
-		.ok()
-		.and_then(|mut tasks| tasks.get_mut(cb_id as usize).and_then(|task| task.take()))
-	{
-		task
-	} else {
-		cb.call(1);
+	if !GOVERNANCE.is(from) {
+		callback.call(Err(DialogueError::NoPermission));
 
 		return;
-	};
+	}
+
+	let address = address.to_lowercase();
+	if let Ok(mut known) = KNOWN_CONTRACTS.write() {
+		known.retain(|(known, _)| *known != address);
+		known.push((address, kind));
+	}
 
-	task.call(value);
+	callback.call(Ok(()));
 }
 
+/// Asks the user whether to send a transaction the wallet has been asked to
+/// sign, showing what its calldata does, and flagging calldata that calls
+/// none of the DAO's contracts.
 #[no_mangle]
-pub extern "C" fn handle_display_login(from: Address) {
-	create_element(
-		DOM_ADDR,
-		String::from("div"),
-		include_str!("./index.html").to_owned(),
-		Callback::new(|_| {
-			eval_js(
-				DOM_ADDR,
-				include_str!("./index.js").to_owned(),
-				Callback::new(|_| {}),
-			);
-		}),
-	);
+#[with_bindings]
+pub extern "C" fn handle_confirm_transaction(
+	from: Address,
+	tx: PendingTransaction,
+	callback: Callback<Result<bool, DialogueError>>,
+) {
+	if !WALLET.is(from) {
+		callback.call(Err(DialogueError::NoPermission));
 
-	// Add a task bar with some basic information on it
-	create_element(
-		DOM_ADDR,
-		String::from("div"),
-		include_str!("./taskbar/taskbar.html").to_owned(),
-		Callback::new(|_| {
-			eval_js(
-				DOM_ADDR,
-				include_str!("./taskbar/taskbar.js").to_owned(),
-				Callback::new(|_| {}),
-			);
+		return;
+	}
+
+	open_dialogue(
+		format!("Actor #{} Wants to Send a Transaction", tx.actor),
+		format!("From {} on {}", tx.from, tx.network),
+		DialogueKind::Choice(String::from("Reject"), String::from("Send")),
+		transaction_details(&tx),
+		Callback::new(move |resp: Result<DialogueResponse, DialogueError>| {
+			callback.call(resp.map(|resp| resp == DialogueResponse::Chose(1)))
 		}),
 	);
 }
 
-/// Displays the network chooser dialogue.
-#[no_mangle]
-pub extern "C" fn handle_change_network(from: Address, nonce: usize, callback: Callback<u32>) {
-	// Make the current network the bolded option
-	get_endpoint(
-		WEB3_ADDR,
-		Callback::new(move |curr_network| {
-			let curr_net_index = DEFAULT_NETWORKS
-				.iter()
-				.position(|x| x == &curr_network)
-				.unwrap();
+/// Gets the ABI of the contract at the address, if it's one of the DAO's
+/// contracts the OS knows.
+fn known_contract(address: &str) -> Option<Contract> {
+	let address = address.to_lowercase();
 
-			create_element(
-				DOM_ADDR,
-				String::from("div"),
-				include_str!("./netdialogue/netdialogue.html")
-					.to_owned()
-					.replace(
-						"#choices#",
-						&DEFAULT_NETWORKS.iter().enumerate().fold(String::new(), |acc, (i, net)| {
-							format!(
-								"{}\n<p class=\"netChoice\" style=\"margin: 0; margin-bottom: 0.5em; cursor: pointer; background-color: #8241BA; text-transform: uppercase; padding: 0.5em; border-radius: 0.25em; transition: 0.3s{}\">{}</p>",
-								acc, if i == curr_net_index { "; font-weight: bold" } else { "" }, net.name
-							)
-						}),
-					)
-					.replace("#curr#", &curr_net_index.to_string()),
-				Callback::new(move |_| {
-					eval_js(
+	KNOWN_CONTRACTS
+		.read()
+		.ok()?
+		.iter()
+		.find(|(known, _)| *known == address)
+		.map(|(_, kind)| kind.abi().clone())
+}
+
+/// Gets the markup of the details of a transaction: who it's sent to, what it
+/// costs, and the function it calls, if it calls one of the DAO's contracts
+/// the OS knows.
+fn transaction_details(tx: &PendingTransaction) -> Html {
+	let data = hex::decode(tx.data.trim_start_matches("0x")).unwrap_or_default();
+
+	let mut rows = vec![
+		(
+			String::from("To"),
+			tx.to
+				.clone()
+				.unwrap_or_else(|| String::from("A new contract")),
+		),
+		(String::from("Value"), tx.value.clone()),
+		(String::from("Gas limit"), tx.gas.to_string()),
+		(String::from("Max cost"), tx.max_cost.clone()),
+	];
+
+	// Plain transfers have no calldata to decode, and calldata is only
+	// decoded for the DAO's contracts, since any contract could have a
+	// function with the same selector that does something else
+	let contract = tx.to.as_deref().and_then(known_contract);
+	let call = contract
+		.as_ref()
+		.and_then(|contract| calldata::decode(std::slice::from_ref(contract), &data));
+	let warning = match call {
+		_ if data.is_empty() => None,
+		Some(call) => {
+			rows.push((String::from("Function"), call.function));
+			rows.extend(call.args);
+
+			None
+		}
+		None => {
+			rows.push((String::from("Data"), tx.data.clone()));
+
+			Some(if contract.is_some() {
+				format!(
+					"This transaction calls one of the DAO's contracts, but what it does can't be shown. Only send it if you trust Actor #{}.",
+					tx.actor
+				)
+			} else {
+				format!(
+					"This transaction calls an unknown contract, which isn't one of the DAO's, so what it does can't be shown. Only send it if you trust Actor #{}.",
+					tx.actor
+				)
+			})
+		}
+	};
+
+	Template::new(include_str!("./dialogue/transaction.html"))
+		.html(
+			"rows",
+			rows.into_iter()
+				.map(|(label, value)| {
+					Template::new(include_str!("./dialogue/detail.html"))
+						.text("label", label)
+						.text("value", value)
+						.render()
+				})
+				.collect(),
+		)
+		.html(
+			"warning",
+			warning
+				.map(|warning| {
+					Template::new(include_str!("./dialogue/warning.html"))
+						.text("warning", warning)
+						.render()
+				})
+				.unwrap_or_default(),
+		)
+		.render()
+}
+
+/// Displays a dialogue with the details, and the field and buttons of its
+/// kind, calling the callback with the user's response once it is dismissed.
+fn open_dialogue(
+	title: String,
+	description: String,
+	kind: DialogueKind,
+	details: Html,
+	callback: Callback<Result<DialogueResponse, DialogueError>>,
+) {
+	// Register the user's callback for later, with the token its script
+	// responds with
+	let (slot, token) = {
+		let (mut pending, token) =
+			if let (Ok(lock), Some(token)) = (DIALOGUES.write(), dialogue_token()) {
+				(lock, token)
+			} else {
+				callback.call(Err(DialogueError::DisplayError));
+
+				return;
+			};
+
+		(pending.open(token, kind.clone(), callback), token)
+	};
+
+	// The field is rendered once the slot it's found by is known
+	create_element(
+		DOM_ADDR,
+		String::from("div"),
+		dialogue::markup(&title, &description, &kind, details, slot).into_string(),
+		Callback::new(move |stat: u8| {
+			if stat != 0 {
+				respond(slot as u32, token, |_| Err(DialogueError::DisplayError));
+
+				return;
+			}
+
+			eval_js(
+				DOM_ADDR,
+				Script::new(include_str!("./dialogue/dialogue.js"))
+					.json("cbid", &slot)
+					.json("token", &token)
+					.render(),
+				Callback::new(|_| {}),
+			);
+		}),
+	);
+}
+
+/// Calls the callback of the dialogue with the response to it, if the
+/// response has the dialogue's token.
+fn respond(
+	cb_id: u32,
+	token: u32,
+	response: impl FnOnce(&DialogueKind) -> Result<DialogueResponse, DialogueError>,
+) {
+	if let Some((kind, callback)) = DIALOGUES
+		.write()
+		.ok()
+		.and_then(|mut pending| pending.take(cb_id, token))
+	{
+		callback.call(response(&kind));
+	}
+}
+
+/// Handles the user clicking one of the buttons of a dialogue.
+#[no_mangle]
+pub extern "C" fn handle_system_dialogue_resp(from: Address, value: u32, cb_id: u32, token: u32) {
+	// Responses come from the dialogue's script, which runs as the display manager
+	if from != address() {
+		return;
+	}
+
+	respond(cb_id, token, |kind| dialogue::button_response(kind, value));
+}
+
+/// Handles the user choosing an option of a select dialogue.
+#[no_mangle]
+pub extern "C" fn handle_system_dialogue_select(from: Address, index: u32, cb_id: u32, token: u32) {
+	if from != address() {
+		return;
+	}
+
+	respond(cb_id, token, |kind| dialogue::select_response(kind, index));
+}
+
+/// Handles the user submitting the text entered in an input dialogue, stored
+/// as JSON in the cell.
+#[no_mangle]
+pub extern "C" fn handle_system_dialogue_input(
+	from: Address,
+	cb_id: u32,
+	token: u32,
+	json_cell: Address,
+) {
+	if from != address() {
+		return;
+	}
+
+	read_cell_string(json_cell, move |json| {
+		respond(cb_id, token, |kind| dialogue::input_response(kind, &json));
+	});
+}
+
+#[no_mangle]
+pub extern "C" fn handle_display_login(from: Address) {
+	create_element(
+		DOM_ADDR,
+		String::from("div"),
+		include_str!("./index.html").to_owned(),
+		Callback::new(|_| {
+			eval_js(
+				DOM_ADDR,
+				include_str!("./index.js").to_owned(),
+				Callback::new(|_| {}),
+			);
+		}),
+	);
+
+	// Add a task bar with some basic information on it
+	create_element(
+		DOM_ADDR,
+		String::from("div"),
+		include_str!("./taskbar/taskbar.html").to_owned(),
+		Callback::new(|_| {
+			eval_js(
+				DOM_ADDR,
+				include_str!("./taskbar/taskbar.js").to_owned(),
+				Callback::new(|_| {}),
+			);
+		}),
+	);
+
+	// Add a strip holding an entry for each open window
+	build(
+		"div",
+		None,
+		"position: absolute; bottom: 0; left: 0; right: 0; display: flex; flex-flow: row nowrap; justify-content: center; padding: 0.5em; color: white; z-index: 2",
+		|strip| {
+			if let Ok(mut taskbar) = TASKBAR.write() {
+				*taskbar = Some(strip);
+			}
+		},
+	);
+
+	// Keep windows inside of the page as it's resized
+	eval_js(
+		DOM_ADDR,
+		include_str!("./viewport.js").to_owned(),
+		Callback::new(|_| {}),
+	);
+}
+
+/// Displays the network chooser dialogue.
+#[no_mangle]
+pub extern "C" fn handle_change_network(from: Address, nonce: usize, callback: Callback<u32>) {
+	show_networks();
+}
+
+/// Displays every network the user can switch to, letting them add networks,
+/// and remove the networks they added.
+fn show_networks() {
+	list_networks(
+		WEB3_ADDR,
+		Callback::new(move |networks: Vec<Network>| {
+			// Make the current network the bolded option
+			get_endpoint(
+				WEB3_ADDR,
+				Callback::new(move |curr_network: Network| {
+					let curr_net_index = networks.iter().position(|x| x == &curr_network);
+
+					create_element(
 						DOM_ADDR,
-						include_str!("./netdialogue/netdialogue.js").to_owned().replace("#curr#", &curr_net_index.to_string()),
-						Callback::new(|_| {}),
+						String::from("div"),
+						Template::new(include_str!("./netdialogue/netdialogue.html"))
+							.text("zindex", DIALOGUE_Z_INDEX.to_string())
+							.html(
+								"choices",
+								networks
+									.iter()
+									.enumerate()
+									.map(|(i, net)| {
+										Template::new(include_str!("./netdialogue/netchoice.html"))
+											.text(
+												"weight",
+												if Some(i) == curr_net_index {
+													"bold"
+												} else {
+													"normal"
+												},
+											)
+											.text("name", &net.name)
+											.html(
+												"remove",
+												if DEFAULT_NETWORKS.contains(net) {
+													Html::default()
+												} else {
+													Template::new(include_str!(
+														"./netdialogue/netremove.html"
+													))
+													.text("index", i.to_string())
+													.text("name", &net.name)
+													.render()
+												},
+											)
+											.render()
+									})
+									.collect(),
+							)
+							.render()
+							.into_string(),
+						Callback::new(move |_| {
+							eval_js(
+								DOM_ADDR,
+								Script::new(include_str!("./netdialogue/netdialogue.js"))
+									.json("curr", &curr_net_index)
+									.render(),
+								Callback::new(|_| {}),
+							);
+						}),
 					);
 				}),
 			);
@@ -463,6 +1058,35 @@ pub extern "C" fn handle_change_network(from: Address, nonce: usize, callback: C
 	);
 }
 
+/// Opens the governance app's window, or brings it to the front.
+#[no_mangle]
+pub extern "C" fn handle_open_governance(from: Address, nonce: usize, callback: Callback<u32>) {
+	extern "C" {
+		fn send_message(addr: Address, msg_name: i32, msg_buf: i32);
+	}
+
+	// Only the taskbar may open the app
+	if from != address() {
+		return;
+	}
+
+	let governance = if let Some(governance) = GOVERNANCE.get() {
+		governance
+	} else {
+		return;
+	};
+
+	let msg_name = CString::new("open").unwrap();
+
+	unsafe {
+		send_message(
+			governance,
+			msg_name.as_ptr() as i32,
+			[0u8; 0].as_ptr() as i32,
+		)
+	};
+}
+
 /// Displays the endpoint chooser dialogue.
 #[no_mangle]
 pub extern "C" fn handle_change_ipfs_endpoint(
@@ -473,17 +1097,46 @@ pub extern "C" fn handle_change_ipfs_endpoint(
 	get_endpoint_ipfs(
 		IPFS_ADDR,
 		Callback::new(move |curr: String| {
-			create_element(
-				DOM_ADDR,
-				String::from("div"),
-				include_str!("./netdialogue/ipfsdialogue.html")
-					.to_owned()
-					.replace("#curr#", &curr),
-				Callback::new(move |_| {
-					eval_js(
+			get_gateway_status(
+				IPFS_ADDR,
+				Callback::new(move |statuses: Vec<GatewayStatus>| {
+					create_element(
 						DOM_ADDR,
-						include_str!("./netdialogue/ipfsdialogue.js").to_owned(),
-						Callback::new(|_| {}),
+						String::from("div"),
+						Template::new(include_str!("./netdialogue/ipfsdialogue.html"))
+							.text("zindex", DIALOGUE_Z_INDEX.to_string())
+							.text("curr", curr)
+							.html(
+								"gateways",
+								statuses
+									.iter()
+									.map(|status| {
+										Template::new(include_str!(
+											"./netdialogue/gatewaystatus.html"
+										))
+										.text(
+											"colour",
+											if status.is_healthy() {
+												"white"
+											} else {
+												"#FFB3B3"
+											},
+										)
+										.text("url", &status.url)
+										.text("status", status.describe())
+										.render()
+									})
+									.collect(),
+							)
+							.render()
+							.into_string(),
+						Callback::new(move |_| {
+							eval_js(
+								DOM_ADDR,
+								include_str!("./netdialogue/ipfsdialogue.js").to_owned(),
+								Callback::new(|_| {}),
+							);
+						}),
 					);
 				}),
 			);
@@ -494,9 +1147,201 @@ pub extern "C" fn handle_change_ipfs_endpoint(
 /// Changes the web3 adapter network according to the chosen network.
 #[no_mangle]
 pub extern "C" fn handle_do_change_network(from: Address, index: u32, callback: Callback<u32>) {
-	change_endpoint(
+	if from != address() {
+		return;
+	}
+
+	with_network(index, |net| {
+		change_endpoint(
+			WEB3_ADDR,
+			net.clone(),
+			Callback::new(move |res: Result<(), Error>| {
+				if let Err(e) = res {
+					network_error(format!("Could not connect to {}", net.name), &net, e);
+				}
+			}),
+		);
+	});
+}
+
+/// Adds the network entered in the network chooser, stored as JSON in the
+/// cell, and shows the chooser again with it.
+#[no_mangle]
+pub extern "C" fn handle_do_add_network(from: Address, json_cell: Address) {
+	if from != address() {
+		return;
+	}
+
+	read_cell_string(json_cell, |json| {
+		let net = if let Some(net) = serde_json::from_str::<String>(&json)
+			.ok()
+			.and_then(|json| serde_json::from_str::<Network>(&json).ok())
+		{
+			net
+		} else {
+			system_dialogue(
+				address(),
+				String::from("Could not add network"),
+				String::from("Networks need a chain ID."),
+				DialogueKind::Alert,
+				Callback::new(|_| {}),
+			);
+
+			return;
+		};
+
+		add_network(
+			WEB3_ADDR,
+			net.clone(),
+			Callback::new(move |res: Result<(), Error>| match res {
+				Ok(_) => {
+					save_networks();
+					show_networks();
+				}
+				Err(e) => network_error(format!("Could not add {}", net.name), &net, e),
+			}),
+		);
+	});
+}
+
+/// Removes the chosen network, which the user added, and shows the network
+/// chooser again without it.
+#[no_mangle]
+pub extern "C" fn handle_do_remove_network(from: Address, index: u32) {
+	if from != address() {
+		return;
+	}
+
+	with_network(index, |net| {
+		remove_network(
+			WEB3_ADDR,
+			net.rpc_url,
+			Callback::new(|res: Result<(), Error>| {
+				if res.is_ok() {
+					save_networks();
+					show_networks();
+				}
+			}),
+		);
+	});
+}
+
+/// Gets the network at the index of the list shown in the network chooser.
+fn with_network(index: u32, f: impl FnOnce(Network) + 'static) {
+	list_networks(
+		WEB3_ADDR,
+		Callback::new(move |networks: Vec<Network>| {
+			if let Some(net) = networks.into_iter().nth(index as usize) {
+				f(net);
+			}
+		}),
+	);
+}
+
+/// Tells the user why the network couldn't be added or connected to.
+fn network_error(title: String, net: &Network, e: Error) {
+	let desc = match e {
+		Error::WrongChain(chain_id) => format!(
+			"The node at {} is on the chain with ID {}, rather than {}.",
+			net.rpc_url, chain_id, net.chain_id
+		),
+		Error::InvalidNetwork => String::from(
+			"Networks need a name, and an HTTP(S) RPC URL that isn't one of a default network's.",
+		),
+		Error::NoPermission => String::from("The network can't be changed right now."),
+		_ => format!("The node at {} could not be reached.", net.rpc_url),
+	};
+
+	system_dialogue(
+		address(),
+		title,
+		desc,
+		DialogueKind::Alert,
+		Callback::new(|_| {}),
+	);
+}
+
+/// The runtime's storage, which keeps each actor's values between sessions
+/// under keys only it can see.
+mod storage {
+	extern "C" {
+		pub fn storage_get(key: i32, len_ptr: i32) -> i32;
+		pub fn storage_set(key: i32, ptr: i32, len: i32) -> u8;
+		pub fn storage_remove(key: i32) -> u8;
+		pub fn read_buffer(token: u32, ptr: i32) -> u8;
+	}
+}
+
+/// The storage key the networks the user added are saved under.
+fn networks_key(user: &str) -> Option<CString> {
+	CString::new(format!("networks/{}", user.to_lowercase())).ok()
+}
+
+/// Saves the networks the logged in user added in the runtime's storage, so
+/// that they're restored when the user logs in again. Networks added by
+/// guests aren't saved.
+fn save_networks() {
+	let user = if let Some(user) = current_user() {
+		user
+	} else {
+		return;
+	};
+
+	list_networks(
 		WEB3_ADDR,
-		DEFAULT_NETWORKS[index as usize].clone(),
+		Callback::new(move |networks: Vec<Network>| {
+			let custom: Vec<Network> = networks
+				.into_iter()
+				.filter(|net| !DEFAULT_NETWORKS.contains(net))
+				.collect();
+
+			let (key, value) = if let (Some(key), Ok(value)) =
+				(networks_key(&user), serde_json::to_vec(&custom))
+			{
+				(key, value)
+			} else {
+				return;
+			};
+
+			unsafe {
+				if custom.is_empty() {
+					storage::storage_remove(key.as_ptr() as i32);
+				} else {
+					storage::storage_set(
+						key.as_ptr() as i32,
+						value.as_ptr() as i32,
+						value.len() as i32,
+					);
+				}
+			}
+		}),
+	);
+}
+
+/// Gets the networks saved for the user in the runtime's storage.
+fn load_networks(user: &str) -> Option<Vec<Network>> {
+	let key = networks_key(user)?;
+	let mut len = [0u8; 4];
+
+	let token = unsafe { storage::storage_get(key.as_ptr() as i32, len.as_mut_ptr() as i32) };
+	if token < 0 {
+		return None;
+	}
+
+	let mut value = vec![0u8; u32::from_le_bytes(len) as usize];
+	if unsafe { storage::read_buffer(token as u32, value.as_mut_ptr() as i32) } != 0 {
+		return None;
+	}
+
+	serde_json::from_slice(&value).ok()
+}
+
+/// Replaces the networks the user can switch to with the networks saved for
+/// them, so that they don't get the networks of whoever was logged in before.
+fn restore_networks(user: &str) {
+	set_networks(
+		WEB3_ADDR,
+		load_networks(user).unwrap_or_default(),
 		Callback::new(|_| {}),
 	);
 }
@@ -504,36 +1349,56 @@ pub extern "C" fn handle_do_change_network(from: Address, index: u32, callback:
 /// Changes the IPFS endpoint according to the chosen RPC URL.
 #[no_mangle]
 pub extern "C" fn handle_do_change_endpoint(from: Address, json_cell: Address) {
+	if from != address() {
+		return;
+	}
+
+	read_cell_string(json_cell, |read_str| {
+		change_endpoint_ipfs(IPFS_ADDR, read_str.replace("\"", ""), Callback::new(|_| {}));
+	});
+}
+
+/// Reads the string stored in the memory cell (e.g., a value passed in from
+/// JS), and calls the callback with it once every byte has been read.
+fn read_cell_string(cell: Address, callback: impl FnOnce(String) + 'static) {
+	let callback = Arc::new(Mutex::new(Some(callback)));
+
 	// Wait until all characters have been read
 	len(
-		json_cell,
+		cell,
 		Callback::new(move |to_read| {
-			// Make a string of n spaces
-			let buff = Arc::new(Mutex::new(
-				(0..to_read).map(|_| '\0').collect::<Vec<char>>(),
-			));
+			if to_read == 0 {
+				if let Some(callback) = callback.lock().unwrap().take() {
+					callback(String::new());
+				}
+
+				return;
+			}
+
+			let buff = Arc::new(Mutex::new(vec![0u8; to_read as usize]));
 			let n_read = Arc::new(AtomicUsize::new(0));
 
 			// Concurrently read n bytes
 			for i in 0..to_read {
 				let buff = buff.clone();
 				let n_read = n_read.clone();
+				let callback = callback.clone();
 
 				read(
-					json_cell,
+					cell,
 					i,
 					Callback::new(move |c: u8| {
-						*buff.lock().unwrap().get_mut(i as usize).unwrap() = c as char;
+						*buff.lock().unwrap().get_mut(i as usize).unwrap() = c;
 
 						// We read the last character
 						if n_read.fetch_add(1usize, Ordering::SeqCst) == (to_read as usize) - 1usize
 						{
-							let read_str: String = buff.lock().unwrap().drain(..).collect();
-							change_endpoint_ipfs(
-								IPFS_ADDR,
-								read_str.replace("\"", ""),
-								Callback::new(|_| {}),
-							);
+							let read_str =
+								String::from_utf8_lossy(&buff.lock().unwrap()).into_owned();
+
+							if let Some(callback) = callback.lock().unwrap().take() {
+								callback(read_str);
+							}
 						}
 					}),
 				);
@@ -541,3 +1406,402 @@ pub extern "C" fn handle_do_change_endpoint(from: Address, json_cell: Address) {
 		}),
 	);
 }
+
+lazy_static::lazy_static! {
+	/// The windows of every running app.
+	static ref WM: RwLock<WindowManager> = RwLock::new(WindowManager::default());
+}
+
+/// The strip along the bottom of the screen holding an entry for each window.
+static TASKBAR: RwLock<Option<u32>> = RwLock::new(None);
+
+/// Actions taken when the user interacts with the parts of a window drawn by
+/// the display manager. Listener IDs encode the window's owner and the action.
+const ACTION_FOCUS: u32 = 0;
+const ACTION_MINIMIZE: u32 = 1;
+const ACTION_CLOSE: u32 = 2;
+const ACTION_ENTRY: u32 = 3;
+
+fn listener_id(owner: Address, action: u32) -> u32 {
+	owner * 4 + action
+}
+
+/// Sends the events to the apps they concern, and redraws every window.
+fn dispatch(events: Events) {
+	extern "C" {
+		fn send_message(addr: Address, msg_name: i32, msg_buf: i32);
+	}
+
+	let msg_name = CString::new("window_event").unwrap();
+
+	for (to, event) in events {
+		let (kind, a, b) = event.encode();
+		let buf = [kind.to_le_bytes(), a.to_le_bytes(), b.to_le_bytes()].concat();
+
+		unsafe { send_message(to, msg_name.as_ptr() as i32, buf.as_ptr() as i32) };
+	}
+
+	redraw();
+}
+
+/// Updates the frames and taskbar entries of every window to match the window
+/// manager.
+fn redraw() {
+	let wm = if let Ok(wm) = WM.read() {
+		wm
+	} else {
+		return;
+	};
+	let focused = wm.focused();
+
+	for owner in wm.stack() {
+		let (window, z) = if let Some(w) = wm.get(*owner).zip(wm.z_index(*owner)) {
+			w
+		} else {
+			continue;
+		};
+
+		if let Some(frame) = window.frame {
+			for (prop, value) in [
+				("left", format!("{}px", window.x)),
+				("top", format!("{}px", window.y)),
+				("width", format!("{}px", window.size.width)),
+				("height", format!("{}px", window.size.height)),
+				("z-index", z.to_string()),
+				(
+					"display",
+					if window.minimized { "none" } else { "flex" }.to_owned(),
+				),
+			] {
+				set_style(
+					DOM_ADDR,
+					frame,
+					prop.to_owned(),
+					value,
+					Callback::new(|_| {}),
+				);
+			}
+		}
+
+		if let Some(entry) = window.entry {
+			set_style(
+				DOM_ADDR,
+				entry,
+				String::from("font-weight"),
+				if focused == Some(*owner) {
+					"bold"
+				} else {
+					"normal"
+				}
+				.to_owned(),
+				Callback::new(|_| {}),
+			);
+		}
+	}
+}
+
+/// Creates an element inside of the parent, styles it, and calls the callback
+/// with its handle.
+fn build(
+	kind: &str,
+	parent: Option<u32>,
+	style: &'static str,
+	callback: impl FnOnce(u32) + 'static,
+) {
+	dom_create(
+		DOM_ADDR,
+		kind.to_owned(),
+		parent,
+		Callback::new(move |handle: Result<u32, DomError>| {
+			if let Ok(handle) = handle {
+				set_attribute(
+					DOM_ADDR,
+					handle,
+					String::from("style"),
+					style.to_owned(),
+					Callback::new(|_| {}),
+				);
+
+				callback(handle);
+			}
+		}),
+	);
+}
+
+/// Creates a button that performs the action on the window when clicked.
+fn build_button(owner: Address, parent: u32, label: &str, action: u32) {
+	let label = label.to_owned();
+
+	build(
+		"span",
+		Some(parent),
+		"cursor: pointer; margin-left: 0.75em",
+		move |button| {
+			set_text(DOM_ADDR, button, label, Callback::new(|_| {}));
+			listen(
+				DOM_ADDR,
+				button,
+				String::from("click"),
+				listener_id(owner, action),
+				Callback::new(|_| {}),
+			);
+		},
+	);
+}
+
+/// Opens a window for the calling app with the given title and size, on top of
+/// every other window. Calls the callback with the DOM handle of the window's
+/// root container, which the app can fill with elements.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_open_window(
+	from: Address,
+	title: String,
+	size: Size,
+	callback: Callback<Result<u32, WmError>>,
+) {
+	let events = match WM
+		.write()
+		.map_err(|_| WmError::DomError)
+		.and_then(|mut wm| wm.open(from, title.clone(), size))
+	{
+		Ok(events) => events,
+		Err(e) => {
+			callback.call(Err(e));
+
+			return;
+		}
+	};
+
+	// Draw a frame around the window with a title bar and controls
+	build("div", None, "position: absolute; display: flex; flex-flow: column nowrap; background-color: #28044E; color: white; border-radius: 0.5em; overflow: hidden", move |frame| {
+		listen(
+			DOM_ADDR,
+			frame,
+			String::from("mousedown"),
+			listener_id(from, ACTION_FOCUS),
+			Callback::new(|_| {}),
+		);
+
+		build("div", Some(frame), "display: flex; flex-flow: row nowrap; justify-content: space-between; padding: 0.25em 0.75em; background-color: #5B2A86", move |bar| {
+			build("span", Some(bar), "", move |label| {
+				set_text(DOM_ADDR, label, title, Callback::new(|_| {}));
+			});
+
+			build("div", Some(bar), "display: flex; flex-flow: row nowrap", move |controls| {
+				build_button(from, controls, "_", ACTION_MINIMIZE);
+				build_button(from, controls, "×", ACTION_CLOSE);
+			});
+
+			// The app's window fills the rest of the frame
+			dom_open_window(
+				DOM_ADDR,
+				from,
+				Some(frame),
+				Callback::new(move |root: Result<u32, DomError>| {
+					let root = match root {
+						Ok(root) => root,
+						Err(_) => {
+							if let Ok(mut wm) = WM.write() {
+								let _ = wm.close(from);
+							}
+							dom_remove(DOM_ADDR, frame, Callback::new(|_| {}));
+
+							callback.call(Err(WmError::DomError));
+
+							return;
+						}
+					};
+
+					set_style(
+						DOM_ADDR,
+						root,
+						String::from("flex"),
+						String::from("1"),
+						Callback::new(|_| {}),
+					);
+
+					if let Some(window) = WM.write().ok().as_mut().and_then(|wm| wm.get_mut(from)) {
+						window.frame = Some(frame);
+					}
+
+					dispatch(events);
+					callback.call(Ok(root));
+				}),
+			);
+		});
+	});
+
+	// Add an entry for the window to the taskbar
+	let taskbar = if let Some(taskbar) = TASKBAR.read().ok().and_then(|taskbar| *taskbar) {
+		taskbar
+	} else {
+		return;
+	};
+
+	build(
+		"span",
+		Some(taskbar),
+		"cursor: pointer; margin: 0 0.75em",
+		move |entry| {
+			let title = WM
+				.write()
+				.ok()
+				.as_mut()
+				.and_then(|wm| wm.get_mut(from))
+				.map(|window| {
+					window.entry = Some(entry);
+
+					window.title.clone()
+				});
+
+			if let Some(title) = title {
+				set_text(DOM_ADDR, entry, title, Callback::new(|_| {}));
+				listen(
+					DOM_ADDR,
+					entry,
+					String::from("click"),
+					listener_id(from, ACTION_ENTRY),
+					Callback::new(|_| {}),
+				);
+			}
+
+			redraw();
+		},
+	);
+}
+
+/// Runs the operation on the calling app's window, notifying apps of the
+/// changes it caused.
+fn with_window(
+	op: impl FnOnce(&mut WindowManager) -> Result<Events, WmError>,
+	callback: Callback<Result<(), WmError>>,
+) {
+	let events = WM
+		.write()
+		.map_err(|_| WmError::DomError)
+		.and_then(|mut wm| op(&mut wm));
+
+	match events {
+		Ok(events) => {
+			dispatch(events);
+			callback.call(Ok(()));
+		}
+		Err(e) => callback.call(Err(e)),
+	}
+}
+
+/// Moves the calling app's window so that its top left corner is at (x, y).
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_move_window(
+	from: Address,
+	x: i32,
+	y: i32,
+	callback: Callback<Result<(), WmError>>,
+) {
+	with_window(|wm| wm.move_to(from, x, y), callback);
+}
+
+/// Changes the size of the calling app's window.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_resize_window(
+	from: Address,
+	size: Size,
+	callback: Callback<Result<(), WmError>>,
+) {
+	with_window(|wm| wm.resize(from, size), callback);
+}
+
+/// Hides the calling app's window. It stays in the taskbar.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_minimize_window(from: Address, callback: Callback<Result<(), WmError>>) {
+	with_window(|wm| wm.minimize(from), callback);
+}
+
+/// Raises the calling app's window above every other window, restoring it if
+/// it is minimized.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_focus_window(from: Address, callback: Callback<Result<(), WmError>>) {
+	with_window(|wm| wm.focus(from), callback);
+}
+
+/// Handles the page reporting its size, moving and shrinking windows so that
+/// they stay on the screen.
+#[no_mangle]
+pub extern "C" fn handle_set_viewport(from: Address, width: u32, height: u32) {
+	if from != address() {
+		return;
+	}
+
+	let events = WM
+		.write()
+		.map(|mut wm| wm.set_viewport(Size { width, height }));
+
+	if let Ok(events) = events {
+		dispatch(events);
+	}
+}
+
+/// Closes the app's window, removing it and its taskbar entry.
+fn close(owner: Address) -> Result<(), WmError> {
+	let (window, events) = WM
+		.write()
+		.map_err(|_| WmError::DomError)
+		.and_then(|mut wm| wm.close(owner))?;
+
+	// The app's window is closed before its frame, so that the DOM service
+	// forgets the app's elements
+	dom_close_window(
+		DOM_ADDR,
+		owner,
+		Callback::new(move |_| {
+			for handle in [window.frame, window.entry].into_iter().flatten() {
+				dom_remove(DOM_ADDR, handle, Callback::new(|_| {}));
+			}
+		}),
+	);
+
+	dispatch(events);
+
+	Ok(())
+}
+
+/// Closes the calling app's window.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_close_window(from: Address, callback: Callback<Result<(), WmError>>) {
+	callback.call(close(from));
+}
+
+/// Handles the user interacting with the frames and taskbar entries of
+/// windows.
+#[no_mangle]
+pub extern "C" fn handle_dom_event(from: Address, listener: u32, buf: u32, len: u32) {
+	// Only the runtime can deliver events
+	if from != 0 || read_event(buf, len).is_none() {
+		return;
+	}
+
+	let (owner, action) = (listener / 4, listener % 4);
+
+	let events = WM
+		.write()
+		.map_err(|_| WmError::DomError)
+		.and_then(|mut wm| match action {
+			ACTION_FOCUS => wm.focus(owner),
+			ACTION_MINIMIZE => wm.minimize(owner),
+			ACTION_ENTRY if wm.focused() == Some(owner) => wm.minimize(owner),
+			ACTION_ENTRY => wm.focus(owner),
+			_ => Ok(Vec::new()),
+		});
+
+	if action == ACTION_CLOSE {
+		let _ = close(owner);
+	} else if let Ok(events) = events {
+		dispatch(events);
+	}
+}
@@ -28,9 +28,45 @@ const close = () => {
 const nets = document.getElementsByClassName("netChoice");
 let selected = #curr#;
 
-buttons[1].addEventListener("click", () => {close(); impulse(address(), "do_change_network", selected)});
+buttons[1].addEventListener("click", () => {
+	close();
+
+	if (selected !== null) {
+		impulse(address(), "do_change_network", selected);
+	}
+});
 buttons[0].addEventListener("click", close);
 
+const add = document.getElementById("addNetwork");
+const field = (id) => document.getElementById(id).value.trim();
+
+add.addEventListener("click", () => {
+	close();
+	impulse(address(), "do_add_network", JSON.stringify({
+		chain_id: Number(field("networkChainId")),
+		name: field("networkName"),
+		ticker: field("networkTicker"),
+		rpc_url: field("networkRpcUrl"),
+	}));
+});
+
+Array.from(document.getElementsByClassName("netRemove")).concat([add]).forEach((b) => {
+	b.addEventListener("mouseover", () => {
+		b.style.opacity = "80%";
+	});
+
+	b.addEventListener("mouseout", () => {
+		b.style.opacity = "100%";
+	});
+});
+
+Array.from(document.getElementsByClassName("netRemove")).forEach((r) => {
+	r.addEventListener("click", () => {
+		close();
+		impulse(address(), "do_remove_network", Number(r.dataset.index));
+	});
+});
+
 Array.from(nets).forEach((n, i) => {
 	n.addEventListener("mouseover", () => {
 		n.style.opacity = "80%";
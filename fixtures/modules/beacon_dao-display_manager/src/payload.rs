@@ -0,0 +1,175 @@
+use crate::template::{Html, Template};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The modules loaded for guests, by name. They're bundled with the display
+/// manager, rather than fetched from IPFS, so that a guest session doesn't
+/// depend on any profile having been published. The workspace builds them
+/// before the display manager.
+#[cfg(feature = "module")]
+pub const GUEST_MODULES: &[(&str, &[u8])] = &[(
+	"Hello World",
+	include_bytes!("../../target/wasm32-unknown-unknown/release/beacon_dao_hello_world_alloc.wasm"),
+)];
+
+/// Modules are only bundled into builds of the display manager as a module.
+#[cfg(not(feature = "module"))]
+pub const GUEST_MODULES: &[(&str, &[u8])] = &[];
+
+/// Reasons a module in a profile may fail to load.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+	/// The payload could not be fetched, or is not an IdeaPayload
+	BadPayload,
+
+	/// The loader could not be fetched, or is not text
+	BadLoader,
+
+	/// The loader's frame could not be created
+	LoaderFailed,
+
+	/// The module could not be fetched
+	BadModule,
+
+	/// The module could not be spawned
+	SpawnFailed,
+}
+
+impl fmt::Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::BadPayload => "its payload could not be read",
+			Self::BadLoader => "its loader could not be read",
+			Self::LoaderFailed => "its loader failed",
+			Self::BadModule => "its module could not be read",
+			Self::SpawnFailed => "its module could not be started",
+		})
+	}
+}
+
+/// Gets the markup of the frame the loader is run in. Loaders are fetched
+/// from a profile, so they run in a sandboxed frame with an origin of its own,
+/// where they can't reach the page, or send messages as the display manager.
+pub fn loader_frame(src: &str) -> Html {
+	Template::new(include_str!("./loader.html"))
+		.text("src", src)
+		.render()
+}
+
+/// The progress of loading every module in a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+	total: usize,
+	loaded: usize,
+
+	/// The position and CID of each payload that failed to load, and why
+	failed: Vec<(usize, String, LoadError)>,
+}
+
+impl Progress {
+	pub fn new(total: usize) -> Self {
+		Self {
+			total,
+			loaded: 0,
+			failed: Vec::new(),
+		}
+	}
+
+	/// Records the outcome of loading the payload at the position.
+	pub fn record(&mut self, index: usize, cid: String, res: Result<(), LoadError>) {
+		match res {
+			Ok(_) => self.loaded += 1,
+			Err(e) => self.failed.push((index, cid, e)),
+		}
+	}
+
+	/// Checks that every payload has either loaded or failed.
+	pub fn is_done(&self) -> bool {
+		self.loaded + self.failed.len() >= self.total
+	}
+
+	pub fn failed(&self) -> &[(usize, String, LoadError)] {
+		&self.failed
+	}
+
+	/// Describes how many modules have loaded so far.
+	pub fn status(&self) -> String {
+		let mut status = format!("Loaded {} of {} modules", self.loaded, self.total);
+		if !self.failed.is_empty() {
+			status.push_str(&format!(" ({} failed)", self.failed.len()));
+		}
+
+		status
+	}
+
+	/// Describes why each module that failed to load failed, in the order
+	/// they appear in the profile.
+	pub fn errors(&self) -> Vec<String> {
+		let mut failed = self.failed.clone();
+		failed.sort_by_key(|(index, _, _)| *index);
+
+		failed
+			.into_iter()
+			.map(|(index, cid, e)| format!("Module {} ({}) did not load: {}.", index + 1, cid, e))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_progress() {
+		let mut progress = Progress::new(3);
+		assert_eq!(progress.status(), "Loaded 0 of 3 modules");
+
+		progress.record(1, String::from("b"), Ok(()));
+		progress.record(2, String::from("c"), Err(LoadError::SpawnFailed));
+		assert!(!progress.is_done());
+		assert_eq!(progress.status(), "Loaded 1 of 3 modules (1 failed)");
+
+		progress.record(0, String::from("a"), Err(LoadError::BadLoader));
+		assert!(progress.is_done());
+		assert_eq!(
+			progress.errors(),
+			vec![
+				String::from("Module 1 (a) did not load: its loader could not be read."),
+				String::from("Module 3 (c) did not load: its module could not be started."),
+			]
+		);
+	}
+
+	#[cfg(feature = "module")]
+	#[test]
+	fn test_guest_modules() {
+		assert!(!GUEST_MODULES.is_empty());
+
+		for (_, bytes) in GUEST_MODULES {
+			assert!(bytes.starts_with(b"\0asm"));
+		}
+	}
+
+	#[test]
+	fn test_loader_frame() {
+		let html = loader_frame("impulse(1, \"x\"); </script><script>alert(1)</script>");
+
+		// The loader can't end the attribute, or the frame, early
+		assert!(html
+			.as_str()
+			.starts_with("<iframe sandbox=\"allow-scripts\""));
+		assert!(!html.as_str().contains("allow-same-origin"));
+		assert!(html.as_str().contains(
+			"srcdoc=\"&lt;script&gt;impulse(1, &quot;x&quot;); &lt;/script&gt;&lt;script&gt;alert(1)&lt;/script&gt;&lt;/script&gt;\""
+		));
+		assert_eq!(html.as_str().matches('<').count(), 2);
+	}
+
+	#[test]
+	fn test_empty_progress() {
+		let progress = Progress::new(0);
+
+		assert!(progress.is_done());
+		assert!(progress.errors().is_empty());
+	}
+}
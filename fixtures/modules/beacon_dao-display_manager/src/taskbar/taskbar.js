@@ -34,3 +34,15 @@ ipfsButton.addEventListener("mouseout", () => {
 });
 
 ipfsButton.addEventListener("click", () => impulse(address(), "change_ipfs_endpoint", 0));
+
+const governanceButton = document.getElementById("governanceSelector");
+
+governanceButton.addEventListener("mouseover", () => {
+	governanceButton.style.opacity = "60%";
+});
+
+governanceButton.addEventListener("mouseout", () => {
+	governanceButton.style.opacity = "100%";
+});
+
+governanceButton.addEventListener("click", () => impulse(address(), "open_governance", 0));
@@ -0,0 +1,232 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A fragment of markup that is safe to insert into a page: either a bundled
+/// template, or the result of rendering one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Html(String);
+
+impl Html {
+	pub fn into_string(self) -> String {
+		self.0
+	}
+
+	pub fn as_str(&self) -> &str {
+		self.0.as_str()
+	}
+}
+
+impl FromIterator<Html> for Html {
+	fn from_iter<I: IntoIterator<Item = Html>>(iter: I) -> Self {
+		Self(iter.into_iter().map(|html| html.0).collect())
+	}
+}
+
+/// Escapes the text so that it is displayed as-is in an HTML element or a
+/// quoted attribute value, rather than interpreted as markup.
+pub fn escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			c => escaped.push(c),
+		}
+	}
+
+	escaped
+}
+
+/// A bundled template with #name# placeholders. Values given with text are
+/// escaped, and values given with html are inserted as-is. Placeholders are
+/// filled in one pass, so placeholders appearing inside of values are left
+/// alone.
+pub struct Template {
+	src: &'static str,
+	vars: HashMap<&'static str, String>,
+}
+
+impl Template {
+	pub fn new(src: &'static str) -> Self {
+		Self {
+			src,
+			vars: HashMap::new(),
+		}
+	}
+
+	/// Fills the placeholder with the text, escaped.
+	pub fn text(mut self, name: &'static str, value: impl AsRef<str>) -> Self {
+		self.vars.insert(name, escape(value.as_ref()));
+
+		self
+	}
+
+	/// Fills the placeholder with markup.
+	pub fn html(mut self, name: &'static str, value: Html) -> Self {
+		self.vars.insert(name, value.0);
+
+		self
+	}
+
+	pub fn render(self) -> Html {
+		let mut out = String::with_capacity(self.src.len());
+		let mut rest = self.src;
+
+		while let Some(start) = rest.find('#') {
+			out.push_str(&rest[..start]);
+			rest = &rest[start + 1..];
+
+			// Colors and selectors also contain #'s. Only names between two
+			// #'s that have been given values are placeholders.
+			let value = rest
+				.find('#')
+				.map(|end| &rest[..end])
+				.filter(|name| {
+					!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+				})
+				.and_then(|name| self.vars.get(name).map(|value| (name.len(), value)));
+
+			match value {
+				Some((len, value)) => {
+					out.push_str(value);
+					rest = &rest[len + 1..];
+				}
+				None => out.push('#'),
+			}
+		}
+
+		out.push_str(rest);
+
+		Html(out)
+	}
+}
+
+/// A bundled script with #name# placeholders, which are filled with values as
+/// JSON literals. Scripts aren't markup, so nothing in them is HTML-escaped.
+pub struct Script(Template);
+
+impl Script {
+	pub fn new(src: &'static str) -> Self {
+		Self(Template::new(src))
+	}
+
+	/// Fills the placeholder with the value as JSON. Characters that could end
+	/// a script early are escaped.
+	pub fn json(mut self, name: &'static str, value: &impl Serialize) -> Self {
+		let json = serde_json::to_string(value).unwrap_or_else(|_| String::from("null"));
+
+		let mut escaped = String::with_capacity(json.len());
+		for c in json.chars() {
+			match c {
+				'<' | '>' | '&' | '\u{2028}' | '\u{2029}' => {
+					escaped.push_str(&format!("\\u{:04x}", c as u32))
+				}
+				c => escaped.push(c),
+			}
+		}
+		self.0.vars.insert(name, escaped);
+
+		self
+	}
+
+	pub fn render(self) -> String {
+		self.0.render().0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_escape() {
+		assert_eq!(
+			escape("<img src=x onerror=\"alert('hi')\"> & more"),
+			"&lt;img src=x onerror=&quot;alert(&#39;hi&#39;)&quot;&gt; &amp; more"
+		);
+		assert_eq!(escape("plain text"), "plain text");
+	}
+
+	#[test]
+	fn test_render_escapes_text() {
+		let html = Template::new("<h1>#title#</h1><p title=\"#desc#\">#desc#</p>")
+			.text("title", "<script>alert(1)</script>")
+			.text("desc", "\" onmouseover=\"alert(1)")
+			.render();
+
+		assert_eq!(
+			html.as_str(),
+			"<h1>&lt;script&gt;alert(1)&lt;/script&gt;</h1><p title=\"&quot; onmouseover=&quot;alert(1)\">&quot; onmouseover=&quot;alert(1)</p>"
+		);
+	}
+
+	#[test]
+	fn test_render_inserts_html() {
+		let button = Template::new("<b>#label#</b>")
+			.text("label", "<ok>")
+			.render();
+		let html = Template::new("<div>#buttons#</div>")
+			.html("buttons", [button.clone(), button].into_iter().collect())
+			.render();
+
+		assert_eq!(
+			html.as_str(),
+			"<div><b>&lt;ok&gt;</b><b>&lt;ok&gt;</b></div>"
+		);
+	}
+
+	#[test]
+	fn test_render_leaves_other_hashes() {
+		let html =
+			Template::new("<p style=\"color: #5B2A86; background: #FFF\">#name# #missing#</p>")
+				.text("name", "x")
+				.render();
+
+		assert_eq!(
+			html.as_str(),
+			"<p style=\"color: #5B2A86; background: #FFF\">x #missing#</p>"
+		);
+	}
+
+	#[test]
+	fn test_render_json() {
+		let js = Script::new("localStorage.setItem(#key#, #value#);")
+			.json("key", &"</script>")
+			.json("value", &vec![("a\"b", 1)])
+			.render();
+
+		assert_eq!(
+			js,
+			"localStorage.setItem(\"\\u003c/script\\u003e\", [[\"a\\\"b\",1]]);"
+		);
+	}
+
+	#[test]
+	fn test_script_not_html_escaped() {
+		// Quotes in strings would be mangled by HTML escaping
+		let js = Script::new("let id = \"dialogue\" + #id#; let name = #name#;")
+			.json("id", &3)
+			.json("name", &"O'Brien \"Bob\"")
+			.render();
+
+		assert_eq!(
+			js,
+			"let id = \"dialogue\" + 3; let name = \"O'Brien \\\"Bob\\\"\";"
+		);
+	}
+
+	#[test]
+	fn test_render_single_pass() {
+		// Values that look like placeholders are not expanded
+		let html = Template::new("<h1>#title#</h1>#field#")
+			.text("title", "#field#")
+			.html("field", Html(String::from("<input>")))
+			.render();
+
+		assert_eq!(html.as_str(), "<h1>#field#</h1><input>");
+	}
+}
@@ -0,0 +1,4 @@
+const reportViewport = () => impulse(address(), "set_viewport", window.innerWidth, window.innerHeight);
+
+window.addEventListener("resize", reportViewport);
+reportViewport();
@@ -0,0 +1,588 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use vision_utils::types::Address;
+
+/// The distance between the corners of successively opened windows.
+const CASCADE_STEP: i32 = 32;
+
+/// The number of windows opened before the cascade wraps back to the corner.
+const CASCADE_LEN: i32 = 8;
+
+/// The position of the first window opened.
+const ORIGIN: (i32, i32) = (64, 64);
+
+/// The z-index of the bottom-most window. Windows stack above the login
+/// screen and taskbar.
+pub const BASE_Z_INDEX: usize = 10;
+
+/// The z-index of dialogues. Windows never stack above it.
+pub const DIALOGUE_Z_INDEX: usize = 1000;
+
+/// The smallest size a window can have.
+pub const MIN_SIZE: Size = Size {
+	width: 120,
+	height: 80,
+};
+
+/// The size of the screen assumed until the page reports its own.
+const DEFAULT_VIEWPORT: Size = Size {
+	width: 1280,
+	height: 720,
+};
+
+/// The number of pixels of a window kept on the screen, so that its title bar
+/// can always be reached.
+const VISIBLE: i32 = 48;
+
+/// The dimensions of a window, in pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+	pub width: u32,
+	pub height: u32,
+}
+
+/// Errors that may be encountered while managing windows.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	NoWindow,
+	WindowExists,
+	DomError,
+}
+
+/// A change to an app's window, sent to the app in a
+/// window_event(kind, a, b) message:
+///
+/// #[no_mangle]
+/// pub extern "C" fn handle_window_event(from: Address, kind: u32, a: i32, b: i32)
+///
+/// Apps should only accept events from the display manager, and can read them
+/// with WindowEvent::decode.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowEvent {
+	Opened,
+	Moved { x: i32, y: i32 },
+	Resized(Size),
+	Minimized,
+	Restored,
+	Focused,
+	Blurred,
+	Closed,
+}
+
+impl WindowEvent {
+	/// Gets the arguments of the window_event message describing the event.
+	pub fn encode(&self) -> (u32, i32, i32) {
+		match *self {
+			Self::Opened => (0, 0, 0),
+			Self::Moved { x, y } => (1, x, y),
+			Self::Resized(Size { width, height }) => (2, width as i32, height as i32),
+			Self::Minimized => (3, 0, 0),
+			Self::Restored => (4, 0, 0),
+			Self::Focused => (5, 0, 0),
+			Self::Blurred => (6, 0, 0),
+			Self::Closed => (7, 0, 0),
+		}
+	}
+
+	/// Reads the event from the arguments of a window_event message.
+	pub fn decode(kind: u32, a: i32, b: i32) -> Option<Self> {
+		Some(match kind {
+			0 => Self::Opened,
+			1 => Self::Moved { x: a, y: b },
+			2 => Self::Resized(Size {
+				width: u32::try_from(a).ok()?,
+				height: u32::try_from(b).ok()?,
+			}),
+			3 => Self::Minimized,
+			4 => Self::Restored,
+			5 => Self::Focused,
+			6 => Self::Blurred,
+			7 => Self::Closed,
+			_ => return None,
+		})
+	}
+}
+
+/// A window belonging to a running app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+	pub title: String,
+	pub x: i32,
+	pub y: i32,
+	pub size: Size,
+	pub minimized: bool,
+
+	/// The DOM handles of the frame around the window, and its taskbar entry,
+	/// once they have been created
+	pub frame: Option<u32>,
+	pub entry: Option<u32>,
+}
+
+/// Notifications to send to apps as the result of an operation.
+pub type Events = Vec<(Address, WindowEvent)>;
+
+/// The placement, stacking order, and focus of every app's window.
+#[derive(Debug)]
+pub struct WindowManager {
+	windows: HashMap<Address, Window>,
+
+	/// The owners of windows, from bottom-most to top-most
+	stack: Vec<Address>,
+
+	/// The number of windows opened so far, used for cascading
+	opened: i32,
+
+	/// The size of the screen that windows are kept inside of
+	viewport: Size,
+}
+
+impl Default for WindowManager {
+	fn default() -> Self {
+		Self {
+			windows: HashMap::new(),
+			stack: Vec::new(),
+			opened: 0,
+			viewport: DEFAULT_VIEWPORT,
+		}
+	}
+}
+
+impl WindowManager {
+	pub fn get(&self, owner: Address) -> Option<&Window> {
+		self.windows.get(&owner)
+	}
+
+	pub fn get_mut(&mut self, owner: Address) -> Option<&mut Window> {
+		self.windows.get_mut(&owner)
+	}
+
+	/// Gets the owners of every window, from bottom-most to top-most.
+	pub fn stack(&self) -> &[Address] {
+		&self.stack
+	}
+
+	/// Gets the app whose window has focus: the top-most window that isn't
+	/// minimized.
+	pub fn focused(&self) -> Option<Address> {
+		self.stack
+			.iter()
+			.rev()
+			.find(|owner| {
+				self.windows
+					.get(owner)
+					.map(|w| !w.minimized)
+					.unwrap_or(false)
+			})
+			.copied()
+	}
+
+	/// Gets the z-index that the app's window should be displayed at.
+	pub fn z_index(&self, owner: Address) -> Option<usize> {
+		self.stack
+			.iter()
+			.position(|o| *o == owner)
+			.map(|pos| (BASE_Z_INDEX + pos).min(DIALOGUE_Z_INDEX - 1))
+	}
+
+	pub fn viewport(&self) -> Size {
+		self.viewport
+	}
+
+	/// Gets the size closest to the requested size that fits on the screen.
+	fn fit_size(&self, size: Size) -> Size {
+		Size {
+			width: size
+				.width
+				.clamp(MIN_SIZE.width, self.viewport.width.max(MIN_SIZE.width)),
+			height: size
+				.height
+				.clamp(MIN_SIZE.height, self.viewport.height.max(MIN_SIZE.height)),
+		}
+	}
+
+	/// Gets the position closest to the requested position at which part of a
+	/// window of the size, including its title bar, stays on the screen.
+	fn fit_position(&self, size: Size, x: i32, y: i32) -> (i32, i32) {
+		let right = (self.viewport.width as i32 - VISIBLE).max(0);
+		let bottom = (self.viewport.height as i32 - VISIBLE).max(0);
+
+		(
+			x.clamp(VISIBLE.min(size.width as i32) - size.width as i32, right),
+			y.clamp(0, bottom),
+		)
+	}
+
+	/// Fits the app's window onto the screen, noting whether it had to be
+	/// moved or resized.
+	fn fit(&mut self, owner: Address, events: &mut Events) {
+		let window = if let Some(window) = self.windows.get(&owner) {
+			window
+		} else {
+			return;
+		};
+
+		let size = self.fit_size(window.size);
+		let (x, y) = self.fit_position(size, window.x, window.y);
+
+		if let Some(window) = self.windows.get_mut(&owner) {
+			if window.size != size {
+				window.size = size;
+				events.push((owner, WindowEvent::Resized(size)));
+			}
+
+			if (window.x, window.y) != (x, y) {
+				window.x = x;
+				window.y = y;
+				events.push((owner, WindowEvent::Moved { x, y }));
+			}
+		}
+	}
+
+	/// Changes the size of the screen, moving and shrinking windows so that
+	/// they stay on it.
+	pub fn set_viewport(&mut self, viewport: Size) -> Events {
+		self.viewport = Size {
+			width: viewport.width.min(i32::MAX as u32),
+			height: viewport.height.min(i32::MAX as u32),
+		};
+
+		let mut events = Vec::new();
+		for owner in self.stack.clone() {
+			self.fit(owner, &mut events);
+		}
+
+		events
+	}
+
+	/// Runs the operation, and notes changes in focus that it causes.
+	fn refocusing(&mut self, op: impl FnOnce(&mut Self, &mut Events)) -> Events {
+		let before = self.focused();
+		let mut events = Vec::new();
+
+		op(self, &mut events);
+
+		let after = self.focused();
+		if before != after {
+			if let Some(prev) = before.filter(|prev| self.windows.contains_key(prev)) {
+				events.push((prev, WindowEvent::Blurred));
+			}
+
+			if let Some(next) = after {
+				events.push((next, WindowEvent::Focused));
+			}
+		}
+
+		events
+	}
+
+	/// Opens a window for the app on top of every other window.
+	pub fn open(&mut self, owner: Address, title: String, size: Size) -> Result<Events, Error> {
+		if self.windows.contains_key(&owner) {
+			return Err(Error::WindowExists);
+		}
+
+		let offset = (self.opened % CASCADE_LEN) * CASCADE_STEP;
+		self.opened += 1;
+
+		let size = self.fit_size(size);
+		let (x, y) = self.fit_position(size, ORIGIN.0 + offset, ORIGIN.1 + offset);
+
+		Ok(self.refocusing(|wm, events| {
+			wm.windows.insert(
+				owner,
+				Window {
+					title,
+					x,
+					y,
+					size,
+					minimized: false,
+					frame: None,
+					entry: None,
+				},
+			);
+			wm.stack.push(owner);
+
+			events.push((owner, WindowEvent::Opened));
+		}))
+	}
+
+	/// Raises the app's window to the top, restoring it if it was minimized.
+	pub fn focus(&mut self, owner: Address) -> Result<Events, Error> {
+		if !self.windows.contains_key(&owner) {
+			return Err(Error::NoWindow);
+		}
+
+		Ok(self.refocusing(|wm, events| {
+			wm.stack.retain(|o| *o != owner);
+			wm.stack.push(owner);
+
+			if let Some(window) = wm.windows.get_mut(&owner).filter(|w| w.minimized) {
+				window.minimized = false;
+				events.push((owner, WindowEvent::Restored));
+			}
+		}))
+	}
+
+	/// Hides the app's window, moving focus to the next window down.
+	pub fn minimize(&mut self, owner: Address) -> Result<Events, Error> {
+		let window = self.windows.get(&owner).ok_or(Error::NoWindow)?;
+		if window.minimized {
+			return Ok(Vec::new());
+		}
+
+		Ok(self.refocusing(|wm, events| {
+			if let Some(window) = wm.windows.get_mut(&owner) {
+				window.minimized = true;
+			}
+
+			events.push((owner, WindowEvent::Minimized));
+		}))
+	}
+
+	/// Moves the app's window as close to (x, y) as it can go while staying on
+	/// the screen.
+	pub fn move_to(&mut self, owner: Address, x: i32, y: i32) -> Result<Events, Error> {
+		let size = self.windows.get(&owner).ok_or(Error::NoWindow)?.size;
+		let (x, y) = self.fit_position(size, x, y);
+
+		let window = self.windows.get_mut(&owner).ok_or(Error::NoWindow)?;
+		window.x = x;
+		window.y = y;
+
+		Ok(vec![(owner, WindowEvent::Moved { x, y })])
+	}
+
+	/// Resizes the app's window to the size closest to the requested size that
+	/// is no smaller than MIN_SIZE, and fits on the screen.
+	pub fn resize(&mut self, owner: Address, size: Size) -> Result<Events, Error> {
+		let size = self.fit_size(size);
+		self.windows.get_mut(&owner).ok_or(Error::NoWindow)?.size = size;
+
+		let mut events = vec![(owner, WindowEvent::Resized(size))];
+		self.fit(owner, &mut events);
+
+		Ok(events)
+	}
+
+	/// Forgets the app's window, moving focus to the next window down.
+	pub fn close(&mut self, owner: Address) -> Result<(Window, Events), Error> {
+		if !self.windows.contains_key(&owner) {
+			return Err(Error::NoWindow);
+		}
+
+		let mut closed = None;
+		let mut events = self.refocusing(|wm, _| {
+			closed = wm.windows.remove(&owner);
+			wm.stack.retain(|o| *o != owner);
+		});
+		events.insert(0, (owner, WindowEvent::Closed));
+
+		closed.map(|w| (w, events)).ok_or(Error::NoWindow)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SIZE: Size = Size {
+		width: 640,
+		height: 480,
+	};
+
+	#[test]
+	fn test_event_encoding() {
+		for event in [
+			WindowEvent::Opened,
+			WindowEvent::Moved { x: -5, y: 10 },
+			WindowEvent::Resized(SIZE),
+			WindowEvent::Minimized,
+			WindowEvent::Restored,
+			WindowEvent::Focused,
+			WindowEvent::Blurred,
+			WindowEvent::Closed,
+		] {
+			let (kind, a, b) = event.encode();
+			assert_eq!(WindowEvent::decode(kind, a, b), Some(event));
+		}
+
+		assert_eq!(WindowEvent::decode(99, 0, 0), None);
+	}
+
+	#[test]
+	fn test_open_focuses_and_cascades() {
+		let mut wm = WindowManager::default();
+
+		assert_eq!(
+			wm.open(1, "a".to_owned(), SIZE),
+			Ok(vec![(1, WindowEvent::Opened), (1, WindowEvent::Focused)])
+		);
+		assert_eq!(
+			wm.open(2, "b".to_owned(), SIZE),
+			Ok(vec![
+				(2, WindowEvent::Opened),
+				(1, WindowEvent::Blurred),
+				(2, WindowEvent::Focused)
+			])
+		);
+		assert_eq!(wm.open(2, "b".to_owned(), SIZE), Err(Error::WindowExists));
+
+		assert_ne!(
+			(wm.get(1).unwrap().x, wm.get(1).unwrap().y),
+			(wm.get(2).unwrap().x, wm.get(2).unwrap().y)
+		);
+		assert_eq!(wm.focused(), Some(2));
+		assert!(wm.z_index(2) > wm.z_index(1));
+	}
+
+	#[test]
+	fn test_focus_raises() {
+		let mut wm = WindowManager::default();
+		wm.open(1, "a".to_owned(), SIZE).unwrap();
+		wm.open(2, "b".to_owned(), SIZE).unwrap();
+
+		assert_eq!(
+			wm.focus(1),
+			Ok(vec![(2, WindowEvent::Blurred), (1, WindowEvent::Focused)])
+		);
+		assert_eq!(wm.stack(), &[2, 1]);
+
+		// Focusing the focused window changes nothing
+		assert_eq!(wm.focus(1), Ok(vec![]));
+		assert_eq!(wm.focus(3), Err(Error::NoWindow));
+	}
+
+	#[test]
+	fn test_minimize_and_restore() {
+		let mut wm = WindowManager::default();
+		wm.open(1, "a".to_owned(), SIZE).unwrap();
+		wm.open(2, "b".to_owned(), SIZE).unwrap();
+
+		assert_eq!(
+			wm.minimize(2),
+			Ok(vec![
+				(2, WindowEvent::Minimized),
+				(2, WindowEvent::Blurred),
+				(1, WindowEvent::Focused)
+			])
+		);
+		assert_eq!(wm.focused(), Some(1));
+		assert_eq!(wm.minimize(2), Ok(vec![]));
+
+		assert_eq!(
+			wm.focus(2),
+			Ok(vec![
+				(2, WindowEvent::Restored),
+				(1, WindowEvent::Blurred),
+				(2, WindowEvent::Focused)
+			])
+		);
+	}
+
+	#[test]
+	fn test_move_and_resize() {
+		let mut wm = WindowManager::default();
+		wm.open(1, "a".to_owned(), SIZE).unwrap();
+
+		wm.move_to(1, 5, 6).unwrap();
+		let size = Size {
+			width: 200,
+			height: 100,
+		};
+		assert_eq!(
+			wm.resize(1, size),
+			Ok(vec![(1, WindowEvent::Resized(size))])
+		);
+
+		let window = wm.get(1).unwrap();
+		assert_eq!((window.x, window.y, window.size), (5, 6, size));
+		assert_eq!(wm.move_to(2, 0, 0), Err(Error::NoWindow));
+	}
+
+	#[test]
+	fn test_geometry_clamped() {
+		let mut wm = WindowManager::default();
+		wm.open(1, "a".to_owned(), SIZE).unwrap();
+
+		assert_eq!(
+			wm.move_to(1, -10_000, -10_000),
+			Ok(vec![(
+				1,
+				WindowEvent::Moved {
+					x: VISIBLE - SIZE.width as i32,
+					y: 0
+				}
+			)])
+		);
+		assert_eq!(
+			wm.move_to(1, 10_000, 10_000),
+			Ok(vec![(
+				1,
+				WindowEvent::Moved {
+					x: DEFAULT_VIEWPORT.width as i32 - VISIBLE,
+					y: DEFAULT_VIEWPORT.height as i32 - VISIBLE
+				}
+			)])
+		);
+
+		assert_eq!(
+			wm.resize(
+				1,
+				Size {
+					width: 0,
+					height: 0
+				}
+			),
+			Ok(vec![(1, WindowEvent::Resized(MIN_SIZE))])
+		);
+		wm.resize(
+			1,
+			Size {
+				width: u32::MAX,
+				height: u32::MAX,
+			},
+		)
+		.unwrap();
+		assert_eq!(wm.get(1).unwrap().size, DEFAULT_VIEWPORT);
+
+		// Shrinking the screen pulls windows back onto it
+		let small = Size {
+			width: 400,
+			height: 300,
+		};
+		let events = wm.set_viewport(small);
+		assert!(events.contains(&(1, WindowEvent::Resized(small))));
+		let window = wm.get(1).unwrap();
+		assert!(window.x <= small.width as i32 - VISIBLE);
+		assert!(window.y <= small.height as i32 - VISIBLE);
+	}
+
+	#[test]
+	fn test_windows_below_dialogues() {
+		let mut wm = WindowManager::default();
+		for owner in 0..(DIALOGUE_Z_INDEX as u32 + 10) {
+			wm.open(owner, "a".to_owned(), SIZE).unwrap();
+		}
+
+		assert!(wm
+			.stack()
+			.iter()
+			.all(|owner| wm.z_index(*owner) < Some(DIALOGUE_Z_INDEX)));
+	}
+
+	#[test]
+	fn test_close_moves_focus() {
+		let mut wm = WindowManager::default();
+		wm.open(1, "a".to_owned(), SIZE).unwrap();
+		wm.open(2, "b".to_owned(), SIZE).unwrap();
+
+		let (closed, events) = wm.close(2).unwrap();
+		assert_eq!(closed.title, "b");
+		assert_eq!(
+			events,
+			vec![(2, WindowEvent::Closed), (1, WindowEvent::Focused)]
+		);
+		assert_eq!(wm.stack(), &[1]);
+		assert!(wm.close(2).is_err());
+	}
+}
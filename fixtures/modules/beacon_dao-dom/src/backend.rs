@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use vision_utils::types::Address;
+
+/// The handle referring to the document's body.
+pub const BODY: u32 = 0;
+
+/// Something that can build and modify a document on behalf of the DOM
+/// service. Elements are referred to by handles, which start at 1. Every
+/// operation returns None if it could not be performed.
+pub trait DomBackend {
+	/// Creates an element of the kind inside of the parent.
+	fn create(&mut self, kind: &str, parent: u32) -> Option<u32>;
+
+	/// Removes the element, and everything inside of it.
+	fn remove(&mut self, handle: u32) -> Option<()>;
+
+	fn set_attribute(&mut self, handle: u32, name: &str, value: &str) -> Option<()>;
+
+	fn set_style(&mut self, handle: u32, prop: &str, value: &str) -> Option<()>;
+
+	fn set_text(&mut self, handle: u32, text: &str) -> Option<()>;
+
+	/// Finds the element with the ID inside of the root, returning a handle to it.
+	fn query(&mut self, root: u32, id: &str) -> Option<u32>;
+
+	/// Has the owner notified with the listener's ID when the event fires on
+	/// the element.
+	fn listen(&mut self, handle: u32, event: &str, owner: Address, listener: u32) -> Option<()>;
+}
+
+/// Manipulates the page the runtime is embedded in, through the runtime's
+/// dom_* imports.
+#[cfg(target_arch = "wasm32")]
+#[derive(Default)]
+pub struct HostDom;
+
+#[cfg(target_arch = "wasm32")]
+mod host {
+	extern "C" {
+		pub fn dom_create(kind: i32, parent: i32) -> i32;
+		pub fn dom_remove(handle: i32) -> u8;
+		pub fn dom_set_attribute(handle: i32, name: i32, value: i32) -> u8;
+		pub fn dom_set_style(handle: i32, prop: i32, value: i32) -> u8;
+		pub fn dom_set_text(handle: i32, text: i32) -> u8;
+		pub fn dom_query(root: i32, id: i32) -> i32;
+		pub fn dom_listen(handle: i32, event: i32, owner: i32, listener: i32) -> u8;
+	}
+}
+
+#[cfg(target_arch = "wasm32")]
+fn c_str(s: &str) -> Option<std::ffi::CString> {
+	std::ffi::CString::new(s).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn status(stat: u8) -> Option<()> {
+	(stat == 0).then_some(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn handle(handle: i32) -> Option<u32> {
+	(handle > 0).then_some(handle as u32)
+}
+
+#[cfg(target_arch = "wasm32")]
+impl DomBackend for HostDom {
+	fn create(&mut self, kind: &str, parent: u32) -> Option<u32> {
+		let kind = c_str(kind)?;
+
+		handle(unsafe { host::dom_create(kind.as_ptr() as i32, parent as i32) })
+	}
+
+	fn remove(&mut self, handle: u32) -> Option<()> {
+		status(unsafe { host::dom_remove(handle as i32) })
+	}
+
+	fn set_attribute(&mut self, handle: u32, name: &str, value: &str) -> Option<()> {
+		let (name, value) = (c_str(name)?, c_str(value)?);
+
+		status(unsafe {
+			host::dom_set_attribute(handle as i32, name.as_ptr() as i32, value.as_ptr() as i32)
+		})
+	}
+
+	fn set_style(&mut self, handle: u32, prop: &str, value: &str) -> Option<()> {
+		let (prop, value) = (c_str(prop)?, c_str(value)?);
+
+		status(unsafe {
+			host::dom_set_style(handle as i32, prop.as_ptr() as i32, value.as_ptr() as i32)
+		})
+	}
+
+	fn set_text(&mut self, handle: u32, text: &str) -> Option<()> {
+		let text = c_str(text)?;
+
+		status(unsafe { host::dom_set_text(handle as i32, text.as_ptr() as i32) })
+	}
+
+	fn query(&mut self, root: u32, id: &str) -> Option<u32> {
+		let id = c_str(id)?;
+
+		handle(unsafe { host::dom_query(root as i32, id.as_ptr() as i32) })
+	}
+
+	fn listen(&mut self, handle: u32, event: &str, owner: Address, listener: u32) -> Option<()> {
+		let event = c_str(event)?;
+
+		status(unsafe {
+			host::dom_listen(
+				handle as i32,
+				event.as_ptr() as i32,
+				owner as i32,
+				listener as i32,
+			)
+		})
+	}
+}
+
+/// An element in a MemoryDom.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryNode {
+	pub kind: String,
+	pub parent: u32,
+	pub children: Vec<u32>,
+	pub attributes: HashMap<String, String>,
+	pub style: HashMap<String, String>,
+	pub text: String,
+
+	/// The event, owner, and listener ID of each listener on the element
+	pub listeners: Vec<(String, Address, u32)>,
+}
+
+/// A document kept entirely in memory, for running the DOM service without
+/// a page (e.g., in tests).
+#[derive(Debug)]
+pub struct MemoryDom {
+	next: u32,
+	nodes: HashMap<u32, MemoryNode>,
+}
+
+impl Default for MemoryDom {
+	fn default() -> Self {
+		let mut nodes = HashMap::new();
+		nodes.insert(
+			BODY,
+			MemoryNode {
+				kind: String::from("body"),
+				..MemoryNode::default()
+			},
+		);
+
+		Self { next: 0, nodes }
+	}
+}
+
+impl MemoryDom {
+	/// Gets the element with the handle.
+	pub fn node(&self, handle: u32) -> Option<&MemoryNode> {
+		self.nodes.get(&handle)
+	}
+
+	/// Gets the owners and IDs of the listeners that would be notified if the
+	/// event fired on the element.
+	pub fn fire(&self, handle: u32, event: &str) -> Vec<(Address, u32)> {
+		self.node(handle)
+			.map(|node| {
+				node.listeners
+					.iter()
+					.filter(|(kind, _, _)| kind == event)
+					.map(|(_, owner, listener)| (*owner, *listener))
+					.collect()
+			})
+			.unwrap_or_default()
+	}
+
+	fn descendants(&self, handle: u32) -> Vec<u32> {
+		let mut found = vec![handle];
+		let mut i = 0;
+
+		while let Some(next) = found.get(i).copied() {
+			if let Some(node) = self.nodes.get(&next) {
+				found.extend(node.children.iter().copied());
+			}
+
+			i += 1;
+		}
+
+		found
+	}
+}
+
+impl DomBackend for MemoryDom {
+	fn create(&mut self, kind: &str, parent: u32) -> Option<u32> {
+		self.nodes.get_mut(&parent)?.children.push(self.next + 1);
+		self.next += 1;
+		self.nodes.insert(
+			self.next,
+			MemoryNode {
+				kind: kind.to_owned(),
+				parent,
+				..MemoryNode::default()
+			},
+		);
+
+		Some(self.next)
+	}
+
+	fn remove(&mut self, handle: u32) -> Option<()> {
+		if handle == BODY {
+			return None;
+		}
+
+		let parent = self.nodes.get(&handle)?.parent;
+		if let Some(parent) = self.nodes.get_mut(&parent) {
+			parent.children.retain(|child| *child != handle);
+		}
+
+		for removed in self.descendants(handle) {
+			self.nodes.remove(&removed);
+		}
+
+		Some(())
+	}
+
+	fn set_attribute(&mut self, handle: u32, name: &str, value: &str) -> Option<()> {
+		self.nodes
+			.get_mut(&handle)?
+			.attributes
+			.insert(name.to_owned(), value.to_owned());
+
+		Some(())
+	}
+
+	fn set_style(&mut self, handle: u32, prop: &str, value: &str) -> Option<()> {
+		self.nodes
+			.get_mut(&handle)?
+			.style
+			.insert(prop.to_owned(), value.to_owned());
+
+		Some(())
+	}
+
+	fn set_text(&mut self, handle: u32, text: &str) -> Option<()> {
+		let node = self.nodes.get_mut(&handle)?;
+		node.text = text.to_owned();
+
+		// Setting the text of an element replaces its children
+		for child in std::mem::take(&mut node.children) {
+			for removed in self.descendants(child) {
+				self.nodes.remove(&removed);
+			}
+		}
+
+		Some(())
+	}
+
+	fn query(&mut self, root: u32, id: &str) -> Option<u32> {
+		self.descendants(root).into_iter().skip(1).find(|handle| {
+			self.nodes
+				.get(handle)
+				.and_then(|node| node.attributes.get("id"))
+				.map(|found| found == id)
+				.unwrap_or(false)
+		})
+	}
+
+	fn listen(&mut self, handle: u32, event: &str, owner: Address, listener: u32) -> Option<()> {
+		self.nodes
+			.get_mut(&handle)?
+			.listeners
+			.push((event.to_owned(), owner, listener));
+
+		Some(())
+	}
+}
@@ -1,31 +1,56 @@
 pub use beacon_dao_permissions;
-use beacon_dao_permissions::{has_permission, register_permission};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
 use vision_derive::with_bindings;
-use vision_utils::types::{Address, Callback, DISPLAY_MANAGER_ADDR, FETCH_ADDR, PERM_ADDR};
+use vision_utils::types::{Address, Callback, DISPLAY_MANAGER_ADDR};
 
-const PERM: &'static str = "control your computer";
-const DESCRIPTION: &'static str =
-	"change what's on your display, your preferences, and your password.";
+/// Implementations of the document that the DOM service manipulates.
+pub mod backend;
 
-#[cfg(feature = "module")]
-#[no_mangle]
-pub extern "C" fn handle_init_async(owner: Address) {
-	register_permission(
-		PERM_ADDR,
-		PERM.to_owned(),
-		DESCRIPTION.to_owned(),
-		Callback::new(|_| {}),
-	);
+/// Per-actor windows, and the rules for which actors may touch which elements.
+pub mod window;
+
+use window::Windows;
+
+#[cfg(target_arch = "wasm32")]
+type Backend = backend::HostDom;
+
+#[cfg(not(target_arch = "wasm32"))]
+type Backend = backend::MemoryDom;
+
+/// The windows of every actor, and the elements inside of them.
+static WINDOWS: Lazy<RwLock<Windows<Backend>>> =
+	Lazy::new(|| RwLock::new(Windows::new(Backend::default())));
+
+/// Errors that may be encountered while manipulating the DOM.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	NoPermission,
+	NotOwner,
+	NotFound,
+	NoWindow,
+	WindowExists,
+	SerializationError,
+	HostError,
+}
+
+/// An event fired on an element that an actor is listening to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DomEvent {
+	/// The type of the event (e.g., "click")
+	pub kind: String,
+
+	/// The value of the target of the event, if it is an input
+	pub value: Option<String>,
+
+	/// The key pressed, if the event came from the keyboard
+	pub key: Option<String>,
 }
 
 /// Returns Ok(()) if the element was created successfully, or Err otherwise.
-fn do_create_element(
-	has_permission: bool,
-	from: Address,
-	kind: String,
-	src: String,
-) -> Result<u8, ()> {
-	if !has_permission && from != DISPLAY_MANAGER_ADDR && from != FETCH_ADDR {
+fn do_create_element(from: Address, kind: String, src: String) -> Result<u8, ()> {
+	if from != DISPLAY_MANAGER_ADDR {
 		return Err(());
 	}
 
@@ -39,8 +64,9 @@ fn do_create_element(
 	unsafe { Ok(append_element(kind.as_ptr() as i32, elem.as_ptr() as i32)) }
 }
 
-/// Appends a new element to the DOM, returning 0 if successful, and 1 if unsuccessful,
-/// if the caller has permission to do so.
+/// Appends a new element of raw HTML to the document's body, returning 0 if
+/// successful, and 1 if unsuccessful. Only the display manager may do this;
+/// other actors work inside of their windows.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_create_element(
@@ -49,23 +75,12 @@ pub extern "C" fn handle_create_element(
 	src: String,
 	callback: Callback<u8>,
 ) {
-	has_permission(
-		PERM_ADDR,
-		from,
-		PERM.to_owned(),
-		Callback::new(move |has_permission: bool| {
-			if let Ok(stat) = do_create_element(has_permission, from, kind, src) {
-				callback.call(stat);
-			} else {
-				callback.call(1);
-			}
-		}),
-	);
+	callback.call(do_create_element(from, kind, src).unwrap_or(1));
 }
 
 /// Returns Ok(()) if the JS was evaluated successfully.
-fn do_eval_js(has_permission: bool, from: Address, src: String) -> Result<u8, ()> {
-	if !has_permission && from != DISPLAY_MANAGER_ADDR && from != FETCH_ADDR {
+fn do_eval_js(from: Address, src: String) -> Result<u8, ()> {
+	if from != DISPLAY_MANAGER_ADDR {
 		return Err(());
 	}
 
@@ -89,20 +104,171 @@ fn do_eval_js(has_permission: bool, from: Address, src: String) -> Result<u8, ()
 	unsafe { Ok(eval_js(src.as_ptr() as i32)) }
 }
 
-/// Executes arbitrary JS if the user has permissions to do so.
+/// Executes arbitrary JS against the page. Only the display manager may do
+/// this.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_eval_js(from: Address, src: String, callback: Callback<u8>) {
-	has_permission(
-		PERM_ADDR,
-		from,
-		PERM.to_owned(),
-		Callback::new(move |has_permission: bool| {
-			if let Ok(stat) = do_eval_js(has_permission, from, src) {
-				callback.call(stat);
-			} else {
-				callback.call(1);
-			}
-		}),
-	);
+	callback.call(do_eval_js(from, src).unwrap_or(1));
+}
+
+/// Reads the event delivered with a dom_event(listener, buf, len) message.
+/// Actors listening to elements should implement the handler, and only accept
+/// events from the runtime (address 0):
+///
+/// #[no_mangle]
+/// pub extern "C" fn handle_dom_event(from: Address, listener: u32, buf: u32, len: u32)
+pub fn read_event(buf: u32, len: u32) -> Option<DomEvent> {
+	extern "C" {
+		fn read_buffer(token: u32, ptr: i32) -> u8;
+	}
+
+	let mut body = vec![0u8; len as usize];
+	if unsafe { read_buffer(buf, body.as_mut_ptr() as i32) } != 0 {
+		return None;
+	}
+
+	serde_json::from_slice(&body).ok()
+}
+
+/// Runs the operation against the windows of every actor.
+fn with_windows<T>(op: impl FnOnce(&mut Windows<Backend>) -> Result<T, Error>) -> Result<T, Error> {
+	op(&mut *WINDOWS.write().map_err(|_| Error::HostError)?)
+}
+
+/// Creates a window for the owner actor inside of the parent element, or the
+/// document's body, returning the handle of its root container. Only the
+/// display manager may do this.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_open_window(
+	from: Address,
+	owner: Address,
+	parent: Option<u32>,
+	callback: Callback<Result<u32, Error>>,
+) {
+	callback.call(with_windows(|windows| {
+		windows.open_window(from, owner, parent.unwrap_or(backend::BODY))
+	}));
+}
+
+/// Removes the owner actor's window, and everything inside of it. Only the
+/// display manager may do this.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_close_window(
+	from: Address,
+	owner: Address,
+	callback: Callback<Result<(), Error>>,
+) {
+	callback.call(with_windows(|windows| windows.close_window(from, owner)));
+}
+
+/// Gets the handle of the root container of the actor's window.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_window_root(from: Address, callback: Callback<Result<u32, Error>>) {
+	callback.call(with_windows(|windows| {
+		windows.root(from).ok_or(Error::NoWindow)
+	}));
+}
+
+/// Creates an element of the kind inside of the parent element, or the root
+/// of the actor's window if no parent is given, returning a handle to it.
+/// Elements that run scripts or embed documents (e.g., script, iframe) can't
+/// be created.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_create(
+	from: Address,
+	kind: String,
+	parent: Option<u32>,
+	callback: Callback<Result<u32, Error>>,
+) {
+	callback.call(with_windows(|windows| windows.create(from, &kind, parent)));
+}
+
+/// Removes the element, and everything inside of it, from the document.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_remove(from: Address, handle: u32, callback: Callback<Result<(), Error>>) {
+	callback.call(with_windows(|windows| windows.remove(from, handle)));
+}
+
+/// Sets the value of an attribute of the element. Event handler attributes,
+/// srcdoc, and javascript: or data: URLs are refused, as are inline styles
+/// with properties apps may not set.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_set_attribute(
+	from: Address,
+	handle: u32,
+	name: String,
+	value: String,
+	callback: Callback<Result<(), Error>>,
+) {
+	callback.call(with_windows(|windows| {
+		windows.set_attribute(from, handle, &name, &value)
+	}));
+}
+
+/// Sets the value of a CSS property in the element's inline style. Apps may
+/// only set properties that keep their elements inside of their windows
+/// (e.g., not position or z-index).
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_set_style(
+	from: Address,
+	handle: u32,
+	prop: String,
+	value: String,
+	callback: Callback<Result<(), Error>>,
+) {
+	callback.call(with_windows(|windows| {
+		windows.set_style(from, handle, &prop, &value)
+	}));
+}
+
+/// Replaces the contents of the element with the text.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_set_text(
+	from: Address,
+	handle: u32,
+	text: String,
+	callback: Callback<Result<(), Error>>,
+) {
+	callback.call(with_windows(|windows| {
+		windows.set_text(from, handle, &text)
+	}));
+}
+
+/// Finds the element with the ID inside of one of the actor's elements,
+/// returning a handle to it.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_query(
+	from: Address,
+	root: u32,
+	id: String,
+	callback: Callback<Result<u32, Error>>,
+) {
+	callback.call(with_windows(|windows| windows.query(from, root, &id)));
+}
+
+/// Registers a listener for the event (e.g., "click") on the element. Each
+/// time the event fires, the actor receives a dom_event message with the
+/// listener's ID, which can be read with read_event.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_listen(
+	from: Address,
+	handle: u32,
+	event: String,
+	listener: u32,
+	callback: Callback<Result<(), Error>>,
+) {
+	callback.call(with_windows(|windows| {
+		windows.listen(from, handle, &event, listener)
+	}));
 }
@@ -0,0 +1,625 @@
+use super::{
+	backend::{DomBackend, BODY},
+	Error,
+};
+use std::collections::HashMap;
+use vision_utils::types::{Address, DISPLAY_MANAGER_ADDR};
+
+/// The kinds of elements actors may create. Elements that run scripts, or
+/// embed other documents (e.g., script, iframe, object), aren't on the list.
+const ALLOWED_KINDS: [&str; 56] = [
+	"a",
+	"abbr",
+	"article",
+	"aside",
+	"audio",
+	"b",
+	"blockquote",
+	"br",
+	"button",
+	"canvas",
+	"caption",
+	"code",
+	"dd",
+	"details",
+	"div",
+	"dl",
+	"dt",
+	"em",
+	"fieldset",
+	"figcaption",
+	"figure",
+	"footer",
+	"h1",
+	"h2",
+	"h3",
+	"h4",
+	"h5",
+	"h6",
+	"header",
+	"hr",
+	"i",
+	"img",
+	"input",
+	"label",
+	"legend",
+	"li",
+	"ol",
+	"option",
+	"p",
+	"pre",
+	"progress",
+	"section",
+	"select",
+	"small",
+	"span",
+	"strong",
+	"sub",
+	"summary",
+	"sup",
+	"table",
+	"td",
+	"textarea",
+	"th",
+	"tr",
+	"ul",
+	"video",
+];
+
+/// Attributes whose values are URLs, which may not use schemes that run
+/// scripts or embed arbitrary documents.
+const URL_ATTRIBUTES: [&str; 9] = [
+	"href",
+	"src",
+	"action",
+	"formaction",
+	"poster",
+	"background",
+	"cite",
+	"data",
+	"xlink:href",
+];
+
+/// URL schemes that run scripts, or embed documents that can.
+const FORBIDDEN_SCHEMES: [&str; 3] = ["javascript:", "vbscript:", "data:"];
+
+/// The CSS properties actors may set on their elements. Properties that place
+/// elements outside of the flow of their window (e.g., position, top,
+/// z-index, transform), or paint outside of them (e.g., box-shadow, outline),
+/// aren't on the list, so that apps can't draw over the rest of the screen.
+const ALLOWED_STYLES: [&str; 73] = [
+	"align-content",
+	"align-items",
+	"align-self",
+	"background-color",
+	"border",
+	"border-bottom",
+	"border-color",
+	"border-left",
+	"border-radius",
+	"border-right",
+	"border-style",
+	"border-top",
+	"border-width",
+	"box-sizing",
+	"color",
+	"column-gap",
+	"cursor",
+	"display",
+	"flex",
+	"flex-basis",
+	"flex-direction",
+	"flex-flow",
+	"flex-grow",
+	"flex-shrink",
+	"flex-wrap",
+	"font-family",
+	"font-size",
+	"font-style",
+	"font-weight",
+	"gap",
+	"grid-area",
+	"grid-column",
+	"grid-row",
+	"grid-template-areas",
+	"grid-template-columns",
+	"grid-template-rows",
+	"height",
+	"justify-content",
+	"justify-items",
+	"justify-self",
+	"letter-spacing",
+	"line-height",
+	"list-style",
+	"margin",
+	"margin-bottom",
+	"margin-left",
+	"margin-right",
+	"margin-top",
+	"max-height",
+	"max-width",
+	"min-height",
+	"min-width",
+	"opacity",
+	"order",
+	"overflow",
+	"overflow-wrap",
+	"overflow-x",
+	"overflow-y",
+	"padding",
+	"padding-bottom",
+	"padding-left",
+	"padding-right",
+	"padding-top",
+	"row-gap",
+	"text-align",
+	"text-decoration",
+	"text-overflow",
+	"text-transform",
+	"vertical-align",
+	"visibility",
+	"white-space",
+	"width",
+	"word-break",
+];
+
+/// Checks that the element may be created by an actor.
+fn check_kind(kind: &str) -> Result<(), Error> {
+	if ALLOWED_KINDS.contains(&kind.to_ascii_lowercase().as_str()) {
+		Ok(())
+	} else {
+		Err(Error::NoPermission)
+	}
+}
+
+/// Checks that setting the attribute can't run scripts in the page, which
+/// could impersonate other actors. Event handlers (e.g., onclick) and srcdoc
+/// are refused, as are script URLs.
+fn check_attribute(name: &str, value: &str) -> Result<(), Error> {
+	let name = name.trim().to_ascii_lowercase();
+
+	if name.starts_with("on") || name == "srcdoc" {
+		return Err(Error::NoPermission);
+	}
+
+	if URL_ATTRIBUTES.contains(&name.as_str()) {
+		// Browsers ignore whitespace and control characters in schemes (e.g.,
+		// "java\tscript:")
+		let url: String = value
+			.chars()
+			.filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+			.collect::<String>()
+			.to_ascii_lowercase();
+
+		if FORBIDDEN_SCHEMES
+			.iter()
+			.any(|scheme| url.starts_with(scheme))
+		{
+			return Err(Error::NoPermission);
+		}
+	}
+
+	Ok(())
+}
+
+/// Checks that the CSS property may be set by an actor.
+fn check_style(prop: &str) -> Result<(), Error> {
+	if ALLOWED_STYLES.contains(&prop.trim().to_ascii_lowercase().as_str()) {
+		Ok(())
+	} else {
+		Err(Error::NoPermission)
+	}
+}
+
+/// Checks every declaration of an inline style attribute (e.g., "color: red;
+/// margin: 1em"). Anything that isn't a plain declaration of an allowed
+/// property (e.g., a comment) refuses the whole attribute.
+fn check_styles(style: &str) -> Result<(), Error> {
+	style
+		.split(';')
+		.filter(|decl| !decl.trim().is_empty())
+		.try_for_each(|decl| check_style(decl.split_once(':').ok_or(Error::NoPermission)?.0))
+}
+
+/// The DOM as seen by actors. Every actor works inside of its own window, a
+/// root container placed by the display manager, and may only touch elements
+/// it created or found inside of that window. The display manager may also
+/// move, style, and close windows, but not touch their contents.
+pub struct Windows<B: DomBackend> {
+	backend: B,
+
+	/// The actor each element handed out belongs to
+	owners: HashMap<u32, Address>,
+
+	/// The root container of each actor's window
+	roots: HashMap<Address, u32>,
+}
+
+impl<B: DomBackend> Windows<B> {
+	pub fn new(backend: B) -> Self {
+		Self {
+			backend,
+			owners: HashMap::new(),
+			roots: HashMap::new(),
+		}
+	}
+
+	pub fn backend(&self) -> &B {
+		&self.backend
+	}
+
+	/// Gets the root container of the actor's window.
+	pub fn root(&self, owner: Address) -> Option<u32> {
+		self.roots.get(&owner).copied()
+	}
+
+	/// Checks that the actor may modify the element. The display manager may
+	/// modify the body, its own elements, and the roots of windows.
+	fn check(&self, from: Address, handle: u32) -> Result<(), Error> {
+		if from == DISPLAY_MANAGER_ADDR
+			&& (handle == BODY || self.roots.values().any(|root| *root == handle))
+		{
+			return Ok(());
+		}
+
+		match self.owners.get(&handle) {
+			Some(owner) if *owner == from => Ok(()),
+			_ => Err(Error::NotOwner),
+		}
+	}
+
+	/// Records that the element belongs to the actor.
+	fn adopt(&mut self, owner: Address, handle: u32) -> u32 {
+		self.owners.insert(handle, owner);
+
+		handle
+	}
+
+	/// Creates a window for the actor inside of the parent element, which must
+	/// belong to the display manager. Only the display manager can do this.
+	pub fn open_window(
+		&mut self,
+		from: Address,
+		owner: Address,
+		parent: u32,
+	) -> Result<u32, Error> {
+		if from != DISPLAY_MANAGER_ADDR {
+			return Err(Error::NoPermission);
+		}
+
+		if self.roots.contains_key(&owner) {
+			return Err(Error::WindowExists);
+		}
+
+		self.check(from, parent)?;
+
+		let root = self.backend.create("div", parent).ok_or(Error::HostError)?;
+		self.roots.insert(owner, root);
+
+		Ok(self.adopt(owner, root))
+	}
+
+	/// Removes the actor's window, and forgets every element it owned. Only
+	/// the display manager can do this.
+	pub fn close_window(&mut self, from: Address, owner: Address) -> Result<(), Error> {
+		if from != DISPLAY_MANAGER_ADDR {
+			return Err(Error::NoPermission);
+		}
+
+		let root = self.roots.remove(&owner).ok_or(Error::NoWindow)?;
+		self.owners.retain(|_, o| *o != owner);
+
+		self.backend.remove(root).ok_or(Error::HostError)
+	}
+
+	/// Creates an element inside of the parent, or the root of the actor's
+	/// window if no parent is given.
+	pub fn create(&mut self, from: Address, kind: &str, parent: Option<u32>) -> Result<u32, Error> {
+		check_kind(kind)?;
+
+		let parent = match parent {
+			Some(parent) => parent,
+			None if from == DISPLAY_MANAGER_ADDR => BODY,
+			None => self.root(from).ok_or(Error::NoWindow)?,
+		};
+		self.check(from, parent)?;
+
+		let handle = self.backend.create(kind, parent).ok_or(Error::HostError)?;
+
+		Ok(self.adopt(from, handle))
+	}
+
+	/// Removes one of the actor's elements. The roots of windows can only be
+	/// removed by closing them.
+	pub fn remove(&mut self, from: Address, handle: u32) -> Result<(), Error> {
+		if handle == BODY || self.roots.values().any(|root| *root == handle) {
+			return Err(Error::NotOwner);
+		}
+
+		self.check(from, handle)?;
+		self.backend.remove(handle).ok_or(Error::HostError)?;
+		self.owners.remove(&handle);
+
+		Ok(())
+	}
+
+	pub fn set_attribute(
+		&mut self,
+		from: Address,
+		handle: u32,
+		name: &str,
+		value: &str,
+	) -> Result<(), Error> {
+		self.check(from, handle)?;
+		check_attribute(name, value)?;
+
+		// The display manager places windows and dialogues with styles apps
+		// can't use
+		if from != DISPLAY_MANAGER_ADDR && name.trim().eq_ignore_ascii_case("style") {
+			check_styles(value)?;
+		}
+
+		self.backend
+			.set_attribute(handle, name, value)
+			.ok_or(Error::HostError)
+	}
+
+	pub fn set_style(
+		&mut self,
+		from: Address,
+		handle: u32,
+		prop: &str,
+		value: &str,
+	) -> Result<(), Error> {
+		self.check(from, handle)?;
+		if from != DISPLAY_MANAGER_ADDR {
+			check_style(prop)?;
+		}
+
+		self.backend
+			.set_style(handle, prop, value)
+			.ok_or(Error::HostError)
+	}
+
+	pub fn set_text(&mut self, from: Address, handle: u32, text: &str) -> Result<(), Error> {
+		self.check(from, handle)?;
+		self.backend.set_text(handle, text).ok_or(Error::HostError)
+	}
+
+	/// Finds the element with the ID inside of one of the actor's elements.
+	pub fn query(&mut self, from: Address, root: u32, id: &str) -> Result<u32, Error> {
+		if root == BODY {
+			return Err(Error::NotOwner);
+		}
+
+		self.check(from, root)?;
+
+		let found = self.backend.query(root, id).ok_or(Error::NotFound)?;
+
+		// Elements inside another actor's window (e.g., the display manager
+		// searching a window's root) are not handed out. Elements nobody has
+		// been handed yet only go to the owner of the root they were found in
+		match self.owners.get(&found) {
+			Some(owner) if *owner == from => Ok(found),
+			None if self.owners.get(&root) == Some(&from) => Ok(self.adopt(from, found)),
+			_ => Err(Error::NotOwner),
+		}
+	}
+
+	/// Has the actor notified with the listener's ID when the event fires on
+	/// one of its elements.
+	pub fn listen(
+		&mut self,
+		from: Address,
+		handle: u32,
+		event: &str,
+		listener: u32,
+	) -> Result<(), Error> {
+		if handle == BODY {
+			return Err(Error::NotOwner);
+		}
+
+		self.check(from, handle)?;
+		self.backend
+			.listen(handle, event, from, listener)
+			.ok_or(Error::HostError)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::backend::MemoryDom;
+	use super::*;
+
+	const APP: Address = 100;
+	const OTHER: Address = 101;
+
+	fn with_window() -> (Windows<MemoryDom>, u32) {
+		let mut windows = Windows::new(MemoryDom::default());
+		let root = windows
+			.open_window(DISPLAY_MANAGER_ADDR, APP, BODY)
+			.unwrap();
+
+		(windows, root)
+	}
+
+	#[test]
+	fn test_only_display_manager_opens_windows() {
+		let mut windows = Windows::new(MemoryDom::default());
+
+		assert_eq!(
+			windows.open_window(APP, APP, BODY),
+			Err(Error::NoPermission)
+		);
+		assert!(windows.open_window(DISPLAY_MANAGER_ADDR, APP, BODY).is_ok());
+		assert_eq!(
+			windows.open_window(DISPLAY_MANAGER_ADDR, APP, BODY),
+			Err(Error::WindowExists)
+		);
+	}
+
+	#[test]
+	fn test_create_defaults_to_window() {
+		let (mut windows, root) = with_window();
+
+		let elem = windows.create(APP, "p", None).unwrap();
+		assert_eq!(windows.backend().node(elem).unwrap().parent, root);
+
+		// Actors without windows have nowhere to put elements
+		assert_eq!(windows.create(OTHER, "p", None), Err(Error::NoWindow));
+	}
+
+	#[test]
+	fn test_actors_cannot_escape_window() {
+		let (mut windows, root) = with_window();
+		windows
+			.open_window(DISPLAY_MANAGER_ADDR, OTHER, BODY)
+			.unwrap();
+		let elem = windows.create(APP, "p", None).unwrap();
+
+		assert_eq!(windows.create(APP, "p", Some(BODY)), Err(Error::NotOwner));
+		assert_eq!(
+			windows.set_text(OTHER, elem, "hijacked"),
+			Err(Error::NotOwner)
+		);
+		assert_eq!(windows.remove(APP, root), Err(Error::NotOwner));
+		assert_eq!(windows.query(APP, BODY, "x"), Err(Error::NotOwner));
+		assert_eq!(
+			windows.listen(OTHER, elem, "click", 0),
+			Err(Error::NotOwner)
+		);
+	}
+
+	#[test]
+	fn test_display_manager_styles_roots_only() {
+		let (mut windows, root) = with_window();
+		let elem = windows.create(APP, "p", None).unwrap();
+
+		assert!(windows
+			.set_style(DISPLAY_MANAGER_ADDR, root, "left", "10px")
+			.is_ok());
+		assert_eq!(
+			windows.set_text(DISPLAY_MANAGER_ADDR, elem, "hijacked"),
+			Err(Error::NotOwner)
+		);
+	}
+
+	#[test]
+	fn test_query_within_window() {
+		let (mut windows, root) = with_window();
+		let elem = windows.create(APP, "input", None).unwrap();
+		windows.set_attribute(APP, elem, "id", "name").unwrap();
+
+		assert_eq!(windows.query(APP, root, "name"), Ok(elem));
+		assert_eq!(windows.query(APP, root, "missing"), Err(Error::NotFound));
+		assert_eq!(
+			windows.query(DISPLAY_MANAGER_ADDR, root, "name"),
+			Err(Error::NotOwner)
+		);
+	}
+
+	#[test]
+	fn test_no_scripts() {
+		let (mut windows, _) = with_window();
+		let link = windows.create(APP, "a", None).unwrap();
+
+		for kind in ["script", "iframe", "object", "embed", "SCRIPT"] {
+			assert_eq!(windows.create(APP, kind, None), Err(Error::NoPermission));
+		}
+
+		for (name, value) in [
+			("onclick", "impulse(1, 'x')"),
+			("OnMouseOver", "impulse(1, 'x')"),
+			("srcdoc", "<script></script>"),
+			("href", "javascript:impulse(1, 'x')"),
+			("href", " JaVa\tScRiPt:impulse(1, 'x')"),
+			("href", "data:text/html,<script></script>"),
+		] {
+			assert_eq!(
+				windows.set_attribute(APP, link, name, value),
+				Err(Error::NoPermission),
+				"{}",
+				name
+			);
+		}
+
+		assert!(windows
+			.set_attribute(APP, link, "href", "https://example.com")
+			.is_ok());
+
+		// Only URLs are checked for schemes
+		assert!(windows
+			.set_attribute(APP, link, "title", "javascript: the good parts")
+			.is_ok());
+	}
+
+	#[test]
+	fn test_styles() {
+		let (mut windows, root) = with_window();
+		let elem = windows.create(APP, "div", None).unwrap();
+
+		assert!(windows.set_style(APP, elem, "margin-left", "1em").is_ok());
+		assert!(windows
+			.set_attribute(APP, elem, "style", "display: flex; COLOR: white;")
+			.is_ok());
+
+		// Apps can't lift their elements out of their windows
+		for (prop, value) in [
+			("position", "fixed"),
+			("z-index", "99999"),
+			("top", "0"),
+			("transform", "translate(-100px, -100px)"),
+			("box-shadow", "0 0 0 9999px white"),
+		] {
+			assert_eq!(
+				windows.set_style(APP, elem, prop, value),
+				Err(Error::NoPermission),
+				"{}",
+				prop
+			);
+		}
+
+		for style in [
+			"color: red; position: fixed",
+			"color: red /* ; */; position: fixed",
+			"posit\\ion: fixed",
+			"color",
+		] {
+			assert_eq!(
+				windows.set_attribute(APP, elem, "style", style),
+				Err(Error::NoPermission),
+				"{}",
+				style
+			);
+		}
+
+		// The display manager places windows
+		assert!(windows
+			.set_style(DISPLAY_MANAGER_ADDR, root, "z-index", "10")
+			.is_ok());
+	}
+
+	#[test]
+	fn test_listen() {
+		let (mut windows, _) = with_window();
+		let button = windows.create(APP, "button", None).unwrap();
+
+		windows.listen(APP, button, "click", 7).unwrap();
+		assert_eq!(windows.backend().fire(button, "click"), vec![(APP, 7)]);
+		assert!(windows.backend().fire(button, "keydown").is_empty());
+	}
+
+	#[test]
+	fn test_close_window() {
+		let (mut windows, root) = with_window();
+		let elem = windows.create(APP, "p", None).unwrap();
+
+		assert_eq!(windows.close_window(APP, APP), Err(Error::NoPermission));
+		windows.close_window(DISPLAY_MANAGER_ADDR, APP).unwrap();
+
+		assert!(windows.backend().node(root).is_none());
+		assert!(windows.backend().node(elem).is_none());
+		assert_eq!(windows.set_text(APP, elem, "gone"), Err(Error::NotOwner));
+		assert_eq!(windows.create(APP, "p", None), Err(Error::NoWindow));
+	}
+}
@@ -1,29 +1,20 @@
-use beacon_dao_dom::eval_js;
-use beacon_dao_logger_manager::info;
-use beacon_dao_permissions::{
-	beacon_dao_allocator::{len, read},
-	has_permission, register_permission,
-};
+use beacon_dao_permissions::{has_permission, register_permission};
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
-use std::{
-	collections::HashMap,
-	sync::{
-		atomic::{AtomicUsize, Ordering},
-		Arc, Mutex, RwLock,
-	},
-};
+use serde_json::Value;
+use std::{collections::HashMap, ffi::CString, sync::RwLock};
 use vision_derive::with_bindings;
 use vision_utils::types::{
-	Address, Callback, DISPLAY_MANAGER_ADDR, DOM_ADDR, EXIT_FAILURE, EXIT_SUCCESS, IPFS_ADDR,
-	LOGGER_ADDR, MOCK_ALLOCATOR_ADDR, PERM_ADDR, WEB3_ADDR,
+	Address, Callback, DISPLAY_MANAGER_ADDR, IPFS_ADDR, PERM_ADDR, WEB3_ADDR,
 };
 
 const PERM: &'static str = "make_http_request";
 const DESCRIPTION: &'static str = "Allows the app to make a request to the web.";
 
-/// Callbacks that should be run after the fetch() promise is returned.
-pub static TASKS: RwLock<Vec<Option<Callback<Result<Response, ()>>>>> = RwLock::new(Vec::new());
+/// Callbacks that should be run after the runtime responds to a request, and
+/// whether the caller wants the response body parsed as JSON. Slots are freed
+/// once the response is delivered, and reused by later requests.
+pub static TASKS: RwLock<Vec<Option<(Callback<Result<Response, ()>>, bool)>>> =
+	RwLock::new(Vec::new());
 
 /// HTTP methods available.
 #[derive(Serialize, Deserialize)]
@@ -45,28 +36,38 @@ impl<T: Serialize> From<OptionsBuilder<T>> for Options {
 		Options {
 			method: opts.method.take().unwrap_or(Method::GET),
 			headers: opts.headers.take().unwrap_or(HashMap::new()),
-			body: opts.body.and_then(|body| serde_json::to_string(&body).ok()),
+			body: opts.body.and_then(|body| serde_json::to_vec(&body).ok()),
 		}
 	}
 }
 
-/// Settings for an HTTP request.
+/// Settings for an HTTP request. The body is sent as-is, so it may be any
+/// bytes, not just JSON.
 #[derive(Serialize, Deserialize)]
 pub struct Options {
 	pub method: Method,
 	pub headers: HashMap<String, String>,
 	#[serde(skip_serializing_if = "Option::is_none")]
-	pub body: Option<String>,
+	pub body: Option<Vec<u8>>,
 }
 
 /// An HTTP response.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Response {
-	pub body: Option<Value>,
+	pub body: Option<Vec<u8>>,
 	pub status: usize,
 	pub json: Option<Value>,
 }
 
+/// A request handed to the runtime's http_request import.
+#[derive(Serialize)]
+struct HostRequest {
+	url: String,
+	method: Method,
+	headers: HashMap<String, String>,
+	body: Option<Vec<u8>>,
+}
+
 #[cfg(feature = "module")]
 #[no_mangle]
 pub extern "C" fn handle_init_async(owner: Address) {
@@ -78,7 +79,7 @@ pub extern "C" fn handle_init_async(owner: Address) {
 	);
 }
 
-/// Calls the fetch window method with the provided arguments.
+/// Makes an HTTP request with the raw bytes of the response as its body.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_fetch_raw(
@@ -87,96 +88,42 @@ pub extern "C" fn handle_fetch_raw(
 	opts: Options,
 	callback: Callback<Result<Response, ()>>,
 ) {
-	// Check that the user can make HTTP requests
-	has_permission(
-		PERM_ADDR,
-		from,
-		PERM.into(),
-		Callback::new(move |has_perm: bool| {
-			// Only let permissioned users make HTTP requests
-			if !has_perm && from != DISPLAY_MANAGER_ADDR && from != WEB3_ADDR && from != IPFS_ADDR {
-				callback.call(Err(()));
-
-				return;
-			}
-
-			// Save the callback to be run after the fetch() call is done
-			let slot = {
-				let mut slots = if let Ok(lock) = TASKS.write() {
-					lock
-				} else {
-					callback.call(Err(()));
-
-					return;
-				};
-
-				let slot = slots.len();
-				slots.push(Some(callback));
-
-				slot
-			};
-
-			let opts_ser = if let Ok(ser) = serde_json::to_string(&opts) {
-				ser
-			} else {
-				let mut slots = if let Ok(slots) = TASKS.write() {
-					slots
-				} else {
-					return;
-				};
-
-				let cb = if let Some(cb) = slots.get_mut(slot).and_then(|task| task.take()) {
-					cb
-				} else {
-					return;
-				};
+	do_fetch(from, resource, opts, false, callback);
+}
 
-				cb.call(Err(()));
+/// Makes an HTTP request, parsing the body of the response as JSON.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_fetch_json(
+	from: Address,
+	resource: String,
+	opts: Options,
+	callback: Callback<Result<Response, ()>>,
+) {
+	do_fetch(from, resource, opts, true, callback);
+}
 
-				return;
-			};
+/// Removes the callback registered for the request from the task list,
+/// freeing its slot.
+fn take_task(slot: usize) -> Option<(Callback<Result<Response, ()>>, bool)> {
+	let mut tasks = TASKS.write().ok()?;
+	let task = tasks.get_mut(slot)?.take();
 
-			eval_js(
-				DOM_ADDR,
-				format!(
-					"fetch('{}', {})
-						  .then((resp) => resp.arrayBuffer().then((data) => [resp, data]))
-						  .then(([resp, data]) => impulse(address(), 'fetch_resp', {}, {{ status: resp.status, body: [...(new Uint8Array(data))] }}))
-						  .catch((err) => impulse(address(), 'fetch_resp_err', {}, err))",
-					resource, opts_ser, slot, slot,
-				),
-				Callback::new(move |stat| {
-					if stat as u32 == EXIT_FAILURE {
-						let mut slots = if let Ok(slots) = TASKS.write() {
-							slots
-						} else {
-							return;
-						};
-
-						let cb = if let Some(cb) = slots.get_mut(slot).and_then(|task| task.take())
-						{
-							cb
-						} else {
-							return;
-						};
-
-						cb.call(Err(()));
-					}
+	// Free slots at the end aren't kept around
+	while let Some(None) = tasks.last() {
+		tasks.pop();
+	}
 
-					// The task will complete in a call to fetch_resp
-				}),
-			);
-		}),
-	);
+	task
 }
 
-/// Calls the fetch window method with the provided arguments.
-#[no_mangle]
-#[with_bindings]
-pub extern "C" fn handle_fetch_json(
+/// Asks the runtime to make the request if the user has permission to, and
+/// saves the callback to be run with its response.
+fn do_fetch(
 	from: Address,
 	resource: String,
 	opts: Options,
+	json: bool,
 	callback: Callback<Result<Response, ()>>,
 ) {
 	// Check that the user can make HTTP requests
@@ -192,7 +139,7 @@ pub extern "C" fn handle_fetch_json(
 				return;
 			}
 
-			// Save the callback to be run after the fetch() call is done
+			// Save the callback to be run after the request is done
 			let slot = {
 				let mut slots = if let Ok(lock) = TASKS.write() {
 					lock
@@ -202,148 +149,84 @@ pub extern "C" fn handle_fetch_json(
 					return;
 				};
 
-				let slot = slots.len();
-				slots.push(Some(callback));
+				// Use a slot freed by a request that completed, if there is one
+				let task = Some((callback, json));
+				match slots.iter().position(Option::is_none) {
+					Some(slot) => {
+						slots[slot] = task;
 
-				slot
+						slot
+					}
+					None => {
+						slots.push(task);
+
+						slots.len() - 1
+					}
+				}
 			};
 
-			let opts_ser = if let Ok(ser) = serde_json::to_string(&opts) {
-				ser
-			} else {
-				let mut slots = if let Ok(slots) = TASKS.write() {
-					slots
-				} else {
-					return;
-				};
+			let req = serde_json::to_string(&HostRequest {
+				url: resource,
+				method: opts.method,
+				headers: opts.headers,
+				body: opts.body,
+			})
+			.ok()
+			.and_then(|req| CString::new(req).ok());
+
+			extern "C" {
+				fn http_request(req: i32, id: u32) -> u8;
+			}
 
-				let cb = if let Some(cb) = slots.get_mut(slot).and_then(|task| task.take()) {
-					cb
-				} else {
+			// The task will complete in a call to http_response
+			if let Some(req) = req {
+				if unsafe { http_request(req.as_ptr() as i32, slot as u32) } == 0 {
 					return;
-				};
+				}
+			}
 
+			if let Some((cb, _)) = take_task(slot) {
 				cb.call(Err(()));
-
-				return;
-			};
-
-			eval_js(
-				DOM_ADDR,
-				format!(
-					"fetch('{}', {})
-						  .then((resp) => resp.json().then((json) => [resp, json]))
-						  .then(([resp, json]) => impulse(address(), 'fetch_resp', {}, {{ status: resp.status, body: resp.body, json: json }}))
-						  .catch((err) => impulse(address(), 'fetch_resp_err', {}, err))",
-					resource, opts_ser, slot, slot,
-				),
-				Callback::new(move |stat| {
-					if stat as u32 == EXIT_FAILURE {
-						let mut slots = if let Ok(slots) = TASKS.write() {
-							slots
-						} else {
-							return;
-						};
-
-						let cb = if let Some(cb) = slots.get_mut(slot).and_then(|task| task.take())
-						{
-							cb
-						} else {
-							return;
-						};
-
-						cb.call(Err(()));
-					}
-
-					// The task will complete in a call to fetch_resp
-				}),
-			);
-		}),
-	);
-}
-
-/// Calls the registered task callback for the fetch call.
-#[no_mangle]
-#[with_bindings]
-pub extern "C" fn handle_fetch_resp(
-	from: Address,
-	cb_id: u32,
-	json_cell: Address,
-	callback: Callback<u32>,
-) {
-	// Wait until all characters have been read
-	len(
-		json_cell,
-		Callback::new(move |to_read| {
-			// Make a string of n spaces
-			let buff = Arc::new(Mutex::new(
-				(0..to_read).map(|_| '\0').collect::<Vec<char>>(),
-			));
-			let n_read = Arc::new(AtomicUsize::new(0));
-
-			// Concurrently read n bytes
-			for i in 0..to_read {
-				let buff = buff.clone();
-				let n_read = n_read.clone();
-
-				read(
-					json_cell,
-					i,
-					Callback::new(move |c: u8| {
-						*buff.lock().unwrap().get_mut(i as usize).unwrap() = c as char;
-
-						// We read the last character
-						if n_read.fetch_add(1usize, Ordering::SeqCst) == (to_read as usize) - 1usize
-						{
-							let task = if let Some(task) =
-								TASKS.write().ok().and_then(|mut tasks| {
-									tasks.get_mut(cb_id as usize).and_then(|task| task.take())
-								}) {
-								task
-							} else {
-								return;
-							};
-
-							let read_str: String = buff.lock().unwrap().drain(..).collect();
-							let read_resp =
-								if let Ok(resp) = serde_json::from_str(read_str.as_str()) {
-									resp
-								} else {
-									task.call(Err(()));
-
-									return;
-								};
-
-							task.call(Ok(read_resp));
-						}
-					}),
-				);
 			}
 		}),
 	);
 }
 
-/// Calls the registered task callback for the erronous fetch call.
+/// Calls the registered task callback for the request with the response
+/// provided by the runtime. A status of 0 indicates that the request failed.
 #[no_mangle]
-#[with_bindings]
-pub extern "C" fn handle_fetch_resp_err(
-	from: Address,
-	cb_id: u32,
-	json_cell: Address,
-	callback: Callback<u32>,
-) {
-	// Read message here
-	let task = if let Some(task) = TASKS
-		.write()
-		.ok()
-		.and_then(|mut tasks| tasks.get_mut(cb_id as usize).and_then(|task| task.take()))
-	{
+pub extern "C" fn handle_http_response(from: Address, id: u32, status: u32, buf: u32, len: u32) {
+	// Only the runtime can respond to requests
+	if from != 0 {
+		return;
+	}
+
+	extern "C" {
+		fn read_buffer(token: u32, ptr: i32) -> u8;
+	}
+
+	let mut body = vec![0u8; len as usize];
+	let read_ok = unsafe { read_buffer(buf, body.as_mut_ptr() as i32) } == 0;
+
+	let (task, json) = if let Some(task) = take_task(id as usize) {
 		task
 	} else {
-		callback.call(EXIT_FAILURE);
-
 		return;
 	};
 
-	task.call(Err(()));
+	if !read_ok || status == 0 {
+		task.call(Err(()));
+
+		return;
+	}
+
+	task.call(Ok(Response {
+		json: if json {
+			serde_json::from_slice(&body).ok()
+		} else {
+			None
+		},
+		body: Some(body),
+		status: status as usize,
+	}));
 }
@@ -0,0 +1,86 @@
+/// A search for the first block a contract has code at, which none of its
+/// logs can be older than. Blocks up to the head are bisected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bisect {
+	low: u64,
+	high: u64,
+}
+
+impl Bisect {
+	pub fn new(head: u64) -> Self {
+		Self { low: 0, high: head }
+	}
+
+	/// Gets the block to check for the contract's code next, or None once the
+	/// first block with its code is found.
+	pub fn next(&self) -> Option<u64> {
+		(self.low < self.high).then(|| self.low + (self.high - self.low) / 2)
+	}
+
+	/// Records whether the contract had code at the block.
+	pub fn update(&mut self, block: u64, has_code: bool) {
+		if has_code {
+			self.high = block.min(self.high);
+		} else {
+			self.low = (block + 1).max(self.low);
+		}
+	}
+
+	/// Gets the first block the contract has code at, once next gives None.
+	pub fn found(&self) -> u64 {
+		self.low
+	}
+}
+
+/// Splits the blocks from..=to into ranges of at most size blocks, the
+/// earliest first.
+pub fn pages(from: u64, to: u64, size: u64) -> Vec<(u64, u64)> {
+	let size = size.max(1);
+
+	(from..=to)
+		.step_by(size as usize)
+		.map(|start| (start, start.saturating_add(size - 1).min(to)))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Finds the first block of a contract deployed at the block.
+	fn search(deployed: u64, head: u64) -> (u64, usize) {
+		let mut bisect = Bisect::new(head);
+		let mut checked = 0;
+
+		while let Some(block) = bisect.next() {
+			bisect.update(block, block >= deployed);
+			checked += 1;
+		}
+
+		(bisect.found(), checked)
+	}
+
+	#[test]
+	fn test_bisect() {
+		assert_eq!(search(0, 100).0, 0);
+		assert_eq!(search(37, 100).0, 37);
+		assert_eq!(search(100, 100).0, 100);
+		assert_eq!(search(0, 0), (0, 0));
+
+		// Only a few blocks are checked on long chains
+		let (found, checked) = search(17_654_321, 20_000_000);
+		assert_eq!(found, 17_654_321);
+		assert!(checked <= 25);
+	}
+
+	#[test]
+	fn test_pages() {
+		assert_eq!(pages(10, 12, 1000), vec![(10, 12)]);
+		assert_eq!(
+			pages(0, 2500, 1000),
+			vec![(0, 999), (1000, 1999), (2000, 2500)]
+		);
+		assert_eq!(pages(5, 5, 1000), vec![(5, 5)]);
+		assert_eq!(pages(6, 5, 1000), Vec::new());
+	}
+}
@@ -0,0 +1,1382 @@
+use beacon_dao_contracts::{Error as ContractError, Idea, MetaProp, Proposal};
+use beacon_dao_display_manager::{
+	add_known_contract, focus_window, get_user, open_window, ContractKind, Size, WindowEvent,
+	WmError,
+};
+use beacon_dao_dom::{
+	create, listen, read_event, remove, set_attribute, set_text, Error as DomError,
+};
+use beacon_dao_ipfs::{get_dag, Error as IpfsError, Format as IpfsFormat, Options as IpfsOptions};
+use beacon_dao_schema::{IdeaMetadata, ProposalMetadata, Schema};
+use beacon_dao_wallet::{get_address, send_transaction, Error as WalletError};
+use beacon_dao_web3::{
+	block_number, get_logs,
+	logs::{topic, MAX_RANGE},
+	push_logs, read_logs, rpc, subscribe_logs, unsubscribe_logs, Error as Web3Error, Event, Log,
+	LogEvent, LogFilter, WALLET,
+};
+use ethabi::Uint;
+use serde_json::Value;
+use std::{collections::HashMap, sync::RwLock};
+use vision_utils::types::{
+	Address, Callback, DISPLAY_MANAGER_ADDR, DOM_ADDR, IPFS_ADDR, WEB3_ADDR,
+};
+
+/// The proposals of an Idea, and what they'd change.
+pub mod proposals;
+
+/// Where the logs of a contract start, and the ranges of blocks they're asked
+/// for in.
+pub mod history;
+
+use history::{pages, Bisect};
+
+use proposals::{
+	diff, format_units, parse_units, Change, Difference, Entry, Outcome, Proposals, Status, Tally,
+};
+
+/// The ways of voting on a proposal, as Proposal.sol's VoteKind.
+const AFFIRMATIVE: u8 = 0;
+const NEGATIVE: u8 = 1;
+
+/// What a listener reacts to. The index of the proposal a listener is for,
+/// if any, is kept in the rest of its ID.
+const ACTION_IDEA_INPUT: u32 = 0;
+const ACTION_LOAD: u32 = 1;
+const ACTION_OPEN: u32 = 2;
+const ACTION_BACK: u32 = 3;
+const ACTION_WEIGHT_INPUT: u32 = 4;
+const ACTION_VOTE_FOR: u32 = 5;
+const ACTION_VOTE_AGAINST: u32 = 6;
+const ACTION_START: u32 = 7;
+const ACTION_FINALIZE: u32 = 8;
+const ACTION_PROPOSAL_INPUT: u32 = 9;
+const ACTION_ADD_PROPOSAL: u32 = 10;
+const N_ACTIONS: u32 = 11;
+
+fn listener_id(index: usize, action: u32) -> u32 {
+	index as u32 * N_ACTIONS + action
+}
+
+/// The Idea whose proposals are shown, and its token.
+#[derive(Debug, Clone)]
+struct Governed {
+	address: String,
+	symbol: String,
+	decimals: u8,
+}
+
+/// The app's window, and what it shows.
+struct App {
+	/// The root container of the window
+	root: u32,
+
+	/// The element holding what's shown, replaced each time the app renders
+	view: Option<u32>,
+
+	/// Incremented each time the app renders. Answers to requests made for an
+	/// earlier render are dropped
+	render: u64,
+
+	/// What's entered in the inputs for the Idea's address, the address of a
+	/// proposal, and the weight of votes
+	idea_input: String,
+	proposal_input: String,
+	weight_input: String,
+
+	/// A message about the last thing done (e.g., a vote being sent)
+	notice: Option<String>,
+
+	governed: Option<Governed>,
+	proposals: Proposals,
+
+	/// The metadata of proposals, by address, once it's been fetched
+	metadata: HashMap<String, ProposalMetadata>,
+
+	/// The index of the proposal shown, if one is
+	selected: Option<usize>,
+
+	/// The subscriptions to the logs of the Idea, and of the proposal shown
+	idea_subscription: Option<u64>,
+	proposal_subscription: Option<u64>,
+}
+
+static APP: RwLock<Option<App>> = RwLock::new(None);
+
+/// Runs the operation on the app, if its window is open.
+fn with_app<T>(op: impl FnOnce(&mut App) -> T) -> Option<T> {
+	APP.write().ok()?.as_mut().map(op)
+}
+
+/// Whether the render is still the one shown.
+fn is_current(render: u64) -> bool {
+	with_app(|app| app.render == render).unwrap_or(false)
+}
+
+/// Opens the app's window, or brings it to the front if it's open. Sent by
+/// the display manager when the user clicks the taskbar's governance button.
+#[no_mangle]
+pub extern "C" fn handle_open(from: Address) {
+	if from != DISPLAY_MANAGER_ADDR {
+		return;
+	}
+
+	if with_app(|_| ()).is_some() {
+		focus_window(DISPLAY_MANAGER_ADDR, Callback::new(|_| {}));
+
+		return;
+	}
+
+	open_window(
+		DISPLAY_MANAGER_ADDR,
+		String::from("Governance"),
+		Size {
+			width: 520,
+			height: 600,
+		},
+		Callback::new(|root: Result<u32, WmError>| {
+			let root = if let Ok(root) = root {
+				root
+			} else {
+				return;
+			};
+
+			if let Ok(mut app) = APP.write() {
+				*app = Some(App {
+					root,
+					view: None,
+					render: 0,
+					idea_input: String::new(),
+					proposal_input: String::new(),
+					weight_input: String::new(),
+					notice: None,
+					governed: None,
+					proposals: Proposals::default(),
+					metadata: HashMap::new(),
+					selected: None,
+					idea_subscription: None,
+					proposal_subscription: None,
+				});
+			}
+
+			// Show the proposals of the logged in user's profile to start with
+			get_user(
+				DISPLAY_MANAGER_ADDR,
+				Callback::new(|user: Option<String>| match user {
+					Some(user) => load_idea(user),
+					None => render(),
+				}),
+			);
+		}),
+	);
+}
+
+/// Forgets what the window showed once it's closed.
+#[no_mangle]
+pub extern "C" fn handle_window_event(from: Address, kind: u32, a: i32, b: i32) {
+	if from != DISPLAY_MANAGER_ADDR || WindowEvent::decode(kind, a, b) != Some(WindowEvent::Closed)
+	{
+		return;
+	}
+
+	let app = APP.write().ok().and_then(|mut app| app.take());
+	if let Some(app) = app {
+		for id in [app.idea_subscription, app.proposal_subscription]
+			.into_iter()
+			.flatten()
+		{
+			unsubscribe_logs(WEB3_ADDR, id, Callback::new(|_| {}));
+		}
+	}
+}
+
+/// Handles the user interacting with the window.
+#[no_mangle]
+pub extern "C" fn handle_dom_event(from: Address, listener: u32, buf: u32, len: u32) {
+	// Only the runtime can deliver events
+	let event = match read_event(buf, len) {
+		Some(event) if from == 0 => event,
+		_ => return,
+	};
+
+	let (index, action) = ((listener / N_ACTIONS) as usize, listener % N_ACTIONS);
+	let value = event.value.unwrap_or_default();
+
+	match action {
+		ACTION_IDEA_INPUT => {
+			with_app(|app| app.idea_input = value);
+		}
+		ACTION_PROPOSAL_INPUT => {
+			with_app(|app| app.proposal_input = value);
+		}
+		ACTION_WEIGHT_INPUT => {
+			with_app(|app| app.weight_input = value);
+		}
+		ACTION_LOAD => {
+			if let Some(input) = with_app(|app| app.idea_input.clone()) {
+				load_idea(input);
+			}
+		}
+		ACTION_ADD_PROPOSAL => {
+			if let Some(input) = with_app(|app| app.proposal_input.clone()) {
+				add_proposal(input);
+			}
+		}
+		ACTION_OPEN => select(Some(index)),
+		ACTION_BACK => select(None),
+		ACTION_VOTE_FOR => vote(index, AFFIRMATIVE),
+		ACTION_VOTE_AGAINST => vote(index, NEGATIVE),
+		ACTION_START => {
+			if let Some(entry) = entry(index) {
+				send(
+					"Start voting",
+					Proposal::new(entry.address).initiate_voting_period(),
+				);
+			}
+		}
+		ACTION_FINALIZE => finalize(index),
+		_ => {}
+	}
+}
+
+/// Gets the proposal at the index of the list.
+fn entry(index: usize) -> Option<Entry> {
+	with_app(|app| app.proposals.get(index).cloned()).flatten()
+}
+
+/// Parses an address entered by the user.
+fn parse_address(s: &str) -> Option<ethabi::Address> {
+	let bytes = hex::decode(s.trim().strip_prefix("0x")?).ok()?;
+
+	(bytes.len() == 20).then(|| ethabi::Address::from_slice(&bytes))
+}
+
+/// Shows the message above what the window shows.
+fn notify(notice: String) {
+	with_app(|app| app.notice = Some(notice));
+	render();
+}
+
+/// Shows the proposals of the Idea at the address found in its logs, and
+/// follows the logs for proposals being finalized.
+fn load_idea(address: String) {
+	let address = address.trim().to_lowercase();
+	let old = with_app(|app| {
+		app.idea_input = address.clone();
+		app.proposal_input.clear();
+		app.notice = None;
+		app.governed = None;
+		app.proposals = Proposals::default();
+		app.selected = None;
+
+		[
+			app.idea_subscription.take(),
+			app.proposal_subscription.take(),
+		]
+	});
+	for id in old.into_iter().flatten().flatten() {
+		unsubscribe_logs(WEB3_ADDR, id, Callback::new(|_| {}));
+	}
+
+	if parse_address(&address).is_none() {
+		notify(format!("{} isn't an address.", address));
+
+		return;
+	}
+
+	render();
+
+	let idea = Idea::new(address.clone());
+	idea.symbol(move |symbol| {
+		Idea::new(address.clone()).decimals(move |decimals| {
+			let (symbol, decimals) = match (symbol, decimals) {
+				(Ok(symbol), Ok(decimals)) => (symbol, decimals),
+				_ => {
+					notify(format!("{} isn't an Idea on this network.", address));
+
+					return;
+				}
+			};
+
+			let loaded = with_app(|app| {
+				if app.idea_input != address {
+					return false;
+				}
+
+				app.governed = Some(Governed {
+					address: address.clone(),
+					symbol,
+					decimals,
+				});
+
+				true
+			});
+			if loaded != Some(true) {
+				return;
+			}
+
+			know(address.clone(), ContractKind::Idea);
+			render();
+			find_proposals(address.clone());
+			subscribe(address, true);
+		});
+	});
+}
+
+/// Gets the address of the Idea shown.
+fn governed_address() -> Option<String> {
+	with_app(|app| app.governed.as_ref().map(|g| g.address.clone())).flatten()
+}
+
+/// Finds the first block the contract at the address has code at, calling f
+/// with it and the latest block.
+fn deployment(address: String, f: impl FnOnce(u64, u64) + 'static) {
+	block_number(
+		WEB3_ADDR,
+		Callback::new(move |head: Result<u64, Web3Error>| match head {
+			Ok(head) => bisect(address, Bisect::new(head), move |deployed| {
+				f(deployed, head)
+			}),
+			Err(e) => notify(format!("Some proposals couldn't be found: {:?}", e)),
+		}),
+	);
+}
+
+/// Checks the blocks the search asks about for the contract's code, until
+/// the first block with it is found.
+fn bisect(address: String, mut search: Bisect, f: impl FnOnce(u64) + 'static) {
+	let block = if let Some(block) = search.next() {
+		block
+	} else {
+		f(search.found());
+
+		return;
+	};
+
+	rpc(
+		WEB3_ADDR,
+		String::from("eth_getCode"),
+		vec![
+			Value::String(address.clone()),
+			Value::String(format!("{:#x}", block)),
+		],
+		Callback::new(move |code: Result<Value, Web3Error>| {
+			// Nodes that have dropped the state of old blocks can't say, so
+			// the contract is taken to have been deployed by then, which only
+			// costs more pages of logs
+			let has_code = !matches!(&code, Ok(Value::String(code)) if code == "0x");
+			search.update(block, has_code);
+
+			bisect(address, search, f);
+		}),
+	);
+}
+
+/// Gets the logs of the contract at the address with the topic, a page of
+/// blocks at a time, for as long as the Idea is shown. Calls f with the logs
+/// of each page, then done with how many logs there were.
+fn paged_logs(
+	idea: String,
+	address: String,
+	event: &'static str,
+	mut pages: std::vec::IntoIter<(u64, u64)>,
+	found: usize,
+	mut f: impl FnMut(Vec<Log>) + 'static,
+	done: impl FnOnce(usize) + 'static,
+) {
+	if governed_address().as_ref() != Some(&idea) {
+		return;
+	}
+
+	let (from_block, to_block) = if let Some(page) = pages.next() {
+		page
+	} else {
+		done(found);
+
+		return;
+	};
+
+	get_logs(
+		WEB3_ADDR,
+		LogFilter {
+			from_block: Some(format!("{:#x}", from_block)),
+			to_block: Some(format!("{:#x}", to_block)),
+			address: Some(address.clone()),
+			topics: vec![topic(event)],
+			block_hash: None,
+		},
+		Callback::new(move |logs: Result<Vec<Log>, Web3Error>| match logs {
+			Ok(logs) => {
+				let found = found + logs.len();
+				f(logs);
+
+				paged_logs(idea, address, event, pages, found, f, done);
+			}
+			Err(e) => notify(format!("Some proposals couldn't be found: {:?}", e)),
+		}),
+	);
+}
+
+/// Finds the proposals of the Idea that have been finalized, in the blocks
+/// since it was deployed.
+fn find_proposals(idea: String) {
+	deployment(idea.clone(), move |deployed, head| {
+		for event in ["ProposalAccepted", "ProposalRejected"] {
+			let applied = idea.clone();
+
+			paged_logs(
+				idea.clone(),
+				idea.clone(),
+				event,
+				pages(deployed, head, MAX_RANGE).into_iter(),
+				0,
+				move |logs| {
+					for log in logs {
+						apply(&applied, LogEvent::from(log));
+					}
+				},
+				|_| {},
+			);
+		}
+	});
+}
+
+/// Lists the proposal at the address, if the Idea shown governs it, and its
+/// voting period has started. The Idea only logs its proposals once they're
+/// finalized, so proposals being voted on are added by their addresses.
+fn add_proposal(address: String) {
+	let address = address.trim().to_lowercase();
+	let idea = if let Some(idea) = governed_address() {
+		idea
+	} else {
+		return;
+	};
+
+	if parse_address(&address).is_none() {
+		notify(format!("{} isn't an address.", address));
+
+		return;
+	}
+
+	Proposal::new(address.clone()).governor(move |governor| {
+		if governor.ok() != parse_address(&idea) {
+			notify(format!("{} isn't a proposal of the Idea.", address));
+
+			return;
+		}
+
+		deployment(address.clone(), move |deployed, head| {
+			let applied = idea.clone();
+			let missing = address.clone();
+
+			paged_logs(
+				idea,
+				address,
+				"VoteStarted",
+				pages(deployed, head, MAX_RANGE).into_iter(),
+				0,
+				move |logs| {
+					for log in logs {
+						apply(&applied, LogEvent::from(log));
+					}
+				},
+				move |found| {
+					if found == 0 {
+						notify(format!("Voting on {} hasn't started yet.", missing));
+					}
+				},
+			);
+		});
+	});
+}
+
+/// Records what the log of the Idea, or of one of its proposals, says
+/// happened, if the Idea is still the one shown.
+fn apply(idea: &str, log: LogEvent) {
+	let block = log.log.block_number.unwrap_or_default();
+	let (address, change) = match log.event {
+		Some(Event::ProposalAccepted {
+			proposal,
+			old_payload,
+			new_payload,
+		}) => (
+			proposal,
+			Change::Finalized(Outcome::Accepted {
+				old_payload,
+				new_payload,
+			}),
+		),
+		Some(Event::ProposalRejected { proposal }) => {
+			(proposal, Change::Finalized(Outcome::Rejected))
+		}
+		Some(Event::VoteStarted { .. }) => (log.log.address.clone(), Change::Started),
+		_ => return,
+	};
+
+	let applied = with_app(|app| {
+		if app.governed.as_ref().map(|g| g.address.as_str()) != Some(idea) {
+			return false;
+		}
+
+		// The proposal shown stays shown as proposals are added
+		let shown = app
+			.selected
+			.and_then(|i| app.proposals.get(i))
+			.map(|entry| entry.address.clone());
+		app.proposals
+			.apply(&address, block, change, log.log.removed);
+		app.selected = shown.and_then(|shown| {
+			app.proposals
+				.entries()
+				.iter()
+				.position(|entry| entry.address == shown)
+		});
+
+		true
+	});
+
+	if applied == Some(true) {
+		know(address, ContractKind::Proposal);
+		render();
+	}
+}
+
+/// Tells the display manager that the contract is one of the DAO's, so that
+/// it shows what transactions calling it do.
+fn know(address: String, kind: ContractKind) {
+	add_known_contract(DISPLAY_MANAGER_ADDR, address, kind, Callback::new(|_| {}));
+}
+
+/// Follows the logs of the Idea, or of the proposal shown, rendering again
+/// when they change something.
+fn subscribe(address: String, is_idea: bool) {
+	subscribe_logs(
+		WEB3_ADDR,
+		address.clone(),
+		Vec::new(),
+		Callback::new(move |id: Result<u64, Web3Error>| {
+			let id = if let Ok(id) = id { id } else { return };
+
+			let kept = with_app(|app| {
+				let (shown, subscription) = if is_idea {
+					(
+						app.governed.as_ref().map(|g| g.address.clone()),
+						&mut app.idea_subscription,
+					)
+				} else {
+					(
+						app.selected
+							.and_then(|i| app.proposals.get(i))
+							.map(|entry| entry.address.clone()),
+						&mut app.proposal_subscription,
+					)
+				};
+
+				if shown.as_deref() != Some(address.as_str()) || subscription.is_some() {
+					return false;
+				}
+				*subscription = Some(id);
+
+				true
+			});
+
+			if kept == Some(true) {
+				follow(id);
+			} else {
+				unsubscribe_logs(WEB3_ADDR, id, Callback::new(|_| {}));
+			}
+		}),
+	);
+}
+
+/// Has the logs of the subscription pushed to the app as they come in.
+fn follow(id: u64) {
+	push_logs(WEB3_ADDR, id, Callback::new(|_: Result<(), Web3Error>| {}));
+}
+
+/// Applies the logs of a subscription pushed by web3, if it's still the
+/// app's, rendering again when they change something.
+#[no_mangle]
+pub extern "C" fn handle_logs(from: Address, buf: u32, len: u32) {
+	if from != WEB3_ADDR {
+		return;
+	}
+
+	let pushed = if let Some(pushed) = read_logs(buf, len) {
+		pushed
+	} else {
+		return;
+	};
+
+	// The Idea the logs are of, or None if they're of the proposal shown
+	let idea = with_app(|app| {
+		if app.idea_subscription == Some(pushed.id) {
+			app.governed.as_ref().map(|g| Some(g.address.clone()))
+		} else if app.proposal_subscription == Some(pushed.id) {
+			Some(None)
+		} else {
+			None
+		}
+	})
+	.flatten();
+
+	match idea {
+		Some(Some(idea)) => {
+			for log in pushed.logs {
+				apply(&idea, log);
+			}
+		}
+		Some(None) if !pushed.logs.is_empty() => {
+			// Votes were cast, or the voting period started
+			render();
+		}
+		_ => {}
+	}
+}
+
+/// Shows the proposal at the index, or the list of proposals, following the
+/// logs of the proposal shown.
+fn select(index: Option<usize>) {
+	let (old, address) = match with_app(|app| {
+		app.selected = index;
+		app.notice = None;
+		app.weight_input.clear();
+
+		(
+			app.proposal_subscription.take(),
+			index
+				.and_then(|i| app.proposals.get(i))
+				.map(|entry| entry.address.clone()),
+		)
+	}) {
+		Some(selected) => selected,
+		None => return,
+	};
+
+	if let Some(id) = old {
+		unsubscribe_logs(WEB3_ADDR, id, Callback::new(|_| {}));
+	}
+
+	if let Some(address) = address {
+		subscribe(address, false);
+	}
+
+	render();
+}
+
+/// Tells the app the address of the wallet. Sent by the runtime once it has
+/// spawned the wallet.
+#[cfg(feature = "module")]
+#[no_mangle]
+pub extern "C" fn handle_set_wallet(from: Address, addr: Address) {
+	WALLET.set(from, addr);
+}
+
+/// Sends the transaction through the wallet, which asks the user to confirm
+/// it, and says what came of it.
+fn send(what: &'static str, call: beacon_dao_contracts::Call) {
+	let wallet = if let Some(wallet) = WALLET.get() {
+		wallet
+	} else {
+		notify(format!("{}: there's no wallet to send it with.", what));
+
+		return;
+	};
+
+	send_transaction(
+		wallet,
+		call.into(),
+		Callback::new(move |res: Result<String, WalletError>| {
+			notify(match res {
+				Ok(hash) => format!("{}: sent transaction {}.", what, hash),
+				Err(WalletError::Rejected) => format!("{}: cancelled.", what),
+				Err(e) => format!("{}: the transaction wasn't sent ({:?}).", what, e),
+			})
+		}),
+	);
+}
+
+/// Votes for or against the proposal at the index with the weight entered, or
+/// with every token of the wallet's account if none is.
+fn vote(index: usize, nature: u8) {
+	let (entry, governed, weight) =
+		match with_app(|app| (app.governed.clone(), app.weight_input.clone())) {
+			Some((Some(governed), weight)) => match entry(index) {
+				Some(entry) => (entry, governed, weight),
+				None => return,
+			},
+			_ => return,
+		};
+	let what = if nature == AFFIRMATIVE {
+		"Vote for"
+	} else {
+		"Vote against"
+	};
+	let proposal = Proposal::new(entry.address);
+
+	if !weight.trim().is_empty() {
+		match parse_units(&weight, governed.decimals) {
+			Some(weight) => send(what, proposal.cast_vote(nature, weight)),
+			None => notify(format!(
+				"{} isn't an amount of {}.",
+				weight, governed.symbol
+			)),
+		}
+
+		return;
+	}
+
+	let wallet = if let Some(wallet) = WALLET.get() {
+		wallet
+	} else {
+		notify(format!("{}: there's no wallet to vote with.", what));
+
+		return;
+	};
+
+	get_address(
+		wallet,
+		Callback::new(move |account: Result<String, WalletError>| {
+			let account = match account.ok().and_then(|account| parse_address(&account)) {
+				Some(account) => account,
+				None => {
+					notify(format!("{}: the wallet isn't unlocked.", what));
+
+					return;
+				}
+			};
+
+			Idea::new(governed.address).balance_of(account, move |balance| match balance {
+				Ok(balance) if !balance.is_zero() => {
+					send(what, proposal.cast_vote(nature, balance))
+				}
+				Ok(_) => notify(format!(
+					"{}: your account has no {} to vote with.",
+					what, governed.symbol
+				)),
+				Err(e) => notify(format!("{}: {:?}", what, e)),
+			});
+		}),
+	);
+}
+
+/// Asks the Idea to finalize the proposal at the index, accepting or rejecting
+/// it.
+fn finalize(index: usize) {
+	let (entry, idea) = match (entry(index), governed_address()) {
+		(Some(entry), Some(idea)) => (entry, idea),
+		_ => return,
+	};
+
+	if let Some(proposal) = parse_address(&entry.address) {
+		send("Finalize", Idea::new(idea).finalize_proposal(proposal));
+	}
+}
+
+/// Gets the content at the CID from IPFS as a value of the schema.
+fn fetch<T: Schema + 'static>(cid: String, f: impl FnOnce(Option<T>) + 'static) {
+	get_dag(
+		IPFS_ADDR,
+		cid,
+		IpfsOptions {
+			format: Some(IpfsFormat::DagJson),
+		},
+		Callback::new(move |resp: Result<Value, IpfsError>| {
+			f(resp.ok().and_then(|resp| T::from_dag_json(&resp).ok()))
+		}),
+	);
+}
+
+/// Gets the metadata of the proposal, fetching it if it hasn't been yet.
+fn proposal_metadata(address: String, f: impl FnOnce(Option<ProposalMetadata>) + 'static) {
+	if let Some(metadata) = with_app(|app| app.metadata.get(&address).cloned()).flatten() {
+		f(Some(metadata));
+
+		return;
+	}
+
+	MetaProp::new(address.clone()).ipfs_addr(move |cid| {
+		let cid = if let Ok(cid) = cid {
+			cid
+		} else {
+			f(None);
+
+			return;
+		};
+
+		fetch(cid, move |metadata: Option<ProposalMetadata>| {
+			if let Some(metadata) = &metadata {
+				with_app(|app| app.metadata.insert(address, metadata.clone()));
+			}
+
+			f(metadata);
+		});
+	});
+}
+
+/// Gets where the proposal is in its life.
+fn status(entry: &Entry, f: impl FnOnce(Option<Status>) + 'static) {
+	let outcome = entry.outcome.clone();
+	let address = entry.address.clone();
+
+	Proposal::new(address.clone()).expiry(move |expiry| {
+		Proposal::new(address.clone()).closed_at(move |closed_at| {
+			Proposal::new(address).active(move |active| {
+				f(match (expiry, closed_at, active) {
+					(Ok(expiry), Ok(closed_at), Ok(active)) => {
+						Some(Status::new(expiry, closed_at, active, outcome.as_ref()))
+					}
+					_ => None,
+				})
+			});
+		});
+	});
+}
+
+/// Gets the votes cast on the proposal, and the supply of the Idea's token.
+fn tally(idea: String, address: String, f: impl FnOnce(Option<Tally>) + 'static) {
+	let proposal = Proposal::new(address.clone());
+
+	proposal.n_affirmative(move |affirmative| {
+		Proposal::new(address.clone()).n_votes(move |votes| {
+			Proposal::new(address).n_voters(move |voters| {
+				Idea::new(idea).total_supply(move |supply| {
+					let read = |res: Result<Uint, ContractError>| res.ok();
+
+					f((|| {
+						Some(Tally {
+							affirmative: read(affirmative)?,
+							votes: read(votes)?,
+							voters: read(voters)?,
+							supply: read(supply)?,
+						})
+					})())
+				});
+			});
+		});
+	});
+}
+
+/* Rendering of the window */
+
+/// Creates an element of the kind with the style inside of the parent, if
+/// the render it's for is still shown.
+fn build(render: u64, kind: &str, parent: u32, style: &'static str, f: impl FnOnce(u32) + 'static) {
+	if !is_current(render) {
+		return;
+	}
+
+	create(
+		DOM_ADDR,
+		kind.to_owned(),
+		Some(parent),
+		Callback::new(move |handle: Result<u32, DomError>| {
+			if let Ok(handle) = handle {
+				set_attribute(
+					DOM_ADDR,
+					handle,
+					String::from("style"),
+					style.to_owned(),
+					Callback::new(|_| {}),
+				);
+
+				f(handle);
+			}
+		}),
+	);
+}
+
+/// Creates an element displaying the text.
+fn text(render: u64, kind: &str, parent: u32, style: &'static str, text: String) {
+	build(render, kind, parent, style, move |elem| {
+		set_text(DOM_ADDR, elem, text, Callback::new(|_| {}));
+	});
+}
+
+/// Creates a button that sends the listener's events when clicked.
+fn button(render: u64, parent: u32, label: &str, listener: u32) {
+	let label = label.to_owned();
+
+	build(
+		render,
+		"button",
+		parent,
+		"cursor: pointer; margin: 0.25em 0.5em 0.25em 0; padding: 0.25em 0.75em; border: none; border-radius: 0.25em; background-color: #5B2A86; color: white",
+		move |button| {
+			set_text(DOM_ADDR, button, label, Callback::new(|_| {}));
+			listen(
+				DOM_ADDR,
+				button,
+				String::from("click"),
+				listener,
+				Callback::new(|_| {}),
+			);
+		},
+	);
+}
+
+/// Creates an input holding the value, which sends the listener what's
+/// entered.
+fn input(render: u64, parent: u32, placeholder: &str, value: String, listener: u32) {
+	let placeholder = placeholder.to_owned();
+
+	build(
+		render,
+		"input",
+		parent,
+		"flex: 1; margin-right: 0.5em; padding: 0.25em; border-radius: 0.25em; border: none",
+		move |input| {
+			for (name, value) in [("placeholder", placeholder), ("value", value)] {
+				set_attribute(
+					DOM_ADDR,
+					input,
+					String::from(name),
+					value,
+					Callback::new(|_| {}),
+				);
+			}
+
+			listen(
+				DOM_ADDR,
+				input,
+				String::from("input"),
+				listener,
+				Callback::new(|_| {}),
+			);
+		},
+	);
+}
+
+/// Replaces what the window shows with the list of proposals, or the
+/// proposal selected.
+fn render() {
+	let (root, old, render, idea_input, notice, selected) = match with_app(|app| {
+		app.render += 1;
+
+		(
+			app.root,
+			app.view.take(),
+			app.render,
+			app.idea_input.clone(),
+			app.notice.clone(),
+			app.selected,
+		)
+	}) {
+		Some(state) => state,
+		None => return,
+	};
+
+	if let Some(old) = old {
+		remove(DOM_ADDR, old, Callback::new(|_| {}));
+	}
+
+	build(
+		render,
+		"div",
+		root,
+		"display: flex; flex-flow: column nowrap; padding: 0.75em; overflow-y: auto; height: 100%; box-sizing: border-box",
+		move |view| {
+			// The app may have rendered again while the view was being created
+			let current = with_app(|app| {
+				let current = app.render == render;
+				if current {
+					app.view = Some(view);
+				}
+
+				current
+			});
+			if current != Some(true) {
+				remove(DOM_ADDR, view, Callback::new(|_| {}));
+
+				return;
+			}
+
+			build(render, "div", view, "display: flex; flex-flow: row nowrap; margin-bottom: 0.5em", move |bar| {
+				input(render, bar, "The address of an Idea", idea_input, listener_id(0, ACTION_IDEA_INPUT));
+				button(render, bar, "Show proposals", listener_id(0, ACTION_LOAD));
+			});
+
+			if let Some(notice) = notice {
+				text(render, "p", view, "margin: 0.25em 0; opacity: 0.8", notice);
+			}
+
+			match selected.and_then(entry) {
+				Some(entry) => render_proposal(render, view, selected.unwrap_or_default(), entry),
+				None => render_list(render, view),
+			}
+		},
+	);
+}
+
+/// Shows the proposals of the Idea, the newest first.
+fn render_list(render: u64, view: u32) {
+	let (governed, entries, proposal_input) = match with_app(|app| {
+		(
+			app.governed.clone(),
+			app.proposals.entries().to_vec(),
+			app.proposal_input.clone(),
+		)
+	}) {
+		Some(state) => state,
+		None => return,
+	};
+
+	let governed = if let Some(governed) = governed {
+		governed
+	} else {
+		text(
+			render,
+			"p",
+			view,
+			"",
+			String::from("Enter the address of an Idea to see its proposals."),
+		);
+
+		return;
+	};
+
+	build(
+		render,
+		"div",
+		view,
+		"display: flex; flex-flow: row nowrap; margin-bottom: 0.5em",
+		move |bar| {
+			input(
+				render,
+				bar,
+				"The address of a proposal being voted on",
+				proposal_input,
+				listener_id(0, ACTION_PROPOSAL_INPUT),
+			);
+			button(
+				render,
+				bar,
+				"Add proposal",
+				listener_id(0, ACTION_ADD_PROPOSAL),
+			);
+		},
+	);
+
+	if entries.is_empty() {
+		text(
+			render,
+			"p",
+			view,
+			"",
+			format!("No proposals to {} have been found yet.", governed.address),
+		);
+
+		return;
+	}
+
+	for (i, entry) in entries.into_iter().enumerate() {
+		build(render, "div", view, "display: flex; flex-flow: row nowrap; justify-content: space-between; cursor: pointer; padding: 0.5em; margin-bottom: 0.25em; border-radius: 0.25em; background-color: #3A0A66", move |row| {
+			listen(DOM_ADDR, row, String::from("click"), listener_id(i, ACTION_OPEN), Callback::new(|_| {}));
+
+			let address = entry.address.clone();
+			build(render, "span", row, "overflow: hidden; text-overflow: ellipsis", move |title| {
+				set_text(DOM_ADDR, title, address.clone(), Callback::new(|_| {}));
+
+				proposal_metadata(address, move |metadata| {
+					if let (Some(metadata), true) = (metadata, is_current(render)) {
+						set_text(DOM_ADDR, title, metadata.title, Callback::new(|_| {}));
+					}
+				});
+			});
+
+			build(render, "span", row, "margin-left: 1em; opacity: 0.8; white-space: nowrap", move |label| {
+				status(&entry, move |status| {
+					if is_current(render) {
+						let label_text = status.map(|s| s.label()).unwrap_or("Unknown");
+						set_text(DOM_ADDR, label, label_text.to_owned(), Callback::new(|_| {}));
+					}
+				});
+			});
+		});
+	}
+}
+
+/// Shows the proposal: its metadata, the votes cast on it, what it would
+/// change, and what can be done with it.
+fn render_proposal(render: u64, view: u32, index: usize, entry: Entry) {
+	let governed = if let Some(governed) = with_app(|app| app.governed.clone()).flatten() {
+		governed
+	} else {
+		return;
+	};
+
+	build(render, "div", view, "", move |back| {
+		button(render, back, "‹ Proposals", listener_id(0, ACTION_BACK));
+	});
+
+	// What the proposal is
+	let address = entry.address.clone();
+	build(render, "div", view, "margin-bottom: 0.5em", move |about| {
+		build(render, "h3", about, "margin: 0.25em 0", move |title| {
+			set_text(DOM_ADDR, title, address.clone(), Callback::new(|_| {}));
+
+			build(
+				render,
+				"p",
+				about,
+				"margin: 0.25em 0; white-space: pre-wrap",
+				move |description| {
+					proposal_metadata(address, move |metadata| {
+						if !is_current(render) {
+							return;
+						}
+
+						let (title_text, description_text) = match metadata {
+							Some(metadata) => (metadata.title, metadata.description),
+							None => (
+								String::from("Untitled proposal"),
+								String::from("The proposal's metadata couldn't be loaded."),
+							),
+						};
+						set_text(DOM_ADDR, title, title_text, Callback::new(|_| {}));
+						set_text(
+							DOM_ADDR,
+							description,
+							description_text,
+							Callback::new(|_| {}),
+						);
+					});
+				},
+			);
+		});
+	});
+	text(
+		render,
+		"p",
+		view,
+		"margin: 0.25em 0; opacity: 0.6; font-size: 0.8em",
+		entry.address.clone(),
+	);
+
+	// How the vote is going
+	let (idea, address, token) = (
+		governed.address.clone(),
+		entry.address.clone(),
+		governed.clone(),
+	);
+	build(render, "p", view, "margin: 0.25em 0", move |votes| {
+		tally(idea, address, move |tally| {
+			if !is_current(render) {
+				return;
+			}
+
+			let summary = match tally {
+				Some(tally) => format!(
+					"For: {} {} ({}% of the supply). Against: {} {}. Voters: {}. {}",
+					format_units(tally.affirmative, token.decimals),
+					token.symbol,
+					tally.percent_affirmative(),
+					format_units(tally.negative(), token.decimals),
+					token.symbol,
+					tally.voters,
+					if tally.passes() {
+						"It has a majority."
+					} else {
+						"It needs more than half of the supply to pass."
+					}
+				),
+				None => String::from("The votes couldn't be counted."),
+			};
+			set_text(DOM_ADDR, votes, summary, Callback::new(|_| {}));
+		});
+	});
+
+	// What the proposal changes
+	let (idea, proposal, outcome) = (
+		governed.address.clone(),
+		entry.address.clone(),
+		entry.outcome.clone(),
+	);
+	build(
+		render,
+		"div",
+		view,
+		"margin: 0.5em 0; padding: 0.5em; border-radius: 0.25em; background-color: #3A0A66",
+		move |changes| {
+			payloads(idea, proposal, outcome, move |payloads| {
+				if !is_current(render) {
+					return;
+				}
+
+				let (old, new) = match payloads {
+					Some(payloads) => payloads,
+					None => {
+						text(
+							render,
+							"p",
+							changes,
+							"margin: 0",
+							String::from("The proposal's payload couldn't be loaded."),
+						);
+
+						return;
+					}
+				};
+				text(
+					render,
+					"p",
+					changes,
+					"margin: 0.25em 0; word-break: break-all",
+					format!("Metadata: {} → {}", old, new),
+				);
+				render_diff(render, changes, old, new);
+			});
+		},
+	);
+
+	// What can be done with the proposal
+	build(
+		render,
+		"div",
+		view,
+		"display: flex; flex-flow: row wrap; align-items: center",
+		move |actions| {
+			status(&entry, move |status| {
+				if !is_current(render) {
+					return;
+				}
+
+				let status = if let Some(status) = status {
+					status
+				} else {
+					return;
+				};
+				text(
+					render,
+					"span",
+					actions,
+					"margin-right: 0.75em",
+					status.label().to_owned(),
+				);
+
+				match status {
+					Status::Draft => button(
+						render,
+						actions,
+						"Start voting",
+						listener_id(index, ACTION_START),
+					),
+					Status::Voting => {
+						let weight = with_app(|app| app.weight_input.clone()).unwrap_or_default();
+						let placeholder = format!(
+							"{} to vote with (all of them if left empty)",
+							governed.symbol
+						);
+						input(
+							render,
+							actions,
+							&placeholder,
+							weight,
+							listener_id(index, ACTION_WEIGHT_INPUT),
+						);
+						button(
+							render,
+							actions,
+							"Vote for",
+							listener_id(index, ACTION_VOTE_FOR),
+						);
+						button(
+							render,
+							actions,
+							"Vote against",
+							listener_id(index, ACTION_VOTE_AGAINST),
+						);
+						text(
+							render,
+							"p",
+							actions,
+							"flex-basis: 100%; margin: 0.25em 0 0; opacity: 0.8",
+							String::from("Votes can't be delegated: only the Idea may cast votes on behalf of others."),
+						);
+					}
+					Status::Ended => button(
+						render,
+						actions,
+						"Finalize",
+						listener_id(index, ACTION_FINALIZE),
+					),
+					_ => {}
+				}
+			});
+		},
+	);
+}
+
+/// Gets the CIDs of the metadata the Idea had, or has, and the metadata the
+/// proposal gave, or would give, it.
+fn payloads(
+	idea: String,
+	proposal: String,
+	outcome: Option<Outcome>,
+	f: impl FnOnce(Option<(String, String)>) + 'static,
+) {
+	if let Some(Outcome::Accepted {
+		old_payload,
+		new_payload,
+	}) = outcome
+	{
+		f(Some((old_payload, new_payload)));
+
+		return;
+	}
+
+	Idea::new(idea).ipfs_addr(move |old| {
+		MetaProp::new(proposal).payload(move |new| f(old.ok().zip(new.ok())));
+	});
+}
+
+/// Shows how the metadata at the new CID differs from the metadata at the old
+/// one.
+fn render_diff(render: u64, parent: u32, old: String, new: String) {
+	fetch(old, move |old: Option<IdeaMetadata>| {
+		fetch(new, move |new: Option<IdeaMetadata>| {
+			let differences = match (old, new) {
+				(Some(old), Some(new)) => diff(&old, &new),
+				_ => {
+					text(
+						render,
+						"p",
+						parent,
+						"margin: 0.25em 0",
+						String::from("The metadata couldn't be compared."),
+					);
+
+					return;
+				}
+			};
+
+			if differences.is_empty() {
+				text(
+					render,
+					"p",
+					parent,
+					"margin: 0.25em 0",
+					String::from("The metadata is unchanged."),
+				);
+			}
+
+			for difference in differences {
+				let line = match difference {
+					Difference::Title { old, new } => format!("Title: {} → {}", old, new),
+					Difference::Description { old, new } => {
+						format!("Description: {} → {}", old, new)
+					}
+					Difference::AddedModule(cid) => format!("+ Module {}", cid),
+					Difference::RemovedModule(cid) => format!("− Module {}", cid),
+				};
+				text(
+					render,
+					"p",
+					parent,
+					"margin: 0.25em 0; word-break: break-all",
+					line,
+				);
+			}
+		});
+	});
+}
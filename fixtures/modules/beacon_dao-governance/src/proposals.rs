@@ -0,0 +1,374 @@
+use beacon_dao_schema::IdeaMetadata;
+use ethabi::Uint;
+
+/// What became of a proposal once it was finalized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+	/// The proposal's payload replaced the CID of the Idea's metadata
+	Accepted {
+		old_payload: String,
+		new_payload: String,
+	},
+
+	/// The proposal didn't get a majority
+	Rejected,
+}
+
+/// What a log says happened to a proposal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+	/// The proposal's voting period started (VoteStarted)
+	Started,
+
+	/// The Idea finalized the proposal (ProposalAccepted or ProposalRejected)
+	Finalized(Outcome),
+}
+
+/// A proposal of the Idea being governed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+	pub address: String,
+
+	/// The block the proposal was first seen in
+	pub block: u64,
+
+	pub outcome: Option<Outcome>,
+}
+
+/// The proposals of an Idea found in the logs, the newest first. Proposals
+/// are only listed once their voting period has started, or once they've
+/// been finalized.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Proposals {
+	entries: Vec<Entry>,
+}
+
+impl Proposals {
+	pub fn entries(&self) -> &[Entry] {
+		&self.entries
+	}
+
+	pub fn get(&self, i: usize) -> Option<&Entry> {
+		self.entries.get(i)
+	}
+
+	/// Records what the log in the block says happened to the proposal at the
+	/// address, or, if the log was removed by a reorg, forgets it.
+	pub fn apply(&mut self, address: &str, block: u64, change: Change, removed: bool) {
+		let address = address.to_lowercase();
+		let i = self.entries.iter().position(|e| e.address == address);
+
+		match (i, change, removed) {
+			(Some(i), Change::Finalized(_), true) => self.entries[i].outcome = None,
+			(Some(i), Change::Started, true) => {
+				if self.entries[i].outcome.is_none() {
+					self.entries.remove(i);
+				}
+			}
+			(None, _, true) => {}
+			(Some(i), change, false) => {
+				let entry = &mut self.entries[i];
+				entry.block = entry.block.min(block);
+
+				if let Change::Finalized(outcome) = change {
+					entry.outcome = Some(outcome);
+				}
+			}
+			(None, change, false) => self.entries.push(Entry {
+				address,
+				block,
+				outcome: match change {
+					Change::Finalized(outcome) => Some(outcome),
+					Change::Started => None,
+				},
+			}),
+		}
+
+		self.entries.sort_by_key(|e| std::cmp::Reverse(e.block));
+	}
+}
+
+/// Where a proposal is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+	/// Its voting period hasn't started
+	Draft,
+	Voting,
+
+	/// Its voting period is over, but nobody has finalized it yet
+	Ended,
+	Accepted,
+	Rejected,
+
+	/// It was finalized, but its outcome wasn't found in the logs
+	Closed,
+}
+
+impl Status {
+	/// Gets the status of the proposal from its expiry and closedAt, whether
+	/// it's active, and its outcome, if it's known.
+	pub fn new(expiry: Uint, closed_at: Uint, active: bool, outcome: Option<&Outcome>) -> Self {
+		match outcome {
+			Some(Outcome::Accepted { .. }) => Self::Accepted,
+			Some(Outcome::Rejected) => Self::Rejected,
+			None if !closed_at.is_zero() => Self::Closed,
+			None if expiry.is_zero() => Self::Draft,
+			None if active => Self::Voting,
+			None => Self::Ended,
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			Self::Draft => "Not started",
+			Self::Voting => "Voting",
+			Self::Ended => "Awaiting finalization",
+			Self::Accepted => "Accepted",
+			Self::Rejected => "Rejected",
+			Self::Closed => "Closed",
+		}
+	}
+}
+
+/// The votes cast on a proposal, and the supply of the Idea's token.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+	pub affirmative: Uint,
+	pub votes: Uint,
+	pub voters: Uint,
+	pub supply: Uint,
+}
+
+impl Tally {
+	pub fn negative(&self) -> Uint {
+		self.votes.saturating_sub(self.affirmative)
+	}
+
+	/// Gets the percentage of the supply voting for the proposal, rounded down
+	/// as finalizeProposal does.
+	pub fn percent_affirmative(&self) -> u64 {
+		if self.supply.is_zero() {
+			return 0;
+		}
+
+		(self.affirmative.saturating_mul(Uint::from(100)) / self.supply).low_u64()
+	}
+
+	/// Whether the proposal would be accepted if it were finalized now, which
+	/// takes more than half of the supply.
+	pub fn passes(&self) -> bool {
+		self.percent_affirmative() > 50
+	}
+}
+
+/// Formats an amount of the smallest units of a token with the decimals
+/// (e.g., 1.5).
+pub fn format_units(amount: Uint, decimals: u8) -> String {
+	let digits = format!(
+		"{:0>width$}",
+		amount.to_string(),
+		width = decimals as usize + 1
+	);
+	let (whole, frac) = digits.split_at(digits.len() - decimals as usize);
+	let frac = frac.trim_end_matches('0');
+
+	if frac.is_empty() {
+		whole.to_owned()
+	} else {
+		format!("{}.{}", whole, frac)
+	}
+}
+
+/// Parses an amount of a token entered by the user (e.g., 1.5) into its
+/// smallest units.
+pub fn parse_units(s: &str, decimals: u8) -> Option<Uint> {
+	let s = s.trim();
+	let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+
+	if (whole.is_empty() && frac.is_empty())
+		|| frac.len() > decimals as usize
+		|| !whole
+			.chars()
+			.chain(frac.chars())
+			.all(|c| c.is_ascii_digit())
+	{
+		return None;
+	}
+
+	let digits = format!("{}{:0<width$}", whole, frac, width = decimals as usize);
+
+	Uint::from_dec_str(digits.trim_start_matches('0'))
+		.ok()
+		.or_else(|| digits.chars().all(|c| c == '0').then(Uint::zero))
+}
+
+/// A difference between the metadata an Idea has and the metadata a proposal
+/// would give it. Modules are told apart by the CIDs of their payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+	Title { old: String, new: String },
+	Description { old: String, new: String },
+	AddedModule(String),
+	RemovedModule(String),
+}
+
+/// Gets the differences between the old and new metadata of an Idea.
+pub fn diff(old: &IdeaMetadata, new: &IdeaMetadata) -> Vec<Difference> {
+	let mut differences = Vec::new();
+
+	if old.title != new.title {
+		differences.push(Difference::Title {
+			old: old.title.clone(),
+			new: new.title.clone(),
+		});
+	}
+
+	if old.description != new.description {
+		differences.push(Difference::Description {
+			old: old.description.clone(),
+			new: new.description.clone(),
+		});
+	}
+
+	differences.extend(
+		old.payload
+			.iter()
+			.filter(|module| !new.payload.contains(module))
+			.map(|module| Difference::RemovedModule(module.cid().to_string())),
+	);
+	differences.extend(
+		new.payload
+			.iter()
+			.filter(|module| !old.payload.contains(module))
+			.map(|module| Difference::AddedModule(module.cid().to_string())),
+	);
+
+	differences
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use beacon_dao_schema::Link;
+
+	#[test]
+	fn test_apply() {
+		let mut proposals = Proposals::default();
+		proposals.apply("0xAA", 10, Change::Started, false);
+		proposals.apply("0xbb", 12, Change::Started, false);
+		assert_eq!(
+			proposals
+				.entries()
+				.iter()
+				.map(|e| e.address.as_str())
+				.collect::<Vec<&str>>(),
+			vec!["0xbb", "0xaa"]
+		);
+
+		let accepted = Outcome::Accepted {
+			old_payload: String::from("bafyold"),
+			new_payload: String::from("bafynew"),
+		};
+		proposals.apply("0xaa", 20, Change::Finalized(accepted.clone()), false);
+		assert_eq!(proposals.get(1).unwrap().outcome, Some(accepted.clone()));
+		assert_eq!(proposals.get(1).unwrap().block, 10);
+
+		// Proposals finalized without a VoteStarted log are still listed
+		proposals.apply("0xcc", 30, Change::Finalized(Outcome::Rejected), false);
+		assert_eq!(proposals.get(0).unwrap().address, "0xcc");
+
+		// Logs removed by reorgs are forgotten
+		proposals.apply("0xaa", 20, Change::Finalized(accepted), true);
+		assert_eq!(proposals.get(2).unwrap().outcome, None);
+		proposals.apply("0xbb", 12, Change::Started, true);
+		assert_eq!(proposals.entries().len(), 2);
+		proposals.apply("0xdd", 12, Change::Started, true);
+		assert_eq!(proposals.entries().len(), 2);
+	}
+
+	#[test]
+	fn test_status() {
+		let (zero, one) = (Uint::zero(), Uint::one());
+
+		assert_eq!(Status::new(zero, zero, false, None), Status::Draft);
+		assert_eq!(Status::new(one, zero, true, None), Status::Voting);
+		assert_eq!(Status::new(one, zero, false, None), Status::Ended);
+		assert_eq!(Status::new(one, one, false, None), Status::Closed);
+		assert_eq!(
+			Status::new(one, one, false, Some(&Outcome::Rejected)),
+			Status::Rejected
+		);
+	}
+
+	#[test]
+	fn test_tally() {
+		let tally = Tally {
+			affirmative: Uint::from(51),
+			votes: Uint::from(60),
+			voters: Uint::from(3),
+			supply: Uint::from(100),
+		};
+		assert_eq!(tally.negative(), Uint::from(9));
+		assert_eq!(tally.percent_affirmative(), 51);
+		assert!(tally.passes());
+
+		// Half of the supply isn't a majority
+		let tally = Tally {
+			affirmative: Uint::from(50),
+			..tally
+		};
+		assert!(!tally.passes());
+		assert!(!Tally::default().passes());
+	}
+
+	#[test]
+	fn test_units() {
+		let ether = Uint::exp10(18);
+
+		assert_eq!(format_units(ether * 3 / 2, 18), "1.5");
+		assert_eq!(format_units(Uint::from(5), 18), "0.000000000000000005");
+		assert_eq!(format_units(Uint::from(1200), 2), "12");
+		assert_eq!(format_units(Uint::from(7), 0), "7");
+
+		assert_eq!(parse_units("1.5", 18), Some(ether * 3 / 2));
+		assert_eq!(parse_units(" 12 ", 2), Some(Uint::from(1200)));
+		assert_eq!(parse_units(".5", 1), Some(Uint::from(5)));
+		assert_eq!(parse_units("0", 18), Some(Uint::zero()));
+		assert_eq!(parse_units("0.001", 2), None);
+		assert_eq!(parse_units("1e3", 18), None);
+		assert_eq!(parse_units(".", 18), None);
+		assert_eq!(parse_units("", 18), None);
+	}
+
+	#[test]
+	fn test_diff() {
+		let module = |cid: &str| Link::new(cid.parse().unwrap());
+		let (a, b) = (
+			"bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e",
+			"QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n",
+		);
+		let old = IdeaMetadata {
+			title: String::from("Vision"),
+			description: String::from("A DAO"),
+			payload: vec![module(a)],
+		};
+		let new = IdeaMetadata {
+			title: String::from("Vision"),
+			description: String::from("A better DAO"),
+			payload: vec![module(b)],
+		};
+
+		assert_eq!(diff(&old, &old), Vec::new());
+		assert_eq!(
+			diff(&old, &new),
+			vec![
+				Difference::Description {
+					old: String::from("A DAO"),
+					new: String::from("A better DAO"),
+				},
+				Difference::RemovedModule(a.to_owned()),
+				Difference::AddedModule(b.to_owned()),
+			]
+		);
+	}
+}
@@ -0,0 +1,330 @@
+use super::verify::verify_block;
+use beacon_dao_schema::Cid;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// The most bytes of blocks kept in memory by default.
+pub const DEFAULT_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Somewhere blocks can be kept between sessions (e.g., the browser's
+/// storage). Blocks loaded from it are verified again before they're used.
+pub trait Persist: Send + Sync {
+	fn load(&self, cid: &Cid) -> Option<Vec<u8>>;
+	fn save(&self, cid: &Cid, block: &[u8]);
+	fn remove(&self, cid: &Cid);
+}
+
+/// Keeps blocks in the runtime's storage (e.g., the browser's local storage)
+/// through its storage_* imports, under the string form of their CIDs.
+#[cfg(target_arch = "wasm32")]
+pub struct HostStorage;
+
+#[cfg(target_arch = "wasm32")]
+mod host {
+	extern "C" {
+		pub fn storage_get(key: i32, len_ptr: i32) -> i32;
+		pub fn storage_set(key: i32, ptr: i32, len: i32) -> u8;
+		pub fn storage_remove(key: i32) -> u8;
+		pub fn read_buffer(token: u32, ptr: i32) -> u8;
+	}
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(cid: &Cid) -> Option<std::ffi::CString> {
+	std::ffi::CString::new(format!("ipfs/{}", cid)).ok()
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Persist for HostStorage {
+	fn load(&self, cid: &Cid) -> Option<Vec<u8>> {
+		let key = storage_key(cid)?;
+		let mut len = [0u8; 4];
+
+		let token = unsafe { host::storage_get(key.as_ptr() as i32, len.as_mut_ptr() as i32) };
+		if token < 0 {
+			return None;
+		}
+
+		let mut block = vec![0u8; u32::from_le_bytes(len) as usize];
+		(unsafe { host::read_buffer(token as u32, block.as_mut_ptr() as i32) } == 0)
+			.then_some(block)
+	}
+
+	// Blocks that don't fit in the storage are only kept in memory
+	fn save(&self, cid: &Cid, block: &[u8]) {
+		if let Some(key) = storage_key(cid) {
+			unsafe {
+				host::storage_set(
+					key.as_ptr() as i32,
+					block.as_ptr() as i32,
+					block.len() as i32,
+				);
+			}
+		}
+	}
+
+	fn remove(&self, cid: &Cid) {
+		if let Some(key) = storage_key(cid) {
+			unsafe {
+				host::storage_remove(key.as_ptr() as i32);
+			}
+		}
+	}
+}
+
+/// How well the cache has been doing.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+
+	/// The number, and total size, of blocks in memory
+	pub blocks: usize,
+	pub size: usize,
+	pub capacity: usize,
+}
+
+/// Blocks kept in memory by their CIDs, up to a total size, past which the
+/// least recently used blocks are dropped. Since a CID always refers to the
+/// same block, blocks never go stale.
+pub struct BlockCache {
+	blocks: HashMap<Cid, (Vec<u8>, u64)>,
+
+	/// The CID of each block, by when it was last used
+	used: BTreeMap<u64, Cid>,
+	clock: u64,
+
+	size: usize,
+	capacity: usize,
+	hits: u64,
+	misses: u64,
+	backend: Option<Box<dyn Persist>>,
+}
+
+impl BlockCache {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			blocks: HashMap::new(),
+			used: BTreeMap::new(),
+			clock: 0,
+			size: 0,
+			capacity,
+			hits: 0,
+			misses: 0,
+			backend: None,
+		}
+	}
+
+	/// Keeps blocks in the backend, as well as in memory.
+	pub fn set_backend(&mut self, backend: Box<dyn Persist>) {
+		self.backend = Some(backend);
+	}
+
+	pub fn stats(&self) -> CacheStats {
+		CacheStats {
+			hits: self.hits,
+			misses: self.misses,
+			blocks: self.blocks.len(),
+			size: self.size,
+			capacity: self.capacity,
+		}
+	}
+
+	/// Marks the block as the most recently used.
+	fn touch(&mut self, cid: &Cid) {
+		self.clock += 1;
+
+		if let Some((_, used)) = self.blocks.get_mut(cid) {
+			self.used.remove(used);
+			*used = self.clock;
+			self.used.insert(self.clock, cid.clone());
+		}
+	}
+
+	/// Gets the block with the CID, from memory, or from the backend.
+	pub fn get(&mut self, cid: &Cid) -> Option<Vec<u8>> {
+		if self.blocks.contains_key(cid) {
+			self.hits += 1;
+			self.touch(cid);
+
+			return self.blocks.get(cid).map(|(block, _)| block.clone());
+		}
+
+		let block = self
+			.backend
+			.as_ref()
+			.and_then(|backend| backend.load(cid))
+			.filter(|block| verify_block(cid, block).is_ok());
+
+		match block {
+			Some(block) => {
+				self.hits += 1;
+				self.keep(cid.clone(), block.clone());
+
+				Some(block)
+			}
+			None => {
+				self.misses += 1;
+
+				None
+			}
+		}
+	}
+
+	/// Adds a verified block to the cache.
+	pub fn insert(&mut self, cid: Cid, block: Vec<u8>) {
+		if let Some(backend) = &self.backend {
+			backend.save(&cid, &block);
+		}
+
+		self.keep(cid, block);
+	}
+
+	/// Keeps the block in memory, dropping the least recently used blocks
+	/// until it fits.
+	fn keep(&mut self, cid: Cid, block: Vec<u8>) {
+		if block.len() > self.capacity {
+			return;
+		}
+
+		self.drop_block(&cid);
+		self.size += block.len();
+		self.blocks.insert(cid.clone(), (block, 0));
+		self.touch(&cid);
+
+		while self.size > self.capacity {
+			let oldest = match self.used.values().next() {
+				Some(cid) => cid.clone(),
+				None => break,
+			};
+
+			self.drop_block(&oldest);
+		}
+	}
+
+	fn drop_block(&mut self, cid: &Cid) -> bool {
+		match self.blocks.remove(cid) {
+			Some((block, used)) => {
+				self.used.remove(&used);
+				self.size -= block.len();
+
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Removes the block from memory, and the backend. Gets whether it was in
+	/// memory.
+	pub fn evict(&mut self, cid: &Cid) -> bool {
+		if let Some(backend) = &self.backend {
+			backend.remove(cid);
+		}
+
+		self.drop_block(cid)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::publish::block_cid;
+	use beacon_dao_schema::cid::RAW;
+	use std::sync::{Arc, Mutex};
+
+	fn block(content: &[u8]) -> (Cid, Vec<u8>) {
+		(block_cid(RAW, content), content.to_vec())
+	}
+
+	#[test]
+	fn test_lru() {
+		let mut cache = BlockCache::new(6);
+		let (a, b, c) = (block(b"aa"), block(b"bb"), block(b"cc"));
+
+		cache.insert(a.0.clone(), a.1.clone());
+		cache.insert(b.0.clone(), b.1.clone());
+		cache.insert(c.0.clone(), c.1.clone());
+		assert_eq!(cache.get(&a.0), Some(a.1.clone()));
+
+		// b and c are the least recently used, so they make room for d
+		let d = block(b"dddd");
+		cache.insert(d.0.clone(), d.1.clone());
+		assert_eq!(cache.get(&b.0), None);
+		assert_eq!(cache.get(&c.0), None);
+		assert_eq!(cache.get(&a.0), Some(a.1));
+		assert_eq!(cache.get(&d.0), Some(d.1));
+
+		assert_eq!(
+			cache.stats(),
+			CacheStats {
+				hits: 3,
+				misses: 2,
+				blocks: 2,
+				size: 6,
+				capacity: 6,
+			}
+		);
+
+		// Blocks bigger than the cache aren't kept
+		let e = block(b"eeeeeee");
+		cache.insert(e.0.clone(), e.1);
+		assert_eq!(cache.get(&e.0), None);
+		assert_eq!(cache.stats().size, 6);
+	}
+
+	#[test]
+	fn test_evict() {
+		let mut cache = BlockCache::new(DEFAULT_CAPACITY);
+		let (cid, content) = block(b"hello");
+
+		cache.insert(cid.clone(), content.clone());
+		cache.insert(cid.clone(), content);
+		assert_eq!(cache.stats().size, 5);
+
+		assert!(cache.evict(&cid));
+		assert!(!cache.evict(&cid));
+		assert_eq!(cache.get(&cid), None);
+		assert_eq!(cache.stats().size, 0);
+	}
+
+	#[derive(Clone, Default)]
+	struct MockBackend(Arc<Mutex<HashMap<Cid, Vec<u8>>>>);
+
+	impl Persist for MockBackend {
+		fn load(&self, cid: &Cid) -> Option<Vec<u8>> {
+			self.0.lock().unwrap().get(cid).cloned()
+		}
+
+		fn save(&self, cid: &Cid, block: &[u8]) {
+			self.0.lock().unwrap().insert(cid.clone(), block.to_vec());
+		}
+
+		fn remove(&self, cid: &Cid) {
+			self.0.lock().unwrap().remove(cid);
+		}
+	}
+
+	#[test]
+	fn test_backend() {
+		let backend = MockBackend::default();
+		let (cid, content) = block(b"hello");
+
+		let mut cache = BlockCache::new(DEFAULT_CAPACITY);
+		cache.set_backend(Box::new(backend.clone()));
+		cache.insert(cid.clone(), content.clone());
+
+		// A new session finds the block in the backend
+		let mut cache = BlockCache::new(DEFAULT_CAPACITY);
+		cache.set_backend(Box::new(backend.clone()));
+		assert_eq!(cache.get(&cid), Some(content));
+		assert_eq!(cache.stats().blocks, 1);
+
+		// Tampered blocks aren't used
+		let (other, _) = block(b"other");
+		backend.save(&other, b"tampered");
+		assert_eq!(cache.get(&other), None);
+
+		cache.evict(&cid);
+		assert_eq!(backend.load(&cid), None);
+	}
+}
@@ -0,0 +1,546 @@
+use super::{unixfs::Reply, Error};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+/// Gateways known by the IPFS adapter by default, in the order they're tried.
+pub const DEFAULT_GATEWAYS: [&str; 3] = [
+	"http://localhost:8080",
+	"https://ipfs.io",
+	"https://dweb.link",
+];
+
+/// Requests taking longer than this are abandoned, and count as having failed.
+pub const TIMEOUT_MS: u64 = 10_000;
+
+/// The number of failures in a row after which a gateway is considered down,
+/// and only tried once every other gateway has been.
+pub const MAX_FAILURES: u32 = 3;
+
+/// How well a gateway has been answering requests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GatewayStatus {
+	pub url: String,
+	pub successes: u32,
+	pub failures: u32,
+
+	/// Failures since the last success
+	pub failing: u32,
+
+	/// A moving average of how long successful requests took
+	pub latency_ms: Option<u64>,
+}
+
+impl GatewayStatus {
+	pub fn new(url: String) -> Self {
+		Self {
+			url,
+			successes: 0,
+			failures: 0,
+			failing: 0,
+			latency_ms: None,
+		}
+	}
+
+	pub fn is_healthy(&self) -> bool {
+		self.failing < MAX_FAILURES
+	}
+
+	/// Describes how the gateway is doing, for display to the user.
+	pub fn describe(&self) -> String {
+		if !self.is_healthy() {
+			return format!("Down ({} failures in a row)", self.failing);
+		}
+
+		match self.latency_ms {
+			Some(latency) => format!(
+				"Up, {} ms on average ({} of {} requests failed)",
+				latency,
+				self.failures,
+				self.successes + self.failures
+			),
+			None if self.failures > 0 => format!("Failing ({} failures in a row)", self.failing),
+			None => String::from("Not used yet"),
+		}
+	}
+}
+
+/// The gateways blocks are fetched from, and how many of them are raced
+/// against each other for each block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gateways {
+	statuses: Vec<GatewayStatus>,
+	race: usize,
+
+	/// The gateway the user chose to have tried first, while it's up
+	preferred: Option<String>,
+}
+
+impl Gateways {
+	pub fn new(urls: Vec<String>) -> Self {
+		Self {
+			statuses: urls.into_iter().map(GatewayStatus::new).collect(),
+			race: 1,
+			preferred: None,
+		}
+	}
+
+	pub fn statuses(&self) -> &[GatewayStatus] {
+		&self.statuses
+	}
+
+	pub fn race(&self) -> usize {
+		self.race
+	}
+
+	/// Sets how many gateways are asked for each block at once. The first to
+	/// answer is used.
+	pub fn set_race(&mut self, race: usize) {
+		self.race = race.max(1);
+	}
+
+	/// Replaces the list of gateways, keeping what is known about gateways
+	/// that are still in it.
+	pub fn set_urls(&mut self, urls: Vec<String>) {
+		let mut old = std::mem::take(&mut self.statuses);
+
+		self.statuses = urls
+			.into_iter()
+			.map(
+				|url| match old.iter().position(|status| status.url == url) {
+					Some(i) => old.remove(i),
+					None => GatewayStatus::new(url),
+				},
+			)
+			.collect();
+
+		if let Some(preferred) = &self.preferred {
+			if !self.statuses.iter().any(|status| status.url == *preferred) {
+				self.preferred = None;
+			}
+		}
+	}
+
+	/// Has the gateway tried first for as long as it's healthy, adding it to
+	/// the front of the list if it isn't in it yet.
+	pub fn prefer(&mut self, url: String) {
+		if !self.statuses.iter().any(|status| status.url == url) {
+			self.statuses.insert(0, GatewayStatus::new(url.clone()));
+		}
+
+		self.preferred = Some(url);
+	}
+
+	/// Gets the gateways in the order they should be tried: healthy gateways
+	/// first, starting with the preferred gateway, then fastest first.
+	/// Gateways that haven't answered yet keep their place at the front, so
+	/// that they get tried.
+	pub fn order(&self) -> Vec<String> {
+		let mut statuses: Vec<&GatewayStatus> = self.statuses.iter().collect();
+		statuses.sort_by_key(|status| {
+			(
+				!status.is_healthy(),
+				self.preferred.as_ref() != Some(&status.url),
+				status.latency_ms.unwrap_or(0),
+			)
+		});
+
+		statuses
+			.into_iter()
+			.map(|status| status.url.clone())
+			.collect()
+	}
+
+	/// Records how long a request to the gateway took, or that it failed.
+	pub fn record(&mut self, url: &str, res: Result<u64, ()>) {
+		let status = if let Some(status) = self.statuses.iter_mut().find(|s| s.url == url) {
+			status
+		} else {
+			return;
+		};
+
+		match res {
+			Ok(elapsed) if elapsed <= TIMEOUT_MS => {
+				status.successes += 1;
+				status.failing = 0;
+				status.latency_ms = Some(match status.latency_ms {
+					Some(avg) => (avg * 3 + elapsed) / 4,
+					None => elapsed,
+				});
+			}
+			_ => {
+				status.failures += 1;
+				status.failing += 1;
+			}
+		}
+	}
+}
+
+/// Splits the reply in two, for answering from whichever of two places
+/// answers first (e.g., a response, or a timeout). Later answers are dropped.
+pub fn first_of<T: 'static>(reply: Reply<T>) -> (Reply<T>, Reply<T>) {
+	let reply = Rc::new(RefCell::new(Some(reply)));
+	let other = reply.clone();
+
+	let answer = |reply: Rc<RefCell<Option<Reply<T>>>>| -> Reply<T> {
+		Box::new(move |res| {
+			let reply = reply.borrow_mut().take();
+			if let Some(reply) = reply {
+				reply(res);
+			}
+		})
+	};
+
+	(answer(reply), answer(other))
+}
+
+/// Gives up on a request.
+pub type GiveUp = Box<dyn FnOnce()>;
+
+/// Requests that will be given up on if they haven't been answered by a
+/// certain time.
+#[derive(Default)]
+pub struct Timeouts {
+	pending: Vec<(u64, GiveUp)>,
+}
+
+impl Timeouts {
+	/// Has the request given up on at or after due_ms.
+	pub fn add(&mut self, due_ms: u64, give_up: GiveUp) {
+		self.pending.push((due_ms, give_up));
+	}
+
+	/// Removes the requests that are due to be given up on at now_ms.
+	pub fn take_due(&mut self, now_ms: u64) -> Vec<GiveUp> {
+		let (due, pending) = std::mem::take(&mut self.pending)
+			.into_iter()
+			.partition(|(due_ms, _)| *due_ms <= now_ms);
+		self.pending = pending;
+
+		due.into_iter().map(|(_, give_up)| give_up).collect()
+	}
+
+	pub fn len(&self) -> usize {
+		self.pending.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+}
+
+/// A request in flight to several gateways.
+struct Race<T> {
+	/// Gateways that haven't been asked yet, in order
+	pending: VecDeque<String>,
+	in_flight: usize,
+	reply: Option<Reply<T>>,
+	last_err: Error,
+}
+
+/// Asks the first `race` gateways at once, then each of the rest in turn as
+/// those fail, replying with the first success, or the last error if every
+/// gateway fails.
+pub fn fetch_any<T: 'static, F: Fn(String, Reply<T>) + 'static>(
+	gateways: Vec<String>,
+	race: usize,
+	fetch: Rc<F>,
+	reply: Reply<T>,
+) {
+	let state = Rc::new(RefCell::new(Race {
+		pending: gateways.into(),
+		in_flight: 0,
+		reply: Some(reply),
+		last_err: Error::ServerError,
+	}));
+
+	for _ in 0..race.max(1) {
+		ask_next(state.clone(), fetch.clone());
+	}
+
+	// No gateways to ask
+	let reply = {
+		let mut state = state.borrow_mut();
+		if state.in_flight > 0 || !state.pending.is_empty() {
+			None
+		} else {
+			state
+				.reply
+				.take()
+				.map(|reply| (reply, state.last_err.clone()))
+		}
+	};
+	if let Some((reply, e)) = reply {
+		reply(Err(e));
+	}
+}
+
+/// Asks the next gateway that hasn't been asked yet, if the request is still
+/// waiting on an answer.
+fn ask_next<T: 'static, F: Fn(String, Reply<T>) + 'static>(
+	state: Rc<RefCell<Race<T>>>,
+	fetch: Rc<F>,
+) {
+	let gateway = {
+		let mut race = state.borrow_mut();
+		if race.reply.is_none() {
+			return;
+		}
+
+		match race.pending.pop_front() {
+			Some(gateway) => {
+				race.in_flight += 1;
+
+				gateway
+			}
+			None => return,
+		}
+	};
+
+	let next = fetch.clone();
+	fetch(
+		gateway,
+		Box::new(move |res| {
+			let done = {
+				let mut race = state.borrow_mut();
+				race.in_flight -= 1;
+
+				match res {
+					Ok(v) => race.reply.take().map(|reply| (reply, Ok(v))),
+					Err(e) => {
+						race.last_err = e;
+
+						if race.in_flight == 0 && race.pending.is_empty() {
+							let e = race.last_err.clone();
+							race.reply.take().map(|reply| (reply, Err(e)))
+						} else {
+							None
+						}
+					}
+				}
+			};
+
+			match done {
+				Some((reply, res)) => reply(res),
+				None => ask_next(state, next),
+			}
+		}),
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn urls(urls: &[&str]) -> Vec<String> {
+		urls.iter().map(|url| url.to_string()).collect()
+	}
+
+	#[test]
+	fn test_order() {
+		let mut gateways = Gateways::new(urls(&["a", "b", "c"]));
+		assert_eq!(gateways.order(), urls(&["a", "b", "c"]));
+
+		gateways.record("a", Ok(500));
+		gateways.record("b", Ok(100));
+		gateways.record("c", Ok(300));
+		assert_eq!(gateways.order(), urls(&["b", "c", "a"]));
+
+		// Gateways that keep failing go last
+		for _ in 0..MAX_FAILURES {
+			gateways.record("b", Err(()));
+		}
+		gateways.record("c", Ok(TIMEOUT_MS + 1));
+		assert_eq!(gateways.order(), urls(&["c", "a", "b"]));
+
+		let b = &gateways.statuses()[1];
+		assert!(!b.is_healthy());
+		assert_eq!((b.successes, b.failures), (1, MAX_FAILURES));
+
+		// A success brings it back
+		gateways.record("b", Ok(100));
+		assert_eq!(gateways.order(), urls(&["b", "c", "a"]));
+	}
+
+	#[test]
+	fn test_prefer() {
+		let mut gateways = Gateways::new(urls(&["a", "b", "c"]));
+		gateways.record("a", Ok(100));
+		gateways.record("b", Ok(500));
+		gateways.prefer(String::from("b"));
+		assert_eq!(gateways.order(), urls(&["b", "c", "a"]));
+
+		// New gateways are added to the list
+		gateways.prefer(String::from("d"));
+		assert_eq!(
+			gateways.statuses()[0],
+			GatewayStatus::new(String::from("d"))
+		);
+		gateways.record("d", Ok(900));
+		assert_eq!(gateways.order(), urls(&["d", "c", "a", "b"]));
+
+		// Only while the gateway is up
+		for _ in 0..MAX_FAILURES {
+			gateways.record("d", Err(()));
+		}
+		assert_eq!(gateways.order(), urls(&["c", "a", "b", "d"]));
+
+		// Or still in the list
+		gateways.set_urls(urls(&["a", "b"]));
+		gateways.set_urls(urls(&["a", "b", "d"]));
+		gateways.record("d", Ok(900));
+		assert_eq!(gateways.order(), urls(&["a", "b", "d"]));
+	}
+
+	#[test]
+	fn test_describe() {
+		let mut gateways = Gateways::new(urls(&["a"]));
+		assert_eq!(gateways.statuses()[0].describe(), "Not used yet");
+
+		gateways.record("a", Err(()));
+		assert_eq!(
+			gateways.statuses()[0].describe(),
+			"Failing (1 failures in a row)"
+		);
+
+		gateways.record("a", Ok(120));
+		assert_eq!(
+			gateways.statuses()[0].describe(),
+			"Up, 120 ms on average (1 of 2 requests failed)"
+		);
+
+		for _ in 0..MAX_FAILURES {
+			gateways.record("a", Err(()));
+		}
+		assert_eq!(
+			gateways.statuses()[0].describe(),
+			"Down (3 failures in a row)"
+		);
+	}
+
+	#[test]
+	fn test_set_urls() {
+		let mut gateways = Gateways::new(urls(&["a", "b"]));
+		gateways.record("b", Ok(100));
+		gateways.set_urls(urls(&["c", "b"]));
+
+		assert_eq!(
+			gateways.statuses()[0],
+			GatewayStatus::new(String::from("c"))
+		);
+		assert_eq!(gateways.statuses()[1].latency_ms, Some(100));
+	}
+
+	/// Fetches from mock gateways, which either answer immediately, or hang.
+	/// Gets the gateways asked, and the result, if there was one.
+	fn run(
+		gateways: &[&str],
+		race: usize,
+		answer: impl Fn(&str) -> Option<Result<u8, Error>> + 'static,
+	) -> (Vec<String>, Option<Result<u8, Error>>) {
+		let asked = Rc::new(RefCell::new(Vec::new()));
+		let out = Rc::new(RefCell::new(None));
+		let held: Rc<RefCell<Vec<Reply<u8>>>> = Rc::new(RefCell::new(Vec::new()));
+
+		let fetch = {
+			let (asked, held) = (asked.clone(), held.clone());
+
+			Rc::new(move |gateway: String, reply: Reply<u8>| {
+				asked.borrow_mut().push(gateway.clone());
+
+				match answer(&gateway) {
+					Some(res) => reply(res),
+					None => held.borrow_mut().push(reply),
+				}
+			})
+		};
+
+		let res = out.clone();
+		fetch_any(
+			urls(gateways),
+			race,
+			fetch,
+			Box::new(move |r| *res.borrow_mut() = Some(r)),
+		);
+
+		let asked = asked.borrow().clone();
+		let out = out.borrow_mut().take();
+
+		(asked, out)
+	}
+
+	#[test]
+	fn test_fallback() {
+		let (asked, res) = run(&["a", "b", "c"], 1, |gateway| match gateway {
+			"c" => Some(Ok(3)),
+			_ => Some(Err(Error::IntegrityError)),
+		});
+		assert_eq!(asked, urls(&["a", "b", "c"]));
+		assert!(matches!(res, Some(Ok(3))));
+
+		let (_, res) = run(&["a", "b"], 1, |_| Some(Err(Error::IntegrityError)));
+		assert!(matches!(res, Some(Err(Error::IntegrityError))));
+
+		let (asked, res) = run(&[], 1, |_| Some(Ok(1)));
+		assert!(asked.is_empty());
+		assert!(matches!(res, Some(Err(Error::ServerError))));
+	}
+
+	#[test]
+	fn test_timeouts() {
+		let out = Rc::new(RefCell::new(Vec::new()));
+		let mut timeouts = Timeouts::default();
+
+		// A request that answers in time, and one that doesn't
+		for i in 0..2 {
+			let res = out.clone();
+			let (answer, timed_out) = first_of(Box::new(move |r: Result<u8, Error>| {
+				res.borrow_mut().push(r)
+			}));
+			timeouts.add(
+				100 * (i + 1),
+				Box::new(move || timed_out(Err(Error::ServerError))),
+			);
+
+			if i == 0 {
+				answer(Ok(1));
+			} else {
+				for give_up in timeouts.take_due(150) {
+					give_up();
+				}
+				assert_eq!(timeouts.len(), 1);
+
+				for give_up in timeouts.take_due(200) {
+					give_up();
+				}
+
+				// The late answer is dropped
+				answer(Ok(2));
+			}
+		}
+
+		assert!(timeouts.is_empty());
+		assert!(matches!(
+			&out.borrow()[..],
+			[Ok(1), Err(Error::ServerError)]
+		));
+	}
+
+	#[test]
+	fn test_race() {
+		// A hung gateway doesn't hold up the rest of the race
+		let (asked, res) = run(&["a", "b", "c"], 2, |gateway| match gateway {
+			"a" => None,
+			_ => Some(Ok(2)),
+		});
+		assert_eq!(asked, urls(&["a", "b"]));
+		assert!(matches!(res, Some(Ok(2))));
+
+		// Without racing, it does
+		let (asked, res) = run(&["a", "b", "c"], 1, |gateway| match gateway {
+			"a" => None,
+			_ => Some(Ok(2)),
+		});
+		assert_eq!(asked, urls(&["a"]));
+		assert!(res.is_none());
+	}
+}
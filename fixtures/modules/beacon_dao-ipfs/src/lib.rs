@@ -4,14 +4,23 @@ use beacon_dao_fetch::{
 };
 use beacon_dao_logger_manager::info;
 use beacon_dao_permissions::{has_permission, register_permission};
+use beacon_dao_schema::{
+	cid::{DAG_CBOR, DAG_JSON, DAG_PB},
+	dag_cbor, dag_json, dag_pb, Cid,
+};
+use cache::{BlockCache, DEFAULT_CAPACITY};
+use gateways::{Gateways, GiveUp, Timeouts, DEFAULT_GATEWAYS, TIMEOUT_MS};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
+	cell::RefCell,
 	collections::HashMap,
 	error::Error as StdError,
+	rc::Rc,
 	sync::{Arc, RwLock},
 };
 use vision_derive::with_bindings;
+use vision_utils::actor::address;
 use vision_utils::types::{
 	Address, Callback, DISPLAY_MANAGER_ADDR, FETCH_ADDR, LOGGER_ADDR, PERM_ADDR,
 };
@@ -22,20 +31,64 @@ const PERM_CHANGE_DESC: &'static str = "change which IPFS provider you're connec
 const PERM_USE: &'static str = "use IPFS";
 const PERM_USE_DESC: &'static str = "interact with the IPFS network.";
 
+const PERM_PUBLISH: &str = "publish to IPFS";
+const PERM_PUBLISH_DESC: &str = "store content with your IPFS pinning service.";
+
+/// Checking that content from gateways is what was asked for.
+pub mod verify;
+
+/// Reading files stored in UnixFS.
+pub mod unixfs;
+
+/// Storing blocks with a Kubo RPC endpoint.
+pub mod publish;
+
+/// Choosing between, and falling back on, several gateways.
+pub mod gateways;
+
+/// Keeping blocks that have already been fetched.
+pub mod cache;
+
+pub use cache::CacheStats;
+pub use gateways::GatewayStatus;
+
 /// Errors that might be encountered when using this API.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Error {
 	NoPermission,
 	SerializationError,
 	ServerError,
+	InvalidCid,
+
+	/// The gateway returned content that doesn't match the CID
+	IntegrityError,
+
+	/// The gateway didn't answer in time
+	Timeout,
 }
 
-/// Providers known by the IPFS adapter by default.
-const DEFAULT_PROVIDER: &'static str = "http://localhost:8080";
+/// The Kubo RPC endpoint content is published to by default.
+const DEFAULT_PIN_PROVIDER: &str = "http://localhost:5001";
 
-/// The current RPC endpoint in use.
+/// The gateways in use, and the endpoint content is published to.
 lazy_static::lazy_static! {
-	pub static ref ADAPTER_RPC: RwLock<String> = RwLock::new(DEFAULT_PROVIDER.to_owned());
+	pub static ref GATEWAYS: RwLock<Gateways> = RwLock::new(Gateways::new(
+		DEFAULT_GATEWAYS.iter().map(|url| url.to_string()).collect()
+	));
+	pub static ref CACHE: RwLock<BlockCache> = RwLock::new(new_cache());
+	pub static ref PIN_RPC: RwLock<String> = RwLock::new(DEFAULT_PIN_PROVIDER.to_owned());
+}
+
+/// Creates the block cache, which keeps blocks in the runtime's storage
+/// between sessions when running in the runtime.
+fn new_cache() -> BlockCache {
+	#[cfg_attr(not(target_arch = "wasm32"), allow(unused_mut))]
+	let mut cache = BlockCache::new(DEFAULT_CAPACITY);
+
+	#[cfg(target_arch = "wasm32")]
+	cache.set_backend(Box::new(cache::HostStorage));
+
+	cache
 }
 
 #[cfg(feature = "module")]
@@ -54,9 +107,18 @@ pub extern "C" fn handle_init_async(owner: Address) {
 		PERM_USE_DESC.to_owned(),
 		Callback::new(|_| {}),
 	);
+
+	register_permission(
+		PERM_ADDR,
+		PERM_PUBLISH.to_owned(),
+		PERM_PUBLISH_DESC.to_owned(),
+		Callback::new(|_| {}),
+	);
 }
 
-/// Replaces the RPC endpoint in use with the new one, if the user has permissions to do so.
+/// Makes the gateway the first to be tried for as long as it's up, if the
+/// user has permissions to do so. The other gateways are kept to fall back
+/// on.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_change_rpc_endpoint(
@@ -75,20 +137,98 @@ pub extern "C" fn handle_change_rpc_endpoint(
 				return;
 			}
 
-			// Replace the provider URL
-			let mut l = ADAPTER_RPC.write().unwrap();
-			*l = new_rpc;
+			GATEWAYS.write().unwrap().prefer(new_rpc);
 
 			callback.call(Ok(()));
 		}),
 	)
 }
 
-/// Gets the Network currently connected to by this web3 adapter.
+/// Gets the gateway that will be tried first.
 #[no_mangle]
 #[with_bindings]
 pub extern "C" fn handle_get_rpc_endpoint(from: Address, callback: Callback<String>) {
-	callback.call(ADAPTER_RPC.write().unwrap().clone());
+	callback.call(
+		GATEWAYS
+			.read()
+			.unwrap()
+			.order()
+			.into_iter()
+			.next()
+			.unwrap_or_default(),
+	);
+}
+
+/// Replaces the gateways in use, and sets how many of them are raced for each
+/// block, if the user has permissions to do so.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_set_gateways(
+	from: Address,
+	urls: Vec<String>,
+	race: usize,
+	callback: Callback<Result<(), Error>>,
+) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_CHANGE.to_owned(),
+		Callback::new(move |has_permission: bool| {
+			if !has_permission && from != DISPLAY_MANAGER_ADDR {
+				callback.call(Err(Error::NoPermission));
+
+				return;
+			}
+
+			let mut gateways = GATEWAYS.write().unwrap();
+			gateways.set_urls(urls);
+			gateways.set_race(race);
+
+			callback.call(Ok(()));
+		}),
+	)
+}
+
+/// Gets how well each gateway has been answering requests.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get_gateway_status(from: Address, callback: Callback<Vec<GatewayStatus>>) {
+	callback.call(GATEWAYS.read().unwrap().statuses().to_vec());
+}
+
+/// Replaces the Kubo RPC endpoint content is published to with the new one, if
+/// the user has permissions to do so.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_change_pin_endpoint(
+	from: Address,
+	new_rpc: String,
+	callback: Callback<Result<(), Error>>,
+) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_CHANGE.to_owned(),
+		Callback::new(move |has_permission: bool| {
+			if !has_permission && from != DISPLAY_MANAGER_ADDR {
+				callback.call(Err(Error::NoPermission));
+
+				return;
+			}
+
+			let mut l = PIN_RPC.write().unwrap();
+			*l = new_rpc;
+
+			callback.call(Ok(()));
+		}),
+	)
+}
+
+/// Gets the Kubo RPC endpoint content is published to.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get_pin_endpoint(from: Address, callback: Callback<String>) {
+	callback.call(PIN_RPC.read().unwrap().clone());
 }
 
 /// Options for how to conduct the query.
@@ -97,20 +237,168 @@ pub struct Options {
 	pub format: Option<Format>,
 }
 
+/// The form content is returned in. Content is always fetched from gateways
+/// as raw blocks, so that it can be verified, and then decoded according to
+/// its CID: Raw gets the block itself, rather than the file it holds.
 #[derive(Serialize, Deserialize)]
 pub enum Format {
 	DagJson,
+	DagCbor,
 	Raw,
 }
 
-/// Gets an entry from the IPLD DAG.
+/// Gets the current time in milliseconds, if the runtime has a clock.
+fn now() -> Option<u64> {
+	extern "C" {
+		fn now_ms() -> i64;
+	}
+
+	u64::try_from(unsafe { now_ms() }).ok()
+}
+
+/// The name of the message sent by the runtime once a request to a gateway
+/// may have timed out.
+const TIMEOUT_MSG: &[u8] = b"gateway_timeout\0";
+
+// Requests can't leave the thread they were made on
+thread_local! {
+	static TIMEOUTS: RefCell<Timeouts> = RefCell::new(Timeouts::default());
+}
+
+/// Gives up on the request once TIMEOUT_MS has passed, if the runtime has a
+/// clock.
+fn set_timeout(give_up: GiveUp) {
+	extern "C" {
+		fn set_timeout(msg: i32, ms: i32) -> u8;
+	}
+
+	let due = if let Some(now) = now() {
+		now + TIMEOUT_MS
+	} else {
+		return;
+	};
+
+	if unsafe { set_timeout(TIMEOUT_MSG.as_ptr() as i32, TIMEOUT_MS as i32) } == 0 {
+		TIMEOUTS.with(|timeouts| timeouts.borrow_mut().add(due, give_up));
+	}
+}
+
+/// Gives up on requests to gateways that have taken longer than TIMEOUT_MS,
+/// so that the next gateway is tried. Sent by the runtime once the timer set
+/// by set_timeout is up.
+#[no_mangle]
+pub extern "C" fn handle_gateway_timeout(from: Address) {
+	if from != address() {
+		return;
+	}
+
+	let now = if let Some(now) = now() {
+		now
+	} else {
+		return;
+	};
+
+	// Giving up may start requests to other gateways, which add timeouts
+	for give_up in TIMEOUTS.with(|timeouts| timeouts.borrow_mut().take_due(now)) {
+		give_up();
+	}
+}
+
+/// Gets the block with the CID from the cache, or else fetches it from the
+/// gateways, checking that it is the block the CID refers to, and falling back
+/// on other gateways if it isn't, or if they don't answer in time.
+fn get_block(cid: &str, callback: impl FnOnce(Result<(Cid, Vec<u8>), Error>) + 'static) {
+	let cid = match cid.parse::<Cid>() {
+		Ok(cid) => cid,
+		Err(_) => {
+			callback(Err(Error::InvalidCid));
+
+			return;
+		}
+	};
+
+	if let Some(block) = CACHE.write().ok().and_then(|mut cache| cache.get(&cid)) {
+		callback(Ok((cid, block)));
+
+		return;
+	}
+
+	let (order, race) = match GATEWAYS.read() {
+		Ok(gateways) => (gateways.order(), gateways.race()),
+		Err(_) => {
+			callback(Err(Error::ServerError));
+
+			return;
+		}
+	};
+
+	let to_fetch = cid.clone();
+	let fetch = Rc::new(move |gateway: String, reply: unixfs::Reply<Vec<u8>>| {
+		let cid = to_fetch.clone();
+		let url = format!("{}/ipfs/{}?format=raw", gateway, cid);
+		let start = now();
+
+		// Whichever of the response and the timeout comes first is recorded.
+		// Gateways returning the wrong content count as failing.
+		let (answer, timed_out) =
+			gateways::first_of(Box::new(move |block: Result<(Vec<u8>, u64), Error>| {
+				if let Ok(mut gateways) = GATEWAYS.write() {
+					gateways.record(
+						&gateway,
+						block.as_ref().map(|(_, elapsed)| *elapsed).map_err(|_| ()),
+					);
+				}
+
+				reply(block.map(|(block, _)| block));
+			}));
+		set_timeout(Box::new(move || timed_out(Err(Error::Timeout))));
+
+		fetch_raw(
+			FETCH_ADDR,
+			url,
+			<FetchOptionsBuilder<String> as Into<FetchOptions>>::into(FetchOptionsBuilder {
+				method: Some(FetchMethod::GET),
+				headers: None,
+				body: None,
+			}),
+			Callback::new(move |resp: Result<Response, ()>| {
+				let elapsed = now()
+					.zip(start)
+					.map(|(end, start)| end.saturating_sub(start))
+					.unwrap_or_default();
+
+				answer(
+					resp.ok()
+						.and_then(|resp| resp.body)
+						.ok_or(Error::ServerError)
+						.and_then(|block| verify::verify_block(&cid, &block).map(|_| block))
+						.map(|block| (block, elapsed)),
+				);
+			}),
+		);
+	});
+
+	gateways::fetch_any(
+		order,
+		race,
+		fetch,
+		Box::new(move |block: Result<Vec<u8>, Error>| {
+			if let (Ok(block), Ok(mut cache)) = (&block, CACHE.write()) {
+				cache.insert(cid.clone(), block.clone());
+			}
+
+			callback(block.map(|block| (cid, block)))
+		}),
+	);
+}
+
+/// Removes the block with the CID from the cache.
 #[no_mangle]
 #[with_bindings]
-pub extern "C" fn handle_get_dag(
+pub extern "C" fn handle_evict(
 	from: Address,
 	cid: String,
-	options: Options,
-	callback: Callback<Result<Value, Error>>,
+	callback: Callback<Result<bool, Error>>,
 ) {
 	has_permission(
 		PERM_ADDR,
@@ -122,43 +410,36 @@ pub extern "C" fn handle_get_dag(
 				return;
 			}
 
-			let adapter = if let Ok(prov) = ADAPTER_RPC.read() {
-				prov
-			} else {
-				callback.call(Err(Error::ServerError));
-
-				return;
-			};
+			let cid = match cid.parse::<Cid>() {
+				Ok(cid) => cid,
+				Err(_) => {
+					callback.call(Err(Error::InvalidCid));
 
-			let url_opts = match options.format {
-				Some(Format::DagJson) => "?format=dag-json",
-				Some(Format::Raw) => "?format=raw",
-				_ => "",
+					return;
+				}
 			};
 
-			fetch_json(
-				FETCH_ADDR,
-				format!("{}/ipfs/{}{}", adapter, cid, url_opts),
-				<FetchOptionsBuilder<String> as Into<FetchOptions>>::into(FetchOptionsBuilder {
-					method: Some(FetchMethod::GET),
-					headers: None,
-					body: None,
-				}),
-				Callback::new(|resp: Result<Response, ()>| {
-					callback.call(
-						resp.ok()
-							.and_then(|resp| resp.json)
-							.ok_or(Error::ServerError),
-					);
-				}),
+			callback.call(
+				CACHE
+					.write()
+					.map(|mut cache| cache.evict(&cid))
+					.map_err(|_| Error::ServerError),
 			);
 		}),
 	);
 }
 
+/// Gets how many blocks have been found in the cache, and how full it is.
 #[no_mangle]
 #[with_bindings]
-pub extern "C" fn handle_get(
+pub extern "C" fn handle_get_cache_stats(from: Address, callback: Callback<CacheStats>) {
+	callback.call(CACHE.read().unwrap().stats());
+}
+
+/// Gets an entry from the IPLD DAG.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get_dag(
 	from: Address,
 	cid: String,
 	options: Options,
@@ -174,36 +455,201 @@ pub extern "C" fn handle_get(
 				return;
 			}
 
-			let adapter = if let Ok(prov) = ADAPTER_RPC.read() {
-				prov
-			} else {
-				callback.call(Err(Error::ServerError));
+			// DAG-CBOR and dag-pb are read as DAG-JSON, so that callers see
+			// the same representation regardless of the codec used
+			get_block(&cid, move |block| {
+				callback.call(block.and_then(|(cid, block)| {
+					match cid.codec() {
+						DAG_JSON => dag_json::from_slice(&block),
+						DAG_CBOR => dag_cbor::from_slice(&block),
+						DAG_PB => dag_pb::from_slice(&block),
+						_ => return Err(Error::SerializationError),
+					}
+					.and_then(|ipld| dag_json::encode(&ipld))
+					.map_err(|_| Error::SerializationError)
+				}));
+			});
+		}),
+	);
+}
 
+/// Gets the raw bytes of an entry from IPFS.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get(
+	from: Address,
+	cid: String,
+	options: Options,
+	callback: Callback<Result<Vec<u8>, Error>>,
+) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_USE.to_owned(),
+		Callback::new(move |has_perm: bool| {
+			if !has_perm && from != DISPLAY_MANAGER_ADDR {
+				callback.call(Err(Error::NoPermission));
+				return;
+			}
+
+			get_block(&cid, move |block| {
+				callback.call(block.and_then(|(cid, block)| match options.format {
+					Some(Format::Raw) => Ok(block),
+					_ => unixfs::leaf_content(&cid, block),
+				}));
+			});
+		}),
+	);
+}
+
+/// Gets the content of a UnixFS file from IPFS, reassembling it from each of
+/// its blocks, which are verified as they are fetched.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get_file(
+	from: Address,
+	cid: String,
+	callback: Callback<Result<Vec<u8>, Error>>,
+) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_USE.to_owned(),
+		Callback::new(move |has_perm: bool| {
+			if !has_perm && from != DISPLAY_MANAGER_ADDR {
+				callback.call(Err(Error::NoPermission));
 				return;
+			}
+
+			let cid = match cid.parse::<Cid>() {
+				Ok(cid) => cid,
+				Err(_) => {
+					callback.call(Err(Error::InvalidCid));
+
+					return;
+				}
 			};
 
-			let url_opts = match options.format {
-				Some(Format::DagJson) => "?format=dag-json",
-				Some(Format::Raw) => "?format=raw",
-				_ => "",
+			let fetch = Rc::new(move |cid: Cid, reply: unixfs::Reply<Vec<u8>>| {
+				get_block(&cid.to_string(), move |block| {
+					reply(block.map(|(_, block)| block))
+				});
+			});
+
+			unixfs::read_file(fetch, cid, Box::new(move |content| callback.call(content)));
+		}),
+	);
+}
+
+/// Makes requests to the Kubo RPC through the fetch service.
+struct FetchPost;
+
+impl publish::Post for FetchPost {
+	fn post(
+		&self,
+		url: String,
+		content_type: String,
+		body: Vec<u8>,
+		reply: unixfs::Reply<Option<Value>>,
+	) {
+		fetch_json(
+			FETCH_ADDR,
+			url,
+			FetchOptions {
+				method: FetchMethod::POST,
+				headers: HashMap::from([(String::from("Content-Type"), content_type)]),
+				body: Some(body),
+			},
+			Callback::new(move |resp: Result<Response, ()>| {
+				reply(resp.map(|resp| resp.json).map_err(|_| Error::ServerError));
+			}),
+		);
+	}
+}
+
+/// Checks that the user can publish content, then stores the blocks with the
+/// pinning service, calling back with the CID of the last of them.
+fn publish_blocks(
+	from: Address,
+	blocks: Result<Vec<(Cid, Vec<u8>)>, Error>,
+	callback: Callback<Result<String, Error>>,
+) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_PUBLISH.to_owned(),
+		Callback::new(move |has_perm: bool| {
+			if !has_perm && from != DISPLAY_MANAGER_ADDR {
+				callback.call(Err(Error::NoPermission));
+				return;
+			}
+
+			let (blocks, url) = match (blocks, PIN_RPC.read()) {
+				(Ok(blocks), Ok(url)) => (blocks, url.clone()),
+				(Err(e), _) => {
+					callback.call(Err(e));
+
+					return;
+				}
+				(_, Err(_)) => {
+					callback.call(Err(Error::ServerError));
+
+					return;
+				}
 			};
 
-			fetch_raw(
-				FETCH_ADDR,
-				format!("{}/ipfs/{}{}", adapter, cid, url_opts),
-				<FetchOptionsBuilder<String> as Into<FetchOptions>>::into(FetchOptionsBuilder {
-					method: Some(FetchMethod::GET),
-					headers: None,
-					body: None,
-				}),
-				Callback::new(|resp: Result<Response, ()>| {
-					callback.call(
-						resp.ok()
-							.and_then(|resp| resp.body)
-							.ok_or(Error::ServerError),
-					);
+			let root = blocks
+				.last()
+				.map(|(cid, _)| cid.to_string())
+				.unwrap_or_default();
+
+			publish::put_blocks(
+				Rc::new(publish::KuboStore {
+					endpoint: url,
+					post: FetchPost,
 				}),
+				blocks,
+				Box::new(move |res| callback.call(res.map(|_| root))),
 			);
 		}),
 	);
 }
+
+/// Adds the bytes to IPFS as a UnixFS file, calling back with its CID.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_add(
+	from: Address,
+	content: Vec<u8>,
+	callback: Callback<Result<String, Error>>,
+) {
+	let (_, blocks) = unixfs::build_file(&content);
+
+	publish_blocks(from, Ok(blocks), callback);
+}
+
+/// Adds the DAG-JSON value to IPFS as a block in the format, calling back with
+/// its CID.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_put_dag(
+	from: Address,
+	value: Value,
+	format: Format,
+	callback: Callback<Result<String, Error>>,
+) {
+	let block = dag_json::decode(&value)
+		.map_err(|_| Error::SerializationError)
+		.and_then(|ipld| {
+			let (codec, block) = match format {
+				Format::DagJson => (DAG_JSON, dag_json::to_vec(&ipld)),
+				Format::DagCbor => (DAG_CBOR, dag_cbor::to_vec(&ipld)),
+				Format::Raw => return Err(Error::SerializationError),
+			};
+			let block = block.map_err(|_| Error::SerializationError)?;
+
+			Ok(vec![(publish::block_cid(codec, &block), block)])
+		});
+
+	publish_blocks(from, block, callback);
+}
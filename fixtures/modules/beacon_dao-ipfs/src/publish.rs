@@ -0,0 +1,439 @@
+use super::{unixfs::Reply, Error};
+use beacon_dao_schema::cid::{Cid, DAG_CBOR, DAG_JSON, DAG_PB, RAW, SHA2_256};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::rc::Rc;
+
+/// Gets the CID of a block encoded with the codec, hashed with sha2-256.
+pub fn block_cid(codec: u64, block: &[u8]) -> Cid {
+	Cid::new_v1(codec, SHA2_256, Sha256::digest(block).to_vec())
+}
+
+/// Gets the name the Kubo RPC uses for the codec.
+fn codec_name(codec: u64) -> Option<&'static str> {
+	match codec {
+		RAW => Some("raw"),
+		DAG_PB => Some("dag-pb"),
+		DAG_CBOR => Some("dag-cbor"),
+		DAG_JSON => Some("dag-json"),
+		_ => None,
+	}
+}
+
+/// Gets the URL of the Kubo RPC call that stores, and pins, the block with
+/// the CID.
+pub fn block_put_url(endpoint: &str, cid: &Cid) -> Result<String, Error> {
+	let codec = codec_name(cid.codec()).ok_or(Error::SerializationError)?;
+
+	Ok(format!(
+		"{}/api/v0/block/put?cid-codec={}&mhtype=sha2-256&pin=true",
+		endpoint.trim_end_matches('/'),
+		codec
+	))
+}
+
+/// Gets the content type and body of the multipart form the Kubo RPC expects
+/// a block to be sent in. The boundary is made from the CID, which can't
+/// appear in the block it is the hash of.
+pub fn block_put_body(cid: &Cid, block: &[u8]) -> (String, Vec<u8>) {
+	let boundary: String = cid.digest().iter().map(|b| format!("{:02x}", b)).collect();
+
+	let mut body = format!(
+		"--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"block\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+		boundary
+	)
+	.into_bytes();
+	body.extend_from_slice(block);
+	body.extend(format!("\r\n--{}--\r\n", boundary).into_bytes());
+
+	(format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+/// Checks that the Kubo RPC stored the block under the CID it was expected to.
+pub fn read_put_response(cid: &Cid, resp: Option<Value>) -> Result<(), Error> {
+	let key = resp
+		.as_ref()
+		.and_then(|resp| resp.get("Key"))
+		.and_then(Value::as_str)
+		.ok_or(Error::ServerError)?;
+
+	match key.parse::<Cid>() {
+		Ok(key) if key == *cid => Ok(()),
+		_ => Err(Error::IntegrityError),
+	}
+}
+
+/// Somewhere blocks can be stored (e.g., a Kubo RPC endpoint, or a mock in
+/// tests).
+pub trait Store {
+	fn put(&self, cid: Cid, block: Vec<u8>, reply: Reply<()>);
+}
+
+impl<F: Fn(Cid, Vec<u8>, Reply<()>)> Store for F {
+	fn put(&self, cid: Cid, block: Vec<u8>, reply: Reply<()>) {
+		self(cid, block, reply)
+	}
+}
+
+/// Something that makes POST requests to the Kubo RPC (e.g., the fetch
+/// service, or a mock in tests), replying with the JSON body of the response.
+pub trait Post {
+	fn post(&self, url: String, content_type: String, body: Vec<u8>, reply: Reply<Option<Value>>);
+}
+
+impl<F: Fn(String, String, Vec<u8>, Reply<Option<Value>>)> Post for F {
+	fn post(&self, url: String, content_type: String, body: Vec<u8>, reply: Reply<Option<Value>>) {
+		self(url, content_type, body, reply)
+	}
+}
+
+/// Stores blocks through the Kubo RPC at the endpoint.
+pub struct KuboStore<P> {
+	pub endpoint: String,
+	pub post: P,
+}
+
+impl<P: Post> Store for KuboStore<P> {
+	fn put(&self, cid: Cid, block: Vec<u8>, reply: Reply<()>) {
+		let url = match block_put_url(&self.endpoint, &cid) {
+			Ok(url) => url,
+			Err(e) => {
+				reply(Err(e));
+
+				return;
+			}
+		};
+		let (content_type, body) = block_put_body(&cid, &block);
+
+		self.post.post(
+			url,
+			content_type,
+			body,
+			Box::new(move |resp| reply(resp.and_then(|resp| read_put_response(&cid, resp)))),
+		);
+	}
+}
+
+/// Stores each of the blocks, one at a time and in order, stopping at the
+/// first that can't be stored. Blocks should be ordered with the root last,
+/// so that it is only published once everything it links to is.
+pub fn put_blocks<S: Store + 'static>(
+	store: Rc<S>,
+	mut blocks: Vec<(Cid, Vec<u8>)>,
+	reply: Reply<()>,
+) {
+	blocks.reverse();
+	put_next(store, blocks, reply);
+}
+
+/// Stores the last of the pending blocks, which are in reverse order.
+fn put_next<S: Store + 'static>(store: Rc<S>, mut pending: Vec<(Cid, Vec<u8>)>, reply: Reply<()>) {
+	let (cid, block) = if let Some(next) = pending.pop() {
+		next
+	} else {
+		reply(Ok(()));
+
+		return;
+	};
+
+	store.clone().put(
+		cid,
+		block,
+		Box::new(move |res| match res {
+			Ok(_) => put_next(store, pending, reply),
+			Err(e) => reply(Err(e)),
+		}),
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+	use std::cell::RefCell;
+
+	#[test]
+	fn test_block_put_request() {
+		let cid = block_cid(RAW, b"hello world");
+		assert_eq!(
+			cid.to_string(),
+			"bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+		);
+
+		assert_eq!(
+			block_put_url("http://localhost:5001/", &cid).unwrap(),
+			"http://localhost:5001/api/v0/block/put?cid-codec=raw&mhtype=sha2-256&pin=true"
+		);
+
+		let (content_type, body) = block_put_body(&cid, b"hello world");
+		let boundary = content_type
+			.strip_prefix("multipart/form-data; boundary=")
+			.unwrap();
+		let body = String::from_utf8(body).unwrap();
+		assert!(body.starts_with(&format!("--{}\r\n", boundary)));
+		assert!(body.ends_with(&format!("\r\n\r\nhello world\r\n--{}--\r\n", boundary)));
+	}
+
+	#[test]
+	fn test_read_put_response() {
+		let cid = block_cid(RAW, b"hello world");
+
+		assert!(
+			read_put_response(&cid, Some(json!({ "Key": cid.to_string(), "Size": 11 }))).is_ok()
+		);
+		assert!(matches!(
+			read_put_response(
+				&block_cid(RAW, b"hello"),
+				Some(json!({ "Key": cid.to_string() }))
+			),
+			Err(Error::IntegrityError)
+		));
+		assert!(matches!(
+			read_put_response(&cid, Some(json!({ "Message": "blockstore is read-only" }))),
+			Err(Error::ServerError)
+		));
+	}
+
+	/// A request made to the Kubo RPC.
+	#[derive(Debug)]
+	struct KuboRequest {
+		path: String,
+		query: Vec<(String, String)>,
+
+		/// The headers and content of each part of the multipart body
+		parts: Vec<(Vec<String>, Vec<u8>)>,
+	}
+
+	/// Reads a request to the Kubo RPC at http://localhost:5001.
+	fn read_request(url: &str, content_type: &str, body: &[u8]) -> KuboRequest {
+		let (path, query) = url
+			.strip_prefix("http://localhost:5001")
+			.unwrap()
+			.split_once('?')
+			.unwrap();
+		let query = query
+			.split('&')
+			.map(|pair| {
+				let (k, v) = pair.split_once('=').unwrap();
+
+				(k.to_owned(), v.to_owned())
+			})
+			.collect();
+
+		let boundary = content_type
+			.strip_prefix("multipart/form-data; boundary=")
+			.unwrap();
+		let delimiter = format!("\r\n--{}", boundary).into_bytes();
+
+		// The body opens with a delimiter, so a CRLF is added to split on it
+		let mut body = [b"\r\n", body].concat();
+		let end = format!("\r\n--{}--\r\n", boundary).into_bytes();
+		assert!(body.ends_with(&end));
+		body.truncate(body.len() - end.len());
+
+		let mut parts = Vec::new();
+		let mut rest = &body[..];
+		while let Some(at) = rest.windows(delimiter.len()).rposition(|w| w == delimiter) {
+			let part = &rest[at + delimiter.len()..];
+			rest = &rest[..at];
+
+			let split = part.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+			let headers = String::from_utf8(part[..split].to_vec()).unwrap();
+			parts.insert(
+				0,
+				(
+					headers
+						.split("\r\n")
+						.filter(|h| !h.is_empty())
+						.map(str::to_owned)
+						.collect(),
+					part[split + 4..].to_vec(),
+				),
+			);
+		}
+		assert!(rest.is_empty());
+
+		KuboRequest {
+			path: path.to_owned(),
+			query,
+			parts,
+		}
+	}
+
+	fn kubo_store(
+		respond: impl Fn(&KuboRequest) -> Option<Value> + 'static,
+	) -> (Rc<impl Store>, Rc<RefCell<Vec<KuboRequest>>>) {
+		let requests = Rc::new(RefCell::new(Vec::new()));
+		let seen = requests.clone();
+
+		let post =
+			move |url: String, content_type: String, body: Vec<u8>, reply: Reply<Option<Value>>| {
+				let req = read_request(&url, &content_type, &body);
+				let resp = respond(&req);
+				seen.borrow_mut().push(req);
+
+				reply(Ok(resp));
+			};
+
+		(
+			Rc::new(KuboStore {
+				endpoint: String::from("http://localhost:5001"),
+				post,
+			}),
+			requests,
+		)
+	}
+
+	fn put(store: Rc<impl Store + 'static>, blocks: Vec<(Cid, Vec<u8>)>) -> Result<(), Error> {
+		let res = Rc::new(RefCell::new(None));
+		let out = res.clone();
+		put_blocks(
+			store,
+			blocks,
+			Box::new(move |r| *out.borrow_mut() = Some(r)),
+		);
+
+		let res = res.take();
+		res.unwrap()
+	}
+
+	#[test]
+	fn test_kubo_store() {
+		// Responds as Kubo does to /api/v0/block/put, with the CID of the block
+		// it was sent
+		let (store, requests) = kubo_store(|req| {
+			let codec = match req.query[0] {
+				(ref k, ref v) if k == "cid-codec" && v == "dag-pb" => DAG_PB,
+				_ => RAW,
+			};
+			let block = &req.parts[0].1;
+
+			Some(json!({
+				"Key": block_cid(codec, block).to_string(),
+				"Size": block.len(),
+			}))
+		});
+
+		let content: Vec<u8> = (0..crate::unixfs::CHUNK_SIZE + 1)
+			.map(|i| i as u8)
+			.collect();
+		let (root, blocks) = crate::unixfs::build_file(&content);
+		assert!(put(store, blocks.clone()).is_ok());
+
+		let requests = requests.borrow();
+		assert_eq!(requests.len(), 3);
+		for (req, (cid, block)) in requests.iter().zip(blocks.iter()) {
+			assert_eq!(req.path, "/api/v0/block/put");
+			assert_eq!(
+				req.query,
+				vec![
+					(
+						String::from("cid-codec"),
+						String::from(codec_name(cid.codec()).unwrap())
+					),
+					(String::from("mhtype"), String::from("sha2-256")),
+					(String::from("pin"), String::from("true")),
+				]
+			);
+
+			assert_eq!(req.parts.len(), 1);
+			assert_eq!(
+				req.parts[0].0,
+				vec![
+					String::from(
+						"Content-Disposition: form-data; name=\"file\"; filename=\"block\""
+					),
+					String::from("Content-Type: application/octet-stream"),
+				]
+			);
+			assert_eq!(&req.parts[0].1, block);
+		}
+
+		// The root is published last
+		assert_eq!(requests[2].query[0].1, "dag-pb");
+		assert_eq!(block_cid(DAG_PB, &requests[2].parts[0].1), root);
+	}
+
+	#[test]
+	fn test_kubo_store_errors() {
+		let blocks = vec![(block_cid(RAW, b"hello world"), b"hello world".to_vec())];
+
+		// Kubo answers failed calls with an error object
+		let (store, _) = kubo_store(|_| {
+			Some(json!({
+				"Message": "cannot add block: blockstore is read-only",
+				"Code": 0,
+				"Type": "error",
+			}))
+		});
+		assert!(matches!(
+			put(store, blocks.clone()),
+			Err(Error::ServerError)
+		));
+
+		// Blocks stored under some other CID
+		let (store, _) = kubo_store(|_| {
+			Some(json!({
+				"Key": "bafkreiaeqmapvdnfnrvrxnhkuuyb5ahfl5ftsuhjtrrrmkdppkdhhxulo4",
+				"Size": 11,
+			}))
+		});
+		assert!(matches!(
+			put(store, blocks.clone()),
+			Err(Error::IntegrityError)
+		));
+
+		// Responses that aren't JSON
+		let (store, _) = kubo_store(|_| None);
+		assert!(matches!(put(store, blocks), Err(Error::ServerError)));
+	}
+
+	#[test]
+	fn test_put_blocks() {
+		let blocks = [
+			(block_cid(RAW, b"a"), b"a".to_vec()),
+			(block_cid(RAW, b"b"), b"b".to_vec()),
+			(block_cid(RAW, b"c"), b"c".to_vec()),
+		];
+
+		let stored = Rc::new(RefCell::new(Vec::new()));
+		let store = {
+			let stored = stored.clone();
+
+			Rc::new(move |cid: Cid, block: Vec<u8>, reply: Reply<()>| {
+				if block == b"c" {
+					reply(Err(Error::ServerError));
+
+					return;
+				}
+
+				stored.borrow_mut().push(cid);
+				reply(Ok(()));
+			})
+		};
+
+		let res = Rc::new(RefCell::new(None));
+		let out = res.clone();
+		put_blocks(
+			store.clone(),
+			blocks[..2].to_vec(),
+			Box::new(move |r| *out.borrow_mut() = Some(r)),
+		);
+		assert!(matches!(res.take(), Some(Ok(()))));
+		assert_eq!(
+			*stored.borrow(),
+			vec![blocks[0].0.clone(), blocks[1].0.clone()]
+		);
+
+		// Blocks after one that fails aren't stored
+		stored.borrow_mut().clear();
+		let out = res.clone();
+		put_blocks(
+			store,
+			vec![blocks[2].clone(), blocks[0].clone()],
+			Box::new(move |r| *out.borrow_mut() = Some(r)),
+		);
+		assert!(matches!(res.take(), Some(Err(Error::ServerError))));
+		assert!(stored.borrow().is_empty());
+	}
+}
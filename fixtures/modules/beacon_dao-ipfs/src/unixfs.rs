@@ -0,0 +1,614 @@
+use super::publish::block_cid;
+use super::Error;
+use beacon_dao_schema::{
+	cid::{Cid, DAG_PB, RAW},
+	dag_pb::{self, read_fields, write_bytes_field, write_varint_field, Field, PbLink, PbNode},
+};
+use std::{cell::RefCell, rc::Rc};
+
+/// The UnixFS data types of nodes holding file content.
+const TYPE_RAW: u64 = 0;
+const TYPE_FILE: u64 = 2;
+
+/// The size of the chunks files are split into when they're added, and the
+/// most links a node of a file may have, as in Kubo.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+pub const MAX_LINKS: usize = 174;
+
+/// The most blocks read for a single file, and the deepest a block may be
+/// under the root. Files of up to 1 GiB in chunks of CHUNK_SIZE fit in both,
+/// while a malicious file (e.g., one linking to an empty node many times
+/// over) can't keep the reader fetching forever.
+pub const MAX_BLOCKS: usize = 4096;
+pub const MAX_DEPTH: usize = 8;
+
+/// The UnixFS metadata in the data of a dag-pb node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnixFsData {
+	pub kind: u64,
+	pub data: Vec<u8>,
+	pub filesize: Option<u64>,
+	pub blocksizes: Vec<u64>,
+}
+
+pub fn decode_data(bytes: &[u8]) -> Result<UnixFsData, Error> {
+	let mut data = UnixFsData::default();
+	let mut has_kind = false;
+
+	read_fields(bytes, |number, field| {
+		match (number, field) {
+			(1, Field::Varint(kind)) => {
+				data.kind = kind;
+				has_kind = true;
+			}
+			(2, Field::Bytes(bytes)) => data.data = bytes.to_vec(),
+			(3, Field::Varint(size)) => data.filesize = Some(size),
+			(4, Field::Varint(size)) => data.blocksizes.push(size),
+			(1..=4, _) => return Err(beacon_dao_schema::Error::InvalidProtobuf),
+			_ => {}
+		}
+
+		Ok(())
+	})
+	.map_err(|_| Error::SerializationError)?;
+
+	if !has_kind {
+		return Err(Error::SerializationError);
+	}
+
+	Ok(data)
+}
+
+pub fn encode_data(data: &UnixFsData) -> Vec<u8> {
+	let mut out = Vec::new();
+
+	write_varint_field(1, data.kind, &mut out);
+	if !data.data.is_empty() {
+		write_bytes_field(2, &data.data, &mut out);
+	}
+	if let Some(size) = data.filesize {
+		write_varint_field(3, size, &mut out);
+	}
+	for size in &data.blocksizes {
+		write_varint_field(4, *size, &mut out);
+	}
+
+	out
+}
+
+/// A block of a UnixFS file: the content it holds itself, and the blocks
+/// holding the rest of its content, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNode {
+	pub content: Vec<u8>,
+	pub links: Vec<Cid>,
+
+	/// The size of the whole file under this node
+	pub filesize: u64,
+}
+
+/// Reads a verified block of a UnixFS file. Raw blocks are leaves holding
+/// only content.
+pub fn read_node(cid: &Cid, block: Vec<u8>) -> Result<FileNode, Error> {
+	match cid.codec() {
+		RAW => Ok(FileNode {
+			filesize: block.len() as u64,
+			content: block,
+			links: Vec::new(),
+		}),
+		DAG_PB => {
+			let node = dag_pb::decode(&block).map_err(|_| Error::SerializationError)?;
+			let data = decode_data(node.data.as_deref().unwrap_or_default())?;
+
+			if data.kind != TYPE_RAW && data.kind != TYPE_FILE {
+				return Err(Error::SerializationError);
+			}
+
+			// Every chunk of the file has its size listed in the node
+			if data.blocksizes.len() != node.links.len() {
+				return Err(Error::SerializationError);
+			}
+
+			Ok(FileNode {
+				filesize: data.filesize.unwrap_or(data.data.len() as u64),
+				content: data.data,
+				links: node.links.into_iter().map(|link| link.cid).collect(),
+			})
+		}
+		_ => Err(Error::SerializationError),
+	}
+}
+
+/// Gets the content of a file stored in a single, verified block: a raw
+/// block, or a dag-pb node without links. Other blocks are returned as-is.
+pub fn leaf_content(cid: &Cid, block: Vec<u8>) -> Result<Vec<u8>, Error> {
+	if cid.codec() != RAW && cid.codec() != DAG_PB {
+		return Ok(block);
+	}
+
+	match read_node(cid, block)? {
+		FileNode { content, links, .. } if links.is_empty() => Ok(content),
+
+		// Files split across several blocks are read with read_file
+		_ => Err(Error::SerializationError),
+	}
+}
+
+/// A node of a file being built, and the sizes the node above it needs.
+struct Built {
+	cid: Cid,
+	filesize: u64,
+
+	/// The size of the block, and every block under it
+	tsize: u64,
+}
+
+/// Splits the content into raw blocks, and links them together into a
+/// balanced tree of UnixFS nodes, as `ipfs add --cid-version=1 --raw-leaves`
+/// does. Gets the CID of the file, and its blocks, with the root last.
+pub fn build_file(content: &[u8]) -> (Cid, Vec<(Cid, Vec<u8>)>) {
+	let mut blocks = Vec::new();
+
+	let chunks: Vec<&[u8]> = if content.is_empty() {
+		vec![content]
+	} else {
+		content.chunks(CHUNK_SIZE).collect()
+	};
+
+	let mut layer: Vec<Built> = chunks
+		.into_iter()
+		.map(|chunk| {
+			let cid = block_cid(RAW, chunk);
+			blocks.push((cid.clone(), chunk.to_vec()));
+
+			Built {
+				cid,
+				filesize: chunk.len() as u64,
+				tsize: chunk.len() as u64,
+			}
+		})
+		.collect();
+
+	while layer.len() > 1 {
+		layer = layer
+			.chunks(MAX_LINKS)
+			.map(|children| {
+				let data = UnixFsData {
+					kind: TYPE_FILE,
+					data: Vec::new(),
+					filesize: Some(children.iter().map(|child| child.filesize).sum()),
+					blocksizes: children.iter().map(|child| child.filesize).collect(),
+				};
+				let block = dag_pb::encode(&PbNode {
+					links: children
+						.iter()
+						.map(|child| PbLink {
+							cid: child.cid.clone(),
+							name: Some(String::new()),
+							size: Some(child.tsize),
+						})
+						.collect(),
+					data: Some(encode_data(&data)),
+				});
+
+				let cid = block_cid(DAG_PB, &block);
+				let built = Built {
+					cid: cid.clone(),
+					filesize: data.filesize.unwrap_or_default(),
+					tsize: block.len() as u64
+						+ children.iter().map(|child| child.tsize).sum::<u64>(),
+				};
+				blocks.push((cid, block));
+
+				built
+			})
+			.collect();
+	}
+
+	(layer.remove(0).cid, blocks)
+}
+
+pub type Reply<T> = Box<dyn FnOnce(Result<T, Error>)>;
+
+/// Something that gets verified blocks by their CIDs (e.g., a gateway, or a
+/// mock in tests).
+pub trait Fetch {
+	fn fetch(&self, cid: Cid, reply: Reply<Vec<u8>>);
+}
+
+impl<F: Fn(Cid, Reply<Vec<u8>>)> Fetch for F {
+	fn fetch(&self, cid: Cid, reply: Reply<Vec<u8>>) {
+		self(cid, reply)
+	}
+}
+
+/// A file being read: the blocks left to read, in reverse order with their
+/// depths, and the content read so far.
+struct Reading {
+	pending: Vec<(Cid, usize)>,
+	fetched: usize,
+	filesize: Option<u64>,
+	content: Vec<u8>,
+	reply: Option<Reply<Vec<u8>>>,
+
+	/// The block fetched most recently, which hasn't been read yet
+	arrived: Option<(Cid, usize, Result<Vec<u8>, Error>)>,
+
+	/// Whether blocks are being read in a loop, which picks up blocks fetched
+	/// synchronously (e.g., from the cache)
+	running: bool,
+}
+
+impl Reading {
+	/// Adds the content of the block to the file, and queues the blocks it
+	/// links to. Reading stops if the blocks hold more content than the root
+	/// block says the file has, since a block may be linked many times over.
+	fn read(
+		&mut self,
+		cid: &Cid,
+		depth: usize,
+		block: Result<Vec<u8>, Error>,
+	) -> Result<(), Error> {
+		let node = block.and_then(|block| read_node(cid, block))?;
+
+		let filesize = *self.filesize.get_or_insert(node.filesize);
+		self.content.extend(node.content);
+		if self.content.len() as u64 > filesize {
+			return Err(Error::SerializationError);
+		}
+
+		self.pending
+			.extend(node.links.into_iter().rev().map(|link| (link, depth + 1)));
+
+		Ok(())
+	}
+
+	/// Gets the next block to fetch, or None if every block has been read.
+	fn next(&mut self) -> Result<Option<(Cid, usize)>, Error> {
+		let (cid, depth) = if let Some(next) = self.pending.pop() {
+			next
+		} else {
+			return Ok(None);
+		};
+
+		if self.fetched >= MAX_BLOCKS || depth > MAX_DEPTH {
+			return Err(Error::SerializationError);
+		}
+		self.fetched += 1;
+
+		Ok(Some((cid, depth)))
+	}
+
+	/// Gets the content of the file, once every block has been read.
+	fn finish(&mut self) -> Result<Vec<u8>, Error> {
+		if self.filesize != Some(self.content.len() as u64) {
+			return Err(Error::SerializationError);
+		}
+
+		Ok(std::mem::take(&mut self.content))
+	}
+}
+
+/// Reads the UnixFS file with the CID, following the links of each of its
+/// blocks in order, and reassembling its content. Files with more than
+/// MAX_BLOCKS blocks, or deeper than MAX_DEPTH, aren't read.
+pub fn read_file<F: Fetch + 'static>(fetch: Rc<F>, cid: Cid, reply: Reply<Vec<u8>>) {
+	let reading = Rc::new(RefCell::new(Reading {
+		pending: vec![(cid, 0)],
+		fetched: 0,
+		filesize: None,
+		content: Vec::new(),
+		reply: Some(reply),
+		arrived: None,
+		running: false,
+	}));
+
+	read_blocks(fetch, reading);
+}
+
+/// Reads blocks of the file until one has to be waited on. Blocks fetched
+/// synchronously are read in a loop, rather than by recursing, so that large
+/// files don't overflow the stack.
+fn read_blocks<F: Fetch + 'static>(fetch: Rc<F>, reading: Rc<RefCell<Reading>>) {
+	reading.borrow_mut().running = true;
+
+	loop {
+		let next = {
+			let mut state = reading.borrow_mut();
+
+			match state.arrived.take() {
+				Some((cid, depth, block)) => state.read(&cid, depth, block),
+				None => Ok(()),
+			}
+			.and_then(|_| state.next())
+		};
+
+		let (cid, depth) = match next {
+			Ok(Some(next)) => next,
+			next => {
+				let (res, reply) = {
+					let mut state = reading.borrow_mut();
+
+					(next.and_then(|_| state.finish()), state.reply.take())
+				};
+				if let Some(reply) = reply {
+					reply(res);
+				}
+
+				return;
+			}
+		};
+
+		let (state, resume) = (reading.clone(), fetch.clone());
+		fetch.fetch(
+			cid.clone(),
+			Box::new(move |block| {
+				let running = {
+					let mut state = state.borrow_mut();
+					state.arrived = Some((cid, depth, block));
+
+					state.running
+				};
+
+				if !running {
+					read_blocks(resume, state);
+				}
+			}),
+		);
+
+		// Wait for blocks that aren't fetched synchronously
+		let mut state = reading.borrow_mut();
+		if state.arrived.is_none() {
+			state.running = false;
+
+			return;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_leaf_content() {
+		let cid: Cid = "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+			.parse()
+			.unwrap();
+		let mut block = vec![0x0a, 0x12, 0x08, 0x02, 0x12, 0x0c];
+		block.extend(b"hello world\n");
+		block.extend([0x18, 0x0c]);
+
+		assert_eq!(
+			leaf_content(&cid, block).unwrap(),
+			b"hello world\n".to_vec()
+		);
+
+		let raw = Cid::new_v1(RAW, 0x12, vec![0; 32]);
+		assert_eq!(leaf_content(&raw, vec![1, 2]).unwrap(), vec![1, 2]);
+	}
+
+	#[test]
+	fn test_decode_data() {
+		assert_eq!(
+			decode_data(&[0x08, 0x02, 0x12, 0x01, 0x61, 0x18, 0x01]).unwrap(),
+			UnixFsData {
+				kind: TYPE_FILE,
+				data: vec![0x61],
+				filesize: Some(1),
+				blocksizes: Vec::new(),
+			}
+		);
+
+		// Directories aren't files
+		let cid = Cid::new_v0(vec![0; 32]).unwrap();
+		assert!(leaf_content(&cid, vec![0x0a, 0x02, 0x08, 0x01]).is_err());
+		assert!(decode_data(&[0x12, 0x00]).is_err());
+	}
+
+	/// Encodes a UnixFS file node, and gets its CID.
+	fn file_node(data: &[u8], links: &[(Cid, u64)]) -> (Cid, Vec<u8>) {
+		use sha2::{Digest, Sha256};
+
+		let mut unixfs = vec![0x08, 0x02];
+		if !data.is_empty() {
+			unixfs.extend([0x12, data.len() as u8]);
+			unixfs.extend(data);
+		}
+		let size = data.len() as u64 + links.iter().map(|(_, size)| size).sum::<u64>();
+		unixfs.extend([0x18, size as u8]);
+		for (_, size) in links {
+			unixfs.extend([0x20, *size as u8]);
+		}
+
+		let mut block = Vec::new();
+		for (cid, _) in links {
+			let hash = cid.to_bytes();
+			block.extend([0x12, hash.len() as u8 + 2, 0x0a, hash.len() as u8]);
+			block.extend(hash);
+		}
+		block.extend([0x0a, unixfs.len() as u8]);
+		block.extend(unixfs);
+
+		(Cid::new_v0(Sha256::digest(&block).to_vec()).unwrap(), block)
+	}
+
+	fn raw_block(data: &[u8]) -> (Cid, Vec<u8>) {
+		use sha2::{Digest, Sha256};
+
+		(
+			Cid::new_v1(RAW, 0x12, Sha256::digest(data).to_vec()),
+			data.to_vec(),
+		)
+	}
+
+	fn read(blocks: Vec<(Cid, Vec<u8>)>, root: Cid) -> Result<Vec<u8>, Error> {
+		let fetch = Rc::new(move |cid: Cid, reply: Reply<Vec<u8>>| {
+			match blocks.iter().find(|(c, _)| *c == cid) {
+				Some((_, block)) => reply(Ok(block.clone())),
+				None => reply(Err(Error::ServerError)),
+			}
+		});
+
+		let out = Rc::new(std::cell::RefCell::new(None));
+		let res = out.clone();
+		read_file(
+			fetch,
+			root,
+			Box::new(move |content| *res.borrow_mut() = Some(content)),
+		);
+
+		out.take().unwrap()
+	}
+
+	#[test]
+	fn test_read_file() {
+		let a = raw_block(b"hello ");
+		let b = raw_block(b"wide ");
+		let c = file_node(b"world", &[]);
+		let inner = file_node(b"", &[(b.0.clone(), 5), (c.0.clone(), 5)]);
+		let root = file_node(b"", &[(a.0.clone(), 6), (inner.0.clone(), 10)]);
+
+		let blocks = vec![a, b, c, inner, root.clone()];
+		assert_eq!(
+			read(blocks.clone(), root.0.clone()).unwrap(),
+			b"hello wide world"
+		);
+
+		// A single block is a file too
+		let (cid, _) = raw_block(b"hello ");
+		assert_eq!(read(blocks.clone(), cid).unwrap(), b"hello ");
+
+		// Blocks that arrive later, as they do from gateways
+		type Delivery = Box<dyn FnOnce()>;
+		let queue: Rc<RefCell<Vec<Delivery>>> = Rc::new(RefCell::new(Vec::new()));
+		let later = queue.clone();
+		let fetch = Rc::new(move |cid: Cid, reply: Reply<Vec<u8>>| {
+			let block = blocks
+				.iter()
+				.find(|(c, _)| *c == cid)
+				.map(|(_, b)| b.clone());
+			later
+				.borrow_mut()
+				.push(Box::new(move || reply(block.ok_or(Error::ServerError))));
+		});
+
+		let out = Rc::new(RefCell::new(None));
+		let res = out.clone();
+		read_file(
+			fetch,
+			root.0,
+			Box::new(move |content| *res.borrow_mut() = Some(content)),
+		);
+
+		loop {
+			let next = queue.borrow_mut().pop();
+			match next {
+				Some(deliver) => deliver(),
+				None => break,
+			}
+		}
+		assert_eq!(out.take().unwrap().unwrap(), b"hello wide world");
+	}
+
+	#[test]
+	fn test_read_file_invalid() {
+		let a = raw_block(b"hello");
+
+		// A missing block
+		let root = file_node(b"", &[(a.0.clone(), 5)]);
+		assert!(read(vec![root.clone()], root.0).is_err());
+
+		// More content than the file should have
+		let root = file_node(b"", &[(a.0.clone(), 1)]);
+		assert!(read(vec![a.clone(), root.clone()], root.0).is_err());
+
+		// A block that isn't part of a file
+		let dag_cbor = Cid::new_v1(0x71, 0x12, a.0.digest().to_vec());
+		assert!(read(vec![(dag_cbor.clone(), a.1)], dag_cbor).is_err());
+	}
+
+	#[test]
+	fn test_read_file_budget() {
+		// An empty node linked many times over never adds content, so only
+		// the budget stops it from being fetched
+		let empty = file_node(b"", &[]);
+		let mut blocks = vec![empty];
+		for _ in 0..3 {
+			let below = blocks.last().unwrap().0.clone();
+			blocks.push(file_node(b"", &vec![(below, 0); 40]));
+		}
+		let root = blocks.last().unwrap().clone();
+
+		let fetched = Rc::new(std::cell::Cell::new(0));
+		let count = fetched.clone();
+		let fetch = Rc::new(move |cid: Cid, reply: Reply<Vec<u8>>| {
+			count.set(count.get() + 1);
+
+			match blocks.iter().find(|(c, _)| *c == cid) {
+				Some((_, block)) => reply(Ok(block.clone())),
+				None => reply(Err(Error::ServerError)),
+			}
+		});
+
+		let out = Rc::new(std::cell::RefCell::new(None));
+		let res = out.clone();
+		read_file(
+			fetch,
+			root.0,
+			Box::new(move |content| *res.borrow_mut() = Some(content)),
+		);
+
+		assert!(out.take().unwrap().is_err());
+		assert_eq!(fetched.get(), MAX_BLOCKS);
+
+		// Chains of nodes deeper than the limit
+		let mut chain = vec![raw_block(b"x")];
+		for _ in 0..=MAX_DEPTH {
+			let below = chain.last().unwrap().0.clone();
+			chain.push(file_node(b"", &[(below, 1)]));
+		}
+		let root = chain.last().unwrap().0.clone();
+		assert!(read(chain.clone(), root).is_err());
+
+		let root = chain[MAX_DEPTH].0.clone();
+		assert_eq!(read(chain, root).unwrap(), b"x");
+	}
+
+	#[test]
+	fn test_build_file() {
+		// Small files are a single raw block
+		let (cid, blocks) = build_file(b"hello world");
+		assert_eq!(
+			cid.to_string(),
+			"bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+		);
+		assert_eq!(blocks, vec![(cid, b"hello world".to_vec())]);
+
+		let content: Vec<u8> = (0..CHUNK_SIZE * 3 + 5).map(|i| i as u8).collect();
+		let (cid, blocks) = build_file(&content);
+		assert_eq!(cid.codec(), DAG_PB);
+		assert_eq!(blocks.len(), 5);
+		assert_eq!(blocks.last().map(|(c, _)| c), Some(&cid));
+		assert_eq!(read(blocks, cid).unwrap(), content);
+	}
+
+	#[test]
+	fn test_encode_data() {
+		let data = UnixFsData {
+			kind: TYPE_FILE,
+			data: b"hello world\n".to_vec(),
+			filesize: Some(12),
+			blocksizes: Vec::new(),
+		};
+
+		// The data of the block in test_leaf_content
+		let mut bytes = vec![0x08, 0x02, 0x12, 0x0c];
+		bytes.extend(b"hello world\n");
+		bytes.push(0x18);
+		bytes.push(0x0c);
+
+		assert_eq!(encode_data(&data), bytes);
+		assert_eq!(decode_data(&bytes).unwrap(), data);
+	}
+}
@@ -0,0 +1,64 @@
+use super::Error;
+use beacon_dao_schema::cid::{Cid, IDENTITY, SHA2_256};
+use sha2::{Digest, Sha256};
+
+/// Checks that the block fetched from a gateway is the block the CID refers
+/// to. Blocks hashed with functions that can't be checked here are rejected,
+/// rather than trusted.
+pub fn verify_block(cid: &Cid, block: &[u8]) -> Result<(), Error> {
+	let matches = match cid.hash_code() {
+		SHA2_256 => Sha256::digest(block).as_slice() == cid.digest(),
+		IDENTITY => block == cid.digest(),
+		_ => false,
+	};
+
+	if matches {
+		Ok(())
+	} else {
+		Err(Error::IntegrityError)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use beacon_dao_schema::cid::RAW;
+
+	#[test]
+	fn test_verify_raw() {
+		let cid: Cid = "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+			.parse()
+			.unwrap();
+
+		assert!(verify_block(&cid, b"hello world").is_ok());
+		assert!(matches!(
+			verify_block(&cid, b"hello w0rld"),
+			Err(Error::IntegrityError)
+		));
+	}
+
+	#[test]
+	fn test_verify_dag_pb() {
+		// echo "hello world" | ipfs add
+		let cid: Cid = "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+			.parse()
+			.unwrap();
+		let mut block = vec![0x0a, 0x12, 0x08, 0x02, 0x12, 0x0c];
+		block.extend(b"hello world\n");
+		block.extend([0x18, 0x0c]);
+
+		assert!(verify_block(&cid, &block).is_ok());
+		assert!(verify_block(&cid, b"hello world\n").is_err());
+	}
+
+	#[test]
+	fn test_verify_other_hashes() {
+		let identity = Cid::new_v1(RAW, IDENTITY, b"hi".to_vec());
+		assert!(verify_block(&identity, b"hi").is_ok());
+		assert!(verify_block(&identity, b"ho").is_err());
+
+		// blake2b-256 can't be checked
+		let blake = Cid::new_v1(RAW, 0xb220, vec![0; 32]);
+		assert!(verify_block(&blake, b"").is_err());
+	}
+}
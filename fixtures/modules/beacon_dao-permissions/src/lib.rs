@@ -1,5 +1,6 @@
 pub use vision_derive::beacon_dao_allocator;
 
+use beacon_dao_capabilities::Capability;
 use vision_derive::with_bindings;
 use vision_utils::types::{
 	Address, Callback, DISPLAY_MANAGER_ADDR, EXIT_FAILURE, EXIT_SUCCESS, PERM_AGENT_ADDR,
@@ -13,7 +14,19 @@ use std::{
 /// Marks which actors have been given permission to use different capabilities.
 lazy_static::lazy_static! {
 	static ref PERMISSIONS: Arc<RwLock<HashMap<String, (String, HashSet<Address>)>>> =
-		Arc::new(RwLock::new(HashMap::new()));
+		Arc::new(RwLock::new(
+			// Permissions for host resources are provided by the runtime, rather
+			// than an actor
+			Capability::ALL
+				.iter()
+				.map(|cap| {
+					(
+						cap.permission().to_string(),
+						(cap.description().to_string(), HashSet::new()),
+					)
+				})
+				.collect(),
+		));
 }
 
 /// Registers a capability of the Vision OS that the user needs to consent to allowing.
@@ -108,5 +121,101 @@ pub fn handle_set_permission(
 	};
 	perm.1.insert(actor);
 
+	// Let the runtime know if the permission is for a host capability
+	if let Some(cap) = Capability::from_permission(&permission) {
+		extern "C" {
+			fn grant_capability(actor: Address, mask: u32) -> u8;
+		}
+
+		if unsafe { grant_capability(actor, cap.bit()) } != 0 {
+			callback.call(EXIT_FAILURE);
+
+			return;
+		}
+	}
+
+	callback.call(EXIT_SUCCESS);
+}
+
+/// Revokes every permission granted so far, keeping the registered
+/// permissions. Used by the display manager to give a new session (e.g., a
+/// guest) a fresh set of permissions.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_reset_permissions(from: Address, callback: Callback<u32>) {
+	if from != DISPLAY_MANAGER_ADDR {
+		callback.call(EXIT_FAILURE);
+
+		return;
+	}
+
+	let mut lock = if let Ok(lock) = PERMISSIONS.write() {
+		lock
+	} else {
+		callback.call(EXIT_FAILURE);
+
+		return;
+	};
+
+	// Take back host capabilities from the runtime as well
+	extern "C" {
+		fn revoke_capability(actor: Address, mask: u32) -> u8;
+	}
+
+	for (actor, cap) in clear_grants(&mut lock) {
+		unsafe { revoke_capability(actor, cap.bit()) };
+	}
+
 	callback.call(EXIT_SUCCESS);
 }
+
+/// Forgets every actor given each permission, getting the host capabilities
+/// that were granted to actors with them.
+fn clear_grants(
+	perms: &mut HashMap<String, (String, HashSet<Address>)>,
+) -> Vec<(Address, Capability)> {
+	let mut granted = Vec::new();
+
+	for (name, (_, actors)) in perms.iter_mut() {
+		if let Some(cap) = Capability::from_permission(name) {
+			granted.extend(actors.iter().map(|actor| (*actor, cap)));
+		}
+
+		actors.clear();
+	}
+
+	granted
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_clear_grants() {
+		let mut perms = HashMap::from([
+			(
+				Capability::Clock.permission().to_string(),
+				(String::new(), HashSet::from([5, 6])),
+			),
+			(
+				String::from("send messages"),
+				(String::from("Sends messages."), HashSet::from([5])),
+			),
+		]);
+
+		let mut granted = clear_grants(&mut perms);
+		granted.sort_by_key(|(actor, _)| *actor);
+		assert_eq!(
+			granted,
+			vec![(5, Capability::Clock), (6, Capability::Clock)]
+		);
+
+		// Registered permissions are kept, without anybody holding them
+		assert_eq!(perms.len(), 2);
+		assert!(perms.values().all(|(_, actors)| actors.is_empty()));
+		assert_eq!(perms["send messages"].0, "Sends messages.");
+
+		assert!(clear_grants(&mut perms).is_empty());
+	}
+}
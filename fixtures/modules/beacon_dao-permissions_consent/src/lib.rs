@@ -1,4 +1,4 @@
-use beacon_dao_display_manager::{system_dialogue, DialogueKind};
+use beacon_dao_display_manager::{system_dialogue, DialogueKind, DialogueResponse};
 use beacon_dao_logger_manager::info;
 use beacon_dao_permissions::{get_permission, set_permission};
 use vision_derive::with_bindings;
@@ -26,8 +26,8 @@ pub fn handle_request_permission(from: Address, permission: String, callback: Ca
 				format!("Grant Actor #{} Permission to {}", from, permission),
 				desc,
 				DialogueKind::Choice(String::from("No"), String::from("Yes")),
-				Callback::new(move |stat| {
-					if stat == 1 {
+				Callback::new(move |resp| {
+					if resp == Ok(DialogueResponse::Chose(1)) {
 						set_permission(
 							PERM_ADDR,
 							from,
@@ -6,6 +6,7 @@ use runtime::gc::Rt;
 use std::{default::Default, panic, sync::Arc};
 use vision_utils::types::DISPLAY_MANAGER_ADDR;
 use wasm_bindgen::prelude::wasm_bindgen;
+use wasmer::Value;
 
 // Global instance of the runtime that external modules can use to interact
 // with.
@@ -96,12 +97,13 @@ pub fn start() {
 	.expect("Failed to start HTTP client.");
 
 	// Web3 client module
-	RT.spawn(
-		None,
-		include_bytes!("../../target/wasm32-unknown-unknown/release/beacon_dao_web3.wasm"),
-		true,
-	)
-	.expect("Failed to start web3 client.");
+	let web3 = RT
+		.spawn(
+			None,
+			include_bytes!("../../target/wasm32-unknown-unknown/release/beacon_dao_web3.wasm"),
+			true,
+		)
+		.expect("Failed to start web3 client.");
 
 	// Permission delegate
 	RT.spawn(
@@ -121,6 +123,40 @@ pub fn start() {
 	)
 	.expect("Failed to start IPFS");
 
+	// Wallet
+	let wallet = RT
+		.spawn(
+			None,
+			include_bytes!("../../target/wasm32-unknown-unknown/release/beacon_dao_wallet.wasm"),
+			false,
+		)
+		.expect("Failed to start wallet");
+
+	// Governance
+	let governance = RT
+		.spawn(
+			None,
+			include_bytes!(
+				"../../target/wasm32-unknown-unknown/release/beacon_dao_governance.wasm"
+			),
+			false,
+		)
+		.expect("Failed to start governance");
+
+	// Addresses depend on the order actors are spawned in, so the services
+	// that talk to the wallet and the governance app are told where they are
+	for to in [web3, DISPLAY_MANAGER_ADDR, governance] {
+		RT.impulse(None, to, "set_wallet", &[Value::I32(wallet as i32)][..])
+			.expect("Failed to locate wallet");
+	}
+	RT.impulse(
+		None,
+		DISPLAY_MANAGER_ADDR,
+		"set_governance",
+		&[Value::I32(governance as i32)][..],
+	)
+	.expect("Failed to locate governance");
+
 	RT.impulse(None, DISPLAY_MANAGER_ADDR, "display_login", &[][..])
 		.expect("Failed to login");
 }
@@ -1,4 +1,4 @@
-use super::gc::Rt;
+use super::{capability::Capability, entropy::MAX_FILL, gc::Rt};
 
 use crate::common::Address;
 use js_sys::eval;
@@ -21,7 +21,7 @@ pub fn log(s: &str) {
 
 impl Rt {
 	// Gets the UTF-8 encoded C-string inside the child at ptr
-	fn read_env_str(env: &FunctionEnvMut<(Address, Rt)>, ptr: i32) -> Option<String> {
+	pub(crate) fn read_env_str(env: &FunctionEnvMut<(Address, Rt)>, ptr: i32) -> Option<String> {
 		let children = env.data().1.children.read().ok()?;
 		let logging_actor = children.get(env.data().0 as usize).map(Option::as_ref)??;
 		let memory = logging_actor.instance.exports.get_memory("memory").ok()?;
@@ -34,6 +34,35 @@ impl Rt {
 			.ok()
 	}
 
+	// Copies len bytes out of the child's memory at ptr
+	pub(crate) fn read_env_bytes(
+		env: &FunctionEnvMut<(Address, Rt)>,
+		ptr: i32,
+		len: u32,
+	) -> Option<Vec<u8>> {
+		let children = env.data().1.children.read().ok()?;
+		let actor = children.get(env.data().0 as usize).map(Option::as_ref)??;
+		let memory = actor.instance.exports.get_memory("memory").ok()?;
+
+		let mut bytes = vec![0u8; len as usize];
+		memory.view(&env).read(ptr as u64, &mut bytes).ok()?;
+
+		Some(bytes)
+	}
+
+	// Copies the bytes into the child's memory at ptr
+	pub(crate) fn write_env_bytes(
+		env: &FunctionEnvMut<(Address, Rt)>,
+		ptr: i32,
+		bytes: &[u8],
+	) -> Option<()> {
+		let children = env.data().1.children.read().ok()?;
+		let actor = children.get(env.data().0 as usize).map(Option::as_ref)??;
+		let memory = actor.instance.exports.get_memory("memory").ok()?;
+
+		memory.view(&env).write(ptr as u64, bytes).ok()
+	}
+
 	/* Implementation of the web console log API */
 	pub fn do_log_safe(env: FunctionEnvMut<(Address, Rt)>, msg: i32) -> Option<()> {
 		let msg = Self::read_env_str(&env, msg)?;
@@ -91,4 +120,65 @@ impl Rt {
 			None => 1,
 		}
 	}
+
+	/* Implementation of the secure randomness API */
+	pub fn do_random_bytes_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		ptr: i32,
+		len: i32,
+	) -> Option<()> {
+		// Like WASI's random_get, unprivileged actors need to be granted the
+		// capability
+		if !env.data().1.may_use(env.data().0, Capability::Random) {
+			return None;
+		}
+
+		// Pointers past 2 GiB are passed as negative numbers
+		let (ptr, len) = (ptr as u32 as u64, len as u32 as u64);
+
+		let children = env.data().1.children.read().ok()?;
+		let actor = children.get(env.data().0 as usize).map(Option::as_ref)??;
+		let memory = actor.instance.exports.get_memory("memory").ok()?;
+		let view = memory.view(&env);
+
+		// Check that the bytes fit in the actor's memory before generating any,
+		// and generate them a chunk at a time, so the host never allocates more
+		// than a chunk
+		if ptr.checked_add(len)? > view.data_size() {
+			return None;
+		}
+
+		let mut buf = vec![0u8; len.min(MAX_FILL as u64) as usize];
+		for offset in (0..len).step_by(MAX_FILL) {
+			let chunk = &mut buf[..(len - offset).min(MAX_FILL as u64) as usize];
+			env.data().1.entropy.write().ok()?.fill(chunk)?;
+			view.write(ptr + offset, chunk).ok()?;
+		}
+
+		Some(())
+	}
+
+	pub fn random_bytes_safe(env: FunctionEnvMut<(Address, Rt)>, ptr: i32, len: i32) -> u8 {
+		match Self::do_random_bytes_safe(env, ptr, len) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	/* Implementation of the clock API. Returns -1 if no clock is available,
+	 * or if the actor hasn't been granted the clock. */
+	pub fn now_ms_safe(env: FunctionEnvMut<(Address, Rt)>) -> i64 {
+		if !env.data().1.may_use(env.data().0, Capability::Clock) {
+			return -1;
+		}
+
+		env.data()
+			.1
+			.entropy
+			.read()
+			.ok()
+			.and_then(|entropy| entropy.now_ms())
+			.map(|now| now as i64)
+			.unwrap_or(-1)
+	}
 }
@@ -0,0 +1,132 @@
+use super::gc::Rt;
+
+use crate::common::Address;
+use std::collections::HashMap;
+use wasmer::{FunctionEnvMut, Value};
+
+/// Bytes produced by the host for an actor (e.g., HTTP response bodies), held
+/// until the actor copies them into its own memory with read_buffer.
+#[derive(Default)]
+pub(crate) struct HostBuffers {
+	next: u32,
+	pending: HashMap<(Address, u32), Vec<u8>>,
+}
+
+impl HostBuffers {
+	/// Holds the bytes for the actor, returning the token it can read them with.
+	pub(crate) fn stash(&mut self, owner: Address, bytes: Vec<u8>) -> u32 {
+		let token = self.next;
+		self.next = self.next.wrapping_add(1);
+		self.pending.insert((owner, token), bytes);
+
+		token
+	}
+
+	fn take(&mut self, owner: Address, token: u32) -> Option<Vec<u8>> {
+		self.pending.remove(&(owner, token))
+	}
+}
+
+impl Rt {
+	/// Holds the bytes for the actor, returning the token it can read them with.
+	pub(crate) fn stash_buffer(&self, owner: Address, bytes: Vec<u8>) -> Option<u32> {
+		Some(self.buffers.write().ok()?.stash(owner, bytes))
+	}
+
+	/* Implementation of the host buffer API */
+	pub fn do_read_buffer_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		token: i32,
+		ptr: i32,
+	) -> Option<()> {
+		let bytes = env
+			.data()
+			.1
+			.buffers
+			.write()
+			.ok()?
+			.take(env.data().0, token as u32)?;
+
+		Self::write_env_bytes(&env, ptr, &bytes)
+	}
+
+	pub fn read_buffer_safe(env: FunctionEnvMut<(Address, Rt)>, token: i32, ptr: i32) -> u8 {
+		match Self::do_read_buffer_safe(env, token, ptr) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	pub fn do_send_buffer_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		to: Address,
+		msg_name: i32,
+		ptr: i32,
+		len: i32,
+	) -> Option<()> {
+		let from = env.data().0;
+
+		// Like send_message, the root service can't be written to or impersonated
+		if from == 0 || to == 0 {
+			return None;
+		}
+
+		let msg_name = Self::read_env_str(&env, msg_name)?;
+		let bytes = Self::read_env_bytes(&env, ptr, u32::try_from(len).ok()?)?;
+		let rt = &env.data().1;
+		let token = rt.stash_buffer(to, bytes)?;
+
+		rt.impulse(
+			Some(from),
+			to,
+			msg_name,
+			&[Value::I32(token as i32), Value::I32(len)][..],
+		)
+		.ok()
+	}
+
+	/// Copies len bytes at ptr into a buffer only the receiver can read, and
+	/// sends it msg_name(token, len), so structured data (e.g., JSON) can be
+	/// delivered as a message.
+	pub fn send_buffer_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		to: i32,
+		msg_name: i32,
+		ptr: i32,
+		len: i32,
+	) -> u8 {
+		match Self::do_send_buffer_safe(env, to as Address, msg_name, ptr, len) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let mut buffers = HostBuffers::default();
+		let a = buffers.stash(1, b"hello".to_vec());
+		let b = buffers.stash(1, Vec::new());
+		assert_ne!(a, b);
+
+		assert_eq!(buffers.take(1, a), Some(b"hello".to_vec()));
+		assert_eq!(buffers.take(1, b), Some(Vec::new()));
+
+		// Buffers can only be read once
+		assert_eq!(buffers.take(1, a), None);
+	}
+
+	#[test]
+	fn test_owner() {
+		let mut buffers = HostBuffers::default();
+		let token = buffers.stash(1, vec![1, 2, 3]);
+
+		// Actors can't read each other's buffers
+		assert_eq!(buffers.take(2, token), None);
+		assert_eq!(buffers.take(1, token), Some(vec![1, 2, 3]));
+	}
+}
@@ -0,0 +1,77 @@
+use super::gc::Rt;
+
+use crate::common::Address;
+use vision_utils::types::PERM_ADDR;
+use wasmer::FunctionEnvMut;
+
+pub use beacon_dao_capabilities::Capability;
+
+impl Rt {
+	/// Determines whether the actor at the address has been granted the capability.
+	pub fn has_capability(&self, actor: Address, cap: Capability) -> bool {
+		self.capabilities
+			.read()
+			.ok()
+			.and_then(|caps| caps.get(&actor).copied())
+			.map(|mask| mask & cap.bit() != 0)
+			.unwrap_or(false)
+	}
+
+	/// Determines whether the actor may use the capability: privileged actors
+	/// always may, and other actors need to have been granted it.
+	pub fn may_use(&self, actor: Address, cap: Capability) -> bool {
+		self.privileged
+			.read()
+			.map(|privileged| privileged.contains(&actor))
+			.unwrap_or(false)
+			|| self.has_capability(actor, cap)
+	}
+
+	/// Adds the capabilities in the mask to the actor's grants.
+	pub fn grant_capabilities(&self, actor: Address, mask: u32) -> Option<()> {
+		*self.capabilities.write().ok()?.entry(actor).or_default() |= mask;
+
+		Some(())
+	}
+
+	/// Removes the capabilities in the mask from the actor's grants.
+	pub fn revoke_capabilities(&self, actor: Address, mask: u32) -> Option<()> {
+		if let Some(grants) = self.capabilities.write().ok()?.get_mut(&actor) {
+			*grants &= !mask;
+		}
+
+		Some(())
+	}
+
+	/* Implementation of the capability grant API. Only the permissions service
+	 * may hand out capabilities. */
+	pub fn grant_capability_safe(env: FunctionEnvMut<(Address, Rt)>, actor: i32, mask: i32) -> u8 {
+		if env.data().0 != PERM_ADDR {
+			return 1;
+		}
+
+		match env
+			.data()
+			.1
+			.grant_capabilities(actor as Address, mask as u32)
+		{
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	pub fn revoke_capability_safe(env: FunctionEnvMut<(Address, Rt)>, actor: i32, mask: i32) -> u8 {
+		if env.data().0 != PERM_ADDR {
+			return 1;
+		}
+
+		match env
+			.data()
+			.1
+			.revoke_capabilities(actor as Address, mask as u32)
+		{
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+}
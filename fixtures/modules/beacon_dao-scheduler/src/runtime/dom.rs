@@ -0,0 +1,339 @@
+use super::gc::Rt;
+
+use crate::common::Address;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::HashMap};
+use vision_utils::types::DOM_ADDR;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasmer::{FunctionEnvMut, Value};
+use web_sys::{Element, Event, HtmlElement};
+
+/// An event fired on an element with a listener, delivered to the listening
+/// actor as JSON through a host buffer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DomEvent {
+	/// The type of the event (e.g., "click")
+	pub kind: String,
+
+	/// The value of the target of the event, if it is an input
+	pub value: Option<String>,
+
+	/// The key pressed, if the event came from the keyboard
+	pub key: Option<String>,
+}
+
+/// An element created or found by an actor, and the listeners attached to it,
+/// which are dropped with the element.
+struct Node {
+	elem: Element,
+	listeners: Vec<Closure<dyn FnMut(Event)>>,
+}
+
+/// Elements handed out to actors, identified by handles. Handles start at 1,
+/// so that 0 can indicate failure.
+#[derive(Default)]
+struct Handles {
+	next: u32,
+	nodes: HashMap<u32, Node>,
+}
+
+impl Handles {
+	fn insert(&mut self, elem: Element) -> u32 {
+		self.next += 1;
+		self.nodes.insert(
+			self.next,
+			Node {
+				elem,
+				listeners: Vec::new(),
+			},
+		);
+
+		self.next
+	}
+
+	fn get(&self, handle: u32) -> Option<&Element> {
+		self.nodes.get(&handle).map(|node| &node.elem)
+	}
+
+	/// Gets the handle of the element, giving it one if it hasn't been handed
+	/// out yet. The DOM service tracks which actor owns each handle, so the
+	/// same element always gets the same handle.
+	fn get_or_insert(&mut self, elem: Element) -> u32 {
+		match self.nodes.iter().find(|(_, node)| node.elem == elem) {
+			Some((handle, _)) => *handle,
+			None => self.insert(elem),
+		}
+	}
+}
+
+// DOM objects cannot leave the thread they were made on, so they are kept out
+// of the runtime
+thread_local! {
+	static HANDLES: RefCell<Handles> = RefCell::new(Handles::default());
+}
+
+/// Gets the element with the handle, or the document's body for handle 0.
+fn element(handle: u32) -> Option<Element> {
+	if handle == 0 {
+		return web_sys::window()?.document()?.body().map(Element::from);
+	}
+
+	HANDLES.with(|handles| handles.borrow().get(handle).cloned())
+}
+
+/// Determines whether the calling actor is the DOM service. Other actors
+/// manipulate the page through it, so that it can keep them in their windows.
+fn is_dom_service(env: &FunctionEnvMut<(Address, Rt)>) -> bool {
+	env.data().0 == DOM_ADDR
+}
+
+impl Rt {
+	/// Hands the event to the actor that registered the listener, in a
+	/// dom_event(listener, buffer, len) message. The event is read with
+	/// read_buffer.
+	fn deliver_event(&self, to: Address, listener: u32, event: &Event) {
+		let target = event.target().map(JsValue::from);
+		let prop = |obj: Option<&JsValue>, name: &str| {
+			obj.and_then(|obj| js_sys::Reflect::get(obj, &JsValue::from_str(name)).ok())
+				.and_then(|v| v.as_string())
+		};
+
+		let body = if let Ok(body) = serde_json::to_vec(&DomEvent {
+			kind: event.type_(),
+			value: prop(target.as_ref(), "value"),
+			key: prop(Some(event.as_ref()), "key"),
+		}) {
+			body
+		} else {
+			return;
+		};
+		let len = body.len() as u32;
+
+		let token = if let Some(token) = self.stash_buffer(to, body) {
+			token
+		} else {
+			return;
+		};
+
+		let _ = self.impulse(
+			None,
+			to,
+			"dom_event",
+			&[
+				Value::I32(listener as i32),
+				Value::I32(token as i32),
+				Value::I32(len as i32),
+			][..],
+		);
+	}
+
+	/* Implementation of the structured DOM API. Elements are referred to by
+	 * handles, with 0 referring to the document's body. */
+	pub fn do_dom_create_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		kind: i32,
+		parent: i32,
+	) -> Option<u32> {
+		if !is_dom_service(&env) {
+			return None;
+		}
+
+		let kind = Self::read_env_str(&env, kind)?;
+		let parent = element(parent as u32)?;
+
+		let elem = web_sys::window()?
+			.document()?
+			.create_element(kind.as_str())
+			.ok()?;
+		parent.append_child(&elem).ok()?;
+
+		Some(HANDLES.with(|handles| handles.borrow_mut().insert(elem)))
+	}
+
+	pub fn dom_create_safe(env: FunctionEnvMut<(Address, Rt)>, kind: i32, parent: i32) -> i32 {
+		Self::do_dom_create_safe(env, kind, parent).unwrap_or(0) as i32
+	}
+
+	/// Removes the element from the document, along with the handles of any
+	/// elements inside of it.
+	pub fn dom_remove_safe(env: FunctionEnvMut<(Address, Rt)>, handle: i32) -> u8 {
+		if !is_dom_service(&env) {
+			return 1;
+		}
+
+		let elem = if let Some(elem) = element(handle as u32).filter(|_| handle != 0) {
+			elem
+		} else {
+			return 1;
+		};
+
+		HANDLES.with(|handles| {
+			handles
+				.borrow_mut()
+				.nodes
+				.retain(|_, node| !elem.contains(Some(&node.elem)))
+		});
+		elem.remove();
+
+		0
+	}
+
+	pub fn do_dom_set_attribute_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		name: i32,
+		value: i32,
+	) -> Option<()> {
+		if !is_dom_service(&env) {
+			return None;
+		}
+
+		let name = Self::read_env_str(&env, name)?;
+		let value = Self::read_env_str(&env, value)?;
+
+		element(handle as u32)?
+			.set_attribute(name.as_str(), value.as_str())
+			.ok()
+	}
+
+	pub fn dom_set_attribute_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		name: i32,
+		value: i32,
+	) -> u8 {
+		match Self::do_dom_set_attribute_safe(env, handle, name, value) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	/// Sets a property of the element's inline style. Only the DOM service
+	/// calls this, after checking that the actor it's for may set the
+	/// property.
+	pub fn do_dom_set_style_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		prop: i32,
+		value: i32,
+	) -> Option<()> {
+		if !is_dom_service(&env) {
+			return None;
+		}
+
+		let prop = Self::read_env_str(&env, prop)?;
+		let value = Self::read_env_str(&env, value)?;
+
+		element(handle as u32)?
+			.dyn_into::<HtmlElement>()
+			.ok()?
+			.style()
+			.set_property(prop.as_str(), value.as_str())
+			.ok()
+	}
+
+	pub fn dom_set_style_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		prop: i32,
+		value: i32,
+	) -> u8 {
+		match Self::do_dom_set_style_safe(env, handle, prop, value) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	pub fn do_dom_set_text_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		text: i32,
+	) -> Option<()> {
+		if !is_dom_service(&env) {
+			return None;
+		}
+
+		let text = Self::read_env_str(&env, text)?;
+		element(handle as u32)?.set_text_content(Some(text.as_str()));
+
+		Some(())
+	}
+
+	pub fn dom_set_text_safe(env: FunctionEnvMut<(Address, Rt)>, handle: i32, text: i32) -> u8 {
+		match Self::do_dom_set_text_safe(env, handle, text) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	/// Finds the element with the ID inside of the root element, returning its
+	/// handle.
+	pub fn do_dom_query_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		root: i32,
+		id: i32,
+	) -> Option<u32> {
+		if !is_dom_service(&env) {
+			return None;
+		}
+
+		let id = Self::read_env_str(&env, id)?;
+		let selector = format!("[id=\"{}\"]", id.replace('\\', "\\\\").replace('"', "\\\""));
+
+		let found = element(root as u32)?
+			.query_selector(selector.as_str())
+			.ok()??;
+
+		Some(HANDLES.with(|handles| handles.borrow_mut().get_or_insert(found)))
+	}
+
+	pub fn dom_query_safe(env: FunctionEnvMut<(Address, Rt)>, root: i32, id: i32) -> i32 {
+		Self::do_dom_query_safe(env, root, id).unwrap_or(0) as i32
+	}
+
+	/// Registers a listener for the event on the element. Events are delivered
+	/// to the owner actor with the listener's ID.
+	pub fn do_dom_listen_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		event: i32,
+		owner: i32,
+		listener: i32,
+	) -> Option<()> {
+		if !is_dom_service(&env) {
+			return None;
+		}
+
+		let event = Self::read_env_str(&env, event)?;
+		let rt = env.data().1.clone();
+
+		let closure = Closure::<dyn FnMut(Event)>::new(move |e: Event| {
+			rt.deliver_event(owner as Address, listener as u32, &e)
+		});
+
+		HANDLES.with(|handles| {
+			let mut handles = handles.borrow_mut();
+			let node = handles.nodes.get_mut(&(handle as u32))?;
+
+			node.elem
+				.add_event_listener_with_callback(event.as_str(), closure.as_ref().unchecked_ref())
+				.ok()?;
+			node.listeners.push(closure);
+
+			Some(())
+		})
+	}
+
+	pub fn dom_listen_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		handle: i32,
+		event: i32,
+		owner: i32,
+		listener: i32,
+	) -> u8 {
+		match Self::do_dom_listen_safe(env, handle, event, owner, listener) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+}
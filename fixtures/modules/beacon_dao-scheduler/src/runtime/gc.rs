@@ -4,7 +4,7 @@ use super::{
 use crate::common::Address;
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	fmt::Display,
 	mem,
 	num::NonZeroU32,
@@ -12,7 +12,16 @@ use std::{
 	sync::{Arc, RwLock},
 };
 
-use super::api::log;
+#[cfg(feature = "wasi")]
+use super::wasi;
+use super::{
+	api::log,
+	buffer::HostBuffers,
+	entropy::Entropy,
+	http::{self, HttpClient},
+	storage::{self, KeyValueStore},
+	timer::Timers,
+};
 use js_sys::{Array, JsString, JSON};
 use snafu::{NoneError, ResultExt};
 use wasmer::{
@@ -76,6 +85,28 @@ pub struct Rt {
 
 	// Queued messages for sending to handlers per actor
 	pub(crate) mailboxes: Arc<RwLock<Usps>>,
+
+	// Masks of host capabilities granted to each actor by the permissions service
+	pub(crate) capabilities: Arc<RwLock<HashMap<Address, u32>>>,
+
+	// Actors spawned as privileged, which use the clock and entropy without
+	// being granted them
+	pub(crate) privileged: Arc<RwLock<HashSet<Address>>>,
+
+	// Where random bytes and the current time given to actors come from
+	pub(crate) entropy: Arc<RwLock<Entropy>>,
+
+	// Performs HTTP requests on behalf of actors
+	pub(crate) http: Arc<RwLock<Box<dyn HttpClient>>>,
+
+	// Bytes produced by the host waiting to be read by actors
+	pub(crate) buffers: Arc<RwLock<HostBuffers>>,
+
+	// Messages actors have scheduled to be sent to themselves
+	pub(crate) timers: Arc<RwLock<Timers>>,
+
+	// Where values actors keep between sessions are stored
+	pub(crate) storage: Arc<RwLock<Box<dyn KeyValueStore>>>,
 }
 
 /// A handle to the runtime exposed to runtime API methods allowing
@@ -115,11 +146,37 @@ impl Default for Rt {
 			children: Arc::new(RwLock::new(vec![None])),
 			free_slots: Arc::new(RwLock::new(Vec::new())),
 			mailboxes: Arc::new(RwLock::new(Usps::new(0))),
+			capabilities: Arc::new(RwLock::new(HashMap::new())),
+			privileged: Arc::new(RwLock::new(HashSet::new())),
+			entropy: Arc::new(RwLock::new(Entropy::default())),
+			http: Arc::new(RwLock::new(http::default_client())),
+			buffers: Arc::new(RwLock::new(HostBuffers::default())),
+			timers: Arc::new(RwLock::new(Timers::default())),
+			storage: Arc::new(RwLock::new(storage::default_store())),
 		}
 	}
 }
 
 impl Rt {
+	/// Creates a runtime whose actors see deterministic random bytes and a
+	/// clock that only moves with advance_clock, for reproducible tests.
+	pub fn seeded(seed: u64) -> Self {
+		Self {
+			entropy: Arc::new(RwLock::new(Entropy::seeded(seed))),
+			..Self::default()
+		}
+	}
+
+	/// Moves the clock of a seeded runtime forward by the number of milliseconds.
+	pub fn advance_clock(&self, ms: u64) -> Result<(), Error> {
+		self.entropy
+			.write()
+			.map_err(|_| Error::LockError)?
+			.advance(ms);
+
+		Ok(())
+	}
+
 	fn view_children(&self) -> Vec<Option<Arc<Actor>>> {
 		// Obtain an immutable copy of the children of the runtime
 		if let Some(children) = self.children.read().ok().map(|children| {
@@ -501,6 +558,13 @@ impl Rt {
 		// Use the most recently freed process ID as the ID of the new process,
 		// or use the index of a new slot
 		let slot: Address = if let Some(free_slot) = slots.pop() {
+			// Timers set by the slot's last actor aren't sent to the new one
+			self.timers
+				.write()
+				.map_err(|_| NoneError)
+				.context(LockSnafu)?
+				.purge(free_slot);
+
 			free_slot
 		} else {
 			let mut mailboxes = self
@@ -518,6 +582,19 @@ impl Rt {
 				.map(NonZeroU32::get)?
 		};
 
+		// The slot's last actor may have been privileged when the new one isn't
+		let mut privileged_slots = self
+			.privileged
+			.write()
+			.map_err(|_| NoneError)
+			.context(LockSnafu)?;
+		if privileged {
+			privileged_slots.insert(slot);
+		} else {
+			privileged_slots.remove(&slot);
+		}
+		drop(privileged_slots);
+
 		let mut store = Store::default();
 		let module = Module::new(&store, src.as_ref())
 			.map_err(|_| NoneError)
@@ -534,7 +611,8 @@ impl Rt {
 		let address_fn = Function::new_typed_with_env(&mut store, &env, Self::address);
 		let env = FunctionEnv::new(&mut store, (slot, self.clone()));
 
-		let imports = if privileged {
+		#[cfg_attr(not(feature = "wasi"), allow(unused_mut))]
+		let mut imports = if privileged {
 			wasmer::imports! {
 				"env" => {
 					"send_message" => send_message_fn,
@@ -546,6 +624,24 @@ impl Rt {
 					"print" => Function::new_typed_with_env(&mut store, &env, Self::log_safe),
 					"append_element" => Function::new_typed_with_env(&mut store, &env, Self::append_element_safe),
 					"eval_js" => Function::new_typed_with_env(&mut store, &env, Self::eval_js_safe),
+					"http_request" => Function::new_typed_with_env(&mut store, &env, Self::http_request_safe),
+					"storage_get" => Function::new_typed_with_env(&mut store, &env, Self::storage_get_safe),
+					"storage_set" => Function::new_typed_with_env(&mut store, &env, Self::storage_set_safe),
+					"storage_remove" => Function::new_typed_with_env(&mut store, &env, Self::storage_remove_safe),
+					"grant_capability" => Function::new_typed_with_env(&mut store, &env, Self::grant_capability_safe),
+					"revoke_capability" => Function::new_typed_with_env(&mut store, &env, Self::revoke_capability_safe),
+					"random_bytes" => Function::new_typed_with_env(&mut store, &env, Self::random_bytes_safe),
+					"now_ms" => Function::new_typed_with_env(&mut store, &env, Self::now_ms_safe),
+					"read_buffer" => Function::new_typed_with_env(&mut store, &env, Self::read_buffer_safe),
+					"send_buffer" => Function::new_typed_with_env(&mut store, &env, Self::send_buffer_safe),
+					"set_timeout" => Function::new_typed_with_env(&mut store, &env, Self::set_timeout_safe),
+					"dom_create" => Function::new_typed_with_env(&mut store, &env, Self::dom_create_safe),
+					"dom_remove" => Function::new_typed_with_env(&mut store, &env, Self::dom_remove_safe),
+					"dom_set_attribute" => Function::new_typed_with_env(&mut store, &env, Self::dom_set_attribute_safe),
+					"dom_set_style" => Function::new_typed_with_env(&mut store, &env, Self::dom_set_style_safe),
+					"dom_set_text" => Function::new_typed_with_env(&mut store, &env, Self::dom_set_text_safe),
+					"dom_query" => Function::new_typed_with_env(&mut store, &env, Self::dom_query_safe),
+					"dom_listen" => Function::new_typed_with_env(&mut store, &env, Self::dom_listen_safe),
 				},
 			}
 		} else {
@@ -558,14 +654,40 @@ impl Rt {
 					// Gets the address of the calling actor
 					"address" => address_fn,
 					"print" => Function::new_typed(&mut store, |_: i32| {}),
+					"grant_capability" => Function::new_typed_with_env(&mut store, &env, Self::grant_capability_safe),
+					"revoke_capability" => Function::new_typed_with_env(&mut store, &env, Self::revoke_capability_safe),
+					"random_bytes" => Function::new_typed_with_env(&mut store, &env, Self::random_bytes_safe),
+					"now_ms" => Function::new_typed_with_env(&mut store, &env, Self::now_ms_safe),
+					"read_buffer" => Function::new_typed_with_env(&mut store, &env, Self::read_buffer_safe),
+					"send_buffer" => Function::new_typed_with_env(&mut store, &env, Self::send_buffer_safe),
+					"set_timeout" => Function::new_typed_with_env(&mut store, &env, Self::set_timeout_safe),
 				},
 			}
 		};
 
+		// Give actors expecting WASI a sandboxed implementation of it
+		#[cfg(feature = "wasi")]
+		let wasi_env = if wasi::is_wasi_module(&module) {
+			Some(wasi::attach(
+				&mut store,
+				&module,
+				&mut imports,
+				slot,
+				self.clone(),
+			)?)
+		} else {
+			None
+		};
+
 		let instance = Instance::new(&mut store, &module, &imports)
 			.context(InstantiationSnafu)
 			.context(ModuleSnafu)?;
 
+		#[cfg(feature = "wasi")]
+		if let Some(wasi_env) = wasi_env {
+			wasi::initialize(&mut store, wasi_env, &instance)?;
+		}
+
 		if let Ok(init) = instance.exports.get_function("init") {
 			if let Some(addr) = spawner {
 				init.call(&mut store, &[Value::I32(addr as i32)]).unwrap();
@@ -642,6 +764,9 @@ impl Rt {
 	}
 
 	pub fn poll(&self) -> Result<(), Error> {
+		// Timers that have come due are sent along with the other queued messages
+		self.fire_timers()?;
+
 		// Handle all "real" (i.e., generated by actors within our system) messages
 		// Continuously handle until no more real messages exist
 		loop {
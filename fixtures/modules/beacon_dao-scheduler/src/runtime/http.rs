@@ -0,0 +1,309 @@
+use super::gc::Rt;
+
+use crate::common::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wasmer::{FunctionEnvMut, Value};
+
+/// An HTTP request made by an actor through the http_request import. Read from
+/// the actor's memory as a NUL-terminated JSON string.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpRequest {
+	pub url: String,
+	pub method: String,
+	#[serde(default)]
+	pub headers: HashMap<String, String>,
+	#[serde(default)]
+	pub body: Option<Vec<u8>>,
+}
+
+/// The response to an HTTP request, delivered to the requesting actor.
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+	pub status: u16,
+	pub body: Vec<u8>,
+}
+
+/// Called with the outcome of a request once it completes.
+pub type Completion = Box<dyn FnOnce(Result<HttpResponse, String>) + Send>;
+
+/// Something that can perform HTTP requests on behalf of actors. Requests may
+/// be completed synchronously or from another thread.
+pub trait HttpClient: Send + Sync {
+	fn send(&self, req: HttpRequest, done: Completion);
+}
+
+/// A client that answers every request with the provided function, for
+/// running actors against canned responses.
+pub struct MockClient<F>(pub F);
+
+impl<F> HttpClient for MockClient<F>
+where
+	F: Fn(&HttpRequest) -> Result<HttpResponse, String> + Send + Sync,
+{
+	fn send(&self, req: HttpRequest, done: Completion) {
+		done((self.0)(&req));
+	}
+}
+
+/// Makes requests with the browser's fetch API.
+#[cfg(feature = "wasm")]
+#[derive(Default)]
+pub struct WebClient;
+
+#[cfg(feature = "wasm")]
+impl HttpClient for WebClient {
+	fn send(&self, req: HttpRequest, done: Completion) {
+		use js_sys::{ArrayBuffer, Uint8Array};
+		use wasm_bindgen::{JsCast, JsValue};
+		use wasm_bindgen_futures::{spawn_local, JsFuture};
+		use web_sys::{Headers, Request, RequestInit, Response};
+
+		let request = (|| {
+			let headers = Headers::new().ok()?;
+			for (k, v) in req.headers.iter() {
+				headers.set(k, v).ok()?;
+			}
+
+			let mut opts = RequestInit::new();
+			opts.method(&req.method).headers(&headers);
+
+			if let Some(body) = req.body.as_ref() {
+				opts.body(Some(&JsValue::from(Uint8Array::from(body.as_slice()))));
+			}
+
+			Request::new_with_str_and_init(&req.url, &opts).ok()
+		})();
+
+		let request = if let Some(request) = request {
+			request
+		} else {
+			done(Err(format!("invalid request to {}", req.url)));
+
+			return;
+		};
+
+		spawn_local(async move {
+			let resp = async {
+				let resp: Response =
+					JsFuture::from(web_sys::window()?.fetch_with_request(&request))
+						.await
+						.ok()?
+						.dyn_into()
+						.ok()?;
+				let body: ArrayBuffer = JsFuture::from(resp.array_buffer().ok()?)
+					.await
+					.ok()?
+					.dyn_into()
+					.ok()?;
+
+				Some(HttpResponse {
+					status: resp.status(),
+					body: Uint8Array::new(&body).to_vec(),
+				})
+			}
+			.await;
+
+			done(resp.ok_or_else(|| format!("request to {} failed", req.url)));
+		});
+	}
+}
+
+/// Makes requests with a blocking HTTP client on a separate thread.
+#[cfg(feature = "cli")]
+#[derive(Default)]
+pub struct NativeClient;
+
+#[cfg(feature = "cli")]
+impl HttpClient for NativeClient {
+	fn send(&self, req: HttpRequest, done: Completion) {
+		use std::io::Read;
+
+		std::thread::spawn(move || {
+			let mut call = ureq::request(&req.method, &req.url);
+			for (k, v) in req.headers.iter() {
+				call = call.set(k, v);
+			}
+
+			let resp = match req.body {
+				Some(body) => call.send_bytes(&body),
+				None => call.call(),
+			};
+
+			done(match resp {
+				Ok(resp) | Err(ureq::Error::Status(_, resp)) => {
+					let status = resp.status();
+					let mut body = Vec::new();
+
+					resp.into_reader()
+						.read_to_end(&mut body)
+						.map(|_| HttpResponse { status, body })
+						.map_err(|e| e.to_string())
+				}
+				Err(e) => Err(e.to_string()),
+			});
+		});
+	}
+}
+
+/// Gets the client used by the runtime when none has been set.
+pub(crate) fn default_client() -> Box<dyn HttpClient> {
+	#[cfg(feature = "wasm")]
+	return Box::new(WebClient);
+
+	#[cfg(all(feature = "cli", not(feature = "wasm")))]
+	return Box::new(NativeClient);
+
+	#[cfg(not(any(feature = "wasm", feature = "cli")))]
+	return Box::new(MockClient(|req: &HttpRequest| {
+		Err(format!("no HTTP client is available for {}", req.url))
+	}));
+}
+
+impl Rt {
+	/// Replaces the client used to perform HTTP requests for actors.
+	pub fn set_http_client(&self, client: impl HttpClient + 'static) {
+		if let Ok(mut lock) = self.http.write() {
+			*lock = Box::new(client);
+		}
+	}
+
+	/// Hands the completed request to the actor that made it, in a
+	/// http_response(id, status, buffer, len) message. A status of 0 indicates
+	/// that no response was received. The body is read with read_buffer.
+	fn deliver_response(&self, to: Address, id: u32, resp: Result<HttpResponse, String>) {
+		let (status, body) = match resp {
+			Ok(resp) => (resp.status as u32, resp.body),
+			Err(e) => {
+				super::api::log(&format!(
+					"HTTP request {} from actor {} failed: {}",
+					id, to, e
+				));
+
+				(0, Vec::new())
+			}
+		};
+		let len = body.len() as u32;
+
+		let token = if let Some(token) = self.stash_buffer(to, body) {
+			token
+		} else {
+			return;
+		};
+
+		let _ = self.impulse(
+			None,
+			to,
+			"http_response",
+			&[
+				Value::I32(id as i32),
+				Value::I32(status as i32),
+				Value::I32(token as i32),
+				Value::I32(len as i32),
+			][..],
+		);
+	}
+
+	/* Implementation of the HTTP API */
+	pub fn do_http_request_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		req: i32,
+		id: i32,
+	) -> Option<()> {
+		let req: HttpRequest = serde_json::from_str(&Self::read_env_str(&env, req)?).ok()?;
+		let (from, rt) = (env.data().0, env.data().1.clone());
+
+		let client = env.data().1.http.read().ok()?;
+		client.send(
+			req,
+			Box::new(move |resp| rt.deliver_response(from, id as u32, resp)),
+		);
+
+		Some(())
+	}
+
+	pub fn http_request_safe(env: FunctionEnvMut<(Address, Rt)>, req: i32, id: i32) -> u8 {
+		match Self::do_http_request_safe(env, req, id) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::{Arc, Mutex};
+
+	/// Sends the request through the client, getting what it completed with.
+	fn send(client: &dyn HttpClient, req: HttpRequest) -> Option<Result<HttpResponse, String>> {
+		let out = Arc::new(Mutex::new(None));
+		let done = out.clone();
+
+		client.send(
+			req,
+			Box::new(move |resp| *done.lock().unwrap() = Some(resp)),
+		);
+
+		let resp = out.lock().unwrap().take();
+		resp
+	}
+
+	#[test]
+	fn test_read_request() {
+		let req: HttpRequest =
+			serde_json::from_str(r#"{"url": "https://example.com", "method": "GET"}"#).unwrap();
+		assert!(req.headers.is_empty());
+		assert_eq!(req.body, None);
+
+		let req: HttpRequest = serde_json::from_str(
+			r#"{"url": "https://example.com", "method": "POST", "headers": {"Content-Type": "application/json"}, "body": [123, 125]}"#,
+		)
+		.unwrap();
+		assert_eq!(req.headers["Content-Type"], "application/json");
+		assert_eq!(req.body, Some(b"{}".to_vec()));
+	}
+
+	#[test]
+	fn test_mock_client() {
+		let client = MockClient(|req: &HttpRequest| match req.url.as_str() {
+			"https://example.com/ok" => Ok(HttpResponse {
+				status: 200,
+				body: req.method.as_bytes().to_vec(),
+			}),
+			url => Err(format!("unreachable: {}", url)),
+		});
+		let req = |url: &str| HttpRequest {
+			url: url.to_owned(),
+			method: String::from("GET"),
+			headers: HashMap::new(),
+			body: None,
+		};
+
+		let resp = send(&client, req("https://example.com/ok"))
+			.unwrap()
+			.unwrap();
+		assert_eq!(resp.status, 200);
+		assert_eq!(resp.body, b"GET".to_vec());
+
+		assert_eq!(
+			send(&client, req("https://example.com/down"))
+				.unwrap()
+				.unwrap_err(),
+			"unreachable: https://example.com/down"
+		);
+	}
+
+	#[cfg(not(any(feature = "wasm", feature = "cli")))]
+	#[test]
+	fn test_no_client() {
+		let req = HttpRequest {
+			url: String::from("https://example.com"),
+			method: String::from("GET"),
+			headers: HashMap::new(),
+			body: None,
+		};
+
+		assert!(send(default_client().as_ref(), req).unwrap().is_err());
+	}
+}
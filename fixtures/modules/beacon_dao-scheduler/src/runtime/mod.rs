@@ -1,8 +1,33 @@
 pub mod api;
 
+/// Bytes handed from the host to actors.
+pub mod buffer;
+
+/// Host resources that actors must be granted access to.
+pub mod capability;
+
+/// Elements created by actors through the structured DOM API.
+pub mod dom;
+
+/// Sources of randomness and time for actors.
+pub mod entropy;
+
 /// Implements basic web API's for actors within the VVM.
 pub mod gc;
 
+/// HTTP requests made by actors, performed by the host.
+pub mod http;
+
+/// Values actors keep between sessions.
+pub mod storage;
+
+/// Messages actors schedule to be sent to themselves later.
+pub mod timer;
+
+/// Capability-gated WASI imports for actors on the native backend.
+#[cfg(feature = "wasi")]
+pub mod wasi;
+
 use snafu::Snafu;
 use wasmer::{ExportError, InstantiationError, RuntimeError};
 
@@ -29,6 +54,9 @@ pub enum Error {
 
 	#[snafu(display("A serialization operation failed"))]
 	SerializationError,
+
+	#[snafu(display("The WASI environment for the actor could not be created"))]
+	WasiError,
 }
 
 #[derive(Debug, Snafu)]
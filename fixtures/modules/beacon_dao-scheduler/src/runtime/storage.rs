@@ -0,0 +1,232 @@
+use super::gc::Rt;
+
+use crate::common::Address;
+use std::{collections::HashMap, sync::Mutex};
+use wasmer::FunctionEnvMut;
+
+/// Somewhere values set by actors are kept between sessions. Each actor sees
+/// only its own keys.
+pub trait KeyValueStore: Send + Sync {
+	fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+	/// Returns None if the value could not be kept (e.g., the store is full).
+	fn set(&self, key: &str, value: &[u8]) -> Option<()>;
+
+	fn remove(&self, key: &str);
+}
+
+/// Gets the key the actor's key is stored under.
+fn namespaced(owner: Address, key: &str) -> String {
+	format!("vision/{}/{}", owner, key)
+}
+
+/// Keeps values in memory, for the length of the session only.
+#[derive(Default)]
+pub struct MemoryStore(Mutex<HashMap<String, Vec<u8>>>);
+
+impl KeyValueStore for MemoryStore {
+	fn get(&self, key: &str) -> Option<Vec<u8>> {
+		self.0.lock().ok()?.get(key).cloned()
+	}
+
+	fn set(&self, key: &str, value: &[u8]) -> Option<()> {
+		self.0.lock().ok()?.insert(key.to_owned(), value.to_vec());
+
+		Some(())
+	}
+
+	fn remove(&self, key: &str) {
+		if let Ok(mut values) = self.0.lock() {
+			values.remove(key);
+		}
+	}
+}
+
+/// Encodes bytes as a string of hex digits.
+#[cfg_attr(not(any(feature = "wasm", feature = "cli")), allow(dead_code))]
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg_attr(not(any(feature = "wasm", feature = "cli")), allow(dead_code))]
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+	if !s.len().is_multiple_of(2) {
+		return None;
+	}
+
+	(0..s.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+		.collect()
+}
+
+/// Keeps values in the browser's local storage, which only holds strings, so
+/// values are stored as hex.
+#[cfg(feature = "wasm")]
+#[derive(Default)]
+pub struct WebStore;
+
+#[cfg(feature = "wasm")]
+impl WebStore {
+	fn storage() -> Option<web_sys::Storage> {
+		web_sys::window()?.local_storage().ok()?
+	}
+}
+
+#[cfg(feature = "wasm")]
+impl KeyValueStore for WebStore {
+	fn get(&self, key: &str) -> Option<Vec<u8>> {
+		from_hex(&Self::storage()?.get_item(key).ok()??)
+	}
+
+	fn set(&self, key: &str, value: &[u8]) -> Option<()> {
+		Self::storage()?.set_item(key, &to_hex(value)).ok()
+	}
+
+	fn remove(&self, key: &str) {
+		if let Some(storage) = Self::storage() {
+			let _ = storage.remove_item(key);
+		}
+	}
+}
+
+/// Keeps values in files in a directory, named by the hex of their keys.
+#[cfg(feature = "cli")]
+pub struct FileStore(pub std::path::PathBuf);
+
+#[cfg(feature = "cli")]
+impl Default for FileStore {
+	fn default() -> Self {
+		Self(std::env::temp_dir().join("vision-storage"))
+	}
+}
+
+#[cfg(feature = "cli")]
+impl KeyValueStore for FileStore {
+	fn get(&self, key: &str) -> Option<Vec<u8>> {
+		std::fs::read(self.0.join(to_hex(key.as_bytes()))).ok()
+	}
+
+	fn set(&self, key: &str, value: &[u8]) -> Option<()> {
+		std::fs::create_dir_all(&self.0).ok()?;
+		std::fs::write(self.0.join(to_hex(key.as_bytes())), value).ok()
+	}
+
+	fn remove(&self, key: &str) {
+		let _ = std::fs::remove_file(self.0.join(to_hex(key.as_bytes())));
+	}
+}
+
+/// Gets the store used by the runtime when none has been set.
+pub(crate) fn default_store() -> Box<dyn KeyValueStore> {
+	#[cfg(feature = "wasm")]
+	return Box::new(WebStore);
+
+	#[cfg(all(feature = "cli", not(feature = "wasm")))]
+	return Box::new(FileStore::default());
+
+	#[cfg(not(any(feature = "wasm", feature = "cli")))]
+	return Box::new(MemoryStore::default());
+}
+
+impl Rt {
+	/// Replaces the store values set by actors are kept in.
+	pub fn set_storage(&self, store: impl KeyValueStore + 'static) {
+		if let Ok(mut lock) = self.storage.write() {
+			*lock = Box::new(store);
+		}
+	}
+
+	/* Implementation of the storage API */
+	pub fn do_storage_get_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		key: i32,
+		len_ptr: i32,
+	) -> Option<u32> {
+		let key = namespaced(env.data().0, &Self::read_env_str(&env, key)?);
+		let value = env.data().1.storage.read().ok()?.get(&key)?;
+
+		Self::write_env_bytes(&env, len_ptr, &(value.len() as u32).to_le_bytes())?;
+		env.data().1.stash_buffer(env.data().0, value)
+	}
+
+	/// Gets the value of the key, writing its length to len_ptr, and
+	/// returning a token to read it with read_buffer, or -1 if the key isn't
+	/// set.
+	pub fn storage_get_safe(env: FunctionEnvMut<(Address, Rt)>, key: i32, len_ptr: i32) -> i32 {
+		match Self::do_storage_get_safe(env, key, len_ptr) {
+			Some(token) => token as i32,
+			None => -1,
+		}
+	}
+
+	pub fn do_storage_set_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		key: i32,
+		ptr: i32,
+		len: i32,
+	) -> Option<()> {
+		let key = namespaced(env.data().0, &Self::read_env_str(&env, key)?);
+		let value = Self::read_env_bytes(&env, ptr, u32::try_from(len).ok()?)?;
+
+		env.data().1.storage.read().ok()?.set(&key, &value)
+	}
+
+	pub fn storage_set_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		key: i32,
+		ptr: i32,
+		len: i32,
+	) -> u8 {
+		match Self::do_storage_set_safe(env, key, ptr, len) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+
+	pub fn do_storage_remove_safe(env: FunctionEnvMut<(Address, Rt)>, key: i32) -> Option<()> {
+		let key = namespaced(env.data().0, &Self::read_env_str(&env, key)?);
+		env.data().1.storage.read().ok()?.remove(&key);
+
+		Some(())
+	}
+
+	pub fn storage_remove_safe(env: FunctionEnvMut<(Address, Rt)>, key: i32) -> u8 {
+		match Self::do_storage_remove_safe(env, key) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_memory_store() {
+		let store = MemoryStore::default();
+		assert_eq!(store.get("a"), None);
+
+		store.set("a", b"hello").unwrap();
+		assert_eq!(store.get("a"), Some(b"hello".to_vec()));
+
+		store.remove("a");
+		assert_eq!(store.get("a"), None);
+	}
+
+	#[test]
+	fn test_namespaced() {
+		assert_ne!(namespaced(1, "block"), namespaced(2, "block"));
+		assert_ne!(namespaced(1, "2/block"), namespaced(12, "block"));
+	}
+
+	#[test]
+	fn test_hex() {
+		let bytes = [0u8, 1, 0xab, 0xff];
+		assert_eq!(to_hex(&bytes), "0001abff");
+		assert_eq!(from_hex("0001abff"), Some(bytes.to_vec()));
+		assert_eq!(from_hex("0001abf"), None);
+		assert_eq!(from_hex("zz"), None);
+	}
+}
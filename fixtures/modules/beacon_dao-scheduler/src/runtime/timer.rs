@@ -0,0 +1,157 @@
+use super::{api::log, gc::Rt, Error};
+
+use crate::common::Address;
+use wasmer::FunctionEnvMut;
+
+/// The most timers an actor may have pending at once.
+pub const MAX_TIMERS: usize = 64;
+
+/// Messages actors have asked to be sent to themselves once the clock reaches
+/// a certain time.
+#[derive(Default)]
+pub(crate) struct Timers {
+	pending: Vec<Timer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Timer {
+	due_ms: u64,
+	owner: Address,
+	msg_name: String,
+}
+
+impl Timers {
+	/// Schedules the message to be sent to the owner at or after due_ms,
+	/// unless the owner already has MAX_TIMERS pending.
+	pub(crate) fn schedule(&mut self, owner: Address, msg_name: String, due_ms: u64) -> Option<()> {
+		if self
+			.pending
+			.iter()
+			.filter(|timer| timer.owner == owner)
+			.count() >= MAX_TIMERS
+		{
+			return None;
+		}
+
+		self.pending.push(Timer {
+			due_ms,
+			owner,
+			msg_name,
+		});
+
+		Some(())
+	}
+
+	/// Drops the timers of the owner (e.g., once its address is given to a
+	/// new actor).
+	pub(crate) fn purge(&mut self, owner: Address) {
+		self.pending.retain(|timer| timer.owner != owner);
+	}
+
+	/// Removes the timers that are due at now_ms, in the order they come due.
+	pub(crate) fn take_due(&mut self, now_ms: u64) -> Vec<(Address, String)> {
+		let (mut due, pending): (Vec<Timer>, Vec<Timer>) = self
+			.pending
+			.drain(..)
+			.partition(|timer| timer.due_ms <= now_ms);
+		self.pending = pending;
+
+		// Timers due at the same time fire in the order they were set
+		due.sort_by_key(|timer| timer.due_ms);
+
+		due.into_iter()
+			.map(|timer| (timer.owner, timer.msg_name))
+			.collect()
+	}
+}
+
+impl Rt {
+	/// Sends the messages of timers that have come due to the actors that set
+	/// them, as though the actors had sent them to themselves.
+	pub(crate) fn fire_timers(&self) -> Result<(), Error> {
+		let now = if let Some(now) = self.entropy.read().map_err(|_| Error::LockError)?.now_ms() {
+			now
+		} else {
+			return Ok(());
+		};
+
+		let due = self
+			.timers
+			.write()
+			.map_err(|_| Error::LockError)?
+			.take_due(now);
+		for (owner, msg_name) in due {
+			if let Err(e) = self.impulse(Some(owner), owner, &msg_name, &[][..]) {
+				log(&format!(
+					"timer error for message {} to process {}: {:?}",
+					msg_name, owner, e
+				));
+			}
+		}
+
+		Ok(())
+	}
+
+	/* Implementation of the timer API */
+	pub fn do_set_timeout_safe(
+		env: FunctionEnvMut<(Address, Rt)>,
+		msg: i32,
+		ms: i32,
+	) -> Option<()> {
+		let msg_name = Self::read_env_str(&env, msg)?;
+		let (owner, rt) = (env.data().0, &env.data().1);
+		let now = rt.entropy.read().ok()?.now_ms()?;
+
+		rt.timers
+			.write()
+			.ok()?
+			.schedule(owner, msg_name, now + u32::try_from(ms).ok()? as u64)
+	}
+
+	pub fn set_timeout_safe(env: FunctionEnvMut<(Address, Rt)>, msg: i32, ms: i32) -> u8 {
+		match Self::do_set_timeout_safe(env, msg, ms) {
+			Some(_) => 0,
+			None => 1,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_take_due() {
+		let mut timers = Timers::default();
+		timers.schedule(2, String::from("later"), 500);
+		timers.schedule(1, String::from("soon"), 100);
+		timers.schedule(3, String::from("also_soon"), 100);
+
+		assert_eq!(timers.take_due(50), Vec::new());
+		assert_eq!(
+			timers.take_due(100),
+			vec![(1, String::from("soon")), (3, String::from("also_soon"))]
+		);
+
+		// Timers only fire once
+		assert_eq!(timers.take_due(100), Vec::new());
+		assert_eq!(timers.take_due(1000), vec![(2, String::from("later"))]);
+	}
+
+	#[test]
+	fn test_limits() {
+		let mut timers = Timers::default();
+		for i in 0..MAX_TIMERS {
+			assert!(timers.schedule(1, format!("msg_{}", i), 100).is_some());
+		}
+
+		// Each actor has its own limit
+		assert!(timers.schedule(1, String::from("over"), 100).is_none());
+		assert!(timers.schedule(2, String::from("other"), 100).is_some());
+
+		// An actor's timers don't outlive it
+		timers.purge(1);
+		assert_eq!(timers.take_due(100), vec![(2, String::from("other"))]);
+		assert!(timers.schedule(1, String::from("again"), 100).is_some());
+	}
+}
@@ -0,0 +1,238 @@
+use super::{capability::Capability, gc::Rt, Error};
+
+use crate::common::Address;
+use std::{fs, path::PathBuf};
+use wasmer::{
+	AsStoreMut, Extern, Function, FunctionEnv, FunctionEnvMut, Imports, Instance, Module, Value,
+};
+use wasmer_wasi::{WasiFunctionEnv, WasiState};
+
+/// The namespace that WASI preview1 imports live under.
+const NAMESPACE: &str = "wasi_snapshot_preview1";
+
+/// The errno returned to an actor calling into WASI without the necessary
+/// capability.
+const ENOTCAPABLE: i32 = 76;
+
+/// How a WASI call is checked against the capabilities of the calling actor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Gate {
+	/// The call always requires the capability
+	Always(Capability),
+
+	/// The call's first arguments are descriptors (e.g., both of
+	/// fd_renumber's). It requires stdio access if they're all 0-2, and
+	/// filesystem access if any of them is another descriptor
+	ByFds(usize),
+}
+
+impl Gate {
+	/// Gets the capability required to make a call with the arguments.
+	fn required(self, args: &[Value]) -> Capability {
+		match self {
+			Self::Always(cap) => cap,
+			Self::ByFds(n) => match args.get(..n) {
+				Some(fds) if fds.iter().all(|fd| matches!(fd, Value::I32(0..=2))) => {
+					Capability::Stdio
+				}
+				_ => Capability::Filesystem,
+			},
+		}
+	}
+}
+
+/// Gets the gate that the WASI call must pass, if it touches the host. Every
+/// call on a descriptor (e.g., fd_seek, fd_close, fd_filestat_get) is gated,
+/// not just reads and writes, so that actors can't learn about, or change,
+/// files they haven't been given access to.
+fn gate_for(name: &str) -> Option<Gate> {
+	match name {
+		// Polling only waits for clocks and descriptors: reading from a
+		// descriptor that's ready still goes through its gate
+		"clock_time_get" | "clock_res_get" | "poll_oneoff" => Some(Gate::Always(Capability::Clock)),
+		"random_get" => Some(Gate::Always(Capability::Random)),
+		"fd_prestat_get" | "fd_prestat_dir_name" | "fd_readdir" => {
+			Some(Gate::Always(Capability::Filesystem))
+		}
+		"fd_renumber" => Some(Gate::ByFds(2)),
+		name if name.starts_with("fd_") || name.starts_with("sock_") => Some(Gate::ByFds(1)),
+		name if name.starts_with("path_") => Some(Gate::Always(Capability::Filesystem)),
+		_ => None,
+	}
+}
+
+/// Gets the directory under which every actor's sandbox directory is created.
+/// Overridable with the VISION_SANDBOX_DIR environment variable.
+pub fn sandbox_root() -> PathBuf {
+	std::env::var_os("VISION_SANDBOX_DIR")
+		.map(PathBuf::from)
+		.unwrap_or_else(|| std::env::temp_dir().join("vision"))
+}
+
+/// Determines whether the module expects to be given WASI imports.
+pub fn is_wasi_module(module: &Module) -> bool {
+	module.imports().any(|import| import.module() == NAMESPACE)
+}
+
+/// Adds WASI imports for the actor at the slot to its import object. The
+/// actor's sandbox directory is preopened as "/", and every call that reaches
+/// outside of the actor is checked against its capabilities when it is made,
+/// so grants from the permissions service take effect immediately.
+pub(crate) fn attach(
+	store: &mut impl AsStoreMut,
+	module: &Module,
+	imports: &mut Imports,
+	slot: Address,
+	rt: Rt,
+) -> Result<WasiFunctionEnv, Error> {
+	let sandbox = sandbox_root().join(slot.to_string());
+	fs::create_dir_all(&sandbox).map_err(|_| Error::WasiError)?;
+
+	let mut builder = WasiState::new(format!("actor-{}", slot));
+	builder
+		.map_dir("/", &sandbox)
+		.map_err(|_| Error::WasiError)?;
+	let wasi_env = builder.finalize(store).map_err(|_| Error::WasiError)?;
+
+	let wasi_imports = wasi_env
+		.import_object(store, module)
+		.map_err(|_| Error::WasiError)?;
+
+	for ((namespace, name), import) in &wasi_imports {
+		let import = match (import, gate_for(name.as_str())) {
+			(Extern::Function(inner), Some(gate)) => {
+				Extern::Function(gated(store, inner, gate, slot, rt.clone()))
+			}
+			(import, _) => import,
+		};
+
+		imports.define(namespace.as_str(), name.as_str(), import);
+	}
+
+	Ok(wasi_env)
+}
+
+/// Binds the WASI environment to the instantiated actor, and runs its reactor
+/// initializer, if it has one.
+pub(crate) fn initialize(
+	store: &mut impl AsStoreMut,
+	mut wasi_env: WasiFunctionEnv,
+	instance: &Instance,
+) -> Result<(), Error> {
+	wasi_env
+		.initialize(store, instance)
+		.map_err(|_| Error::WasiError)?;
+
+	if let Ok(init) = instance.exports.get_function("_initialize") {
+		init.call(store, &[]).map_err(|_| Error::WasiError)?;
+	}
+
+	Ok(())
+}
+
+/// Wraps the WASI function in a check that the calling actor holds the
+/// capability demanded by the gate.
+fn gated(
+	store: &mut impl AsStoreMut,
+	inner: Function,
+	gate: Gate,
+	slot: Address,
+	rt: Rt,
+) -> Function {
+	let ty = inner.ty(&*store);
+	let env = FunctionEnv::new(store, (slot, rt, inner, gate));
+
+	Function::new_with_env(
+		store,
+		&env,
+		ty,
+		|mut env: FunctionEnvMut<(Address, Rt, Function, Gate)>, args: &[Value]| {
+			let (slot, rt, inner, gate) = env.data().clone();
+
+			if !rt.has_capability(slot, gate.required(args)) {
+				return Ok(vec![Value::I32(ENOTCAPABLE)]);
+			}
+
+			inner.call(&mut env, args).map(|ret| ret.into_vec())
+		},
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_gate_for() {
+		for name in [
+			"fd_read",
+			"fd_write",
+			"fd_seek",
+			"fd_tell",
+			"fd_close",
+			"fd_fdstat_get",
+			"fd_fdstat_set_flags",
+			"fd_filestat_get",
+			"fd_filestat_set_size",
+			"fd_sync",
+			"sock_recv",
+		] {
+			assert_eq!(gate_for(name), Some(Gate::ByFds(1)), "{}", name);
+		}
+
+		assert_eq!(gate_for("fd_renumber"), Some(Gate::ByFds(2)));
+
+		assert_eq!(
+			gate_for("poll_oneoff"),
+			Some(Gate::Always(Capability::Clock))
+		);
+		assert_eq!(
+			gate_for("random_get"),
+			Some(Gate::Always(Capability::Random))
+		);
+		assert_eq!(
+			gate_for("fd_prestat_get"),
+			Some(Gate::Always(Capability::Filesystem))
+		);
+		assert_eq!(
+			gate_for("path_open"),
+			Some(Gate::Always(Capability::Filesystem))
+		);
+
+		// Calls that don't reach outside of the actor are let through
+		for name in ["args_get", "environ_sizes_get", "proc_exit", "sched_yield"] {
+			assert_eq!(gate_for(name), None, "{}", name);
+		}
+	}
+
+	#[test]
+	fn test_required() {
+		assert_eq!(
+			Gate::ByFds(1).required(&[Value::I32(1), Value::I32(4096)]),
+			Capability::Stdio
+		);
+		assert_eq!(
+			Gate::ByFds(1).required(&[Value::I32(3)]),
+			Capability::Filesystem
+		);
+		assert_eq!(Gate::ByFds(1).required(&[]), Capability::Filesystem);
+		assert_eq!(
+			Gate::Always(Capability::Clock).required(&[Value::I32(1)]),
+			Capability::Clock
+		);
+
+		// Moving a file over stdio needs access to the file
+		assert_eq!(
+			Gate::ByFds(2).required(&[Value::I32(0), Value::I32(3)]),
+			Capability::Filesystem
+		);
+		assert_eq!(
+			Gate::ByFds(2).required(&[Value::I32(3), Value::I32(1)]),
+			Capability::Filesystem
+		);
+		assert_eq!(
+			Gate::ByFds(2).required(&[Value::I32(2), Value::I32(1)]),
+			Capability::Stdio
+		);
+	}
+}
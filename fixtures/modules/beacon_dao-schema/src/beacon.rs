@@ -0,0 +1,327 @@
+use super::{
+	cid::Cid,
+	ipld::{Ipld, Kind},
+	Error, Schema,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::BTreeMap, fmt, marker::PhantomData};
+
+/// A link to a block holding a T. Untyped links (Link in the schema) are
+/// Link<Ipld>.
+pub struct Link<T = Ipld> {
+	cid: Cid,
+	target: PhantomData<fn() -> T>,
+}
+
+impl<T> Link<T> {
+	pub fn new(cid: Cid) -> Self {
+		Self {
+			cid,
+			target: PhantomData,
+		}
+	}
+
+	pub fn cid(&self) -> &Cid {
+		&self.cid
+	}
+}
+
+impl<T> Clone for Link<T> {
+	fn clone(&self) -> Self {
+		Self::new(self.cid.clone())
+	}
+}
+
+impl<T> PartialEq for Link<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.cid == other.cid
+	}
+}
+
+impl<T> Eq for Link<T> {}
+
+impl<T> fmt::Debug for Link<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("Link").field(&self.cid).finish()
+	}
+}
+
+/// Links are sent between actors as they are written in DAG-JSON: {"/": cid}.
+#[derive(Serialize, Deserialize)]
+struct LinkRepr {
+	#[serde(rename = "/")]
+	cid: Cid,
+}
+
+impl<T> Serialize for Link<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		LinkRepr {
+			cid: self.cid.clone(),
+		}
+		.serialize(serializer)
+	}
+}
+
+impl<'de, T> Deserialize<'de> for Link<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		LinkRepr::deserialize(deserializer).map(|repr| Self::new(repr.cid))
+	}
+}
+
+impl<T> Schema for Link<T> {
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error> {
+		match ipld {
+			Ipld::Link(cid) => Ok(Self::new(cid.clone())),
+			other => Err(wrong_kind(path, Kind::Link, other)),
+		}
+	}
+
+	fn to_ipld(&self) -> Ipld {
+		Ipld::Link(self.cid.clone())
+	}
+}
+
+impl Schema for String {
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error> {
+		match ipld {
+			Ipld::String(s) => Ok(s.clone()),
+			other => Err(wrong_kind(path, Kind::String, other)),
+		}
+	}
+
+	fn to_ipld(&self) -> Ipld {
+		Ipld::String(self.clone())
+	}
+}
+
+impl<T: Schema> Schema for Vec<T> {
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error> {
+		match ipld {
+			Ipld::List(items) => items
+				.iter()
+				.enumerate()
+				.map(|(i, item)| T::from_ipld_at(item, &format!("{}[{}]", path, i)))
+				.collect(),
+			other => Err(wrong_kind(path, Kind::List, other)),
+		}
+	}
+
+	fn to_ipld(&self) -> Ipld {
+		Ipld::List(self.iter().map(Schema::to_ipld).collect())
+	}
+}
+
+fn wrong_kind(path: &str, expected: Kind, found: &Ipld) -> Error {
+	Error::WrongKind {
+		path: path.to_owned(),
+		expected,
+		found: found.kind(),
+	}
+}
+
+/// Reads the fields of a struct, represented as a map. Every field must be
+/// present, and no others may be.
+struct Fields<'a> {
+	path: &'a str,
+	entries: &'a BTreeMap<String, Ipld>,
+}
+
+impl<'a> Fields<'a> {
+	fn new(ipld: &'a Ipld, path: &'a str, names: &[&str]) -> Result<Self, Error> {
+		let entries = match ipld {
+			Ipld::Map(entries) => entries,
+			other => return Err(wrong_kind(path, Kind::Map, other)),
+		};
+
+		if let Some(unknown) = entries.keys().find(|k| !names.contains(&k.as_str())) {
+			return Err(Error::UnknownField(field_path(path, unknown)));
+		}
+
+		Ok(Self { path, entries })
+	}
+
+	fn get<T: Schema>(&self, name: &str) -> Result<T, Error> {
+		let path = field_path(self.path, name);
+
+		match self.entries.get(name) {
+			Some(value) => T::from_ipld_at(value, &path),
+			None => Err(Error::MissingField(path)),
+		}
+	}
+}
+
+fn field_path(path: &str, name: &str) -> String {
+	if path.is_empty() {
+		name.to_owned()
+	} else {
+		format!("{}.{}", path, name)
+	}
+}
+
+fn to_map(fields: Vec<(&str, Ipld)>) -> Ipld {
+	Ipld::Map(fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+}
+
+/// A WASM module loaded by a DAO.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IdeaPayload {
+	/// JavaScript evaluated to load the module. Represented by a UnixFS file
+	pub loader: Link,
+
+	/// The WASM module, executed through its start entrypoint. Represented by
+	/// a UnixFS file
+	pub module: Link,
+}
+
+impl Schema for IdeaPayload {
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error> {
+		let fields = Fields::new(ipld, path, &["loader", "module"])?;
+
+		Ok(Self {
+			loader: fields.get("loader")?,
+			module: fields.get("module")?,
+		})
+	}
+
+	fn to_ipld(&self) -> Ipld {
+		to_map(vec![
+			("loader", self.loader.to_ipld()),
+			("module", self.module.to_ipld()),
+		])
+	}
+}
+
+/// The metadata of a DAO (or a user's profile): the modules it loads.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IdeaMetadata {
+	pub title: String,
+	pub description: String,
+	pub payload: Vec<Link<IdeaPayload>>,
+}
+
+impl Schema for IdeaMetadata {
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error> {
+		let fields = Fields::new(ipld, path, &["title", "description", "payload"])?;
+
+		Ok(Self {
+			title: fields.get("title")?,
+			description: fields.get("description")?,
+			payload: fields.get("payload")?,
+		})
+	}
+
+	fn to_ipld(&self) -> Ipld {
+		to_map(vec![
+			("title", self.title.to_ipld()),
+			("description", self.description.to_ipld()),
+			("payload", self.payload.to_ipld()),
+		])
+	}
+}
+
+/// The new metadata that a proposal would give its governing contract.
+pub type ProposalPayload = Link<IdeaMetadata>;
+
+/// The metadata of a proposal, stored next to its payload on-chain.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProposalMetadata {
+	pub title: String,
+	pub description: String,
+}
+
+impl Schema for ProposalMetadata {
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error> {
+		let fields = Fields::new(ipld, path, &["title", "description"])?;
+
+		Ok(Self {
+			title: fields.get("title")?,
+			description: fields.get("description")?,
+		})
+	}
+
+	fn to_ipld(&self) -> Ipld {
+		to_map(vec![
+			("title", self.title.to_ipld()),
+			("description", self.description.to_ipld()),
+		])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	const LOADER: &str = "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e";
+	const MODULE: &str = "QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n";
+
+	fn payload() -> IdeaPayload {
+		IdeaPayload {
+			loader: Link::new(LOADER.parse().unwrap()),
+			module: Link::new(MODULE.parse().unwrap()),
+		}
+	}
+
+	#[test]
+	fn test_payload_dag_json() {
+		let value = json!({ "loader": { "/": LOADER }, "module": { "/": MODULE } });
+
+		assert_eq!(IdeaPayload::from_dag_json(&value), Ok(payload()));
+		assert_eq!(payload().to_dag_json(), Ok(value.clone()));
+
+		// Messages between actors use the same representation
+		assert_eq!(serde_json::to_value(payload()).unwrap(), value);
+	}
+
+	#[test]
+	fn test_metadata_dag_cbor() {
+		let metadata = IdeaMetadata {
+			title: String::from("Vision"),
+			description: String::from("A DAO"),
+			payload: vec![Link::new(LOADER.parse().unwrap())],
+		};
+		let bytes = metadata.to_dag_cbor().unwrap();
+
+		assert_eq!(IdeaMetadata::from_dag_cbor(&bytes), Ok(metadata));
+		assert_eq!(
+			ProposalMetadata::from_dag_cbor(&bytes),
+			Err(Error::UnknownField(String::from("payload")))
+		);
+	}
+
+	#[test]
+	fn test_validation() {
+		assert_eq!(
+			IdeaMetadata::from_dag_json(&json!({ "title": "t", "description": "d" })),
+			Err(Error::MissingField(String::from("payload")))
+		);
+		assert_eq!(
+			IdeaMetadata::from_dag_json(&json!({
+				"title": "t",
+				"description": "d",
+				"payload": [{ "/": LOADER }, "QmNotALink"]
+			})),
+			Err(Error::WrongKind {
+				path: String::from("payload[1]"),
+				expected: Kind::Link,
+				found: Kind::String,
+			})
+		);
+		assert_eq!(
+			IdeaPayload::from_dag_json(&json!({ "loader": { "/": LOADER }, "module": 1 })),
+			Err(Error::WrongKind {
+				path: String::from("module"),
+				expected: Kind::Link,
+				found: Kind::Integer,
+			})
+		);
+		assert_eq!(
+			ProposalPayload::from_dag_json(&json!({ "title": "t" })),
+			Err(Error::WrongKind {
+				path: String::new(),
+				expected: Kind::Link,
+				found: Kind::Map,
+			})
+		);
+	}
+}
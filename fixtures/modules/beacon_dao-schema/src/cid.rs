@@ -0,0 +1,290 @@
+use super::Error;
+use data_encoding::{BASE32_NOPAD, HEXLOWER_PERMISSIVE};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// The multicodec of raw bytes.
+pub const RAW: u64 = 0x55;
+
+/// The multicodec of the protobuf DAG used by UnixFS.
+pub const DAG_PB: u64 = 0x70;
+
+pub const DAG_CBOR: u64 = 0x71;
+
+pub const DAG_JSON: u64 = 0x0129;
+
+/// The multihash code of the identity hash, whose digest is the block itself.
+pub const IDENTITY: u64 = 0x00;
+
+/// The multihash code of sha2-256.
+pub const SHA2_256: u64 = 0x12;
+
+/// Reads an unsigned LEB128 varint, returning it and the number of bytes it
+/// took up.
+pub fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+	let mut n: u64 = 0;
+
+	for (i, b) in bytes.iter().enumerate().take(10) {
+		n |= ((b & 0x7f) as u64).checked_shl(7 * i as u32)?;
+
+		if b & 0x80 == 0 {
+			return Some((n, i + 1));
+		}
+	}
+
+	None
+}
+
+pub fn write_varint(mut n: u64, out: &mut Vec<u8>) {
+	while n >= 0x80 {
+		out.push((n as u8 & 0x7f) | 0x80);
+		n >>= 7;
+	}
+
+	out.push(n as u8);
+}
+
+/// A content identifier: the address of a block in IPFS, made up of the codec
+/// the block is encoded with, and a hash of the block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cid {
+	version: u64,
+	codec: u64,
+	hash_code: u64,
+	digest: Vec<u8>,
+}
+
+impl Cid {
+	/// Creates a version 0 CID, which always refers to a dag-pb block by its
+	/// sha2-256 hash.
+	pub fn new_v0(digest: Vec<u8>) -> Result<Self, Error> {
+		if digest.len() != 32 {
+			return Err(Error::InvalidCid);
+		}
+
+		Ok(Self {
+			version: 0,
+			codec: DAG_PB,
+			hash_code: SHA2_256,
+			digest,
+		})
+	}
+
+	pub fn new_v1(codec: u64, hash_code: u64, digest: Vec<u8>) -> Self {
+		Self {
+			version: 1,
+			codec,
+			hash_code,
+			digest,
+		}
+	}
+
+	pub fn version(&self) -> u64 {
+		self.version
+	}
+
+	pub fn codec(&self) -> u64 {
+		self.codec
+	}
+
+	/// Gets the multihash code of the hash function the digest was made with.
+	pub fn hash_code(&self) -> u64 {
+		self.hash_code
+	}
+
+	pub fn digest(&self) -> &[u8] {
+		&self.digest
+	}
+
+	fn multihash(&self, out: &mut Vec<u8>) {
+		write_varint(self.hash_code, out);
+		write_varint(self.digest.len() as u64, out);
+		out.extend_from_slice(&self.digest);
+	}
+
+	/// Reads a binary CID from the start of the bytes, returning it and the
+	/// number of bytes it took up.
+	pub fn read_bytes(bytes: &[u8]) -> Result<(Self, usize), Error> {
+		// Version 0 CIDs are bare sha2-256 multihashes
+		if bytes.starts_with(&[SHA2_256 as u8, 32]) {
+			let digest = bytes.get(2..34).ok_or(Error::InvalidCid)?;
+
+			return Ok((Self::new_v0(digest.to_vec())?, 34));
+		}
+
+		let mut at = 0;
+		let mut next = || -> Result<u64, Error> {
+			let (n, len) = read_varint(&bytes[at..]).ok_or(Error::InvalidCid)?;
+			at += len;
+
+			Ok(n)
+		};
+
+		let (version, codec, hash_code, len) = (next()?, next()?, next()?, next()?);
+		if version != 1 {
+			return Err(Error::InvalidCid);
+		}
+
+		let end = usize::try_from(len)
+			.ok()
+			.and_then(|len| at.checked_add(len))
+			.ok_or(Error::InvalidCid)?;
+		let digest = bytes.get(at..end).ok_or(Error::InvalidCid)?;
+
+		Ok((Self::new_v1(codec, hash_code, digest.to_vec()), end))
+	}
+
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+		match Self::read_bytes(bytes)? {
+			(cid, len) if len == bytes.len() => Ok(cid),
+			_ => Err(Error::InvalidCid),
+		}
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+
+		if self.version == 1 {
+			write_varint(self.version, &mut out);
+			write_varint(self.codec, &mut out);
+		}
+
+		self.multihash(&mut out);
+
+		out
+	}
+}
+
+impl FromStr for Cid {
+	type Err = Error;
+
+	/// Reads a version 0 CID (Qm..), or a version 1 CID in base32 (b..),
+	/// base58 (z..), or hex (f..).
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.len() == 46 && s.starts_with("Qm") {
+			let bytes = bs58::decode(s).into_vec().map_err(|_| Error::InvalidCid)?;
+
+			return match Self::from_bytes(&bytes)? {
+				cid if cid.version == 0 => Ok(cid),
+				_ => Err(Error::InvalidCid),
+			};
+		}
+
+		let (base, rest) = s.split_at(s.chars().next().ok_or(Error::InvalidCid)?.len_utf8());
+		let bytes = match base {
+			"b" | "B" => BASE32_NOPAD
+				.decode(rest.to_ascii_uppercase().as_bytes())
+				.map_err(|_| Error::InvalidCid)?,
+			"z" => bs58::decode(rest)
+				.into_vec()
+				.map_err(|_| Error::InvalidCid)?,
+			"f" | "F" => HEXLOWER_PERMISSIVE
+				.decode(rest.as_bytes())
+				.map_err(|_| Error::InvalidCid)?,
+			_ => return Err(Error::InvalidCid),
+		};
+
+		match Self::from_bytes(&bytes)? {
+			cid if cid.version == 1 => Ok(cid),
+			_ => Err(Error::InvalidCid),
+		}
+	}
+}
+
+impl fmt::Display for Cid {
+	/// Writes version 0 CIDs in base58, and version 1 CIDs in base32.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let bytes = self.to_bytes();
+
+		if self.version == 0 {
+			f.write_str(&bs58::encode(bytes).into_string())
+		} else {
+			write!(f, "b{}", BASE32_NOPAD.encode(&bytes).to_ascii_lowercase())
+		}
+	}
+}
+
+impl Serialize for Cid {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.to_string())
+	}
+}
+
+impl<'de> Deserialize<'de> for Cid {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer)?
+			.parse()
+			.map_err(|_| de::Error::custom("invalid CID"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_varint() {
+		for n in [0, 1, 127, 128, 300, 0x0129, u64::MAX] {
+			let mut buf = Vec::new();
+			write_varint(n, &mut buf);
+
+			assert_eq!(read_varint(&buf), Some((n, buf.len())));
+		}
+
+		assert_eq!(read_varint(&[0x80]), None);
+	}
+
+	#[test]
+	fn test_v0() {
+		let cid: Cid = "QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n"
+			.parse()
+			.unwrap();
+
+		assert_eq!(cid.version(), 0);
+		assert_eq!(cid.codec(), DAG_PB);
+		assert_eq!(cid.hash_code(), SHA2_256);
+		assert_eq!(
+			cid.to_string(),
+			"QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1n"
+		);
+	}
+
+	#[test]
+	fn test_v1() {
+		// The raw block of "hello world"
+		let s = "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e";
+		let cid: Cid = s.parse().unwrap();
+
+		assert_eq!(cid.version(), 1);
+		assert_eq!(cid.codec(), RAW);
+		assert_eq!(
+			HEXLOWER_PERMISSIVE.encode(cid.digest()),
+			"b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+		);
+		assert_eq!(cid.to_string(), s);
+		assert_eq!(Cid::from_bytes(&cid.to_bytes()), Ok(cid.clone()));
+
+		// The same CID in other bases
+		let hex = format!("f{}", HEXLOWER_PERMISSIVE.encode(&cid.to_bytes()));
+		assert_eq!(hex.parse(), Ok(cid.clone()));
+		assert_eq!(s.to_ascii_uppercase().parse(), Ok(cid));
+	}
+
+	#[test]
+	fn test_invalid() {
+		for s in [
+			"",
+			"Qm",
+			"bafy",
+			"xyz",
+			"QmdfTbBqBPQ7VNxZEYEj14VmRuZBkqFbiwReogJgS1zR1O",
+		] {
+			assert_eq!(s.parse::<Cid>(), Err(Error::InvalidCid), "{}", s);
+		}
+
+		// Trailing bytes are not part of a CID
+		let mut bytes = Cid::new_v1(RAW, SHA2_256, vec![0; 32]).to_bytes();
+		bytes.push(0);
+		assert_eq!(Cid::from_bytes(&bytes), Err(Error::InvalidCid));
+	}
+}
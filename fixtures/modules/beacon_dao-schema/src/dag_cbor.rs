@@ -0,0 +1,338 @@
+use super::{cid::Cid, ipld::Ipld, Error};
+use std::collections::BTreeMap;
+
+/// The CBOR tag marking a CID.
+const TAG_CID: u64 = 42;
+
+/// The deepest nesting of lists and maps that will be decoded.
+const MAX_DEPTH: usize = 128;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NEGINT: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_STRING: u8 = 3;
+const MAJOR_LIST: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+fn write_head(major: u8, n: u64, out: &mut Vec<u8>) {
+	let major = major << 5;
+
+	if n < 24 {
+		out.push(major | n as u8);
+	} else if n <= u8::MAX as u64 {
+		out.push(major | 24);
+		out.push(n as u8);
+	} else if n <= u16::MAX as u64 {
+		out.push(major | 25);
+		out.extend_from_slice(&(n as u16).to_be_bytes());
+	} else if n <= u32::MAX as u64 {
+		out.push(major | 26);
+		out.extend_from_slice(&(n as u32).to_be_bytes());
+	} else {
+		out.push(major | 27);
+		out.extend_from_slice(&n.to_be_bytes());
+	}
+}
+
+fn write(ipld: &Ipld, out: &mut Vec<u8>) -> Result<(), Error> {
+	match ipld {
+		Ipld::Null => out.push(0xf6),
+		Ipld::Bool(false) => out.push(0xf4),
+		Ipld::Bool(true) => out.push(0xf5),
+		Ipld::Integer(n) if *n >= 0 => write_head(
+			MAJOR_UINT,
+			u64::try_from(*n).map_err(|_| Error::InvalidCbor)?,
+			out,
+		),
+		Ipld::Integer(n) => write_head(
+			MAJOR_NEGINT,
+			u64::try_from(-1 - *n).map_err(|_| Error::InvalidCbor)?,
+			out,
+		),
+		// DAG-CBOR only allows finite 64-bit floats
+		Ipld::Float(n) if n.is_finite() => {
+			out.push(0xfb);
+			out.extend_from_slice(&n.to_be_bytes());
+		}
+		Ipld::Float(_) => return Err(Error::InvalidCbor),
+		Ipld::String(s) => {
+			write_head(MAJOR_STRING, s.len() as u64, out);
+			out.extend_from_slice(s.as_bytes());
+		}
+		Ipld::Bytes(bytes) => {
+			write_head(MAJOR_BYTES, bytes.len() as u64, out);
+			out.extend_from_slice(bytes);
+		}
+		Ipld::List(items) => {
+			write_head(MAJOR_LIST, items.len() as u64, out);
+
+			for item in items {
+				write(item, out)?;
+			}
+		}
+		Ipld::Map(entries) => {
+			write_head(MAJOR_MAP, entries.len() as u64, out);
+
+			// Keys are sorted by length first, then bytewise
+			let mut entries = entries.iter().collect::<Vec<_>>();
+			entries.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+			for (k, v) in entries {
+				write_head(MAJOR_STRING, k.len() as u64, out);
+				out.extend_from_slice(k.as_bytes());
+				write(v, out)?;
+			}
+		}
+		Ipld::Link(cid) => {
+			let bytes = cid.to_bytes();
+
+			// CIDs are prefixed with the identity multibase
+			write_head(MAJOR_TAG, TAG_CID, out);
+			write_head(MAJOR_BYTES, bytes.len() as u64 + 1, out);
+			out.push(0);
+			out.extend_from_slice(&bytes);
+		}
+	}
+
+	Ok(())
+}
+
+/// Writes the value in canonical DAG-CBOR.
+pub fn to_vec(ipld: &Ipld) -> Result<Vec<u8>, Error> {
+	let mut out = Vec::new();
+	write(ipld, &mut out)?;
+
+	Ok(out)
+}
+
+/// A position in CBOR being decoded.
+struct Reader<'a> {
+	bytes: &'a [u8],
+	at: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+		let end = self.at.checked_add(len).ok_or(Error::InvalidCbor)?;
+		let taken = self.bytes.get(self.at..end).ok_or(Error::InvalidCbor)?;
+		self.at = end;
+
+		Ok(taken)
+	}
+
+	fn remaining(&self) -> usize {
+		self.bytes.len() - self.at
+	}
+
+	/// Reads the major type, additional information, and argument of the
+	/// next item.
+	fn head(&mut self) -> Result<(u8, u8, u64), Error> {
+		let initial = self.take(1)?[0];
+		let (major, info) = (initial >> 5, initial & 0x1f);
+
+		let n = match info {
+			0..=23 => info as u64,
+			24 => self.take(1)?[0] as u64,
+			25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+			26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+			27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+
+			// Indefinite lengths are not allowed
+			_ => return Err(Error::InvalidCbor),
+		};
+
+		Ok((major, info, n))
+	}
+
+	fn len(&mut self, n: u64) -> Result<usize, Error> {
+		usize::try_from(n)
+			.ok()
+			.filter(|n| *n <= self.remaining())
+			.ok_or(Error::InvalidCbor)
+	}
+
+	fn string(&mut self, n: u64) -> Result<String, Error> {
+		let len = self.len(n)?;
+
+		String::from_utf8(self.take(len)?.to_vec()).map_err(|_| Error::InvalidCbor)
+	}
+
+	fn read(&mut self, depth: usize) -> Result<Ipld, Error> {
+		if depth > MAX_DEPTH {
+			return Err(Error::InvalidCbor);
+		}
+
+		let (major, info, n) = self.head()?;
+
+		Ok(match major {
+			MAJOR_UINT => Ipld::Integer(n as i128),
+			MAJOR_NEGINT => Ipld::Integer(-1 - n as i128),
+			MAJOR_BYTES => {
+				let len = self.len(n)?;
+
+				Ipld::Bytes(self.take(len)?.to_vec())
+			}
+			MAJOR_STRING => Ipld::String(self.string(n)?),
+			MAJOR_LIST => {
+				// Every item takes up at least one byte
+				let len = self.len(n)?;
+
+				Ipld::List(
+					(0..len)
+						.map(|_| self.read(depth + 1))
+						.collect::<Result<_, _>>()?,
+				)
+			}
+			MAJOR_MAP => {
+				let len = self.len(n)?;
+				let mut entries = BTreeMap::new();
+
+				for _ in 0..len {
+					let key = match self.head()? {
+						(MAJOR_STRING, _, n) => self.string(n)?,
+						_ => return Err(Error::InvalidCbor),
+					};
+
+					let value = self.read(depth + 1)?;
+					if entries.insert(key, value).is_some() {
+						return Err(Error::InvalidCbor);
+					}
+				}
+
+				Ipld::Map(entries)
+			}
+			MAJOR_TAG if n == TAG_CID => match self.read(depth + 1)? {
+				Ipld::Bytes(bytes) if bytes.first() == Some(&0) => {
+					Ipld::Link(Cid::from_bytes(&bytes[1..])?)
+				}
+				_ => return Err(Error::InvalidCbor),
+			},
+			MAJOR_SIMPLE => match info {
+				20 => Ipld::Bool(false),
+				21 => Ipld::Bool(true),
+				22 => Ipld::Null,
+				27 => {
+					let f = f64::from_bits(n);
+					if !f.is_finite() {
+						return Err(Error::InvalidCbor);
+					}
+
+					Ipld::Float(f)
+				}
+				_ => return Err(Error::InvalidCbor),
+			},
+			_ => return Err(Error::InvalidCbor),
+		})
+	}
+}
+
+/// Reads a value from DAG-CBOR. Only CID tags, and 64-bit floats are
+/// accepted.
+pub fn from_slice(bytes: &[u8]) -> Result<Ipld, Error> {
+	let mut reader = Reader { bytes, at: 0 };
+	let ipld = reader.read(0)?;
+
+	if reader.remaining() != 0 {
+		return Err(Error::InvalidCbor);
+	}
+
+	Ok(ipld)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use data_encoding::HEXLOWER;
+
+	fn map(entries: Vec<(&str, Ipld)>) -> Ipld {
+		Ipld::Map(
+			entries
+				.into_iter()
+				.map(|(k, v)| (k.to_owned(), v))
+				.collect(),
+		)
+	}
+
+	#[test]
+	fn test_encode() {
+		let cases = [
+			(Ipld::Integer(0), "00"),
+			(Ipld::Integer(500), "1901f4"),
+			(Ipld::Integer(-1), "20"),
+			(Ipld::Integer(-500), "3901f3"),
+			(Ipld::Float(1.5), "fb3ff8000000000000"),
+			(Ipld::String(String::from("a")), "6161"),
+			(Ipld::Bytes(vec![1, 2]), "420102"),
+			(Ipld::List(vec![Ipld::Null, Ipld::Bool(true)]), "82f6f5"),
+			// Shorter keys come first
+			(
+				map(vec![("bb", Ipld::Integer(1)), ("a", Ipld::Integer(2))]),
+				"a261610262626201",
+			),
+		];
+
+		for (ipld, hex) in cases {
+			assert_eq!(HEXLOWER.encode(&to_vec(&ipld).unwrap()), hex);
+			assert_eq!(
+				from_slice(&HEXLOWER.decode(hex.as_bytes()).unwrap()),
+				Ok(ipld)
+			);
+		}
+
+		assert_eq!(to_vec(&Ipld::Float(f64::NAN)), Err(Error::InvalidCbor));
+	}
+
+	#[test]
+	fn test_link() {
+		let cid: Cid = "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+			.parse()
+			.unwrap();
+		let bytes = to_vec(&Ipld::Link(cid.clone())).unwrap();
+
+		assert_eq!(&bytes[..4], &[0xd8, 0x2a, 0x58, 0x25]);
+		assert_eq!(bytes[4], 0);
+		assert_eq!(from_slice(&bytes), Ok(Ipld::Link(cid)));
+	}
+
+	#[test]
+	fn test_invalid() {
+		for hex in [
+			// Truncated
+			"19",
+			"6261",
+			// Indefinite length list
+			"9fff",
+			// Trailing bytes
+			"0000",
+			// Non-string key
+			"a10102",
+			// Duplicate key
+			"a2616101616102",
+			// Tags other than CIDs
+			"c100",
+			// 32-bit float
+			"fa3fc00000",
+			// Undefined
+			"f7",
+			// List claiming more items than there are bytes
+			"9b00000000ffffffff",
+		] {
+			assert_eq!(
+				from_slice(&HEXLOWER.decode(hex.as_bytes()).unwrap()),
+				Err(Error::InvalidCbor),
+				"{}",
+				hex
+			);
+		}
+	}
+
+	#[test]
+	fn test_max_depth() {
+		let mut bytes = vec![0x81; MAX_DEPTH + 2];
+		bytes.push(0);
+
+		assert_eq!(from_slice(&bytes), Err(Error::InvalidCbor));
+	}
+}
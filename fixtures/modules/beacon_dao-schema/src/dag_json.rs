@@ -0,0 +1,140 @@
+use super::{ipld::Ipld, Error};
+use data_encoding::BASE64_NOPAD;
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+
+/// Reads the value from its DAG-JSON representation. Links are written as
+/// {"/": cid}, and bytes as {"/": {"bytes": base64}}.
+pub fn decode(value: &Value) -> Result<Ipld, Error> {
+	Ok(match value {
+		Value::Null => Ipld::Null,
+		Value::Bool(b) => Ipld::Bool(*b),
+		Value::Number(n) => {
+			if let Some(n) = n.as_u64() {
+				Ipld::Integer(n as i128)
+			} else if let Some(n) = n.as_i64() {
+				Ipld::Integer(n as i128)
+			} else {
+				Ipld::Float(n.as_f64().ok_or(Error::InvalidJson)?)
+			}
+		}
+		Value::String(s) => Ipld::String(s.clone()),
+		Value::Array(items) => Ipld::List(items.iter().map(decode).collect::<Result<_, _>>()?),
+		Value::Object(entries) => match entries.get("/") {
+			Some(Value::String(cid)) if entries.len() == 1 => Ipld::Link(cid.parse()?),
+			Some(Value::Object(inner)) if entries.len() == 1 => {
+				match (inner.get("bytes"), inner.len()) {
+					(Some(Value::String(bytes)), 1) => Ipld::Bytes(
+						BASE64_NOPAD
+							.decode(bytes.trim_end_matches('=').as_bytes())
+							.map_err(|_| Error::InvalidJson)?,
+					),
+					_ => return Err(Error::InvalidJson),
+				}
+			}
+			// Maps may not use the reserved key for anything else
+			Some(_) => return Err(Error::InvalidJson),
+			None => Ipld::Map(
+				entries
+					.iter()
+					.map(|(k, v)| decode(v).map(|v| (k.clone(), v)))
+					.collect::<Result<BTreeMap<_, _>, _>>()?,
+			),
+		},
+	})
+}
+
+fn reserved(value: Value) -> Value {
+	let mut m = Map::new();
+	m.insert(String::from("/"), value);
+
+	Value::Object(m)
+}
+
+/// Gets the DAG-JSON representation of the value.
+pub fn encode(ipld: &Ipld) -> Result<Value, Error> {
+	Ok(match ipld {
+		Ipld::Null => Value::Null,
+		Ipld::Bool(b) => Value::Bool(*b),
+		Ipld::Integer(n) => {
+			if let Ok(n) = u64::try_from(*n) {
+				Value::Number(n.into())
+			} else {
+				Value::Number(i64::try_from(*n).map_err(|_| Error::InvalidJson)?.into())
+			}
+		}
+		Ipld::Float(n) => Value::Number(Number::from_f64(*n).ok_or(Error::InvalidJson)?),
+		Ipld::String(s) => Value::String(s.clone()),
+		Ipld::Bytes(bytes) => {
+			let mut m = Map::new();
+			m.insert(
+				String::from("bytes"),
+				Value::String(BASE64_NOPAD.encode(bytes)),
+			);
+
+			reserved(Value::Object(m))
+		}
+		Ipld::List(items) => Value::Array(items.iter().map(encode).collect::<Result<_, _>>()?),
+		Ipld::Map(entries) => Value::Object(
+			entries
+				.iter()
+				.map(|(k, v)| encode(v).map(|v| (k.clone(), v)))
+				.collect::<Result<_, _>>()?,
+		),
+		Ipld::Link(cid) => reserved(Value::String(cid.to_string())),
+	})
+}
+
+pub fn from_slice(bytes: &[u8]) -> Result<Ipld, Error> {
+	decode(&serde_json::from_slice(bytes).map_err(|_| Error::InvalidJson)?)
+}
+
+/// Writes the value in DAG-JSON, with the keys of maps sorted.
+pub fn to_vec(ipld: &Ipld) -> Result<Vec<u8>, Error> {
+	serde_json::to_vec(&encode(ipld)?).map_err(|_| Error::InvalidJson)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	#[test]
+	fn test_round_trip() {
+		let value = json!({
+			"title": "Vision",
+			"count": 3,
+			"neg": -4,
+			"ratio": 0.5,
+			"ok": true,
+			"none": null,
+			"link": { "/": "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e" },
+			"bytes": { "/": { "bytes": "aGVsbG8" } },
+			"list": [1, "two"]
+		});
+		let ipld = decode(&value).unwrap();
+
+		match &ipld {
+			Ipld::Map(m) => {
+				assert!(matches!(m.get("link"), Some(Ipld::Link(_))));
+				assert_eq!(m.get("bytes"), Some(&Ipld::Bytes(b"hello".to_vec())));
+				assert_eq!(m.get("neg"), Some(&Ipld::Integer(-4)));
+			}
+			_ => panic!("not a map"),
+		}
+
+		assert_eq!(encode(&ipld).unwrap(), value);
+		assert_eq!(from_slice(&to_vec(&ipld).unwrap()).unwrap(), ipld);
+	}
+
+	#[test]
+	fn test_invalid() {
+		assert_eq!(decode(&json!({ "/": "not a cid" })), Err(Error::InvalidCid));
+		assert_eq!(decode(&json!({ "/": 1 })), Err(Error::InvalidJson));
+		assert_eq!(
+			decode(&json!({ "/": { "bytes": "aGVsbG8", "x": 1 } })),
+			Err(Error::InvalidJson)
+		);
+		assert_eq!(from_slice(b"{"), Err(Error::InvalidJson));
+	}
+}
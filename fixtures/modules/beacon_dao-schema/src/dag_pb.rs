@@ -0,0 +1,302 @@
+use super::{
+	cid::{read_varint, write_varint, Cid},
+	Error, Ipld,
+};
+use std::collections::BTreeMap;
+
+/// A link from a dag-pb node to another block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PbLink {
+	pub cid: Cid,
+	pub name: Option<String>,
+
+	/// The cumulative size of the linked block, and everything it links to
+	pub size: Option<u64>,
+}
+
+/// A node in the protobuf DAG used by UnixFS.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PbNode {
+	pub links: Vec<PbLink>,
+	pub data: Option<Vec<u8>>,
+}
+
+impl PbNode {
+	/// Gets the node in the IPLD data model, as the dag-pb spec describes it:
+	/// a map of its Links, and its Data, if it has any.
+	pub fn to_ipld(&self) -> Ipld {
+		let links = self
+			.links
+			.iter()
+			.map(|link| {
+				let mut fields =
+					BTreeMap::from([(String::from("Hash"), Ipld::Link(link.cid.clone()))]);
+				if let Some(name) = &link.name {
+					fields.insert(String::from("Name"), Ipld::String(name.clone()));
+				}
+				if let Some(size) = link.size {
+					fields.insert(String::from("Tsize"), Ipld::Integer(size.into()));
+				}
+
+				Ipld::Map(fields)
+			})
+			.collect();
+
+		let mut fields = BTreeMap::from([(String::from("Links"), Ipld::List(links))]);
+		if let Some(data) = &self.data {
+			fields.insert(String::from("Data"), Ipld::Bytes(data.clone()));
+		}
+
+		Ipld::Map(fields)
+	}
+}
+
+/// A field of a protobuf message.
+pub enum Field<'a> {
+	Varint(u64),
+	Bytes(&'a [u8]),
+
+	/// A fixed-width field, which nothing in dag-pb or UnixFS uses
+	Fixed,
+}
+
+/// Reads the fields of a protobuf message, calling the callback with the
+/// number and value of each one.
+pub fn read_fields<'a>(
+	mut bytes: &'a [u8],
+	mut f: impl FnMut(u64, Field<'a>) -> Result<(), Error>,
+) -> Result<(), Error> {
+	let varint = |bytes: &mut &'a [u8]| -> Result<u64, Error> {
+		let (n, len) = read_varint(bytes).ok_or(Error::InvalidProtobuf)?;
+		*bytes = &bytes[len..];
+
+		Ok(n)
+	};
+
+	while !bytes.is_empty() {
+		let key = varint(&mut bytes)?;
+		let (number, wire_type) = (key >> 3, key & 0x7);
+
+		let field = match wire_type {
+			0 => Field::Varint(varint(&mut bytes)?),
+			1 | 5 => {
+				let len = if wire_type == 1 { 8 } else { 4 };
+				bytes = bytes.get(len..).ok_or(Error::InvalidProtobuf)?;
+
+				Field::Fixed
+			}
+			2 => {
+				let len =
+					usize::try_from(varint(&mut bytes)?).map_err(|_| Error::InvalidProtobuf)?;
+				let value = bytes.get(..len).ok_or(Error::InvalidProtobuf)?;
+				bytes = &bytes[len..];
+
+				Field::Bytes(value)
+			}
+			_ => return Err(Error::InvalidProtobuf),
+		};
+
+		f(number, field)?;
+	}
+
+	Ok(())
+}
+
+fn read_link(bytes: &[u8]) -> Result<PbLink, Error> {
+	let (mut cid, mut name, mut size) = (None, None, None);
+
+	read_fields(bytes, |number, field| {
+		match (number, field) {
+			(1, Field::Bytes(hash)) => cid = Some(Cid::from_bytes(hash)?),
+			(2, Field::Bytes(n)) => {
+				name = Some(String::from_utf8(n.to_vec()).map_err(|_| Error::InvalidProtobuf)?)
+			}
+			(3, Field::Varint(n)) => size = Some(n),
+			(1..=3, _) => return Err(Error::InvalidProtobuf),
+			_ => {}
+		}
+
+		Ok(())
+	})?;
+
+	Ok(PbLink {
+		cid: cid.ok_or(Error::InvalidProtobuf)?,
+		name,
+		size,
+	})
+}
+
+/// Reads a dag-pb node.
+pub fn decode(block: &[u8]) -> Result<PbNode, Error> {
+	let mut node = PbNode::default();
+
+	read_fields(block, |number, field| {
+		match (number, field) {
+			(1, Field::Bytes(data)) => node.data = Some(data.to_vec()),
+			(2, Field::Bytes(link)) => node.links.push(read_link(link)?),
+			(1 | 2, _) => return Err(Error::InvalidProtobuf),
+			_ => {}
+		}
+
+		Ok(())
+	})?;
+
+	Ok(node)
+}
+
+/// Reads a dag-pb node as a value in the IPLD data model.
+pub fn from_slice(block: &[u8]) -> Result<Ipld, Error> {
+	decode(block).map(|node| node.to_ipld())
+}
+
+/// Writes a varint field of a protobuf message.
+pub fn write_varint_field(number: u64, n: u64, out: &mut Vec<u8>) {
+	write_varint(number << 3, out);
+	write_varint(n, out);
+}
+
+/// Writes a length-delimited field of a protobuf message.
+pub fn write_bytes_field(number: u64, bytes: &[u8], out: &mut Vec<u8>) {
+	write_varint(number << 3 | 2, out);
+	write_varint(bytes.len() as u64, out);
+	out.extend_from_slice(bytes);
+}
+
+/// Writes a dag-pb node. Links come before data, as the spec requires.
+pub fn encode(node: &PbNode) -> Vec<u8> {
+	let mut out = Vec::new();
+
+	for link in &node.links {
+		let mut buf = Vec::new();
+		write_bytes_field(1, &link.cid.to_bytes(), &mut buf);
+		if let Some(name) = &link.name {
+			write_bytes_field(2, name.as_bytes(), &mut buf);
+		}
+		if let Some(size) = link.size {
+			write_varint_field(3, size, &mut buf);
+		}
+
+		write_bytes_field(2, &buf, &mut out);
+	}
+
+	if let Some(data) = &node.data {
+		write_bytes_field(1, data, &mut out);
+	}
+
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_decode_leaf() {
+		// The node of a small UnixFS file
+		let block = [0x0a, 0x04, 0x08, 0x02, 0x18, 0x00];
+
+		assert_eq!(
+			decode(&block),
+			Ok(PbNode {
+				links: Vec::new(),
+				data: Some(vec![0x08, 0x02, 0x18, 0x00]),
+			})
+		);
+	}
+
+	#[test]
+	fn test_decode_links() {
+		let cid = Cid::new_v0(vec![7; 32]).unwrap();
+		let hash = cid.to_bytes();
+
+		let mut link = vec![0x0a, hash.len() as u8];
+		link.extend(&hash);
+		link.extend([0x12, 0x01, b'a', 0x18, 0x2a]);
+
+		let mut block = vec![0x12, link.len() as u8];
+		block.extend(&link);
+
+		assert_eq!(
+			decode(&block),
+			Ok(PbNode {
+				links: vec![PbLink {
+					cid,
+					name: Some(String::from("a")),
+					size: Some(42),
+				}],
+				data: None,
+			})
+		);
+	}
+
+	#[test]
+	fn test_from_slice() {
+		let node = PbNode {
+			links: vec![PbLink {
+				cid: Cid::new_v0(vec![7; 32]).unwrap(),
+				name: Some(String::from("a")),
+				size: Some(42),
+			}],
+			data: Some(vec![0x08, 0x01]),
+		};
+
+		assert_eq!(
+			from_slice(&encode(&node)),
+			Ok(Ipld::Map(BTreeMap::from([
+				(String::from("Data"), Ipld::Bytes(vec![0x08, 0x01])),
+				(
+					String::from("Links"),
+					Ipld::List(vec![Ipld::Map(BTreeMap::from([
+						(String::from("Hash"), Ipld::Link(node.links[0].cid.clone())),
+						(String::from("Name"), Ipld::String(String::from("a"))),
+						(String::from("Tsize"), Ipld::Integer(42)),
+					]))])
+				),
+			])))
+		);
+
+		// Nodes without data or links still have their Links
+		assert_eq!(
+			from_slice(&[]),
+			Ok(Ipld::Map(BTreeMap::from([(
+				String::from("Links"),
+				Ipld::List(Vec::new())
+			)])))
+		);
+	}
+
+	#[test]
+	fn test_decode_invalid() {
+		for block in [
+			// Truncated data
+			&[0x0a, 0x05, 0x00][..],
+			// Data that isn't bytes
+			&[0x08, 0x01],
+			// A link without a hash
+			&[0x12, 0x02, 0x18, 0x01],
+			// An unknown wire type
+			&[0x0b],
+		] {
+			assert_eq!(decode(block), Err(Error::InvalidProtobuf), "{:?}", block);
+		}
+	}
+
+	#[test]
+	fn test_encode() {
+		let node = PbNode {
+			links: vec![PbLink {
+				cid: Cid::new_v0(vec![7; 32]).unwrap(),
+				name: Some(String::new()),
+				size: Some(300),
+			}],
+			data: Some(vec![0x08, 0x02]),
+		};
+
+		let block = encode(&node);
+		assert_eq!(&block[..2], &[0x12, 0x29]);
+		assert_eq!(&block[block.len() - 4..], &[0x0a, 0x02, 0x08, 0x02]);
+		assert_eq!(decode(&block), Ok(node));
+
+		assert!(encode(&PbNode::default()).is_empty());
+	}
+}
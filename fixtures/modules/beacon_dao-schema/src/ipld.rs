@@ -0,0 +1,53 @@
+use super::cid::Cid;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A value in the IPLD data model, which every codec can represent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ipld {
+	Null,
+	Bool(bool),
+	Integer(i128),
+	Float(f64),
+	String(String),
+	Bytes(Vec<u8>),
+	List(Vec<Ipld>),
+	Map(BTreeMap<String, Ipld>),
+	Link(Cid),
+}
+
+/// The kinds of values in the IPLD data model.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+	Null,
+	Bool,
+	Integer,
+	Float,
+	String,
+	Bytes,
+	List,
+	Map,
+	Link,
+}
+
+impl Ipld {
+	pub fn kind(&self) -> Kind {
+		match self {
+			Self::Null => Kind::Null,
+			Self::Bool(_) => Kind::Bool,
+			Self::Integer(_) => Kind::Integer,
+			Self::Float(_) => Kind::Float,
+			Self::String(_) => Kind::String,
+			Self::Bytes(_) => Kind::Bytes,
+			Self::List(_) => Kind::List,
+			Self::Map(_) => Kind::Map,
+			Self::Link(_) => Kind::Link,
+		}
+	}
+}
+
+impl From<Cid> for Ipld {
+	fn from(cid: Cid) -> Self {
+		Ipld::Link(cid)
+	}
+}
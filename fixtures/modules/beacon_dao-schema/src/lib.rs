@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Content identifiers.
+pub mod cid;
+
+/// The IPLD data model.
+pub mod ipld;
+
+/// Codecs between the data model and DAG-JSON or DAG-CBOR.
+pub mod dag_cbor;
+pub mod dag_json;
+
+/// Decoding of the protobuf DAG used by UnixFS.
+pub mod dag_pb;
+
+/// Types described by fixtures/beacon_layer.ipldsch.
+pub mod beacon;
+
+pub use beacon::{IdeaMetadata, IdeaPayload, Link, ProposalMetadata, ProposalPayload};
+pub use cid::Cid;
+pub use ipld::{Ipld, Kind};
+
+/// Errors encountered while decoding or validating data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	InvalidCid,
+	InvalidJson,
+	InvalidCbor,
+	InvalidProtobuf,
+
+	/// A field required by the schema is missing, at the path
+	MissingField(String),
+
+	/// A field not in the schema is present, at the path
+	UnknownField(String),
+
+	/// The value at the path is not of the kind required by the schema
+	WrongKind {
+		path: String,
+		expected: Kind,
+		found: Kind,
+	},
+}
+
+/// A type described by the schema, which can be validated and read from, or
+/// written to, the IPLD data model.
+pub trait Schema: Sized {
+	/// Reads the value, naming the path it was found at in errors.
+	fn from_ipld_at(ipld: &Ipld, path: &str) -> Result<Self, Error>;
+
+	fn to_ipld(&self) -> Ipld;
+
+	fn from_ipld(ipld: &Ipld) -> Result<Self, Error> {
+		Self::from_ipld_at(ipld, "")
+	}
+
+	fn from_dag_json(value: &Value) -> Result<Self, Error> {
+		Self::from_ipld(&dag_json::decode(value)?)
+	}
+
+	fn to_dag_json(&self) -> Result<Value, Error> {
+		dag_json::encode(&self.to_ipld())
+	}
+
+	fn from_dag_cbor(bytes: &[u8]) -> Result<Self, Error> {
+		Self::from_ipld(&dag_cbor::from_slice(bytes)?)
+	}
+
+	fn to_dag_cbor(&self) -> Result<Vec<u8>, Error> {
+		dag_cbor::to_vec(&self.to_ipld())
+	}
+}
@@ -0,0 +1,264 @@
+use super::{tx::keccak, Error};
+use aes::{
+	cipher::{KeyIvInit, StreamCipher},
+	Aes128,
+};
+use beacon_dao_display_manager::{DialogueError, DialogueKind, DialogueResponse};
+use k256::ecdsa::SigningKey;
+use serde::Deserialize;
+use sha2::Sha256;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// A private key encrypted with a password, in the Web3 Secret Storage (v3
+/// keystore) format. This is what `User.keyPair` holds.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Keystore {
+	pub version: u32,
+	#[serde(alias = "Crypto")]
+	pub crypto: Crypto,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Crypto {
+	pub cipher: String,
+	pub cipherparams: CipherParams,
+	pub ciphertext: String,
+	#[serde(flatten)]
+	pub kdf: Kdf,
+	pub mac: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CipherParams {
+	pub iv: String,
+}
+
+/// How the key the private key is encrypted with is derived from the password.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kdf", content = "kdfparams", rename_all = "lowercase")]
+pub enum Kdf {
+	Scrypt {
+		dklen: usize,
+		n: u64,
+		r: u32,
+		p: u32,
+		salt: String,
+	},
+	Pbkdf2 {
+		c: u32,
+		dklen: usize,
+		prf: String,
+		salt: String,
+	},
+}
+
+impl Kdf {
+	fn derive(&self, password: &str) -> Result<Vec<u8>, Error> {
+		match self {
+			Self::Scrypt {
+				dklen,
+				n,
+				r,
+				p,
+				salt,
+			} => {
+				if !n.is_power_of_two() || *dklen < 32 {
+					return Err(Error::InvalidKeyPair);
+				}
+
+				let salt = hex::decode(salt).map_err(|_| Error::InvalidKeyPair)?;
+				let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p, *dklen)
+					.map_err(|_| Error::InvalidKeyPair)?;
+
+				let mut key = vec![0; *dklen];
+				scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key)
+					.map_err(|_| Error::InvalidKeyPair)?;
+
+				Ok(key)
+			}
+			Self::Pbkdf2 {
+				c,
+				dklen,
+				prf,
+				salt,
+			} => {
+				if prf != "hmac-sha256" || *dklen < 32 {
+					return Err(Error::InvalidKeyPair);
+				}
+
+				let salt = hex::decode(salt).map_err(|_| Error::InvalidKeyPair)?;
+
+				let mut key = vec![0; *dklen];
+				pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, *c, &mut key);
+
+				Ok(key)
+			}
+		}
+	}
+}
+
+impl Keystore {
+	/// Decrypts the private key with the password. Fails with BadPassword if
+	/// the password is wrong.
+	pub fn decrypt(&self, password: &str) -> Result<SigningKey, Error> {
+		if self.version != 3 || self.crypto.cipher != "aes-128-ctr" {
+			return Err(Error::InvalidKeyPair);
+		}
+
+		let decode = |s: &str| hex::decode(s).map_err(|_| Error::InvalidKeyPair);
+		let iv = decode(&self.crypto.cipherparams.iv)?;
+		let mut key = decode(&self.crypto.ciphertext)?;
+		let mac = decode(&self.crypto.mac)?;
+
+		let derived = self.crypto.kdf.derive(password)?;
+
+		// The MAC shows whether the password was right
+		let mut mac_body = derived[16..32].to_vec();
+		mac_body.extend_from_slice(&key);
+		if keccak(&mac_body)[..] != mac[..] {
+			return Err(Error::BadPassword);
+		}
+
+		let mut cipher =
+			Aes128Ctr::new_from_slices(&derived[..16], &iv).map_err(|_| Error::InvalidKeyPair)?;
+		cipher.apply_keystream(&mut key);
+
+		SigningKey::from_slice(&key).map_err(|_| Error::InvalidKeyPair)
+	}
+}
+
+/// Gets the dialogue the user enters their password in to unlock the wallet.
+pub fn password_prompt() -> DialogueKind {
+	DialogueKind::Password(String::from("Password"))
+}
+
+/// Decrypts the private key with the password the user entered in the
+/// password prompt. Fails with Rejected if they didn't enter one.
+pub fn unlock(
+	keystore: &Keystore,
+	resp: Result<DialogueResponse, DialogueError>,
+) -> Result<SigningKey, Error> {
+	match resp {
+		Ok(DialogueResponse::Text(password)) => keystore.decrypt(&password),
+		_ => Err(Error::Rejected),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use beacon_dao_display_manager::{dialogue, template::Html};
+
+	/// The PBKDF2 test vector of the Web3 Secret Storage definition.
+	const PBKDF2: &str = r#"{
+		"crypto": {
+			"cipher": "aes-128-ctr",
+			"cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+			"ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+			"kdf": "pbkdf2",
+			"kdfparams": {
+				"c": 262144,
+				"dklen": 32,
+				"prf": "hmac-sha256",
+				"salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+			},
+			"mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+		},
+		"id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+		"version": 3
+	}"#;
+
+	#[test]
+	fn test_decrypt_pbkdf2() {
+		let keystore: Keystore = serde_json::from_str(PBKDF2).unwrap();
+		let key = keystore.decrypt("testpassword").unwrap();
+
+		assert_eq!(
+			hex::encode(key.to_bytes()),
+			"7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d"
+		);
+		assert_eq!(
+			keystore.decrypt("wrongpassword").err(),
+			Some(Error::BadPassword)
+		);
+	}
+
+	/// Encrypts the key the way other wallets do, with a cheap scrypt.
+	fn encrypt_scrypt(key: &[u8], password: &str) -> String {
+		let (salt, iv) = ([0x5a; 32], [0x1f; 16]);
+		let kdf = Kdf::Scrypt {
+			dklen: 32,
+			n: 1024,
+			r: 8,
+			p: 1,
+			salt: hex::encode(salt),
+		};
+		let derived = kdf.derive(password).unwrap();
+
+		let mut ciphertext = key.to_vec();
+		Aes128Ctr::new_from_slices(&derived[..16], &iv)
+			.unwrap()
+			.apply_keystream(&mut ciphertext);
+
+		let mut mac_body = derived[16..32].to_vec();
+		mac_body.extend_from_slice(&ciphertext);
+
+		serde_json::json!({
+			"crypto": {
+				"cipher": "aes-128-ctr",
+				"cipherparams": { "iv": hex::encode(iv) },
+				"ciphertext": hex::encode(ciphertext),
+				"kdf": "scrypt",
+				"kdfparams": { "dklen": 32, "n": 1024, "r": 8, "p": 1, "salt": hex::encode(salt) },
+				"mac": hex::encode(keccak(&mac_body)),
+			},
+			"version": 3
+		})
+		.to_string()
+	}
+
+	#[test]
+	fn test_decrypt_scrypt() {
+		let key = [0x42; 32];
+		let keystore: Keystore = serde_json::from_str(&encrypt_scrypt(&key, "hunter2")).unwrap();
+
+		assert_eq!(keystore.decrypt("hunter2").unwrap().to_bytes()[..], key[..]);
+		assert_eq!(keystore.decrypt("hunter3").err(), Some(Error::BadPassword));
+	}
+
+	#[test]
+	fn test_unlock() {
+		let key = [0x42; 32];
+		let keystore: Keystore = serde_json::from_str(&encrypt_scrypt(&key, "hunter2")).unwrap();
+		let prompt = password_prompt();
+
+		// The password is entered in the prompt's field, which its script
+		// finds by the dialogue's slot, and submitted as JSON
+		let html = dialogue::markup("Unlock Wallet", "", &prompt, Html::default(), 4);
+		assert!(html.as_str().contains("id=\"dialogueField4\""));
+		assert!(html.as_str().contains("type=\"password\""));
+
+		let entered = dialogue::input_response(&prompt, "\"hunter2\"");
+		assert_eq!(unlock(&keystore, entered).unwrap().to_bytes()[..], key[..]);
+
+		let wrong = dialogue::input_response(&prompt, "\"hunter3\"");
+		assert_eq!(unlock(&keystore, wrong).err(), Some(Error::BadPassword));
+
+		// Cancelling the prompt rejects unlocking the wallet
+		let cancelled = dialogue::button_response(&prompt, 1);
+		assert_eq!(unlock(&keystore, cancelled).err(), Some(Error::Rejected));
+	}
+
+	#[test]
+	fn test_invalid() {
+		let mut keystore: Keystore = serde_json::from_str(PBKDF2).unwrap();
+		keystore.version = 1;
+		assert_eq!(
+			keystore.decrypt("testpassword").err(),
+			Some(Error::InvalidKeyPair)
+		);
+
+		assert!(serde_json::from_str::<Keystore>(r#"{ "version": 3 }"#).is_err());
+	}
+}
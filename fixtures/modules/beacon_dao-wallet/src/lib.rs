@@ -0,0 +1,340 @@
+use beacon_dao_contracts::{Call, Error as ContractError, User};
+use beacon_dao_display_manager::{
+	confirm_transaction, get_user, system_dialogue, DialogueError, PendingTransaction,
+};
+use beacon_dao_permissions::{has_permission, register_permission};
+use beacon_dao_web3::{get_endpoint, rpc, send_raw_transaction, Error as Web3Error, Network};
+use k256::ecdsa::SigningKey;
+use keystore::Keystore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{cell::RefCell, rc::Rc, sync::RwLock};
+use tx::{address_of, format_units, Quote, Transaction};
+use vision_derive::with_bindings;
+use vision_utils::types::{Address, Callback, DISPLAY_MANAGER_ADDR, PERM_ADDR, WEB3_ADDR};
+
+const PERM_SEND: &'static str = "send transactions";
+const PERM_SEND_DESC: &'static str =
+	"ask you to sign and send Ethereum transactions from your account.";
+
+/// RLP encoding, as used by Ethereum transactions.
+pub mod rlp;
+
+/// Building and signing EIP-1559 transactions.
+pub mod tx;
+
+/// Decrypting the key pairs stored in users' profiles.
+pub mod keystore;
+
+/// Errors that might be encountered when using this API.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+	NoPermission,
+
+	/// Nobody is logged in, or a guest is, so there is no key to sign with
+	NoAccount,
+
+	/// The profile's key pair isn't a keystore the wallet can read
+	InvalidKeyPair,
+
+	/// The password the user entered doesn't decrypt the key pair
+	BadPassword,
+
+	/// The transaction asked for isn't well formed
+	InvalidTransaction,
+
+	/// The user declined to unlock the wallet, or to send the transaction
+	Rejected,
+
+	/// The node couldn't be asked about, or refused, the transaction
+	Web3(Web3Error),
+}
+
+/// A transaction an actor asks the wallet to send. Whatever is left out is
+/// filled in by the wallet.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionRequest {
+	/// The account or contract called, or None to deploy a contract
+	pub to: Option<String>,
+
+	/// The wei sent with the transaction, as a hex quantity (e.g., 0x1a)
+	pub value: Option<String>,
+
+	/// The calldata, as 0x-prefixed hex
+	pub data: Option<String>,
+
+	/// The most gas the transaction may use. Estimated if left out
+	pub gas: Option<u64>,
+}
+
+/// Transactions calling contracts through their bindings (e.g.,
+/// `proposal.cast_vote(0, weight).into()`).
+impl From<Call> for TransactionRequest {
+	fn from(call: Call) -> Self {
+		Self {
+			to: Some(call.to),
+			data: Some(call.data),
+			..Self::default()
+		}
+	}
+}
+
+/// The key of the logged in user, once they've unlocked the wallet, with the
+/// address of the profile it was unlocked for.
+static ACCOUNT: RwLock<Option<(String, SigningKey)>> = RwLock::new(None);
+
+#[cfg(feature = "module")]
+#[no_mangle]
+pub extern "C" fn handle_init_async(owner: Address) {
+	register_permission(
+		PERM_ADDR,
+		PERM_SEND.to_owned(),
+		PERM_SEND_DESC.to_owned(),
+		Callback::new(|_| {}),
+	);
+}
+
+/// Gets the address transactions are sent from, asking the user to unlock the
+/// wallet if they haven't yet.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_get_address(from: Address, callback: Callback<Result<String, Error>>) {
+	with_key(from, move |key| {
+		callback.call(key.map(|key| format!("0x{}", hex::encode(address_of(key.verifying_key())))))
+	});
+}
+
+/// Signs the transaction with the user's key, and submits it, once the user
+/// has confirmed it. Gets the hash of the transaction.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_send_transaction(
+	from: Address,
+	req: TransactionRequest,
+	callback: Callback<Result<String, Error>>,
+) {
+	with_key(from, move |key| {
+		let key = match key {
+			Ok(key) => key,
+			Err(e) => {
+				callback.call(Err(e));
+
+				return;
+			}
+		};
+		let sender = format!("0x{}", hex::encode(address_of(key.verifying_key())));
+
+		quote(&sender, &req, move |quote| {
+			let tx = match quote.and_then(|quote| Transaction::new(&req, &quote)) {
+				Ok(tx) => tx,
+				Err(e) => {
+					callback.call(Err(e));
+
+					return;
+				}
+			};
+
+			confirm(from, &sender, &tx, move |confirmed| {
+				if !confirmed {
+					callback.call(Err(Error::Rejected));
+
+					return;
+				}
+
+				let raw = match tx.sign(&key) {
+					Ok(raw) => raw,
+					Err(e) => {
+						callback.call(Err(e));
+
+						return;
+					}
+				};
+
+				send_raw_transaction(
+					WEB3_ADDR,
+					format!("0x{}", hex::encode(raw)),
+					Callback::new(move |res: Result<String, Web3Error>| {
+						callback.call(res.map_err(Error::Web3))
+					}),
+				);
+			});
+		});
+	});
+}
+
+/// Gets the key of the logged in user if the actor may send transactions,
+/// asking the user to unlock the wallet if they haven't yet.
+fn with_key(from: Address, f: impl FnOnce(Result<SigningKey, Error>) + 'static) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_SEND.to_owned(),
+		Callback::new(move |has_permission: bool| {
+			if !has_permission && from != DISPLAY_MANAGER_ADDR {
+				f(Err(Error::NoPermission));
+
+				return;
+			}
+
+			get_user(
+				DISPLAY_MANAGER_ADDR,
+				Callback::new(move |user: Option<String>| {
+					let user = if let Some(user) = user {
+						user
+					} else {
+						f(Err(Error::NoAccount));
+
+						return;
+					};
+
+					// Keys unlocked for somebody else aren't used
+					let key = ACCOUNT.read().ok().and_then(|account| {
+						account
+							.as_ref()
+							.filter(|(unlocked_for, _)| *unlocked_for == user)
+							.map(|(_, key)| key.clone())
+					});
+
+					match key {
+						Some(key) => f(Ok(key)),
+						None => unlock(user, f),
+					}
+				}),
+			);
+		}),
+	);
+}
+
+/// Decrypts the key pair stored in the user's profile with a password they
+/// enter, keeping the key for the rest of the session.
+fn unlock(user: String, f: impl FnOnce(Result<SigningKey, Error>) + 'static) {
+	User::new(user.clone()).key_pair(move |res| {
+		let keystore = match res {
+			Ok(keystore) => keystore,
+			Err(ContractError::Web3(e)) => {
+				f(Err(Error::Web3(e)));
+
+				return;
+			}
+			Err(ContractError::InvalidOutput) => {
+				f(Err(Error::InvalidKeyPair));
+
+				return;
+			}
+		};
+		let keystore = if let Ok(keystore) = serde_json::from_str::<Keystore>(&keystore) {
+			keystore
+		} else {
+			f(Err(Error::InvalidKeyPair));
+
+			return;
+		};
+
+		system_dialogue(
+			DISPLAY_MANAGER_ADDR,
+			String::from("Unlock Wallet"),
+			format!(
+				"Enter your password to sign transactions from the account of {}.",
+				user
+			),
+			keystore::password_prompt(),
+			Callback::new(move |resp| {
+				let key = keystore::unlock(&keystore, resp);
+				if let (Ok(key), Ok(mut account)) = (&key, ACCOUNT.write()) {
+					*account = Some((user, key.clone()));
+				}
+
+				f(key);
+			}),
+		);
+	});
+}
+
+/// Answers to the requests making up a quote, as they come in.
+struct Quoting {
+	answers: Vec<Option<Value>>,
+	done: Option<Box<dyn FnOnce(Result<Quote, Error>)>>,
+}
+
+/// Asks the node, all at once, for what the transaction needs: the chain it's
+/// for, the sender's next nonce, how much gas it will use, and current fees.
+fn quote(
+	sender: &str,
+	req: &TransactionRequest,
+	done: impl FnOnce(Result<Quote, Error>) + 'static,
+) {
+	let requests = Quote::requests(sender, req);
+	let state = Rc::new(RefCell::new(Quoting {
+		answers: vec![None; requests.len()],
+		done: Some(Box::new(done)),
+	}));
+
+	for (i, (method, params)) in requests.into_iter().enumerate() {
+		let state = state.clone();
+
+		rpc(
+			WEB3_ADDR,
+			method,
+			params,
+			Callback::new(move |res: Result<Value, Web3Error>| {
+				let finished = {
+					let mut quoting = state.borrow_mut();
+
+					match res {
+						Ok(answer) => {
+							quoting.answers[i] = Some(answer);
+
+							if quoting.answers.iter().all(Option::is_some) {
+								let answers: Vec<Value> = quoting
+									.answers
+									.iter_mut()
+									.filter_map(Option::take)
+									.collect();
+
+								quoting.done.take().map(|done| {
+									(
+										done,
+										Quote::read(&answers)
+											.ok_or(Error::Web3(Web3Error::SerializationError)),
+									)
+								})
+							} else {
+								None
+							}
+						}
+						Err(e) => quoting.done.take().map(|done| (done, Err(Error::Web3(e)))),
+					}
+				};
+
+				if let Some((done, res)) = finished {
+					done(res);
+				}
+			}),
+		);
+	}
+}
+
+/// Asks the user whether to send the transaction the actor asked for.
+fn confirm(from: Address, sender: &str, tx: &Transaction, f: impl FnOnce(bool) + 'static) {
+	let (sender, tx) = (sender.to_owned(), tx.clone());
+
+	get_endpoint(
+		WEB3_ADDR,
+		Callback::new(move |network: Network| {
+			confirm_transaction(
+				DISPLAY_MANAGER_ADDR,
+				PendingTransaction {
+					actor: from,
+					network: network.name,
+					from: sender,
+					to: tx.to.map(|to| format!("0x{}", hex::encode(to))),
+					value: format_units(tx.value, &network.ticker),
+					max_cost: format_units(tx.max_cost(), &network.ticker),
+					gas: tx.gas,
+					data: format!("0x{}", hex::encode(&tx.data)),
+				},
+				Callback::new(move |resp: Result<bool, DialogueError>| f(resp == Ok(true))),
+			);
+		}),
+	);
+}
@@ -0,0 +1,107 @@
+/// A value that can be RLP encoded: a string of bytes, or a list of values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+	Bytes(Vec<u8>),
+	List(Vec<Item>),
+}
+
+impl Item {
+	/// An unsigned integer, as its big-endian bytes without leading zeroes.
+	pub fn uint(n: u128) -> Self {
+		Self::big_uint(&n.to_be_bytes())
+	}
+
+	/// An unsigned integer of any size, given as its big-endian bytes.
+	pub fn big_uint(bytes: &[u8]) -> Self {
+		let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+
+		Self::Bytes(bytes[start..].to_vec())
+	}
+
+	pub fn encode(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		self.write(&mut out);
+
+		out
+	}
+
+	fn write(&self, out: &mut Vec<u8>) {
+		match self {
+			// Single bytes below 0x80 are their own encoding
+			Self::Bytes(bytes) if bytes.len() == 1 && bytes[0] < 0x80 => out.push(bytes[0]),
+			Self::Bytes(bytes) => {
+				write_len(out, 0x80, bytes.len());
+				out.extend_from_slice(bytes);
+			}
+			Self::List(items) => {
+				let mut payload = Vec::new();
+				for item in items {
+					item.write(&mut payload);
+				}
+
+				write_len(out, 0xc0, payload.len());
+				out.extend(payload);
+			}
+		}
+	}
+}
+
+/// Writes the prefix of a string (offset 0x80) or list (offset 0xc0) with a
+/// payload of the length.
+fn write_len(out: &mut Vec<u8>, offset: u8, len: usize) {
+	if len < 56 {
+		out.push(offset + len as u8);
+
+		return;
+	}
+
+	let bytes = len.to_be_bytes();
+	let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+
+	out.push(offset + 55 + (bytes.len() - start) as u8);
+	out.extend_from_slice(&bytes[start..]);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn bytes(s: &str) -> Item {
+		Item::Bytes(s.as_bytes().to_vec())
+	}
+
+	#[test]
+	fn test_encode() {
+		assert_eq!(bytes("dog").encode(), hex::decode("83646f67").unwrap());
+		assert_eq!(
+			Item::List(vec![bytes("cat"), bytes("dog")]).encode(),
+			hex::decode("c88363617483646f67").unwrap()
+		);
+		assert_eq!(bytes("").encode(), vec![0x80]);
+		assert_eq!(Item::List(Vec::new()).encode(), vec![0xc0]);
+		assert_eq!(Item::Bytes(vec![0x00]).encode(), vec![0x00]);
+		assert_eq!(Item::Bytes(vec![0x80]).encode(), vec![0x81, 0x80]);
+
+		let lorem = "Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+		let mut expected = vec![0xb8, 0x38];
+		expected.extend_from_slice(lorem.as_bytes());
+		assert_eq!(bytes(lorem).encode(), expected);
+
+		// [ [], [[]], [ [], [[]] ] ]
+		let empty = || Item::List(Vec::new());
+		let nested = Item::List(vec![
+			empty(),
+			Item::List(vec![empty()]),
+			Item::List(vec![empty(), Item::List(vec![empty()])]),
+		]);
+		assert_eq!(nested.encode(), hex::decode("c7c0c1c0c3c0c1c0").unwrap());
+	}
+
+	#[test]
+	fn test_uint() {
+		assert_eq!(Item::uint(0).encode(), vec![0x80]);
+		assert_eq!(Item::uint(15).encode(), vec![0x0f]);
+		assert_eq!(Item::uint(1024).encode(), vec![0x82, 0x04, 0x00]);
+		assert_eq!(Item::uint(u128::MAX).encode()[..2], [0x90, 0xff][..]);
+	}
+}
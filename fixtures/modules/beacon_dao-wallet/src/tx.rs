@@ -0,0 +1,391 @@
+use super::{rlp::Item, Error, TransactionRequest};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tiny_keccak::{Hasher, Keccak};
+
+/// The type byte of EIP-1559 transactions.
+pub const TX_TYPE: u8 = 0x02;
+
+pub fn keccak(bytes: &[u8]) -> [u8; 32] {
+	let mut hasher = Keccak::v256();
+	let mut out = [0; 32];
+	hasher.update(bytes);
+	hasher.finalize(&mut out);
+
+	out
+}
+
+/// Gets the address of the account with the public key.
+pub fn address_of(key: &VerifyingKey) -> [u8; 20] {
+	let point = key.to_encoded_point(false);
+	let hash = keccak(&point.as_bytes()[1..]);
+
+	let mut addr = [0; 20];
+	addr.copy_from_slice(&hash[12..]);
+
+	addr
+}
+
+/// Parses a hex quantity (e.g., 0x1a), as used by JSON RPC.
+pub fn parse_quantity(s: &str) -> Option<u128> {
+	s.strip_prefix("0x")
+		.filter(|digits| !digits.is_empty())
+		.and_then(|digits| u128::from_str_radix(digits, 16).ok())
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+	hex::decode(s.strip_prefix("0x").unwrap_or(s)).ok()
+}
+
+/// What the node says a transaction needs to be included in a block.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+	pub chain_id: u64,
+	pub nonce: u64,
+	pub gas: u64,
+
+	/// The base fee of the latest block, and the suggested tip, in wei
+	pub base_fee: u128,
+	pub priority_fee: u128,
+}
+
+impl Quote {
+	/// Gets the JSON RPC requests whose answers make up a quote for the
+	/// transaction sent by the sender, in the order `read` takes them.
+	pub fn requests(sender: &str, req: &TransactionRequest) -> Vec<(String, Vec<Value>)> {
+		let mut call = Map::new();
+		call.insert(String::from("from"), Value::String(sender.to_owned()));
+		for (field, value) in [("to", &req.to), ("value", &req.value), ("data", &req.data)] {
+			if let Some(value) = value {
+				call.insert(field.to_owned(), Value::String(value.clone()));
+			}
+		}
+
+		vec![
+			(String::from("eth_chainId"), Vec::new()),
+			(
+				String::from("eth_getTransactionCount"),
+				vec![Value::String(sender.to_owned()), Value::from("pending")],
+			),
+			(String::from("eth_estimateGas"), vec![Value::Object(call)]),
+			(
+				String::from("eth_getBlockByNumber"),
+				vec![Value::from("latest"), Value::Bool(false)],
+			),
+			(String::from("eth_maxPriorityFeePerGas"), Vec::new()),
+		]
+	}
+
+	/// Reads the answers to the requests, in order. Gets None for networks
+	/// without a base fee, which don't take EIP-1559 transactions.
+	pub fn read(answers: &[Value]) -> Option<Self> {
+		let quantity = |v: &Value| v.as_str().and_then(parse_quantity);
+
+		match answers {
+			[chain_id, nonce, gas, block, priority_fee] => Some(Self {
+				chain_id: quantity(chain_id)?.try_into().ok()?,
+				nonce: quantity(nonce)?.try_into().ok()?,
+				gas: quantity(gas)?.try_into().ok()?,
+				base_fee: block.get("baseFeePerGas").and_then(quantity)?,
+				priority_fee: quantity(priority_fee)?,
+			}),
+			_ => None,
+		}
+	}
+}
+
+/// Formats an amount of wei in whole units of the network's currency (e.g.,
+/// 0.25 ETH).
+pub fn format_units(wei: u128, ticker: &str) -> String {
+	let (whole, frac) = (wei / 10u128.pow(18), wei % 10u128.pow(18));
+	if frac == 0 {
+		return format!("{} {}", whole, ticker);
+	}
+
+	let frac = format!("{:018}", frac);
+
+	format!("{}.{} {}", whole, frac.trim_end_matches('0'), ticker)
+}
+
+/// An unsigned EIP-1559 transaction, with an empty access list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+	pub chain_id: u64,
+	pub nonce: u64,
+	pub max_priority_fee_per_gas: u128,
+	pub max_fee_per_gas: u128,
+	pub gas: u64,
+	pub to: Option<[u8; 20]>,
+	pub value: u128,
+	pub data: Vec<u8>,
+}
+
+impl Transaction {
+	/// Fills in the request with what the node quoted. The max fee leaves room
+	/// for the base fee to double before the transaction is included.
+	pub fn new(req: &TransactionRequest, quote: &Quote) -> Result<Self, Error> {
+		let to = match &req.to {
+			Some(to) => Some(
+				parse_hex(to)
+					.and_then(|to| <[u8; 20]>::try_from(to).ok())
+					.ok_or(Error::InvalidTransaction)?,
+			),
+			None => None,
+		};
+		let value = match &req.value {
+			Some(value) => parse_quantity(value).ok_or(Error::InvalidTransaction)?,
+			None => 0,
+		};
+		let data = match &req.data {
+			Some(data) => parse_hex(data).ok_or(Error::InvalidTransaction)?,
+			None => Vec::new(),
+		};
+
+		Ok(Self {
+			chain_id: quote.chain_id,
+			nonce: quote.nonce,
+			max_priority_fee_per_gas: quote.priority_fee,
+			max_fee_per_gas: quote
+				.base_fee
+				.saturating_mul(2)
+				.saturating_add(quote.priority_fee),
+			gas: req.gas.unwrap_or(quote.gas),
+			to,
+			value,
+			data,
+		})
+	}
+
+	/// The most wei the transaction can cost the sender, value included.
+	pub fn max_cost(&self) -> u128 {
+		(self.gas as u128)
+			.saturating_mul(self.max_fee_per_gas)
+			.saturating_add(self.value)
+	}
+
+	fn fields(&self) -> Vec<Item> {
+		vec![
+			Item::uint(self.chain_id as u128),
+			Item::uint(self.nonce as u128),
+			Item::uint(self.max_priority_fee_per_gas),
+			Item::uint(self.max_fee_per_gas),
+			Item::uint(self.gas as u128),
+			Item::Bytes(self.to.map(|to| to.to_vec()).unwrap_or_default()),
+			Item::uint(self.value),
+			Item::Bytes(self.data.clone()),
+			Item::List(Vec::new()),
+		]
+	}
+
+	fn envelope(fields: Vec<Item>) -> Vec<u8> {
+		let mut out = vec![TX_TYPE];
+		out.extend(Item::List(fields).encode());
+
+		out
+	}
+
+	/// The hash the sender signs.
+	pub fn signing_hash(&self) -> [u8; 32] {
+		keccak(&Self::envelope(self.fields()))
+	}
+
+	/// Signs the transaction, getting it encoded as it is sent to the network.
+	pub fn sign(&self, key: &SigningKey) -> Result<Vec<u8>, Error> {
+		let (sig, recid) = key
+			.sign_prehash_recoverable(&self.signing_hash())
+			.map_err(|_| Error::InvalidTransaction)?;
+		let (r, s) = sig.split_bytes();
+
+		let mut fields = self.fields();
+		fields.push(Item::uint(recid.is_y_odd() as u128));
+		fields.push(Item::big_uint(&r));
+		fields.push(Item::big_uint(&s));
+
+		Ok(Self::envelope(fields))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use k256::ecdsa::{RecoveryId, Signature};
+
+	fn key() -> SigningKey {
+		SigningKey::from_slice(
+			&hex::decode("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")
+				.unwrap(),
+		)
+		.unwrap()
+	}
+
+	fn quote() -> Quote {
+		Quote {
+			chain_id: 421613,
+			nonce: 7,
+			gas: 50_000,
+			base_fee: 100_000_000,
+			priority_fee: 1_000_000,
+		}
+	}
+
+	#[test]
+	fn test_address_of() {
+		assert_eq!(
+			hex::encode(address_of(key().verifying_key())),
+			"2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+		);
+	}
+
+	#[test]
+	fn test_new() {
+		let req = TransactionRequest {
+			to: Some(String::from("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23")),
+			value: Some(String::from("0x10")),
+			data: Some(String::from("0xdeadbeef")),
+			gas: None,
+		};
+		let tx = Transaction::new(&req, &quote()).unwrap();
+
+		assert_eq!(tx.gas, 50_000);
+		assert_eq!(tx.max_fee_per_gas, 201_000_000);
+		assert_eq!(tx.value, 16);
+		assert_eq!(tx.data, vec![0xde, 0xad, 0xbe, 0xef]);
+		assert_eq!(tx.max_cost(), 50_000 * 201_000_000 + 16);
+
+		// Gas given by the sender is kept
+		let tx = Transaction::new(
+			&TransactionRequest {
+				gas: Some(21_000),
+				..req.clone()
+			},
+			&quote(),
+		)
+		.unwrap();
+		assert_eq!(tx.gas, 21_000);
+
+		for bad in [
+			TransactionRequest {
+				to: Some(String::from("0x1234")),
+				..req.clone()
+			},
+			TransactionRequest {
+				value: Some(String::from("16")),
+				..req.clone()
+			},
+			TransactionRequest {
+				data: Some(String::from("0xzz")),
+				..req
+			},
+		] {
+			assert_eq!(
+				Transaction::new(&bad, &quote()),
+				Err(Error::InvalidTransaction)
+			);
+		}
+	}
+
+	#[test]
+	fn test_quote() {
+		let sender = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+		let req = TransactionRequest {
+			to: Some(String::from("0x1111111111111111111111111111111111111111")),
+			data: Some(String::from("0xdeadbeef")),
+			..Default::default()
+		};
+		let requests = Quote::requests(sender, &req);
+
+		assert_eq!(requests[2].0, "eth_estimateGas");
+		assert_eq!(
+			requests[2].1,
+			vec![serde_json::json!({
+				"from": sender,
+				"to": "0x1111111111111111111111111111111111111111",
+				"data": "0xdeadbeef"
+			})]
+		);
+
+		let mut answers = vec![
+			serde_json::json!("0x66eed"),
+			serde_json::json!("0x7"),
+			serde_json::json!("0xc350"),
+			serde_json::json!({ "number": "0x10", "baseFeePerGas": "0x5f5e100" }),
+			serde_json::json!("0xf4240"),
+		];
+		assert_eq!(answers.len(), requests.len());
+		assert_eq!(Quote::read(&answers), Some(quote()));
+
+		// Networks without EIP-1559
+		answers[3] = serde_json::json!({ "number": "0x10" });
+		assert_eq!(Quote::read(&answers), None);
+	}
+
+	#[test]
+	fn test_format_units() {
+		assert_eq!(format_units(0, "ETH"), "0 ETH");
+		assert_eq!(format_units(25 * 10u128.pow(16), "ETH"), "0.25 ETH");
+		assert_eq!(
+			format_units(10u128.pow(18) + 1, "AGOR"),
+			"1.000000000000000001 AGOR"
+		);
+	}
+
+	/// Reads an RLP string shorter than 56 bytes, getting it, and what follows.
+	fn read_short(buf: &[u8]) -> (&[u8], &[u8]) {
+		match buf[0] {
+			0x00..=0x7f => buf.split_at(1),
+			prefix => buf[1..].split_at((prefix - 0x80) as usize),
+		}
+	}
+
+	#[test]
+	fn test_sign() {
+		let tx = Transaction {
+			chain_id: 1,
+			nonce: 0,
+			max_priority_fee_per_gas: 1_000_000_000,
+			max_fee_per_gas: 30_000_000_000,
+			gas: 21_000,
+			to: Some([0x11; 20]),
+			value: 1,
+			data: Vec::new(),
+		};
+		let raw = tx.sign(&key()).unwrap();
+
+		// Signing is deterministic
+		assert_eq!(raw, tx.sign(&key()).unwrap());
+
+		// 0x02 || rlp([1, 0, 1 gwei, 30 gwei, 21000, to, 1, "", [], v, r, s])
+		assert_eq!(raw[0], TX_TYPE);
+		let unsigned = hex::decode(concat!(
+			"01",
+			"80",
+			"843b9aca00",
+			"8506fc23ac00",
+			"825208",
+			"941111111111111111111111111111111111111111",
+			"01",
+			"80",
+			"c0"
+		))
+		.unwrap();
+		let start = raw.iter().position(|b| *b == 0x01).unwrap();
+		assert_eq!(raw[start..start + unsigned.len()], unsigned[..]);
+
+		// The signature is over the signing hash, by the key, with a low s
+		let (v, rest) = read_short(&raw[start + unsigned.len()..]);
+		let (r, rest) = read_short(rest);
+		let (s, rest) = read_short(rest);
+		assert!(rest.is_empty());
+
+		let mut sig_bytes = [0; 64];
+		sig_bytes[32 - r.len()..32].copy_from_slice(r);
+		sig_bytes[64 - s.len()..].copy_from_slice(s);
+		let sig = Signature::from_slice(&sig_bytes).unwrap();
+		assert!(sig.normalize_s().is_none());
+
+		let recid = RecoveryId::new(v == [0x01], false);
+		let signer = VerifyingKey::recover_from_prehash(&tx.signing_hash(), &sig, recid).unwrap();
+		assert_eq!(&signer, key().verifying_key());
+	}
+}
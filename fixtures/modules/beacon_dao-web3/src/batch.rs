@@ -0,0 +1,206 @@
+use super::{rpc::read_response, Reply, Request, Transport};
+use serde_json::Value;
+use std::{collections::HashMap, sync::Arc};
+
+/// Sends requests queued together to the node: as a single batch if there is
+/// more than one, and the node accepts batches, or else each on its own.
+/// If the node doesn't answer the batch with an array of responses, the
+/// requests are sent on their own. `on_rejected` is only called first if it
+/// answered with a JSON-RPC error object: a batch that failed to reach the
+/// node (e.g., because of a network error) says nothing about whether the
+/// node accepts batches.
+pub fn send_queued<T: Transport + ?Sized + 'static>(
+	transport: Arc<T>,
+	queued: Vec<(Request, Reply<Value>)>,
+	batch: bool,
+	on_rejected: impl FnOnce() + Send + 'static,
+) {
+	if !batch || queued.len() < 2 {
+		for (req, reply) in queued {
+			transport.send(req, reply);
+		}
+
+		return;
+	}
+
+	let reqs = queued.iter().map(|(req, _)| req.clone()).collect();
+
+	transport.clone().send_batch(
+		reqs,
+		Box::new(move |body| match body {
+			Ok(Value::Array(resps)) => demux(queued, resps),
+			body => {
+				if matches!(&body, Ok(Value::Object(resp)) if resp.contains_key("error")) {
+					on_rejected();
+				}
+
+				for (req, reply) in queued {
+					transport.send(req, reply);
+				}
+			}
+		}),
+	);
+}
+
+/// Gives each request the response with its ID, in whatever order the node
+/// answered them.
+fn demux(queued: Vec<(Request, Reply<Value>)>, resps: Vec<Value>) {
+	let mut by_id: HashMap<u64, Value> = resps
+		.into_iter()
+		.filter_map(|resp| Some((resp.get("id")?.as_u64()?, resp)))
+		.collect();
+
+	for (req, reply) in queued {
+		reply(read_response(by_id.remove(&(req.id() as u64))));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::Error;
+	use super::*;
+	use serde_json::json;
+	use std::sync::Mutex;
+
+	/// How a node answers batches.
+	#[derive(Clone, Copy, PartialEq)]
+	enum Batches {
+		Accepted,
+
+		/// Answered with an error object
+		Rejected,
+
+		/// Answered with an HTTP error that isn't JSON
+		Failed,
+	}
+
+	/// A node answering eth_chainId requests, recording the requests and
+	/// batches it gets.
+	struct Node {
+		batches: Batches,
+		seen: Mutex<Vec<Value>>,
+	}
+
+	impl Node {
+		fn new(batches: Batches) -> Arc<Self> {
+			Arc::new(Self {
+				batches,
+				seen: Mutex::new(Vec::new()),
+			})
+		}
+	}
+
+	fn answer(req: &Value) -> Value {
+		match req["method"].as_str() {
+			Some("eth_chainId") => json!({ "jsonrpc": "2.0", "id": req["id"], "result": "0x1" }),
+			_ => json!({
+				"jsonrpc": "2.0",
+				"id": req["id"],
+				"error": { "code": -32601, "message": "method not found" }
+			}),
+		}
+	}
+
+	impl Transport for Node {
+		fn send(&self, req: Request, reply: Reply<Value>) {
+			let req = serde_json::to_value(&req).unwrap();
+			self.seen.lock().unwrap().push(req.clone());
+
+			reply(read_response(Some(answer(&req))));
+		}
+
+		fn send_batch(&self, reqs: Vec<Request>, reply: Reply<Value>) {
+			let reqs = serde_json::to_value(&reqs).unwrap();
+			self.seen.lock().unwrap().push(reqs.clone());
+
+			if self.batches == Batches::Failed {
+				reply(Err(Error::ServerError));
+
+				return;
+			}
+
+			if self.batches == Batches::Rejected {
+				reply(Ok(json!({
+					"jsonrpc": "2.0",
+					"id": null,
+					"error": { "code": -32600, "message": "batches not supported" }
+				})));
+
+				return;
+			}
+
+			// Answer out of order
+			let mut resps: Vec<Value> = reqs.as_array().unwrap().iter().map(answer).collect();
+			resps.reverse();
+
+			reply(Ok(Value::Array(resps)));
+		}
+	}
+
+	fn run(node: Arc<Node>, methods: &[&str]) -> (Vec<Result<Value, Error>>, bool) {
+		let out = Arc::new(Mutex::new(vec![None; methods.len()]));
+		let rejected = Arc::new(Mutex::new(false));
+
+		let queued = methods
+			.iter()
+			.enumerate()
+			.map(|(i, method)| {
+				let out = out.clone();
+				let reply: Reply<Value> = Box::new(move |res| out.lock().unwrap()[i] = Some(res));
+
+				(Request::new(method, Vec::new()), reply)
+			})
+			.collect();
+
+		let on_rejected = rejected.clone();
+		send_queued(node, queued, true, move || {
+			*on_rejected.lock().unwrap() = true
+		});
+
+		let out = out.lock().unwrap().drain(..).map(Option::unwrap).collect();
+		let rejected = *rejected.lock().unwrap();
+
+		(out, rejected)
+	}
+
+	#[test]
+	fn test_batch() {
+		let node = Node::new(Batches::Accepted);
+		let (out, rejected) = run(node.clone(), &["eth_chainId", "eth_foo", "eth_chainId"]);
+
+		assert!(!rejected);
+		assert_eq!(out[0], Ok(json!("0x1")));
+		assert!(matches!(&out[1], Err(Error::Rpc(e)) if e.code == -32601));
+		assert_eq!(out[2], Ok(json!("0x1")));
+
+		// Sent as one batch
+		let seen = node.seen.lock().unwrap();
+		assert_eq!(seen.len(), 1);
+		assert_eq!(seen[0].as_array().map(Vec::len), Some(3));
+	}
+
+	#[test]
+	fn test_batch_rejected() {
+		for batches in [Batches::Rejected, Batches::Failed] {
+			let node = Node::new(batches);
+			let (out, rejected) = run(node.clone(), &["eth_chainId", "eth_chainId"]);
+
+			// Only nodes that answered with an error are sent requests on
+			// their own from then on
+			assert_eq!(rejected, batches == Batches::Rejected);
+			assert_eq!(out, vec![Ok(json!("0x1")), Ok(json!("0x1"))]);
+
+			// The batch, then each request on its own
+			assert_eq!(node.seen.lock().unwrap().len(), 3);
+		}
+	}
+
+	#[test]
+	fn test_single() {
+		let node = Node::new(Batches::Accepted);
+		let (out, _) = run(node.clone(), &["eth_chainId"]);
+
+		assert_eq!(out, vec![Ok(json!("0x1"))]);
+		assert!(node.seen.lock().unwrap()[0].is_object());
+	}
+}
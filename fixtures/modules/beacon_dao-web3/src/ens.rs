@@ -0,0 +1,444 @@
+use super::{rpc, BlockSelector, Error, Reply, TransactionCall, Transport};
+use std::sync::Arc;
+use tiny_keccak::{Hasher, Keccak};
+
+/// The address of the ENS registry, which is the same on every network ENS
+/// is deployed to.
+pub const ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
+
+/// resolver(bytes32) on the registry
+const SEL_RESOLVER: [u8; 4] = [0x01, 0x78, 0xb8, 0xbf];
+
+/// addr(bytes32) on a resolver
+const SEL_ADDR: [u8; 4] = [0x3b, 0x3b, 0x57, 0xde];
+
+/// name(bytes32) on a resolver
+const SEL_NAME: [u8; 4] = [0x69, 0x1f, 0x34, 0x31];
+
+fn keccak(data: &[u8]) -> [u8; 32] {
+	let mut hasher = Keccak::v256();
+	let mut out = [0; 32];
+	hasher.update(data);
+	hasher.finalize(&mut out);
+
+	out
+}
+
+/// Checks that the text is a hex-encoded Ethereum address (e.g., 0xab..).
+pub fn is_address(text: &str) -> bool {
+	text.len() == 42 && text.starts_with("0x") && text[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Gets the canonical form of the ENS name, if it is well-formed. Only ASCII
+/// names are supported, so full UTS-46 normalization is not needed: names
+/// are lowercased, and names with empty labels or spaces are rejected.
+pub fn normalize(name: &str) -> Option<String> {
+	let name = name.to_ascii_lowercase();
+
+	if !name.is_ascii()
+		|| name
+			.split('.')
+			.any(|label| label.is_empty() || label.contains(char::is_whitespace))
+	{
+		return None;
+	}
+
+	Some(name)
+}
+
+/// Gets the node identifying the name in the ENS registry, as specified in
+/// EIP-137.
+pub fn namehash(name: &str) -> [u8; 32] {
+	let mut node = [0; 32];
+	if name.is_empty() {
+		return node;
+	}
+
+	for label in name.rsplit('.') {
+		let mut buf = [0; 64];
+		buf[..32].copy_from_slice(&node);
+		buf[32..].copy_from_slice(&keccak(label.as_bytes()));
+
+		node = keccak(&buf);
+	}
+
+	node
+}
+
+/// Gets the name of the reverse record of the address.
+pub fn reverse_name(addr: &str) -> Option<String> {
+	is_address(addr).then(|| format!("{}.addr.reverse", addr[2..].to_ascii_lowercase()))
+}
+
+/// Reads the 32-byte word at the offset of ABI-encoded data as an offset or
+/// length.
+fn read_usize(data: &[u8], at: usize) -> Option<usize> {
+	let word = data.get(at..at.checked_add(32)?)?;
+	if word[..24].iter().any(|b| *b != 0) {
+		return None;
+	}
+
+	usize::try_from(u64::from_be_bytes(word[24..].try_into().ok()?)).ok()
+}
+
+/// Reads an ABI-encoded address returned by a call. The zero address, which
+/// ENS uses for missing records, is read as None.
+fn decode_address(data: &[u8]) -> Option<String> {
+	let word = data.get(..32)?;
+	if word.iter().all(|b| *b == 0) {
+		return None;
+	}
+
+	Some(format!("0x{}", hex::encode(&word[12..])))
+}
+
+/// Reads an ABI-encoded string returned by a call. Empty strings, which ENS
+/// uses for missing records, are read as None.
+fn decode_string(data: &[u8]) -> Option<String> {
+	let offset = read_usize(data, 0)?;
+	let len = read_usize(data, offset)?;
+	let start = offset.checked_add(32)?;
+	let bytes = data.get(start..start.checked_add(len)?)?;
+
+	String::from_utf8(bytes.to_vec())
+		.ok()
+		.filter(|s| !s.is_empty())
+}
+
+/// Calls the function on the contract with the node as its only argument,
+/// and decodes the output.
+fn call<T: Transport + ?Sized + 'static, O: 'static>(
+	transport: &Arc<T>,
+	to: String,
+	selector: [u8; 4],
+	node: [u8; 32],
+	decode: fn(&[u8]) -> Option<O>,
+	reply: Reply<O>,
+) {
+	let params = serde_json::to_value(TransactionCall {
+		from: None,
+		to,
+		gas: None,
+		gasPrice: None,
+		value: None,
+		data: Some(format!("0x{}{}", hex::encode(selector), hex::encode(node))),
+	})
+	.and_then(|p1| serde_json::to_value(BlockSelector::Latest).map(|p2| vec![p1, p2]));
+
+	let params = match params {
+		Ok(params) => params,
+		Err(_) => {
+			reply(Err(Error::SerializationError));
+
+			return;
+		}
+	};
+
+	rpc::call(
+		&**transport,
+		"eth_call",
+		params,
+		Box::new(move |resp: Result<String, Error>| {
+			reply(resp.and_then(|result| {
+				result
+					.strip_prefix("0x")
+					.and_then(|result| hex::decode(result).ok())
+					.ok_or(Error::SerializationError)
+					.and_then(|data| decode(&data).ok_or(Error::NotFound))
+			}))
+		}),
+	);
+}
+
+/// Finds the resolver of the node in the registry, and calls the function on
+/// it.
+fn call_resolver<T: Transport + ?Sized + 'static, O: 'static>(
+	transport: Arc<T>,
+	selector: [u8; 4],
+	node: [u8; 32],
+	decode: fn(&[u8]) -> Option<O>,
+	reply: Reply<O>,
+) {
+	call(
+		&transport.clone(),
+		ENS_REGISTRY.to_owned(),
+		SEL_RESOLVER,
+		node,
+		decode_address,
+		Box::new(move |resolver| match resolver {
+			Ok(resolver) => call(&transport, resolver, selector, node, decode, reply),
+			Err(e) => reply(Err(e)),
+		}),
+	);
+}
+
+/// Gets the address that the ENS name points to.
+pub fn resolve<T: Transport + ?Sized + 'static>(
+	transport: Arc<T>,
+	name: &str,
+	reply: Reply<String>,
+) {
+	let name = if let Some(name) = normalize(name) {
+		name
+	} else {
+		reply(Err(Error::InvalidName));
+
+		return;
+	};
+
+	call_resolver(transport, SEL_ADDR, namehash(&name), decode_address, reply);
+}
+
+/// Gets the primary ENS name of the address. The name is only returned if it
+/// also resolves back to the address, since anybody can claim any name in
+/// their reverse record.
+pub fn lookup<T: Transport + ?Sized + 'static>(
+	transport: Arc<T>,
+	addr: &str,
+	reply: Reply<String>,
+) {
+	let (addr, reverse) = if let Some(reverse) = reverse_name(addr) {
+		(addr.to_ascii_lowercase(), reverse)
+	} else {
+		reply(Err(Error::InvalidAddress));
+
+		return;
+	};
+
+	call_resolver(
+		transport.clone(),
+		SEL_NAME,
+		namehash(&reverse),
+		decode_string,
+		Box::new(move |name| {
+			let name = match name {
+				Ok(name) => name,
+				Err(e) => {
+					reply(Err(e));
+
+					return;
+				}
+			};
+
+			resolve(
+				transport,
+				&name.clone(),
+				Box::new(move |resolved| {
+					reply(resolved.and_then(|resolved| {
+						if resolved == addr {
+							Ok(name)
+						} else {
+							Err(Error::NotFound)
+						}
+					}))
+				}),
+			);
+		}),
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::{
+		rpc::{read_response, RpcError},
+		Request,
+	};
+	use super::*;
+	use serde_json::{json, Value};
+	use std::{
+		collections::HashMap,
+		sync::{Arc, Mutex},
+	};
+
+	const RESOLVER: &str = "0x4976fb03c32e5b8cfe2b6ccb31c09ba78ebaba41";
+	const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+
+	/// An endpoint answering eth_calls from a table of (to, data) -> result,
+	/// and answering everything else with an error.
+	fn endpoint(table: Vec<(&'static str, String, String)>) -> Arc<impl Transport + 'static> {
+		let table: HashMap<(String, String), String> = table
+			.into_iter()
+			.map(|(to, data, result)| ((to.to_ascii_lowercase(), data), result))
+			.collect();
+
+		Arc::new(move |req: Request, reply: Reply<Value>| {
+			let resp = serde_json::to_value(&req).unwrap();
+			assert_eq!(resp["method"], "eth_call");
+			assert_eq!(resp["params"][1], "latest");
+
+			let key = (
+				resp["params"][0]["to"]
+					.as_str()
+					.unwrap()
+					.to_ascii_lowercase(),
+				resp["params"][0]["data"].as_str().unwrap().to_owned(),
+			);
+
+			let body = match table.get(&key) {
+				Some(result) => json!({ "jsonrpc": "2.0", "id": 1, "result": result }),
+				None => json!({
+					"jsonrpc": "2.0",
+					"id": 1,
+					"error": { "code": -32000, "message": "execution reverted" }
+				}),
+			};
+
+			reply(read_response(Some(body)))
+		})
+	}
+
+	fn calldata(selector: [u8; 4], name: &str) -> String {
+		format!("0x{}{}", hex::encode(selector), hex::encode(namehash(name)))
+	}
+
+	fn word_address(addr: &str) -> String {
+		format!("0x{:0>64}", &addr[2..])
+	}
+
+	fn word_string(s: &str) -> String {
+		let mut data = vec![0u8; 64];
+		data[31] = 32;
+		data[63] = s.len() as u8;
+		data.extend(s.as_bytes());
+		data.resize(64 + s.len().div_ceil(32) * 32, 0);
+
+		format!("0x{}", hex::encode(data))
+	}
+
+	/// Runs the lookup against the endpoint, which answers immediately.
+	fn run(lookup: impl FnOnce(Reply<String>)) -> Result<String, Error> {
+		let out = Arc::new(Mutex::new(None));
+		let out_cb = out.clone();
+
+		lookup(Box::new(move |res| {
+			*out_cb.lock().unwrap() = Some(res);
+		}));
+
+		let res = out.lock().unwrap().take();
+		res.expect("no reply")
+	}
+
+	fn alice_table() -> Vec<(&'static str, String, String)> {
+		let reverse = format!("{}.addr.reverse", &ALICE[2..]);
+
+		vec![
+			(
+				ENS_REGISTRY,
+				calldata(SEL_RESOLVER, "alice.eth"),
+				word_address(RESOLVER),
+			),
+			(
+				RESOLVER,
+				calldata(SEL_ADDR, "alice.eth"),
+				word_address(ALICE),
+			),
+			(
+				ENS_REGISTRY,
+				calldata(SEL_RESOLVER, &reverse),
+				word_address(RESOLVER),
+			),
+			(
+				RESOLVER,
+				calldata(SEL_NAME, &reverse),
+				word_string("alice.eth"),
+			),
+		]
+	}
+
+	#[test]
+	fn test_namehash() {
+		assert_eq!(namehash(""), [0; 32]);
+		assert_eq!(
+			hex::encode(namehash("eth")),
+			"93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae"
+		);
+		assert_eq!(
+			hex::encode(namehash("foo.eth")),
+			"de9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f"
+		);
+	}
+
+	#[test]
+	fn test_normalize() {
+		assert_eq!(normalize("Alice.ETH"), Some(String::from("alice.eth")));
+		assert_eq!(normalize("alice..eth"), None);
+		assert_eq!(normalize("al ice.eth"), None);
+		assert_eq!(normalize(""), None);
+	}
+
+	#[test]
+	fn test_decode() {
+		assert_eq!(
+			decode_address(&hex::decode(&word_address(ALICE)[2..]).unwrap()),
+			Some(ALICE.to_owned())
+		);
+		assert_eq!(decode_address(&[0; 32]), None);
+		assert_eq!(
+			decode_string(&hex::decode(&word_string("alice.eth")[2..]).unwrap()),
+			Some(String::from("alice.eth"))
+		);
+		assert_eq!(decode_string(&[0; 64]), None);
+		assert_eq!(decode_string(&[0xff; 64]), None);
+	}
+
+	#[test]
+	fn test_resolve() {
+		let rpc = endpoint(alice_table());
+
+		assert_eq!(
+			run(|reply| resolve(rpc.clone(), "Alice.eth", reply)),
+			Ok(ALICE.to_owned())
+		);
+		assert_eq!(
+			run(|reply| resolve(rpc.clone(), "bob.eth", reply)),
+			Err(Error::Rpc(RpcError {
+				code: -32000,
+				message: String::from("execution reverted"),
+				data: None,
+			}))
+		);
+		assert_eq!(
+			run(|reply| resolve(rpc, "alice..eth", reply)),
+			Err(Error::InvalidName)
+		);
+	}
+
+	#[test]
+	fn test_resolve_unset() {
+		// Names without a resolver point nowhere
+		let rpc = endpoint(vec![(
+			ENS_REGISTRY,
+			calldata(SEL_RESOLVER, "alice.eth"),
+			word_address("0x0000000000000000000000000000000000000000"),
+		)]);
+
+		assert_eq!(
+			run(|reply| resolve(rpc, "alice.eth", reply)),
+			Err(Error::NotFound)
+		);
+	}
+
+	#[test]
+	fn test_lookup() {
+		let rpc = endpoint(alice_table());
+
+		assert_eq!(
+			run(|reply| lookup(rpc.clone(), ALICE, reply)),
+			Ok(String::from("alice.eth"))
+		);
+		assert_eq!(
+			run(|reply| lookup(rpc, "alice", reply)),
+			Err(Error::InvalidAddress)
+		);
+	}
+
+	#[test]
+	fn test_lookup_verifies_forward() {
+		// The reverse record claims a name that points somewhere else
+		let mut table = alice_table();
+		table[1].2 = word_address("0x000000000000000000000000000000000000b0b0");
+		let rpc = endpoint(table);
+
+		assert_eq!(run(|reply| lookup(rpc, ALICE, reply)), Err(Error::NotFound));
+	}
+}
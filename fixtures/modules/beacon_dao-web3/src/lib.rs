@@ -3,12 +3,66 @@ use beacon_dao_permissions::{has_permission, register_permission};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	error::Error as StdError,
-	sync::{Arc, RwLock},
+	sync::{
+		atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+		Arc, Mutex, RwLock,
+	},
 };
 use vision_derive::with_bindings;
-use vision_utils::types::{Address, Callback, DISPLAY_MANAGER_ADDR, FETCH_ADDR, PERM_ADDR};
+use vision_utils::{
+	actor::address,
+	types::{
+		Address, Callback, DISPLAY_MANAGER_ADDR, EXIT_FAILURE, EXIT_SUCCESS, FETCH_ADDR, PERM_ADDR,
+	},
+};
+
+/// The address of an actor that other services talk to. It depends on the
+/// order actors are spawned in, so the runtime tells it to those services
+/// once the actor is running.
+#[derive(Default)]
+pub struct Service(AtomicU32);
+
+impl Service {
+	pub const fn new() -> Self {
+		Self(AtomicU32::new(0))
+	}
+
+	/// Gets the address of the actor, if the runtime has said what it is.
+	pub fn get(&self) -> Option<Address> {
+		match self.0.load(Ordering::SeqCst) {
+			0 => None,
+			addr => Some(addr),
+		}
+	}
+
+	pub fn is(&self, addr: Address) -> bool {
+		self.get() == Some(addr)
+	}
+
+	/// Records the address of the actor if it came from the runtime (address
+	/// 0), and none has been recorded yet.
+	pub fn set(&self, from: Address, addr: Address) {
+		if from == 0 {
+			let _ = self
+				.0
+				.compare_exchange(0, addr, Ordering::SeqCst, Ordering::SeqCst);
+		}
+	}
+}
+
+/// The wallet actor, which asks the node about the transactions it signs for
+/// the user.
+pub static WALLET: Service = Service::new();
+
+/// Tells the service the address of the wallet. Sent by the runtime once it
+/// has spawned the wallet.
+#[cfg(feature = "module")]
+#[no_mangle]
+pub extern "C" fn handle_set_wallet(from: Address, addr: Address) {
+	WALLET.set(from, addr);
+}
 
 const PERM_CHANGE: &'static str = "change network";
 const PERM_CHANGE_DESC: &'static str = "change which Ethereum network you're connected to.";
@@ -16,16 +70,50 @@ const PERM_CHANGE_DESC: &'static str = "change which Ethereum network you're con
 const PERM_USE: &'static str = "use web3";
 const PERM_USE_DESC: &'static str = "interact with the Ethereum network.";
 
+/// ENS name resolution.
+pub mod ens;
+
+/// Typed JSON RPC methods.
+pub mod rpc;
+
+/// Sending requests made together as JSON RPC batches.
+pub mod batch;
+
+/// Decoding the events of the Beacon DAO contracts, and tracking which logs of
+/// a subscription have been delivered through reorgs.
+pub mod logs;
+
+/// The networks the user can switch between.
+pub mod networks;
+
+use logs::Tracker;
+pub use logs::{Event, LogEvent, PushedLogs};
+use networks::Networks;
+pub use rpc::{Log, LogFilter, RpcError, TransactionReceipt};
+
 /// Errors that might be encountered when using this API.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub enum Error {
 	NoPermission,
 	SerializationError,
 	ServerError,
+	InvalidName,
+	InvalidAddress,
+	NotFound,
+
+	/// The node returned an error object in place of a result
+	Rpc(RpcError),
+
+	/// The node at the network's RPC URL is on the chain with the ID, rather
+	/// than the network's
+	WrongChain(u64),
+
+	/// The network can't be added or removed (e.g., it's a default network)
+	InvalidNetwork,
 }
 
 /// An EVM compatible network.
-#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Network {
 	pub chain_id: usize,
 	pub name: String,
@@ -80,32 +168,188 @@ pub extern "C" fn handle_init_async(owner: Address) {
 	);
 }
 
-/// Replaces the RPC endpoint in use with the new one, if the user has permissions to do so.
-#[no_mangle]
-#[with_bindings]
-pub extern "C" fn handle_change_endpoint(
-	from: Address,
-	new_net: Network,
-	callback: Callback<Result<(), Error>>,
-) {
+/// Networks the user has added, besides the default networks.
+static NETWORKS: RwLock<Networks> = RwLock::new(Networks::new());
+
+/// Checks that the actor may change which networks are used.
+fn with_change_permission(from: Address, f: impl FnOnce(Result<(), Error>) + 'static) {
 	has_permission(
 		PERM_ADDR,
 		from,
 		PERM_CHANGE.to_owned(),
 		Callback::new(move |has_permission: bool| {
 			if !has_permission && from != DISPLAY_MANAGER_ADDR {
-				callback.call(Err(Error::NoPermission));
+				f(Err(Error::NoPermission));
 
 				return;
 			}
 
+			f(Ok(()));
+		}),
+	)
+}
+
+/// Checks that the node at the network's RPC URL is on the network's chain,
+/// getting the network back if it is. Fails with WrongChain if it isn't.
+fn verify_chain(net: Network, f: impl FnOnce(Result<Network, Error>) + Send + 'static) {
+	let transport = BatchTransport {
+		url: net.rpc_url.clone(),
+	};
+
+	rpc::chain_id(
+		&transport,
+		Box::new(move |res| {
+			f(res.and_then(|chain_id| {
+				if chain_id == net.chain_id as u64 {
+					Ok(net)
+				} else {
+					Err(Error::WrongChain(chain_id))
+				}
+			}))
+		}),
+	);
+}
+
+/// Replaces the RPC endpoint in use with the new one, if the user has permissions to do so,
+/// once the node is found to be on the network's chain. Networks that aren't
+/// known yet are added to the networks the user can switch between.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_change_endpoint(
+	from: Address,
+	new_net: Network,
+	callback: Callback<Result<(), Error>>,
+) {
+	with_change_permission(from, move |res| {
+		if let Err(e) = res {
+			callback.call(Err(e));
+
+			return;
+		}
+
+		verify_chain(new_net, move |res| {
+			let new_net = match res {
+				Ok(new_net) => new_net,
+				Err(e) => {
+					callback.call(Err(e));
+
+					return;
+				}
+			};
+
+			if let Ok(mut networks) = NETWORKS.write() {
+				if !networks.contains(&new_net) {
+					let _ = networks.add(new_net.clone());
+				}
+			}
+
 			// Replace the provider URL
 			let mut l = ADAPTER_NET.write().unwrap();
 			*l = new_net;
 
 			callback.call(Ok(()));
-		}),
-	)
+		});
+	})
+}
+
+/// Gets every network the user can switch to: the default networks, followed
+/// by the networks they've added.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_list_networks(from: Address, callback: Callback<Vec<Network>>) {
+	callback.call(
+		NETWORKS
+			.read()
+			.map(|networks| networks.all())
+			.unwrap_or_else(|_| DEFAULT_NETWORKS.to_vec()),
+	);
+}
+
+/// Adds a network the user can switch to, once the node at its RPC URL is
+/// found to be on its chain. Replaces the network added before with the same
+/// RPC URL.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_add_network(from: Address, net: Network, callback: Callback<Result<(), Error>>) {
+	with_change_permission(from, move |res| {
+		if let Err(e) = res {
+			callback.call(Err(e));
+
+			return;
+		}
+
+		verify_chain(net, move |res| {
+			callback.call(res.and_then(|net| {
+				NETWORKS
+					.write()
+					.map_err(|_| Error::ServerError)
+					.and_then(|mut networks| networks.add(net))
+			}))
+		});
+	})
+}
+
+/// Removes the network the user added with the RPC URL. If it's the network
+/// in use, the adapter switches back to the first default network.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_remove_network(
+	from: Address,
+	rpc_url: String,
+	callback: Callback<Result<(), Error>>,
+) {
+	with_change_permission(from, move |res| {
+		let removed = res.and_then(|_| {
+			NETWORKS
+				.write()
+				.map_err(|_| Error::ServerError)
+				.and_then(|mut networks| networks.remove(&rpc_url))
+		});
+
+		if let (Ok(removed), Ok(mut curr)) = (&removed, ADAPTER_NET.write()) {
+			if curr.rpc_url == removed.rpc_url {
+				*curr = DEFAULT_NETWORKS[0].clone();
+			}
+		}
+
+		callback.call(removed.map(|_| ()));
+	})
+}
+
+/// Replaces the networks the user added, without checking their chains again.
+/// Used by the display manager to restore the networks saved for the user
+/// who logged in. If the network in use is no longer among them, the adapter
+/// switches back to the first default network.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_set_networks(
+	from: Address,
+	custom: Vec<Network>,
+	callback: Callback<Result<(), Error>>,
+) {
+	if from != DISPLAY_MANAGER_ADDR {
+		callback.call(Err(Error::NoPermission));
+
+		return;
+	}
+
+	let networks = if let Ok(mut networks) = NETWORKS.write() {
+		networks.set_custom(custom);
+
+		networks.clone()
+	} else {
+		callback.call(Err(Error::ServerError));
+
+		return;
+	};
+
+	if let Ok(mut curr) = ADAPTER_NET.write() {
+		if !networks.contains(&curr) {
+			*curr = DEFAULT_NETWORKS[0].clone();
+		}
+	}
+
+	callback.call(Ok(()));
 }
 
 /// Gets the Network currently connected to by this web3 adapter.
@@ -116,7 +360,7 @@ pub extern "C" fn handle_get_endpoint(from: Address, callback: Callback<Network>
 }
 
 /// A request to a JSON RPC API
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Request {
 	method: String,
 	jsonrpc: String,
@@ -124,6 +368,209 @@ pub struct Request {
 	id: usize,
 }
 
+/// The ID of the next request made.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+impl Request {
+	/// Creates a request with an ID no other request made by the adapter has.
+	pub fn new(method: &str, params: Vec<Value>) -> Self {
+		Self {
+			method: method.to_owned(),
+			jsonrpc: String::from("2.0"),
+			params,
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+		}
+	}
+
+	pub fn id(&self) -> usize {
+		self.id
+	}
+}
+
+/// A continuation receiving the outcome of a request.
+pub type Reply<T> = Box<dyn FnOnce(Result<T, Error>) + Send>;
+
+/// Something that answers JSON RPC requests with the result of the request
+/// (e.g., a node reached through the fetch service, or a mock in tests).
+pub trait Transport: Send + Sync {
+	fn send(&self, req: Request, reply: Reply<Value>);
+
+	/// Sends the requests as a batch, replying with the body of the response.
+	/// Transports that can't send batches reply with null, so that each
+	/// request gets sent on its own.
+	fn send_batch(&self, _reqs: Vec<Request>, reply: Reply<Value>) {
+		reply(Ok(Value::Null));
+	}
+}
+
+impl<F: Fn(Request, Reply<Value>) + Send + Sync> Transport for F {
+	fn send(&self, req: Request, reply: Reply<Value>) {
+		self(req, reply)
+	}
+}
+
+/// Sends requests to the node at the URL through the fetch service.
+pub struct FetchTransport {
+	url: String,
+}
+
+impl FetchTransport {
+	/// Posts the JSON body to the node, replying with the body of the
+	/// response.
+	fn post(&self, body: impl Serialize, reply: Reply<Value>) {
+		fetch_json(
+			FETCH_ADDR,
+			self.url.clone(),
+			OptionsBuilder {
+				method: Some(Method::POST),
+				headers: Some({
+					let mut h = HashMap::new();
+					h.insert(
+						String::from("Content-Type"),
+						String::from("application/json"),
+					);
+
+					h
+				}),
+				body: Some(body),
+			}
+			.into(),
+			Callback::new(move |resp: Result<Response, ()>| {
+				reply(
+					resp.ok()
+						.and_then(|resp| resp.json)
+						.ok_or(Error::ServerError),
+				);
+			}),
+		);
+	}
+}
+
+impl Transport for FetchTransport {
+	fn send(&self, req: Request, reply: Reply<Value>) {
+		self.post(
+			req,
+			Box::new(move |body| reply(body.and_then(|body| rpc::read_response(Some(body))))),
+		);
+	}
+
+	fn send_batch(&self, reqs: Vec<Request>, reply: Reply<Value>) {
+		self.post(reqs, reply);
+	}
+}
+
+/// Requests waiting to be sent to each node, as a batch, once the handler
+/// making them has returned.
+static QUEUE: Mutex<Vec<(String, Request, Reply<Value>)>> = Mutex::new(Vec::new());
+
+/// Whether a message has been sent to flush the queue.
+static FLUSH_QUEUED: AtomicBool = AtomicBool::new(false);
+
+/// Nodes that answered a batch with an error, which requests are sent to on
+/// their own.
+lazy_static::lazy_static! {
+	static ref NO_BATCH: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+}
+
+/// Queues requests to the node at the URL, to be sent together with every
+/// other request made in the same tick.
+pub struct BatchTransport {
+	url: String,
+}
+
+impl Transport for BatchTransport {
+	fn send(&self, req: Request, reply: Reply<Value>) {
+		match QUEUE.lock() {
+			Ok(mut queue) => queue.push((self.url.clone(), req, reply)),
+			Err(_) => {
+				reply(Err(Error::ServerError));
+
+				return;
+			}
+		}
+
+		// The flush is handled once the current message has been
+		if !FLUSH_QUEUED.swap(true, Ordering::SeqCst) {
+			flush(address(), Callback::new(|_| {}));
+		}
+	}
+}
+
+/// Sends every queued request, batching requests to the same node together.
+#[no_mangle]
+#[with_bindings]
+pub extern "C" fn handle_flush(from: Address, callback: Callback<u32>) {
+	if from != address() {
+		callback.call(EXIT_FAILURE);
+
+		return;
+	}
+
+	FLUSH_QUEUED.store(false, Ordering::SeqCst);
+	let queued = match QUEUE.lock() {
+		Ok(mut queue) => std::mem::take(&mut *queue),
+		Err(_) => {
+			callback.call(EXIT_FAILURE);
+
+			return;
+		}
+	};
+
+	// Group the requests by node, keeping the order they were made in
+	let mut by_url: Vec<(String, Vec<(Request, Reply<Value>)>)> = Vec::new();
+	for (url, req, reply) in queued {
+		match by_url.iter_mut().find(|(u, _)| *u == url) {
+			Some((_, reqs)) => reqs.push((req, reply)),
+			None => by_url.push((url, vec![(req, reply)])),
+		}
+	}
+
+	for (url, reqs) in by_url {
+		let batch = NO_BATCH
+			.read()
+			.map(|no_batch| !no_batch.contains(&url))
+			.unwrap_or_default();
+		let transport = Arc::new(FetchTransport { url: url.clone() });
+
+		batch::send_queued(transport, reqs, batch, move || {
+			if let Ok(mut no_batch) = NO_BATCH.write() {
+				no_batch.insert(url);
+			}
+		});
+	}
+
+	callback.call(EXIT_SUCCESS);
+}
+
+/// Gets a transport to the current network if the actor has permission to
+/// use web3.
+fn with_transport(from: Address, f: impl FnOnce(Result<BatchTransport, Error>) + 'static) {
+	has_permission(
+		PERM_ADDR,
+		from,
+		PERM_USE.to_owned(),
+		Callback::new(move |has_permission: bool| {
+			if !has_permission && from != DISPLAY_MANAGER_ADDR && !WALLET.is(from) {
+				f(Err(Error::NoPermission));
+
+				return;
+			}
+
+			f(current_transport());
+		}),
+	)
+}
+
+/// Gets a transport to the current network.
+fn current_transport() -> Result<BatchTransport, Error> {
+	ADAPTER_NET
+		.read()
+		.map(|lock| BatchTransport {
+			url: lock.rpc_url.to_owned(),
+		})
+		.map_err(|_| Error::NoPermission)
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum BlockSelector {
@@ -139,7 +586,7 @@ impl From<BlockSelector> for String {
 			BlockSelector::Latest => String::from("latest"),
 			BlockSelector::Earliest => String::from("earliest"),
 			BlockSelector::Pending => String::from("pending"),
-			BlockSelector::BlockNumber(n) => format!("{:x}", n),
+			BlockSelector::BlockNumber(n) => format!("{:#x}", n),
 		}
 	}
 }
@@ -164,73 +611,561 @@ pub fn handle_eth_call(
 	params_1: BlockSelector,
 	callback: Callback<Result<String, Error>>,
 ) {
-	has_permission(
-		PERM_ADDR,
-		from,
-		PERM_USE.to_owned(),
-		Callback::new(move |has_permission: bool| {
-			if !has_permission && from != DISPLAY_MANAGER_ADDR {
-				callback.call(Err(Error::NoPermission));
+	with_transport(from, move |transport| {
+		let (transport, p1) = match transport.and_then(|transport| {
+			serde_json::to_value(params_0)
+				.map(|p1| (transport, p1))
+				.map_err(|_| Error::SerializationError)
+		}) {
+			Ok(v) => v,
+			Err(e) => {
+				callback.call(Err(e));
+				return;
+			}
+		};
+
+		rpc::call(
+			&transport,
+			"eth_call",
+			vec![p1, Value::String(params_1.into())],
+			Box::new(move |res| callback.call(res)),
+		);
+	})
+}
+
+/// Gets the address that the ENS name (e.g., alice.eth) points to.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_resolve_name(from: Address, name: String, callback: Callback<Result<String, Error>>) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => ens::resolve(
+			Arc::new(transport),
+			&name,
+			Box::new(move |res| callback.call(res)),
+		),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Gets the primary ENS name of the address, if it has one.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_lookup_address(
+	from: Address,
+	address: String,
+	callback: Callback<Result<String, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => ens::lookup(
+			Arc::new(transport),
+			&address,
+			Box::new(move |res| callback.call(res)),
+		),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Makes any JSON RPC request to the current network, getting its result.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_rpc(
+	from: Address,
+	method: String,
+	params: Vec<Value>,
+	callback: Callback<Result<Value, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => rpc::call(
+			&transport,
+			&method,
+			params,
+			Box::new(move |res| callback.call(res)),
+		),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Gets the number of the latest block. Wraps eth_blockNumber.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_block_number(from: Address, callback: Callback<Result<u64, Error>>) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => rpc::block_number(&transport, Box::new(move |res| callback.call(res))),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Gets the ID of the chain the node is on. Wraps eth_chainId.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_chain_id(from: Address, callback: Callback<Result<u64, Error>>) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => rpc::chain_id(&transport, Box::new(move |res| callback.call(res))),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Gets the balance of the address in wei, as a hex quantity. Wraps
+/// eth_getBalance.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_get_balance(
+	from: Address,
+	address: String,
+	block: BlockSelector,
+	callback: Callback<Result<String, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => rpc::get_balance(
+			&transport,
+			address,
+			block,
+			Box::new(move |res| callback.call(res)),
+		),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Gets the receipt of the transaction, if it has been included in a block.
+/// Wraps eth_getTransactionReceipt.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_get_transaction_receipt(
+	from: Address,
+	hash: String,
+	callback: Callback<Result<Option<TransactionReceipt>, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => {
+			rpc::get_transaction_receipt(&transport, hash, Box::new(move |res| callback.call(res)))
+		}
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Gets the logs matching the filter. Wraps eth_getLogs.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_get_logs(
+	from: Address,
+	filter: LogFilter,
+	callback: Callback<Result<Vec<Log>, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => rpc::get_logs(&transport, filter, Box::new(move |res| callback.call(res))),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Estimates the gas the transaction would use. Wraps eth_estimateGas.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_estimate_gas(
+	from: Address,
+	tx: TransactionCall,
+	callback: Callback<Result<u64, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => rpc::estimate_gas(&transport, tx, Box::new(move |res| callback.call(res))),
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// Submits a signed transaction, getting its hash. Wraps
+/// eth_sendRawTransaction.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_send_raw_transaction(
+	from: Address,
+	data: String,
+	callback: Callback<Result<String, Error>>,
+) {
+	with_transport(from, move |transport| match transport {
+		Ok(transport) => {
+			rpc::send_raw_transaction(&transport, data, Box::new(move |res| callback.call(res)))
+		}
+		Err(e) => callback.call(Err(e)),
+	})
+}
+
+/// How often subscriptions are polled for new logs, in milliseconds.
+const POLL_INTERVAL_MS: i32 = 4000;
+
+/// The message the runtime sends when it is time to poll subscriptions.
+const POLL_MSG: &[u8] = b"poll_logs\0";
+
+/// The message logs are pushed to subscribers with.
+const LOGS_MSG: &[u8] = b"logs\0";
+
+/// Logs an actor has subscribed to, and the logs waiting to be delivered to
+/// it.
+struct Subscription {
+	id: u64,
+	subscriber: Address,
+	address: String,
+	topics: Vec<Option<String>>,
+
+	/// The node blocks are being tracked on, and the blocks scanned so far.
+	/// Tracking starts over at the latest block when the network changes
+	tracked: Option<(String, Tracker)>,
+
+	/// Whether logs have been asked for, and haven't come back yet
+	polling: bool,
+
+	/// Whether logs are sent to the subscriber in logs messages, rather than
+	/// waiting for it to ask for them
+	push: bool,
+	queued: Vec<LogEvent>,
+	waiting: Option<Callback<Result<Vec<LogEvent>, Error>>>,
+}
+
+/// Logs ready to be delivered, and who to deliver them to.
+enum Ready {
+	Waiting(Callback<Result<Vec<LogEvent>, Error>>, Vec<LogEvent>),
+	Pushed(Address, PushedLogs),
+}
+
+impl Ready {
+	/// Delivers the logs. Must not be called with SUBSCRIPTIONS locked.
+	fn deliver(self) {
+		extern "C" {
+			fn send_buffer(to: Address, msg: i32, ptr: i32, len: i32) -> u8;
+		}
+
+		match self {
+			Self::Waiting(waiting, logs) => waiting.call(Ok(logs)),
+			Self::Pushed(subscriber, logs) => {
+				if let Ok(body) = serde_json::to_vec(&logs) {
+					unsafe {
+						send_buffer(
+							subscriber,
+							LOGS_MSG.as_ptr() as i32,
+							body.as_ptr() as i32,
+							body.len() as i32,
+						)
+					};
+				}
+			}
+		}
+	}
+}
+
+impl Subscription {
+	/// Gets the logs waiting to be delivered, and who to deliver them to, if
+	/// there are any, and the subscriber is ready for them.
+	fn take_ready(&mut self) -> Option<Ready> {
+		if self.queued.is_empty() {
+			return None;
+		}
+
+		if self.push {
+			return Some(Ready::Pushed(
+				self.subscriber,
+				PushedLogs {
+					id: self.id,
+					logs: std::mem::take(&mut self.queued),
+				},
+			));
+		}
+
+		self.waiting
+			.take()
+			.map(|waiting| Ready::Waiting(waiting, std::mem::take(&mut self.queued)))
+	}
+}
+
+static SUBSCRIPTIONS: Mutex<Vec<Subscription>> = Mutex::new(Vec::new());
+
+/// The ID of the next subscription made.
+static NEXT_SUBSCRIPTION: AtomicU64 = AtomicU64::new(1);
+
+/// Whether a timer has been set to poll subscriptions.
+static POLL_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+/// Asks the runtime to send a poll_logs message once the poll interval has
+/// passed, unless it already will.
+fn schedule_poll() {
+	extern "C" {
+		fn set_timeout(msg: i32, ms: i32) -> u8;
+	}
+
+	if POLL_SCHEDULED.swap(true, Ordering::SeqCst) {
+		return;
+	}
+
+	if unsafe { set_timeout(POLL_MSG.as_ptr() as i32, POLL_INTERVAL_MS) } != 0 {
+		POLL_SCHEDULED.store(false, Ordering::SeqCst);
+	}
+}
+
+/// Subscribes to the logs of the contract at the address, with the topics,
+/// where None matches any topic. Logs emitted from the next block on are
+/// delivered through next_logs, or pushed once push_logs is called, along
+/// with the event they hold if it's one of the events of the Idea and
+/// Proposal contracts. Gets the ID of the subscription.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_subscribe_logs(
+	from: Address,
+	address: String,
+	topics: Vec<Option<String>>,
+	callback: Callback<Result<u64, Error>>,
+) {
+	with_transport(from, move |transport| {
+		if let Err(e) = transport {
+			callback.call(Err(e));
+
+			return;
+		}
+
+		let id = NEXT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+		match SUBSCRIPTIONS.lock() {
+			Ok(mut subscriptions) => subscriptions.push(Subscription {
+				id,
+				subscriber: from,
+				address,
+				topics,
+				tracked: None,
+				polling: false,
+				push: false,
+				queued: Vec::new(),
+				waiting: None,
+			}),
+			Err(_) => {
+				callback.call(Err(Error::ServerError));
 
 				return;
 			}
+		}
+
+		schedule_poll();
+		callback.call(Ok(id));
+	})
+}
 
-			let url = if let Ok(lock) = ADAPTER_NET.read() {
-				lock.rpc_url.to_owned()
-			} else {
-				callback.call(Err(Error::NoPermission));
+/// Gets the logs of the subscription that haven't been delivered yet, waiting
+/// for new logs if there are none. Logs dropped by a reorg after they were
+/// delivered are delivered again, marked as removed.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_next_logs(from: Address, id: u64, callback: Callback<Result<Vec<LogEvent>, Error>>) {
+	let (replaced, ready) = {
+		let mut subscriptions = match SUBSCRIPTIONS.lock() {
+			Ok(subscriptions) => subscriptions,
+			Err(_) => {
+				callback.call(Err(Error::ServerError));
 
 				return;
-			};
+			}
+		};
 
-			let (p1, p2) = match serde_json::to_value(params_0)
-				.and_then(|p1| serde_json::to_value(params_1).map(|p2| (p1, p2)))
-			{
-				Ok((p1, p2)) => (p1, p2),
-				Err(_) => {
-					callback.call(Err(Error::SerializationError));
-					return;
-				}
-			};
+		let sub = if let Some(sub) = subscriptions
+			.iter_mut()
+			.find(|sub| sub.id == id && sub.subscriber == from)
+		{
+			sub
+		} else {
+			drop(subscriptions);
+			callback.call(Err(Error::NotFound));
 
-			// Use the fetch client to make the request
-			fetch_json(
-				FETCH_ADDR,
-				url,
-				OptionsBuilder {
-					method: Some(Method::POST),
-					headers: Some({
-						let mut h = HashMap::new();
-						h.insert(
-							String::from("Content-Type"),
-							String::from("application/json"),
-						);
-
-						h
-					}),
-					body: Some(Request {
-						method: String::from("eth_call"),
-						jsonrpc: String::from("2.0"),
-						params: vec![p1, p2],
-						id: 1,
-					}),
-				}
-				.into(),
-				Callback::new(|resp: Result<Response, ()>| {
-					// Get the ETH response and call the callback
-					if let Ok(resp) = resp {
-						if let Some(Value::Object(json)) = resp.json {
-							if let Some(Value::String(result)) = json.get("result") {
-								callback.call(Ok(result.clone()));
-
-								return;
-							}
+			return;
+		};
+
+		// Only the latest request for logs is answered with them
+		let replaced = sub.waiting.replace(callback);
+
+		(replaced, sub.take_ready())
+	};
+
+	if let Some(replaced) = replaced {
+		replaced.call(Ok(Vec::new()));
+	}
+
+	if let Some(ready) = ready {
+		ready.deliver();
+	}
+}
+
+/// Sends the logs of the subscription to the subscriber as they come in,
+/// instead of waiting for next_logs. The subscriber gets a logs(buf, len)
+/// message, which it can read with read_logs, and should only accept from
+/// this actor:
+///
+/// #[no_mangle]
+/// pub extern "C" fn handle_logs(from: Address, buf: u32, len: u32)
+#[no_mangle]
+#[with_bindings]
+pub fn handle_push_logs(from: Address, id: u64, callback: Callback<Result<(), Error>>) {
+	let found = SUBSCRIPTIONS.lock().ok().and_then(|mut subscriptions| {
+		let sub = subscriptions
+			.iter_mut()
+			.find(|sub| sub.id == id && sub.subscriber == from)?;
+		sub.push = true;
+
+		Some((sub.waiting.take(), sub.take_ready()))
+	});
+
+	let (waiting, ready) = if let Some(found) = found {
+		found
+	} else {
+		callback.call(Err(Error::NotFound));
+
+		return;
+	};
+
+	// Requests for logs still waiting are answered with nothing, since the
+	// logs are pushed instead
+	if let Some(waiting) = waiting {
+		waiting.call(Ok(Vec::new()));
+	}
+
+	callback.call(Ok(()));
+
+	if let Some(ready) = ready {
+		ready.deliver();
+	}
+}
+
+/// Reads the logs delivered with a logs(buf, len) message.
+pub fn read_logs(buf: u32, len: u32) -> Option<PushedLogs> {
+	extern "C" {
+		fn read_buffer(token: u32, ptr: i32) -> u8;
+	}
+
+	let mut body = vec![0u8; len as usize];
+	if unsafe { read_buffer(buf, body.as_mut_ptr() as i32) } != 0 {
+		return None;
+	}
+
+	serde_json::from_slice(&body).ok()
+}
+
+/// Stops delivering the logs of the subscription. A request for its logs
+/// that is still waiting fails with NotFound.
+#[no_mangle]
+#[with_bindings]
+pub fn handle_unsubscribe_logs(from: Address, id: u64, callback: Callback<Result<(), Error>>) {
+	let removed = SUBSCRIPTIONS.lock().ok().and_then(|mut subscriptions| {
+		let i = subscriptions
+			.iter()
+			.position(|sub| sub.id == id && sub.subscriber == from)?;
+
+		Some(subscriptions.remove(i))
+	});
+
+	match removed {
+		Some(sub) => {
+			if let Some(waiting) = sub.waiting {
+				waiting.call(Err(Error::NotFound));
+			}
+
+			callback.call(Ok(()));
+		}
+		None => callback.call(Err(Error::NotFound)),
+	}
+}
+
+/// Asks the current network for the logs of every subscription in the blocks
+/// since they were last polled. Sent by the runtime once the timer set by
+/// schedule_poll is up.
+#[no_mangle]
+pub extern "C" fn handle_poll_logs(from: Address) {
+	if from != address() {
+		return;
+	}
+
+	POLL_SCHEDULED.store(false, Ordering::SeqCst);
+	if SUBSCRIPTIONS
+		.lock()
+		.map(|subscriptions| subscriptions.is_empty())
+		.unwrap_or(true)
+	{
+		return;
+	}
+
+	schedule_poll();
+
+	let transport = if let Ok(transport) = current_transport() {
+		transport
+	} else {
+		return;
+	};
+
+	let url = transport.url.clone();
+	rpc::block_number(
+		&transport,
+		Box::new(move |head| {
+			if let Ok(head) = head {
+				poll_subscriptions(url, head);
+			}
+		}),
+	);
+}
+
+/// Gets the logs of each subscription up to the head, unless they're already
+/// being gotten.
+fn poll_subscriptions(url: String, head: u64) {
+	// Requests are sent once the lock is released, since the transport may
+	// answer them before returning
+	let requests = if let Ok(mut subscriptions) = SUBSCRIPTIONS.lock() {
+		subscriptions
+			.iter_mut()
+			.filter(|sub| !sub.polling)
+			.filter_map(|sub| {
+				let (from_block, to_block) = match &sub.tracked {
+					Some((tracked_url, tracker)) if *tracked_url == url => tracker.range(head)?,
+					_ => {
+						sub.tracked = Some((url.clone(), Tracker::new(head + 1)));
+
+						return None;
+					}
+				};
+
+				sub.polling = true;
+
+				Some((
+					sub.id,
+					from_block,
+					to_block,
+					LogFilter {
+						from_block: Some(format!("{:#x}", from_block)),
+						to_block: Some(format!("{:#x}", to_block)),
+						address: Some(sub.address.clone()),
+						topics: sub.topics.clone(),
+						block_hash: None,
+					},
+				))
+			})
+			.collect::<Vec<_>>()
+	} else {
+		return;
+	};
+
+	for (id, from_block, to_block, filter) in requests {
+		let url = url.clone();
+		rpc::get_logs(
+			&BatchTransport { url: url.clone() },
+			filter,
+			Box::new(move |logs| {
+				let ready = SUBSCRIPTIONS.lock().ok().and_then(|mut subscriptions| {
+					let sub = subscriptions.iter_mut().find(|sub| sub.id == id)?;
+					sub.polling = false;
+
+					// Logs from a network that is no longer tracked are dropped
+					if let (Ok(logs), Some((tracked_url, tracker))) = (logs, sub.tracked.as_mut()) {
+						if *tracked_url == url {
+							let changes = tracker.update(from_block, to_block, logs);
+							sub.queued.extend(changes.into_iter().map(LogEvent::from));
 						}
 					}
 
-					callback.call(Err(Error::ServerError));
-				}),
-			);
-		}),
-	)
+					sub.take_ready()
+				});
+
+				if let Some(ready) = ready {
+					ready.deliver();
+				}
+			}),
+		);
+	}
 }
@@ -0,0 +1,375 @@
+use super::Log;
+use ethabi::{ethereum_types::H256, Event as AbiEvent, EventParam, ParamType, RawLog, Token};
+use serde::{Deserialize, Serialize};
+
+/// How many blocks behind the head are scanned again each poll, to notice
+/// logs that were dropped, or added, by a reorg.
+pub const REORG_DEPTH: u64 = 12;
+
+/// The most blocks asked about in a single eth_getLogs request.
+pub const MAX_RANGE: u64 = 1000;
+
+/// An event emitted by the Idea or Proposal contracts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// A proposal failed to get a majority when it was finalized
+	ProposalRejected { proposal: String },
+
+	/// A proposal was executed, replacing the Idea's metadata CID
+	ProposalAccepted {
+		proposal: String,
+		old_payload: String,
+		new_payload: String,
+	},
+
+	/// A vote was cast for (nature 0) or against (nature 1) a proposal. The
+	/// weight is a decimal number of tokens
+	VoteCast {
+		voter: String,
+		nature: u8,
+		weight: String,
+	},
+
+	/// The voting period of a proposal started
+	VoteStarted { organizer: String },
+}
+
+/// A log delivered to a subscriber, with the event it holds if it is one of
+/// the events the adapter knows.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+	pub log: Log,
+	pub event: Option<Event>,
+}
+
+/// Logs of a subscription pushed to its subscriber in a logs(buf, len)
+/// message.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PushedLogs {
+	pub id: u64,
+	pub logs: Vec<LogEvent>,
+}
+
+impl From<Log> for LogEvent {
+	fn from(log: Log) -> Self {
+		Self {
+			event: decode(&log),
+			log,
+		}
+	}
+}
+
+fn abi_event(name: &str, inputs: &[(&str, ParamType)]) -> AbiEvent {
+	AbiEvent {
+		name: name.to_owned(),
+		inputs: inputs
+			.iter()
+			.map(|(name, kind)| EventParam {
+				name: (*name).to_owned(),
+				kind: kind.clone(),
+				indexed: false,
+			})
+			.collect(),
+		anonymous: false,
+	}
+}
+
+/// The events of Idea.sol and Proposal.sol. None of their parameters are
+/// indexed.
+fn known_events() -> [AbiEvent; 4] {
+	[
+		abi_event("ProposalRejected", &[("proposal", ParamType::Address)]),
+		abi_event(
+			"ProposalAccepted",
+			&[
+				("proposal", ParamType::Address),
+				("oldPayload", ParamType::String),
+				("newPayload", ParamType::String),
+			],
+		),
+		abi_event(
+			"VoteCast",
+			&[
+				("voter", ParamType::Address),
+				("nature", ParamType::Uint(8)),
+				("weight", ParamType::Uint(256)),
+			],
+		),
+		abi_event("VoteStarted", &[("organizer", ParamType::Address)]),
+	]
+}
+
+/// Gets the topic that logs of the known event with the name (e.g., VoteCast)
+/// start with, for filtering subscriptions.
+pub fn topic(name: &str) -> Option<String> {
+	known_events()
+		.iter()
+		.find(|event| event.name == name)
+		.map(|event| format!("{:#x}", event.signature()))
+}
+
+/// Decodes the log as one of the known events.
+pub fn decode(log: &Log) -> Option<Event> {
+	let topics = log
+		.topics
+		.iter()
+		.map(|topic| {
+			let bytes = hex::decode(topic.trim_start_matches("0x")).ok()?;
+
+			(bytes.len() == 32).then(|| H256::from_slice(&bytes))
+		})
+		.collect::<Option<Vec<H256>>>()?;
+	let data = hex::decode(log.data.trim_start_matches("0x")).ok()?;
+
+	let events = known_events();
+	let event = events
+		.iter()
+		.find(|event| topics.first() == Some(&event.signature()))?;
+	let params: Vec<Token> = event
+		.parse_log(RawLog { topics, data })
+		.ok()?
+		.params
+		.into_iter()
+		.map(|param| param.value)
+		.collect();
+
+	let address = |i: usize| match params.get(i) {
+		Some(Token::Address(addr)) => Some(format!("{:#x}", addr)),
+		_ => None,
+	};
+	let string = |i: usize| match params.get(i) {
+		Some(Token::String(s)) => Some(s.clone()),
+		_ => None,
+	};
+
+	match event.name.as_str() {
+		"ProposalRejected" => Some(Event::ProposalRejected {
+			proposal: address(0)?,
+		}),
+		"ProposalAccepted" => Some(Event::ProposalAccepted {
+			proposal: address(0)?,
+			old_payload: string(1)?,
+			new_payload: string(2)?,
+		}),
+		"VoteCast" => match (params.get(1), params.get(2)) {
+			(Some(Token::Uint(nature)), Some(Token::Uint(weight))) => Some(Event::VoteCast {
+				voter: address(0)?,
+				nature: nature.low_u32() as u8,
+				weight: weight.to_string(),
+			}),
+			_ => None,
+		},
+		"VoteStarted" => Some(Event::VoteStarted {
+			organizer: address(0)?,
+		}),
+		_ => None,
+	}
+}
+
+/// Which blocks of a subscription have been scanned for logs, and the logs
+/// delivered from the most recent of them, so that the recent blocks can be
+/// scanned again without delivering their logs twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tracker {
+	/// Blocks before this are never scanned
+	start: u64,
+
+	/// The first block that hasn't been scanned
+	next: u64,
+
+	/// Logs delivered from the last REORG_DEPTH blocks scanned
+	delivered: Vec<Log>,
+}
+
+/// Whether the logs are the same log, in the same block.
+fn same_log(a: &Log, b: &Log) -> bool {
+	a.block_hash == b.block_hash
+		&& a.transaction_hash == b.transaction_hash
+		&& a.log_index == b.log_index
+}
+
+impl Tracker {
+	/// Tracks logs in the block, and the blocks after it.
+	pub fn new(start: u64) -> Self {
+		Self {
+			start,
+			next: start,
+			delivered: Vec::new(),
+		}
+	}
+
+	/// Gets the blocks to get logs for next, inclusive, given the number of
+	/// the latest block. None if there are no blocks to scan yet.
+	pub fn range(&self, head: u64) -> Option<(u64, u64)> {
+		let from = self.next.saturating_sub(REORG_DEPTH).max(self.start);
+		let to = head.min(self.next + MAX_RANGE - 1);
+
+		(from <= to).then_some((from, to))
+	}
+
+	/// Records the logs of the blocks, which are a range given by range.
+	/// Gets the logs that weren't delivered before, and the logs that were
+	/// delivered but are no longer in the chain, marked as removed.
+	pub fn update(&mut self, from: u64, to: u64, logs: Vec<Log>) -> Vec<Log> {
+		let logs: Vec<Log> = logs
+			.into_iter()
+			.filter(|log| !log.removed && log.block_number.is_some())
+			.collect();
+
+		// Logs delivered from blocks that were scanned again, but that aren't
+		// there anymore, were dropped by a reorg
+		let (rescanned, mut kept): (Vec<Log>, Vec<Log>) = self
+			.delivered
+			.drain(..)
+			.partition(|log| log.block_number.unwrap_or_default() >= from);
+		let mut changes: Vec<Log> = rescanned
+			.iter()
+			.filter(|old| !logs.iter().any(|log| same_log(old, log)))
+			.cloned()
+			.map(|mut old| {
+				old.removed = true;
+
+				old
+			})
+			.collect();
+
+		for log in logs {
+			if !rescanned.iter().any(|old| same_log(old, &log)) {
+				changes.push(log.clone());
+			}
+
+			kept.push(log);
+		}
+
+		self.next = to + 1;
+		let oldest = self.next.saturating_sub(REORG_DEPTH);
+		kept.retain(|log| log.block_number.unwrap_or_default() >= oldest);
+		self.delivered = kept;
+
+		changes
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn log(block: u64, hash: &str, index: u64) -> Log {
+		Log {
+			address: String::from("0x02"),
+			topics: Vec::new(),
+			data: String::from("0x"),
+			block_number: Some(block),
+			block_hash: Some(hash.to_owned()),
+			transaction_hash: Some(String::from("0xab")),
+			log_index: Some(index),
+			removed: false,
+		}
+	}
+
+	#[test]
+	fn test_range() {
+		let mut tracker = Tracker::new(100);
+		assert_eq!(tracker.range(99), None);
+		assert_eq!(tracker.range(105), Some((100, 105)));
+		assert_eq!(tracker.range(5000), Some((100, 1099)));
+
+		tracker.update(100, 105, Vec::new());
+		assert_eq!(tracker.range(105), Some((100, 105)));
+
+		tracker.update(100, 200, Vec::new());
+		assert_eq!(tracker.range(210), Some((201 - REORG_DEPTH, 210)));
+	}
+
+	#[test]
+	fn test_update() {
+		let mut tracker = Tracker::new(100);
+		let (a, b) = (log(101, "0xa1", 0), log(102, "0xb1", 0));
+
+		assert_eq!(
+			tracker.update(100, 102, vec![a.clone(), b.clone()]),
+			vec![a.clone(), b.clone()]
+		);
+
+		// Scanning the blocks again doesn't deliver their logs twice
+		assert_eq!(
+			tracker.update(100, 103, vec![a.clone(), b.clone()]),
+			Vec::new()
+		);
+
+		// Block 102 was replaced by a block without the log, and block 103 by
+		// one with a log
+		let c = log(103, "0xc2", 4);
+		let mut removed = b.clone();
+		removed.removed = true;
+		assert_eq!(
+			tracker.update(100, 103, vec![a.clone(), c.clone()]),
+			vec![removed, c]
+		);
+	}
+
+	#[test]
+	fn test_forget_old_blocks() {
+		let mut tracker = Tracker::new(0);
+		tracker.update(0, 1, vec![log(1, "0x01", 0)]);
+		tracker.update(0, 100, Vec::new());
+
+		// Logs too old to be scanned again aren't kept
+		assert_eq!(tracker.delivered, Vec::new());
+		assert_eq!(tracker.range(100), Some((101 - REORG_DEPTH, 100)));
+	}
+
+	#[test]
+	fn test_decode() {
+		let word = |last: u8| {
+			let mut word = [0u8; 32];
+			word[31] = last;
+
+			hex::encode(word)
+		};
+		let addr = format!("0x{}", "11".repeat(20));
+		let mut vote = log(1, "0x01", 0);
+		vote.topics = vec![topic("VoteCast").unwrap()];
+		vote.data = format!(
+			"0x{}{}{}",
+			"0".repeat(24) + &"11".repeat(20),
+			word(1),
+			word(5)
+		);
+
+		assert_eq!(
+			decode(&vote),
+			Some(Event::VoteCast {
+				voter: addr.clone(),
+				nature: 1,
+				weight: String::from("5"),
+			})
+		);
+
+		let accepted = ethabi::encode(&[
+			Token::Address([0x11; 20].into()),
+			Token::String(String::from("bafyold")),
+			Token::String(String::from("bafynew")),
+		]);
+		let mut log = log(1, "0x01", 1);
+		log.topics = vec![topic("ProposalAccepted").unwrap()];
+		log.data = format!("0x{}", hex::encode(accepted));
+
+		assert_eq!(
+			LogEvent::from(log.clone()).event,
+			Some(Event::ProposalAccepted {
+				proposal: addr,
+				old_payload: String::from("bafyold"),
+				new_payload: String::from("bafynew"),
+			})
+		);
+
+		// Logs of other events, and malformed logs
+		log.topics = vec![format!("0x{}", "00".repeat(32))];
+		assert_eq!(decode(&log), None);
+		log.topics = vec![topic("VoteStarted").unwrap()];
+		log.data = String::from("0x");
+		assert_eq!(decode(&log), None);
+		assert_eq!(topic("Transfer"), None);
+	}
+}
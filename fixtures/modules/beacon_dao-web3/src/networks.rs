@@ -0,0 +1,150 @@
+use super::{Error, Network, DEFAULT_NETWORKS};
+
+/// The networks the user can switch between: the default networks, and the
+/// networks they've added, which are told apart by their RPC URLs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Networks {
+	custom: Vec<Network>,
+}
+
+impl Networks {
+	pub const fn new() -> Self {
+		Self { custom: Vec::new() }
+	}
+
+	/// Gets every network, the default networks first.
+	pub fn all(&self) -> Vec<Network> {
+		DEFAULT_NETWORKS
+			.iter()
+			.chain(self.custom.iter())
+			.cloned()
+			.collect()
+	}
+
+	/// Gets the networks the user has added.
+	pub fn custom(&self) -> &[Network] {
+		&self.custom
+	}
+
+	pub fn contains(&self, net: &Network) -> bool {
+		DEFAULT_NETWORKS.contains(net) || self.custom.contains(net)
+	}
+
+	/// Adds the network, replacing the network added before with the same RPC
+	/// URL. Fails with InvalidNetwork if the network is missing a name or an
+	/// HTTP(S) URL, or if its URL is one of a default network's.
+	pub fn add(&mut self, net: Network) -> Result<(), Error> {
+		let is_http = net.rpc_url.starts_with("https://") || net.rpc_url.starts_with("http://");
+		if net.name.is_empty()
+			|| !is_http
+			|| DEFAULT_NETWORKS
+				.iter()
+				.any(|default| default.rpc_url == net.rpc_url)
+		{
+			return Err(Error::InvalidNetwork);
+		}
+
+		match self.custom.iter_mut().find(|n| n.rpc_url == net.rpc_url) {
+			Some(existing) => *existing = net,
+			None => self.custom.push(net),
+		}
+
+		Ok(())
+	}
+
+	/// Removes the network the user added with the RPC URL. Default networks
+	/// can't be removed.
+	pub fn remove(&mut self, rpc_url: &str) -> Result<Network, Error> {
+		if DEFAULT_NETWORKS.iter().any(|n| n.rpc_url == rpc_url) {
+			return Err(Error::InvalidNetwork);
+		}
+
+		let i = self
+			.custom
+			.iter()
+			.position(|n| n.rpc_url == rpc_url)
+			.ok_or(Error::NotFound)?;
+
+		Ok(self.custom.remove(i))
+	}
+
+	/// Replaces the networks the user added (e.g., with the networks saved for
+	/// the user who logged in), leaving out invalid networks.
+	pub fn set_custom(&mut self, nets: Vec<Network>) {
+		self.custom.clear();
+
+		for net in nets {
+			let _ = self.add(net);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn network(name: &str, rpc_url: &str) -> Network {
+		Network {
+			chain_id: 31337,
+			name: name.to_owned(),
+			ticker: String::from("ETH"),
+			rpc_url: rpc_url.to_owned(),
+		}
+	}
+
+	#[test]
+	fn test_add_remove() {
+		let mut networks = Networks::new();
+		let local = network("Hardhat", "http://localhost:8545");
+
+		networks.add(local.clone()).unwrap();
+		assert_eq!(networks.all().len(), DEFAULT_NETWORKS.len() + 1);
+		assert_eq!(networks.all().last(), Some(&local));
+		assert!(networks.contains(&local));
+
+		// Networks with the same URL replace each other
+		let renamed = network("Localhost", "http://localhost:8545");
+		networks.add(renamed.clone()).unwrap();
+		assert_eq!(networks.custom(), &[renamed.clone()][..]);
+
+		assert_eq!(networks.remove("http://localhost:8545"), Ok(renamed));
+		assert_eq!(
+			networks.remove("http://localhost:8545"),
+			Err(Error::NotFound)
+		);
+		assert_eq!(networks.all(), DEFAULT_NETWORKS.to_vec());
+	}
+
+	#[test]
+	fn test_invalid() {
+		let mut networks = Networks::new();
+		let default_url = DEFAULT_NETWORKS[0].rpc_url.clone();
+
+		assert_eq!(
+			networks.add(network("Mainnet", &default_url)),
+			Err(Error::InvalidNetwork)
+		);
+		assert_eq!(networks.remove(&default_url), Err(Error::InvalidNetwork));
+		assert_eq!(
+			networks.add(network("", "http://localhost:8545")),
+			Err(Error::InvalidNetwork)
+		);
+		assert_eq!(
+			networks.add(network("Local", "javascript:alert(1)")),
+			Err(Error::InvalidNetwork)
+		);
+		assert!(networks.contains(&DEFAULT_NETWORKS[1]));
+	}
+
+	#[test]
+	fn test_set_custom() {
+		let mut networks = Networks::new();
+		networks
+			.add(network("Old", "https://old.example.com"))
+			.unwrap();
+
+		let new = network("New", "https://new.example.com");
+		networks.set_custom(vec![new.clone(), network("Bad", "ftp://new.example.com")]);
+		assert_eq!(networks.custom(), &[new][..]);
+	}
+}
@@ -0,0 +1,421 @@
+use super::{BlockSelector, Error, Reply, Request, TransactionCall, Transport};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+/// An error object returned by a node in place of a result.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+	pub code: i64,
+	pub message: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub data: Option<Value>,
+}
+
+/// (De)serializes a number as the hex quantity JSON RPC uses (e.g., 0x1a).
+pub mod quantity {
+	use serde::{de, Deserialize, Deserializer, Serializer};
+
+	pub fn parse(s: &str) -> Option<u64> {
+		s.strip_prefix("0x")
+			.filter(|digits| !digits.is_empty())
+			.and_then(|digits| u64::from_str_radix(digits, 16).ok())
+	}
+
+	pub fn serialize<S: Serializer>(n: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&format!("{:#x}", n))
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+		let s = String::deserialize(deserializer)?;
+
+		parse(&s).ok_or_else(|| de::Error::custom("invalid quantity"))
+	}
+
+	/// (De)serializes quantities that may be missing, or null.
+	pub mod option {
+		use serde::{de, Deserialize, Deserializer, Serializer};
+
+		pub fn serialize<S: Serializer>(n: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+			match n {
+				Some(n) => super::serialize(n, serializer),
+				None => serializer.serialize_none(),
+			}
+		}
+
+		pub fn deserialize<'de, D: Deserializer<'de>>(
+			deserializer: D,
+		) -> Result<Option<u64>, D::Error> {
+			Option::<String>::deserialize(deserializer)?
+				.map(|s| super::parse(&s).ok_or_else(|| de::Error::custom("invalid quantity")))
+				.transpose()
+		}
+	}
+}
+
+/// An event emitted by a contract.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Log {
+	pub address: String,
+	pub topics: Vec<String>,
+	pub data: String,
+	#[serde(default, with = "quantity::option")]
+	pub block_number: Option<u64>,
+	#[serde(default)]
+	pub block_hash: Option<String>,
+	#[serde(default)]
+	pub transaction_hash: Option<String>,
+	#[serde(default, with = "quantity::option")]
+	pub log_index: Option<u64>,
+
+	/// Whether the log was dropped by a reorg
+	#[serde(default)]
+	pub removed: bool,
+}
+
+/// The outcome of a transaction that has been included in a block.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReceipt {
+	pub transaction_hash: String,
+	pub block_hash: String,
+	#[serde(with = "quantity")]
+	pub block_number: u64,
+	pub from: String,
+	pub to: Option<String>,
+	#[serde(with = "quantity")]
+	pub gas_used: u64,
+	#[serde(default)]
+	pub contract_address: Option<String>,
+	pub logs: Vec<Log>,
+
+	/// 1 if the transaction succeeded, and 0 if it reverted
+	#[serde(default, with = "quantity::option")]
+	pub status: Option<u64>,
+}
+
+/// Which logs to get with eth_getLogs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFilter {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub from_block: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub to_block: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub address: Option<String>,
+
+	/// The topics logs must have at each position, where None matches any
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub topics: Vec<Option<String>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub block_hash: Option<String>,
+}
+
+/// Gets the result of a JSON RPC response, or the error object returned in
+/// its place.
+pub fn read_response(body: Option<Value>) -> Result<Value, Error> {
+	let mut body = match body {
+		Some(Value::Object(body)) => body,
+		_ => return Err(Error::ServerError),
+	};
+
+	if let Some(error) = body.remove("error") {
+		return Err(serde_json::from_value(error)
+			.map(Error::Rpc)
+			.unwrap_or(Error::ServerError));
+	}
+
+	body.remove("result").ok_or(Error::ServerError)
+}
+
+/// Sends the request, and reads its result as the type.
+pub fn call<T: Transport + ?Sized, O: DeserializeOwned + 'static>(
+	transport: &T,
+	method: &str,
+	params: Vec<Value>,
+	reply: Reply<O>,
+) {
+	transport.send(
+		Request::new(method, params),
+		Box::new(move |res| {
+			reply(
+				res.and_then(|v| serde_json::from_value(v).map_err(|_| Error::SerializationError)),
+			)
+		}),
+	);
+}
+
+/// Sends the request, and reads its result as a quantity.
+fn call_quantity<T: Transport + ?Sized>(
+	transport: &T,
+	method: &str,
+	params: Vec<Value>,
+	reply: Reply<u64>,
+) {
+	call(
+		transport,
+		method,
+		params,
+		Box::new(move |res: Result<String, Error>| {
+			reply(res.and_then(|s| quantity::parse(&s).ok_or(Error::SerializationError)))
+		}),
+	);
+}
+
+/// Gets the params of a request, or replies with an error if they can't be
+/// serialized.
+fn to_params<O>(
+	params: Result<Vec<Value>, serde_json::Error>,
+	reply: Reply<O>,
+) -> Option<(Vec<Value>, Reply<O>)> {
+	match params {
+		Ok(params) => Some((params, reply)),
+		Err(_) => {
+			reply(Err(Error::SerializationError));
+
+			None
+		}
+	}
+}
+
+pub fn block_number<T: Transport + ?Sized>(transport: &T, reply: Reply<u64>) {
+	call_quantity(transport, "eth_blockNumber", Vec::new(), reply);
+}
+
+pub fn chain_id<T: Transport + ?Sized>(transport: &T, reply: Reply<u64>) {
+	call_quantity(transport, "eth_chainId", Vec::new(), reply);
+}
+
+/// Gets the balance of the address in wei, as a hex quantity, since balances
+/// may not fit in 64 bits.
+pub fn get_balance<T: Transport + ?Sized>(
+	transport: &T,
+	address: String,
+	block: BlockSelector,
+	reply: Reply<String>,
+) {
+	call(
+		transport,
+		"eth_getBalance",
+		vec![Value::String(address), Value::String(block.into())],
+		reply,
+	);
+}
+
+/// Gets the receipt of the transaction, or None if it hasn't been included in
+/// a block yet.
+pub fn get_transaction_receipt<T: Transport + ?Sized>(
+	transport: &T,
+	hash: String,
+	reply: Reply<Option<TransactionReceipt>>,
+) {
+	call(
+		transport,
+		"eth_getTransactionReceipt",
+		vec![Value::String(hash)],
+		reply,
+	);
+}
+
+pub fn get_logs<T: Transport + ?Sized>(transport: &T, filter: LogFilter, reply: Reply<Vec<Log>>) {
+	if let Some((params, reply)) = to_params(serde_json::to_value(filter).map(|f| vec![f]), reply) {
+		call(transport, "eth_getLogs", params, reply);
+	}
+}
+
+pub fn estimate_gas<T: Transport + ?Sized>(transport: &T, tx: TransactionCall, reply: Reply<u64>) {
+	if let Some((params, reply)) = to_params(serde_json::to_value(tx).map(|tx| vec![tx]), reply) {
+		call_quantity(transport, "eth_estimateGas", params, reply);
+	}
+}
+
+/// Submits a signed transaction, getting its hash.
+pub fn send_raw_transaction<T: Transport + ?Sized>(
+	transport: &T,
+	data: String,
+	reply: Reply<String>,
+) {
+	call(
+		transport,
+		"eth_sendRawTransaction",
+		vec![Value::String(data)],
+		reply,
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+	use std::sync::{Arc, Mutex};
+
+	/// A node answering each method with a fixed response body, recording the
+	/// requests it gets.
+	fn node(answers: Vec<(&'static str, Value)>) -> (impl Transport, Arc<Mutex<Vec<Value>>>) {
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let seen = requests.clone();
+
+		let transport = move |req: Request, reply: Reply<Value>| {
+			let req = serde_json::to_value(&req).unwrap();
+			seen.lock().unwrap().push(req.clone());
+
+			let body = answers
+				.iter()
+				.find(|(method, _)| req["method"] == *method)
+				.map(|(_, body)| body.clone());
+
+			reply(read_response(body))
+		};
+
+		(transport, requests)
+	}
+
+	fn run<O: 'static + Send>(f: impl FnOnce(Reply<O>)) -> Result<O, Error> {
+		let out = Arc::new(Mutex::new(None));
+		let out_cb = out.clone();
+
+		f(Box::new(move |res| *out_cb.lock().unwrap() = Some(res)));
+
+		let res = out.lock().unwrap().take();
+		res.expect("no reply")
+	}
+
+	#[test]
+	fn test_quantities() {
+		let (node, requests) = node(vec![
+			("eth_blockNumber", json!({ "id": 1, "result": "0x10d4f" })),
+			("eth_chainId", json!({ "id": 2, "result": "0xa4b1" })),
+			("eth_estimateGas", json!({ "id": 3, "result": "0x" })),
+		]);
+
+		assert_eq!(run(|reply| block_number(&node, reply)), Ok(68943));
+		assert_eq!(run(|reply| chain_id(&node, reply)), Ok(42161));
+		assert_eq!(
+			run(|reply| estimate_gas(
+				&node,
+				TransactionCall {
+					from: None,
+					to: String::from("0x00000000000000000000000000000000000a11ce"),
+					gas: None,
+					gasPrice: None,
+					value: None,
+					data: None,
+				},
+				reply
+			)),
+			Err(Error::SerializationError)
+		);
+
+		// Each request gets its own ID
+		let requests = requests.lock().unwrap();
+		assert_eq!(requests[0]["jsonrpc"], "2.0");
+		assert!(requests[1]["id"].as_u64() > requests[0]["id"].as_u64());
+	}
+
+	#[test]
+	fn test_errors() {
+		let (node, _) = node(vec![(
+			"eth_sendRawTransaction",
+			json!({
+				"id": 1,
+				"error": { "code": 3, "message": "execution reverted", "data": "0x08c379a0" }
+			}),
+		)]);
+
+		assert_eq!(
+			run(|reply| send_raw_transaction(&node, String::from("0x02f8"), reply)),
+			Err(Error::Rpc(RpcError {
+				code: 3,
+				message: String::from("execution reverted"),
+				data: Some(json!("0x08c379a0")),
+			}))
+		);
+
+		// Malformed responses
+		assert_eq!(run(|reply| chain_id(&node, reply)), Err(Error::ServerError));
+		assert_eq!(
+			read_response(Some(json!({ "error": "oops" }))),
+			Err(Error::ServerError)
+		);
+	}
+
+	#[test]
+	fn test_server_error_result() {
+		assert_eq!(read_response(Some(Value::Null)), Err(Error::ServerError));
+		assert_eq!(read_response(None), Err(Error::ServerError));
+	}
+
+	#[test]
+	fn test_receipt() {
+		let (node, requests) = node(vec![(
+			"eth_getTransactionReceipt",
+			json!({
+				"id": 1,
+				"result": {
+					"transactionHash": "0xab",
+					"blockHash": "0xcd",
+					"blockNumber": "0x5",
+					"from": "0x01",
+					"to": null,
+					"gasUsed": "0x5208",
+					"contractAddress": "0x02",
+					"status": "0x1",
+					"logs": [{
+						"address": "0x02",
+						"topics": ["0xef"],
+						"data": "0x",
+						"blockNumber": "0x5",
+						"logIndex": "0x0",
+						"removed": false
+					}]
+				}
+			}),
+		)]);
+
+		let receipt = run(|reply| get_transaction_receipt(&node, String::from("0xab"), reply))
+			.unwrap()
+			.unwrap();
+		assert_eq!(receipt.block_number, 5);
+		assert_eq!(receipt.gas_used, 21000);
+		assert_eq!(receipt.status, Some(1));
+		assert_eq!(receipt.logs[0].log_index, Some(0));
+		assert_eq!(receipt.logs[0].transaction_hash, None);
+		assert_eq!(requests.lock().unwrap()[0]["params"], json!(["0xab"]));
+	}
+
+	#[test]
+	fn test_get_logs() {
+		let (node, requests) = node(vec![
+			("eth_getLogs", json!({ "id": 1, "result": [] })),
+			(
+				"eth_getBalance",
+				json!({ "id": 2, "result": "0xde0b6b3a7640000" }),
+			),
+		]);
+
+		let filter = LogFilter {
+			from_block: Some(String::from("0x1")),
+			address: Some(String::from("0x02")),
+			topics: vec![Some(String::from("0xef")), None],
+			..LogFilter::default()
+		};
+		assert_eq!(run(|reply| get_logs(&node, filter, reply)), Ok(Vec::new()));
+		assert_eq!(
+			run(|reply| get_balance(
+				&node,
+				String::from("0x01"),
+				BlockSelector::BlockNumber(16),
+				reply
+			)),
+			Ok(String::from("0xde0b6b3a7640000"))
+		);
+
+		let requests = requests.lock().unwrap();
+		assert_eq!(
+			requests[0]["params"],
+			json!([{ "fromBlock": "0x1", "address": "0x02", "topics": ["0xef", null] }])
+		);
+		assert_eq!(requests[1]["params"], json!(["0x01", "0x10"]));
+	}
+}